@@ -0,0 +1,324 @@
+// Reference adapter: serve a JavaScript ruleset over the Forseti NDJSON protocol.
+//
+//   const { serve } = require("./forseti-ruleset");
+//   serve({
+//     id: "js-style",
+//     version: "0.1.0",
+//     filePatterns: ["**/*.js"],
+//     rules: [{
+//       id: "no-console",
+//       description: "Disallow console calls",
+//       defaultConfig: "warn",
+//       check(text, ctx) {
+//         // return diagnostics: { message, range, severity? }
+//       },
+//     }],
+//   });
+//
+// Mirrors RulesetServer: one request per line on stdin, replies on stdout,
+// `diagnostics` event before the `analyzeFile` response, and a final
+// `terminated` event.
+"use strict";
+
+const crypto = require("crypto");
+const readline = require("readline");
+
+const PROTOCOL_VERSION = 1;
+const LOG_LEVELS = ["trace", "debug", "info", "warn", "error"];
+// Consecutive malformed lines tolerated before the adapter gives up
+const MAX_PARSE_FAILURES = 8;
+// Process exit status per `terminated` reason, as ServerExit defines them
+const EXIT_CODES = { eof: 0, shutdown: 0, exit: 1, parseFailures: 76 };
+
+// `options.authToken`: token every host must send as `authToken` in
+// `initialize`; defaults to the variable named by `--auth-token-env <var>`
+function serve(ruleset, input = process.stdin, output = process.stdout, options = {}) {
+  const sessions = new Map();
+  let shuttingDown = false;
+  let logLevel = "info";
+  const authToken = options.authToken ?? authTokenFromArgs(process.argv);
+  let authenticated = false;
+  // Messages produced while a batch line is handled, sent as one line
+  let batch = null;
+  const write = (msg) => {
+    if (batch) batch.push(msg);
+    else output.write(JSON.stringify(msg) + "\n");
+  };
+
+  const reply = (req, payload) =>
+    write(withSession({ v: PROTOCOL_VERSION, kind: "res", type: req.type, id: req.id, payload }, req));
+  const event = (type, payload, req) =>
+    write(withSession({ v: PROTOCOL_VERSION, kind: "event", type, payload }, req));
+  // Structured `log` event, dropped below the level the host asked for
+  const log = (level, message, fields, req) => {
+    if (LOG_LEVELS.indexOf(level) < LOG_LEVELS.indexOf(logLevel)) return;
+    const payload = { level, message, target: `forseti::js::${ruleset.id}` };
+    if (fields && Object.keys(fields).length) payload.fields = fields;
+    event("log", payload, req);
+  };
+  const fail = (req, error, message) => {
+    const payload = { ok: false, error, message };
+    if (req && req.id && req.type) reply(req, payload);
+    else event("error", payload, req);
+  };
+
+  const ruleInfo = (rule) => ({
+    id: rule.id,
+    description: rule.description,
+    categories: rule.categories || [],
+    fixable: Boolean(rule.fixable),
+    docs_url: rule.docsUrl || null,
+    examples: rule.examples || [],
+  });
+  const defaultConfig = () =>
+    Object.fromEntries(ruleset.rules.map((r) => [r.id, r.defaultConfig ?? "warn"]));
+  const newSession = () => ({
+    initialized: false,
+    config: {},
+    settings: {},
+    // `setRules` toggles by rule id, applied over `config`
+    ruleOverrides: new Map(),
+    disabled: false,
+  });
+  // Session config with its `setRules` toggles applied
+  const effectiveConfig = (session) => {
+    const config = { ...session.config };
+    for (const [ruleId, enabled] of session.ruleOverrides) {
+      const rule = ruleset.rules.find((r) => r.id === ruleId);
+      if (!enabled) delete config[ruleId];
+      else if (!(ruleId in config) && rule) config[ruleId] = rule.defaultConfig ?? "warn";
+    }
+    return config;
+  };
+
+  const handlers = {
+    initialize(req) {
+      const range = req.payload.protocolVersions || { min: PROTOCOL_VERSION, max: PROTOCOL_VERSION };
+      if (range.min > PROTOCOL_VERSION || range.max < PROTOCOL_VERSION) {
+        return fail(req, "unsupported_protocol_version", "no common protocol version");
+      }
+      if (LOG_LEVELS.includes(req.payload.logLevel)) logLevel = req.payload.logLevel;
+      const session = newSession();
+      sessions.set(req.sessionId || "", session);
+      session.initialized = true;
+      session.config = req.payload.rulesetConfig || defaultConfig();
+      session.settings = req.payload.rulesetSettings || {};
+      reply(req, { ok: true, features: [], protocolVersion: PROTOCOL_VERSION });
+    },
+    getDefaultConfig(req) {
+      reply(req, defaultConfig());
+    },
+    getCapabilities(req) {
+      reply(req, {
+        ruleset_id: ruleset.id,
+        version: ruleset.version || "0.0.0",
+        file_patterns: ruleset.filePatterns || [],
+        max_file_size: ruleset.maxFileSize ?? null,
+        annotation_prefixes: ruleset.annotationPrefixes || [],
+        rules: ruleset.rules.map(ruleInfo),
+        default_config: defaultConfig(),
+        config_settings: [],
+        dependencies: [],
+        features: [],
+      });
+    },
+    getRuleDoc(req) {
+      const rule = ruleset.rules.find((r) => r.id === req.payload.ruleId);
+      if (rule) reply(req, ruleInfo(rule));
+      else reply(req, { ok: false, error: "unknown_rule", message: `unknown rule ${req.payload.ruleId}` });
+    },
+    preprocessFiles(req) {
+      const files = (req.payload.fileUris || []).map((uri) => ({
+        uri,
+        content: "",
+        language: null,
+        context: {},
+      }));
+      reply(req, { ruleset_id: ruleset.id, files, global_context: {} });
+    },
+    analyzeFile(req, session) {
+      if (!session.initialized) {
+        return fail(req, "not_initialized", "initialize has not completed");
+      }
+      const { uri = "", content = "", contentHash, encoding } = req.payload;
+      const bytes = encoding === "base64" ? Buffer.from(content, "base64") : Buffer.from(content, "utf8");
+      const text = bytes.toString("utf8");
+      const diagnostics = [];
+      const config = effectiveConfig(session);
+      // A disabled ruleset still sends the event so editors clear stale results
+      for (const rule of session.disabled ? [] : ruleset.rules) {
+        const options = config[rule.id];
+        if (options === undefined || options === "off") continue;
+        const ctx = {
+          uri,
+          bytes,
+          options,
+          settings: session.settings,
+          log: (level, message, fields) => log(level, message, { rule: rule.id, ...fields }, req),
+        };
+        for (const d of rule.check(text, ctx) || []) {
+          diagnostics.push({
+            rule_id: rule.id,
+            severity: typeof options === "string" ? options : "warn",
+            uri,
+            ...d,
+          });
+        }
+      }
+      const payload = { uri, diagnostics };
+      if (contentHash !== undefined) payload.contentHash = contentHash;
+      event("diagnostics", payload, req);
+      reply(req, { ok: true });
+    },
+    setRules(req, session) {
+      if (!session.initialized) {
+        return fail(req, "not_initialized", "initialize has not completed");
+      }
+      const { rules = {}, enabled, reset } = req.payload;
+      const unknown = Object.keys(rules)
+        .filter((ruleId) => !ruleset.rules.some((r) => r.id === ruleId))
+        .sort();
+      if (unknown.length) {
+        const message = `unknown rules: [${unknown.map((id) => JSON.stringify(id)).join(", ")}]`;
+        return reply(req, { ok: false, error: "unknown_rule", message, details: unknown });
+      }
+      if (reset) {
+        session.ruleOverrides.clear();
+        session.disabled = false;
+      }
+      for (const [ruleId, on] of Object.entries(rules)) session.ruleOverrides.set(ruleId, Boolean(on));
+      if (typeof enabled === "boolean") session.disabled = !enabled;
+      const disabledRules = [...session.ruleOverrides]
+        .filter(([, on]) => !on)
+        .map(([ruleId]) => ruleId)
+        .sort();
+      reply(req, { ok: true, enabled: !session.disabled, disabledRules });
+    },
+    cancel(req) {
+      // Requests are handled synchronously, so there is never anything to abort
+      reply(req, { ok: true });
+    },
+    shutdown(req) {
+      reply(req, { ok: true });
+      if (req.sessionId) sessions.delete(req.sessionId);
+      else shuttingDown = true;
+    },
+    exit() {
+      return shuttingDown ? "shutdown" : "exit";
+    },
+  };
+
+  // Answer one decoded message: "handled", "malformed", or `{ reason }` to stop
+  const handleMessage = (req) => {
+    if (!req || typeof req !== "object" || Array.isArray(req) || typeof req.type !== "string") {
+      fail(null, "parse_error", "malformed message");
+      return "malformed";
+    }
+    const handler = handlers[req.type];
+    if (!handler) {
+      fail(req, "unknown_type", `unknown message type: ${req.type}`);
+      return "handled";
+    }
+    if (req.type === "exit") return { reason: handler() };
+    if (shuttingDown) {
+      fail(req, "shutting_down", "only exit is accepted after shutdown");
+      return "handled";
+    }
+    req.payload = req.payload || {};
+    if (authToken !== undefined && !authenticated) {
+      const presented = req.type === "initialize" ? req.payload.authToken : undefined;
+      if (!tokensMatch(presented, authToken)) {
+        log("warn", "rejected request without a valid auth token", { type: req.type }, req);
+        fail(req, "unauthorized", "missing or invalid auth token");
+        return "handled";
+      }
+      authenticated = true;
+    }
+    // Only `initialize` creates a session; others see an uninitialized one
+    const session = sessions.get(req.sessionId || "") || newSession();
+    try {
+      handler(req, session);
+    } catch (err) {
+      const message = String(err && err.message ? err.message : err);
+      log("error", message, err && err.stack ? { stack: err.stack } : undefined, req);
+      fail(req, "internal", message);
+    }
+    return "handled";
+  };
+
+  const lines = readline.createInterface({ input, crlfDelay: Infinity });
+  let stopped = false;
+  let parseFailures = 0;
+  const terminate = (termination) => {
+    stopped = true;
+    event("terminated", termination);
+    process.exitCode = EXIT_CODES[termination.reason];
+    lines.close();
+    // Release stdin so the process exits even while the host keeps its end open
+    if (typeof input.destroy === "function") input.destroy();
+  };
+  lines.on("line", (line) => {
+    if (stopped || !line.trim()) return;
+    // Invalid JSON is reported like any other malformed message
+    let msg;
+    try {
+      msg = JSON.parse(line);
+    } catch {
+      msg = undefined;
+    }
+    let steps;
+    if (Array.isArray(msg)) {
+      // A batch line is answered with one line holding every message it produced
+      batch = [];
+      steps = [];
+      for (const req of msg) {
+        const step = handleMessage(req);
+        steps.push(step);
+        if (step.reason) break;
+      }
+      const replies = batch;
+      batch = null;
+      output.write(JSON.stringify(replies) + "\n");
+    } else {
+      steps = [handleMessage(msg)];
+    }
+    for (const step of steps) {
+      if (step.reason) return terminate({ reason: step.reason });
+      parseFailures = step === "malformed" ? parseFailures + 1 : 0;
+    }
+    if (parseFailures >= MAX_PARSE_FAILURES) {
+      terminate({ reason: "parseFailures", count: parseFailures });
+    }
+  });
+  lines.on("close", () => {
+    if (!stopped) terminate({ reason: "eof" });
+  });
+}
+
+// Compare without an early exit so response timing does not leak the prefix
+function tokensMatch(presented, expected) {
+  const a = Buffer.from(String(presented ?? ""));
+  const b = Buffer.from(expected);
+  return presented !== undefined && a.length === b.length && crypto.timingSafeEqual(a, b);
+}
+
+// Token in the variable named by `--auth-token-env <var>`, as RulesetServer's CLI reads it
+function authTokenFromArgs(argv) {
+  const i = argv.findIndex((arg) => arg === "--auth-token-env" || arg.startsWith("--auth-token-env="));
+  if (i < 0) return undefined;
+  const name = argv[i].includes("=") ? argv[i].split("=")[1] : argv[i + 1];
+  const token = name && process.env[name];
+  if (!token) throw new Error(`--auth-token-env: ${name} is not set`);
+  return token;
+}
+
+function withSession(msg, req) {
+  if (req && req.sessionId) msg.sessionId = req.sessionId;
+  if (req && req.traceId) {
+    msg.traceId = req.traceId;
+    if (msg.kind === "event" && req.id) msg.parentId = req.id;
+  }
+  return msg;
+}
+
+module.exports = { serve, PROTOCOL_VERSION };
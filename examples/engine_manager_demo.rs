@@ -1,71 +1,86 @@
-use forseti_sdk::linter::EngineManager;
+use forseti_sdk::config::{Config, find_config};
+use forseti_sdk::core::SharedConfig;
+use forseti_sdk::protocol::{AnalyzeFileParams, Request};
+use forseti_sdk::transport::{start_from_config, wait_or_kill};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-/// Demonstrates the enhanced engine management functionality
+/// Demonstrates starting the rulesets of a config and analyzing a file with them
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cache_dir = PathBuf::from("~/.forseti/cache");
+    let timeout = Duration::from_secs(10);
 
     println!("🔍 Engine Manager Demo");
     println!("======================");
 
-    // Create engine manager
-    let mut manager = EngineManager::new(cache_dir.clone());
-
-    // Discover available engines
-    println!("📦 Discovering engines in: {}", cache_dir.display());
-    let engines = manager.discover_engines()?;
-
-    if engines.is_empty() {
-        println!("❌ No engines found. Install some engines first with:");
-        println!("   forseti install");
+    // Load the nearest forseti.toml, or start from the defaults
+    let cfg = match find_config(&std::env::current_dir()?) {
+        Some(path) => {
+            println!("📦 Using config: {}", path.display());
+            Config::load_from_path(path)?
+        }
+        None => Config::from_default(),
+    };
+    if cfg.enabled_ruleset_ids().is_empty() {
+        println!("❌ No rulesets enabled. Add one to forseti.toml first:");
+        println!("   [ruleset.example]");
+        println!("   path = \"/path/to/ruleset-binary\"");
         return Ok(());
     }
 
-    println!("✅ Found {} engine(s):", engines.len());
-    for engine in &engines {
-        println!("   - {} ({})", engine.id, engine.binary_path.display());
+    // Start and initialize every enabled ruleset
+    let report = start_from_config(&SharedConfig(Arc::new(cfg)), &cache_dir, timeout);
+    for (id, e) in &report.failed {
+        println!("❌ Failed to start ruleset {id}: {e}");
     }
 
-    // Try to start the first engine
-    if let Some(first_engine) = engines.first() {
-        println!("🚀 Starting engine: {}", first_engine.id);
+    for mut ruleset in report.started {
+        println!("✅ Started ruleset: {}", ruleset.ruleset_id);
 
-        match manager.start_engine(&first_engine.id, None) {
+        // Analyze a sample file
+        let params = AnalyzeFileParams {
+            uri: "demo://sample.txt".to_string(),
+            content: "Hello world   \nThis is a test file\n".to_string(),
+            ..Default::default()
+        };
+        println!("🔍 Analyzing sample content with ruleset...");
+        match ruleset
+            .correlator
+            .call_with_timeout(Request::AnalyzeFile(params), timeout)
+        {
             Ok(_) => {
-                println!("✅ Engine started successfully");
-
-                // Analyze a sample file
-                let sample_content = "Hello world   \nThis is a test file\n";
-                let uri = "demo://sample.txt";
-
-                println!("🔍 Analyzing sample content with engine...");
+                let diagnostics: Vec<_> = ruleset
+                    .correlator
+                    .take_events()
+                    .into_iter()
+                    .filter(|event| event.typ == "diagnostics")
+                    .filter_map(|event| event.payload?.get("diagnostics").cloned())
+                    .flat_map(|diagnostics| diagnostics.as_array().cloned().unwrap_or_default())
+                    .collect();
+                println!("   Found {} diagnostic(s):", diagnostics.len());
 
-                match manager.analyze_file(&first_engine.id, uri, sample_content) {
-                    Ok(result) => {
-                        println!("✅ Analysis completed in {:?}", result.duration);
-                        println!("   Found {} diagnostic(s):", result.diagnostics.len());
-
-                        for (i, diagnostic) in result.diagnostics.iter().enumerate() {
-                            println!(
-                                "   {}. [{}] {} (line {}, col {})",
-                                i + 1,
-                                diagnostic.severity,
-                                diagnostic.message,
-                                diagnostic.range.start.line,
-                                diagnostic.range.start.character
-                            );
-                        }
-                    }
-                    Err(e) => println!("❌ Analysis failed: {}", e),
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    println!(
+                        "   {}. [{}] {} (line {}, col {})",
+                        i + 1,
+                        diagnostic["severity"],
+                        diagnostic["message"],
+                        diagnostic["range"]["start"]["line"],
+                        diagnostic["range"]["start"]["character"]
+                    );
                 }
-
-                // Shutdown the engine
-                println!("🛑 Shutting down engine...");
-                manager.shutdown_engine(&first_engine.id)?;
-                println!("✅ Engine shutdown complete");
             }
-            Err(e) => println!("❌ Failed to start engine: {}", e),
+            Err(e) => println!("❌ Analysis failed: {}", e),
+        }
+
+        // Shutdown the ruleset
+        println!("🛑 Shutting down ruleset...");
+        ruleset.correlator.shutdown(timeout)?;
+        if let Some(child) = &mut ruleset.child {
+            wait_or_kill(child, timeout)?;
         }
+        println!("✅ Ruleset shutdown complete");
     }
 
     println!("✅ Demo completed");
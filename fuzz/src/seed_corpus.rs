@@ -0,0 +1,8 @@
+use std::path::Path;
+
+fn main() -> std::io::Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let written = forseti_sdk::fuzz::write_corpus(&dir)?;
+    println!("wrote {written} seeds to {}", dir.display());
+    Ok(())
+}
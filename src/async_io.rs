@@ -0,0 +1,74 @@
+//! Tokio counterparts of the blocking NDJSON helpers (feature `tokio`).
+
+use crate::transport::MessageWriter;
+use serde::Serialize;
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+/// Async NDJSON writer.
+pub struct AsyncNdjson<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncNdjson<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub async fn send<S: Serialize>(&mut self, obj: &S) -> io::Result<()> {
+        self.send_line(&serde_json::to_string(obj)?).await
+    }
+
+    /// Write an already serialized message as one line
+    pub async fn send_line(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        self.writer.flush().await
+    }
+}
+
+/// Read one NDJSON line from `reader` as raw JSON.
+pub async fn read_line_value_async<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Value> {
+    let mut buf = String::new();
+    let n = reader.read_line(&mut buf).await?;
+    if n == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "input closed"));
+    }
+    let mut value: Value = serde_json::from_str(buf.trim())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    crate::core::decode_payload(&mut value)?;
+    Ok(value)
+}
+
+/// Hands serialized messages from synchronous handlers to an async writer task
+pub(crate) struct ChannelWriter {
+    tx: UnboundedSender<String>,
+}
+
+impl ChannelWriter {
+    pub(crate) fn new(tx: UnboundedSender<String>) -> Self {
+        Self { tx }
+    }
+}
+
+impl MessageWriter for ChannelWriter {
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.tx
+            .send(message.to_string())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output closed"))
+    }
+}
+
+/// Drain `rx` into `writer` until every sender is gone or a write fails
+pub(crate) async fn write_lines<W: AsyncWrite + Unpin>(
+    writer: W,
+    mut rx: UnboundedReceiver<String>,
+) -> io::Result<()> {
+    let mut out = AsyncNdjson::new(writer);
+    while let Some(line) = rx.recv().await {
+        out.send_line(&line).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1830 @@
+use crate::core::{
+    Clock, Diagnostic, LintResults, RuleCatalog, RuleProfile, RuleTiming, SystemClock,
+    canonical_json, glob_match, sha256_hex, uri_to_path,
+};
+use crate::progress::ProgressReporter;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+const TIMINGS_FILE: &str = "timings.json";
+
+/// Recorded analysis durations for a single file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileTiming {
+    pub last_ms: u64,
+    pub mean_ms: u64,
+    pub runs: u32,
+    /// Sum of every recorded duration, saturating; `mean_ms` is derived from it
+    #[serde(default)]
+    pub total_ms: u64,
+}
+
+/// Per-file timing history persisted in the cache directory between runs.
+/// The host records each file's analysis time after a run, starts the next
+/// one with [`TimingHistory::start_run`] so long files do not trail at the end
+/// and progress shows the predicted duration, and stores
+/// [`TimingHistory::snapshot`] in the run's
+/// [`RunMetrics::timings`](crate::core::RunMetrics::timings).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimingHistory {
+    pub files: HashMap<String, FileTiming>,
+    /// Accumulated per-rule timings from profiled runs
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rules: RuleProfile,
+}
+
+/// Point-in-time view of the timing history for metrics reporting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingSnapshot {
+    pub files_tracked: usize,
+    pub total_ms: u64,
+    /// Slowest files first as (uri, last_ms)
+    pub slowest: Vec<(String, u64)>,
+    /// Rules worth disabling or narrowing, costliest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<RuleSuggestion>,
+}
+
+impl TimingHistory {
+    /// Load the history from `cache_dir`; a missing file yields an empty history.
+    pub fn load(cache_dir: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(Self::path(cache_dir)) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(cache_dir)?;
+        let raw = serde_json::to_string(self)?;
+        std::fs::write(Self::path(cache_dir), raw)
+    }
+
+    fn path(cache_dir: &Path) -> PathBuf {
+        cache_dir.join(TIMINGS_FILE)
+    }
+
+    /// Record one analysis of `uri`.
+    pub fn record(&mut self, uri: &str, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let entry = self.files.entry(uri.to_string()).or_default();
+        if entry.total_ms == 0 {
+            // Histories written before `total_ms` existed only kept the mean
+            entry.total_ms = entry.mean_ms.saturating_mul(entry.runs as u64);
+        }
+        entry.total_ms = entry.total_ms.saturating_add(ms);
+        entry.runs = entry.runs.saturating_add(1);
+        entry.mean_ms = entry.total_ms / entry.runs as u64;
+        entry.last_ms = ms;
+    }
+
+    /// Fold the per-rule timings of one profiled run into the history
+    pub fn record_rules(&mut self, profile: &RuleProfile) {
+        for (rule_id, timing) in profile {
+            self.rules.entry(rule_id.clone()).or_default().merge(timing);
+        }
+    }
+
+    /// Order files slowest-first by their last recorded duration.
+    /// Files without history go first since they may be arbitrarily slow.
+    pub fn schedule_slowest_first(&self, uris: &mut [String]) {
+        uris.sort_by_key(|uri| {
+            std::cmp::Reverse(self.files.get(uri).map(|t| t.last_ms).unwrap_or(u64::MAX))
+        });
+    }
+
+    /// Predict the total run time for `uris`; unknown files are assumed to take
+    /// the mean of the known ones.
+    pub fn predict_total(&self, uris: &[String]) -> Duration {
+        let fallback = if self.files.is_empty() {
+            0
+        } else {
+            self.files
+                .values()
+                .fold(0u64, |sum, t| sum.saturating_add(t.mean_ms))
+                / self.files.len() as u64
+        };
+        let ms = uris
+            .iter()
+            .map(|uri| self.files.get(uri).map(|t| t.mean_ms).unwrap_or(fallback))
+            .fold(0u64, u64::saturating_add);
+        Duration::from_millis(ms)
+    }
+
+    /// Order `uris` slowest-first and announce the run to `reporter`,
+    /// with its predicted duration when the history knows any file
+    pub fn start_run(&self, uris: &mut [String], reporter: &dyn ProgressReporter) {
+        self.schedule_slowest_first(uris);
+        reporter.started(uris.len());
+        if !self.files.is_empty() {
+            reporter.predicted(self.predict_total(uris));
+        }
+    }
+
+    /// Summarize the history, listing up to `top` of the slowest files.
+    pub fn snapshot(&self, top: usize) -> TimingSnapshot {
+        let mut slowest: Vec<(String, u64)> = self
+            .files
+            .iter()
+            .map(|(uri, t)| (uri.clone(), t.last_ms))
+            .collect();
+        slowest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        slowest.truncate(top);
+        TimingSnapshot {
+            files_tracked: self.files.len(),
+            total_ms: self
+                .files
+                .values()
+                .fold(0u64, |sum, t| sum.saturating_add(t.last_ms)),
+            slowest,
+            suggestions: advise_rules(&self.rules),
+        }
+    }
+}
+
+/// Minimum share of total rule time before the advisor looks at a rule
+pub const ADVISOR_MIN_SHARE: f64 = 0.10;
+/// A rule costing this many times the average time per finding is flagged
+pub const ADVISOR_COST_RATIO: f64 = 10.0;
+
+/// What the advisor recommends doing with a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SuggestedAction {
+    Disable,
+    /// Only run the rule on files matching these patterns
+    RestrictPatterns {
+        patterns: Vec<String>,
+    },
+}
+
+/// One actionable finding of the rule advisor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSuggestion {
+    pub rule_id: String,
+    #[serde(flatten)]
+    pub action: SuggestedAction,
+    /// Fraction of all profiled rule time spent in this rule
+    pub time_share: f64,
+    pub findings: u64,
+    pub reason: String,
+}
+
+/// Flag rules whose share of run time is out of proportion to what they find:
+/// rules that never fire are suggested for disabling, rules that only fire on
+/// some extensions for narrowing, and rules far costlier per finding than
+/// average for disabling.
+pub fn advise_rules(rules: &RuleProfile) -> Vec<RuleSuggestion> {
+    let total_us: u64 = rules.values().map(|t| t.total_us).sum();
+    let total_findings: u64 = rules.values().map(|t| t.findings).sum();
+    if total_us == 0 {
+        return Vec::new();
+    }
+    let mean_cost = total_us as f64 / total_findings.max(1) as f64;
+    let mut suggestions: Vec<RuleSuggestion> = rules
+        .iter()
+        .filter_map(|(rule_id, timing)| {
+            let share = timing.total_us as f64 / total_us as f64;
+            if share < ADVISOR_MIN_SHARE {
+                return None;
+            }
+            let (action, reason) = advise_rule(timing, share, mean_cost)?;
+            Some(RuleSuggestion {
+                rule_id: rule_id.clone(),
+                action,
+                time_share: share,
+                findings: timing.findings,
+                reason,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.time_share
+            .total_cmp(&a.time_share)
+            .then_with(|| a.rule_id.cmp(&b.rule_id))
+    });
+    suggestions
+}
+
+fn advise_rule(
+    timing: &RuleTiming,
+    share: f64,
+    mean_cost: f64,
+) -> Option<(SuggestedAction, String)> {
+    let percent = share * 100.0;
+    if timing.findings == 0 {
+        return Some((
+            SuggestedAction::Disable,
+            format!(
+                "{percent:.0}% of rule time with no findings in {} files",
+                timing.files
+            ),
+        ));
+    }
+    // Extensions where the rule spends time but never fires
+    let idle_us: u64 = timing
+        .by_extension
+        .values()
+        .filter(|t| t.findings == 0)
+        .map(|t| t.total_us)
+        .sum();
+    if idle_us * 2 >= timing.total_us {
+        let patterns = timing
+            .by_extension
+            .iter()
+            .filter(|(_, t)| t.findings > 0)
+            .map(|(ext, _)| {
+                if ext.is_empty() {
+                    "**/*".to_string()
+                } else {
+                    format!("**/*.{ext}")
+                }
+            })
+            .collect();
+        return Some((
+            SuggestedAction::RestrictPatterns { patterns },
+            format!(
+                "{:.0}% of its time goes to file types where it found nothing",
+                idle_us as f64 * 100.0 / timing.total_us as f64
+            ),
+        ));
+    }
+    let cost = timing.total_us as f64 / timing.findings as f64;
+    (cost >= mean_cost * ADVISOR_COST_RATIO).then(|| {
+        (
+            SuggestedAction::Disable,
+            format!(
+                "{percent:.0}% of rule time for {} findings, {:.0}x the average cost per finding",
+                timing.findings,
+                cost / mean_cost
+            ),
+        )
+    })
+}
+
+/// Share of a directory's files with findings in generated code above which
+/// the ignore advisor suggests excluding it
+pub const NOISE_MIN_SHARE: f64 = 0.5;
+/// Directories with fewer linted files than this are left alone
+pub const NOISE_MIN_FILES: usize = 3;
+
+/// Directory names holding build output or vendored code
+pub const GENERATED_DIRS: &[&str] = &[
+    "dist",
+    "build",
+    "out",
+    "vendor",
+    "node_modules",
+    "target",
+    "generated",
+    "__generated__",
+];
+
+/// File name patterns of generated code
+pub const GENERATED_FILES: &[&str] = &[
+    "*_pb2.py",
+    "*_pb2_grpc.py",
+    "*.pb.go",
+    "*.pb.cc",
+    "*.pb.h",
+    "*.min.js",
+    "*.min.css",
+    "*.bundle.js",
+    "*.generated.*",
+    "*.g.dart",
+    "*_generated.go",
+];
+
+/// Ignore glob proposed for a directory whose findings are mostly generated code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoreSuggestion {
+    pub pattern: String,
+    pub directory: String,
+    /// Linted files in the directory
+    pub files: usize,
+    /// Of those, generated files with findings
+    pub noisy_files: usize,
+    pub findings: usize,
+    pub reason: String,
+}
+
+/// Find directories where more than [`NOISE_MIN_SHARE`] of the linted `files`
+/// are generated code with findings in `results`, and suggest an ignore glob
+/// for each: `<dir>/**` for build and vendor directories, `<dir>/<name pattern>`
+/// when the noise comes from one kind of generated file. Paths are taken as
+/// given, or decoded from `file://` URIs; directories under an already suggested `/**` are
+/// skipped. Apply the result with [`crate::config::add_ignore_patterns`].
+pub fn advise_ignores(files: &[String], results: &LintResults) -> Vec<IgnoreSuggestion> {
+    let path_of = |uri: &str| uri_to_path(uri).to_string_lossy().into_owned();
+    let mut findings: HashMap<String, usize> = HashMap::new();
+    for uri in results
+        .results
+        .iter()
+        .flat_map(|r| &r.diagnostics)
+        .filter_map(|d| d.uri.as_deref())
+    {
+        *findings.entry(path_of(uri)).or_default() += 1;
+    }
+
+    let files: Vec<String> = files.iter().map(|file| path_of(file)).collect();
+    let mut by_dir: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for path in &files {
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        by_dir.entry(dir).or_default().push(path);
+    }
+
+    let mut suggestions: Vec<IgnoreSuggestion> = Vec::new();
+    for (dir, paths) in by_dir {
+        if paths.len() < NOISE_MIN_FILES
+            || suggestions
+                .iter()
+                .any(|s| s.pattern.ends_with("/**") && is_within(dir, &s.directory))
+        {
+            continue;
+        }
+        let noisy: Vec<&str> = paths
+            .iter()
+            .copied()
+            .filter(|path| findings.contains_key(*path) && generated_kind(path).is_some())
+            .collect();
+        let share = noisy.len() as f64 / paths.len() as f64;
+        if share <= NOISE_MIN_SHARE {
+            continue;
+        }
+        let kinds: BTreeSet<&str> = noisy.iter().filter_map(|p| generated_kind(p)).collect();
+        let pattern = match kinds.iter().next() {
+            Some(kind) if kinds.len() == 1 && kind.starts_with('*') => format!("{dir}/{kind}"),
+            _ => format!("{dir}/**"),
+        };
+        suggestions.push(IgnoreSuggestion {
+            pattern,
+            directory: dir.to_string(),
+            files: paths.len(),
+            noisy_files: noisy.len(),
+            findings: noisy.iter().map(|p| findings[*p]).sum(),
+            reason: format!(
+                "{:.0}% of its files are generated code with findings ({})",
+                share * 100.0,
+                kinds.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        });
+    }
+    suggestions
+}
+
+/// The generated-code pattern `path` matches: a file name pattern, or the
+/// name of a build or vendor directory on its path
+fn generated_kind(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    GENERATED_FILES
+        .iter()
+        .find(|pattern| glob_match(pattern, name))
+        .or_else(|| {
+            path.split('/')
+                .rev()
+                .skip(1)
+                .find_map(|dir| GENERATED_DIRS.iter().find(|d| **d == dir))
+        })
+        .copied()
+}
+
+fn is_within(dir: &str, ancestor: &str) -> bool {
+    dir.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Number of trailing stderr lines kept in a crash report
+pub const CRASH_STDERR_LINES: usize = 50;
+/// Maximum bytes of the last request payload kept in a crash report
+pub const CRASH_REQUEST_BYTES: usize = 16 * 1024;
+
+/// Diagnostic bundle written when a ruleset process dies unexpectedly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub ruleset_id: String,
+    /// Process exit code, if it exited normally rather than by signal
+    pub exit_code: Option<i32>,
+    /// Last lines the process wrote to stderr
+    pub stderr_tail: Vec<String>,
+    /// Last request sent to the process, truncated to `CRASH_REQUEST_BYTES`
+    pub last_request: Option<String>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl CrashReport {
+    pub fn new(
+        ruleset_id: &str,
+        exit_code: Option<i32>,
+        stderr: &str,
+        last_request: Option<&str>,
+    ) -> Self {
+        let lines: Vec<&str> = stderr.lines().collect();
+        let skip = lines.len().saturating_sub(CRASH_STDERR_LINES);
+        Self {
+            ruleset_id: ruleset_id.to_string(),
+            exit_code,
+            stderr_tail: lines[skip..].iter().map(|l| l.to_string()).collect(),
+            last_request: last_request.map(|r| truncate_utf8(r, CRASH_REQUEST_BYTES).to_string()),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Write the report under `<cache_dir>/crashes/` and return its path. The
+    /// name is `<ruleset>-<unix seconds>.json`, or `<ruleset>-<seconds>.<n>.json`
+    /// for the n-th further crash within the same second.
+    pub fn write(&self, cache_dir: &Path) -> std::io::Result<PathBuf> {
+        let name = self
+            .ruleset_id
+            .replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+        write_unique(
+            &cache_dir.join("crashes"),
+            &format!("{}-{}", name, self.timestamp),
+            serde_json::to_string_pretty(self)?.as_bytes(),
+        )
+    }
+}
+
+/// Names [`write_unique`] tries before giving up
+const WRITE_UNIQUE_ATTEMPTS: u32 = 1000;
+
+/// Create `<dir>/<stem>.json`, or `<dir>/<stem>.<n>.json` with the smallest
+/// free `n` when that name is taken, and write `bytes` to it. Files are
+/// created exclusively, so concurrent writers never overwrite each other.
+/// Fails with the last error once [`WRITE_UNIQUE_ATTEMPTS`] names are taken.
+fn write_unique(dir: &Path, stem: &str, bytes: &[u8]) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let mut last = None;
+    for n in 0..WRITE_UNIQUE_ATTEMPTS {
+        let path = match n {
+            0 => dir.join(format!("{stem}.json")),
+            n => dir.join(format!("{stem}.{n}.json")),
+        };
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(bytes)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => last = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    let last = last.map_or_else(String::new, |e| format!(": {e}"));
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!(
+            "no free file name for {stem} in {} after {WRITE_UNIQUE_ATTEMPTS} attempts{last}",
+            dir.display()
+        ),
+    ))
+}
+
+fn truncate_utf8(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+const CATALOG_FILE: &str = "capabilities.json";
+
+/// Load the cached rule catalog, so frontends can list rules without starting
+/// every ruleset; a missing cache yields None.
+pub fn load_catalog(cache_dir: &Path) -> std::io::Result<Option<RuleCatalog>> {
+    match std::fs::read_to_string(cache_dir.join(CATALOG_FILE)) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_catalog(cache_dir: &Path, catalog: &RuleCatalog) -> std::io::Result<()> {
+    std::fs::create_dir_all(cache_dir)?;
+    std::fs::write(
+        cache_dir.join(CATALOG_FILE),
+        serde_json::to_string(catalog)?,
+    )
+}
+
+const RUNS_DIR: &str = "runs";
+
+/// Directory for the artifacts of run `run_id`, passed to rulesets as `artifactsDir`
+pub fn artifacts_dir(cache_dir: &Path, run_id: &str) -> PathBuf {
+    cache_dir.join("artifacts").join(run_id)
+}
+
+/// Summary of one lint run kept for trend reporting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub total_files: usize,
+    pub total_diagnostics: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+    pub rulesets_used: Vec<String>,
+    /// Findings per rule id
+    pub rules: BTreeMap<String, usize>,
+    /// `Diagnostic::fingerprint` of every finding
+    pub fingerprints: BTreeSet<String>,
+}
+
+impl RunRecord {
+    pub fn from_results(results: &LintResults) -> Self {
+        Self::from_results_at(results, &SystemClock)
+    }
+
+    /// [`RunRecord::from_results`], timestamped by `clock`
+    pub fn from_results_at(results: &LintResults, clock: &dyn Clock) -> Self {
+        let mut rules = BTreeMap::new();
+        let mut fingerprints = BTreeSet::new();
+        for d in results.results.iter().flat_map(|r| &r.diagnostics) {
+            *rules.entry(d.rule_id.clone()).or_insert(0) += 1;
+            fingerprints.insert(d.fingerprint());
+        }
+        Self {
+            timestamp: clock.unix_millis(),
+            total_files: results.total_files,
+            total_diagnostics: results.total_diagnostics,
+            errors: results.summary.errors,
+            warnings: results.summary.warnings,
+            info: results.summary.info,
+            rulesets_used: results.summary.rulesets_used.clone(),
+            rules,
+            fingerprints,
+        }
+    }
+
+    /// Write the record under `<cache_dir>/runs/` and return its path. The
+    /// name is `<unix millis>.json`, or `<millis>.<n>.json` for the n-th
+    /// further run within the same millisecond.
+    pub fn save(&self, cache_dir: &Path) -> std::io::Result<PathBuf> {
+        write_unique(
+            &cache_dir.join(RUNS_DIR),
+            &self.timestamp.to_string(),
+            serde_json::to_string(self)?.as_bytes(),
+        )
+    }
+}
+
+/// Finding counts of one run, as plotted in trend reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendPoint {
+    pub timestamp: u64,
+    pub total_diagnostics: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+}
+
+/// Recorded runs, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunHistory {
+    pub runs: Vec<RunRecord>,
+}
+
+impl RunHistory {
+    /// Load every run recorded in `cache_dir`; unreadable records are skipped.
+    pub fn load(cache_dir: &Path) -> std::io::Result<Self> {
+        let runs = Self::load_files(cache_dir)?
+            .into_iter()
+            .map(|(_, run)| run)
+            .collect();
+        Ok(Self { runs })
+    }
+
+    /// Readable records with the file each was loaded from, oldest first
+    fn load_files(cache_dir: &Path) -> std::io::Result<Vec<(PathBuf, RunRecord)>> {
+        let entries = match std::fs::read_dir(cache_dir.join(RUNS_DIR)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut runs: Vec<(PathBuf, RunRecord)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let raw = std::fs::read_to_string(&path).ok()?;
+                let run = serde_json::from_str(&raw).ok()?;
+                Some((path, run))
+            })
+            .collect();
+        runs.sort_by_key(|(_, run)| run.timestamp);
+        Ok(runs)
+    }
+
+    /// Finding counts over time
+    pub fn trend(&self) -> Vec<TrendPoint> {
+        self.runs
+            .iter()
+            .map(|run| TrendPoint {
+                timestamp: run.timestamp,
+                total_diagnostics: run.total_diagnostics,
+                errors: run.errors,
+                warnings: run.warnings,
+                info: run.info,
+            })
+            .collect()
+    }
+
+    /// Fingerprints in the latest run that the previous run did not have
+    pub fn new_findings(&self) -> Vec<String> {
+        match self.last_two() {
+            Some((previous, latest)) => latest
+                .fingerprints
+                .difference(&previous.fingerprints)
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Rules with findings in the latest run but none in the previous one
+    pub fn newly_introduced_rules(&self) -> Vec<String> {
+        match self.last_two() {
+            Some((previous, latest)) => latest
+                .rules
+                .keys()
+                .filter(|rule| !previous.rules.contains_key(*rule))
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn last_two(&self) -> Option<(&RunRecord, &RunRecord)> {
+        match self.runs.as_slice() {
+            [.., previous, latest] => Some((previous, latest)),
+            _ => None,
+        }
+    }
+
+    /// Delete all but the newest `keep` records from `cache_dir`
+    pub fn prune(cache_dir: &Path, keep: usize) -> std::io::Result<()> {
+        let runs = Self::load_files(cache_dir)?;
+        let excess = runs.len().saturating_sub(keep);
+        for (path, _) in &runs[..excess] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Identity of one cached per-file result. Any change to the file content, the
+/// effective config or the engine build yields a different key. Parts are
+/// SHA-256 hashes, since keys are shared with other machines through a remote.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResultKey {
+    pub content_hash: String,
+    pub config_hash: String,
+    pub engine_hash: String,
+}
+
+impl ResultKey {
+    /// Key for `content` linted with the serialized `config` by `engine`, e.g.
+    /// `"<ruleset id>@<version>"`
+    pub fn new(content: &str, config: &str, engine: &str) -> Self {
+        Self {
+            content_hash: sha256_hex(content.as_bytes()),
+            config_hash: sha256_hex(config.as_bytes()),
+            engine_hash: sha256_hex(engine.as_bytes()),
+        }
+    }
+
+    /// Key for `content` linted with `config` by `engine`; the config is
+    /// hashed in canonical form, so key order in its maps does not matter
+    pub fn for_config<C: Serialize + ?Sized>(
+        content: &str,
+        config: &C,
+        engine: &str,
+    ) -> serde_json::Result<Self> {
+        let config = canonical_json(&serde_json::to_value(config)?);
+        Ok(Self::new(content, &config, engine))
+    }
+
+    /// Storage key shared by every backend: the SHA-256 of the three parts,
+    /// 64 hex digits
+    pub fn digest(&self) -> String {
+        sha256_hex(
+            format!(
+                "{}{}{}",
+                self.content_hash, self.config_hash, self.engine_hash
+            )
+            .as_bytes(),
+        )
+    }
+}
+
+/// Content-addressed blob store behind a [`ResultCache`]
+pub trait CacheBackend: Send + Sync {
+    /// Stored bytes for `key`, or None on a miss
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>>;
+    fn put(&self, key: &str, value: &[u8]) -> std::io::Result<()>;
+}
+
+/// Backend storing one file per key under `<cache_dir>/results/`
+pub struct LocalBackend {
+    dir: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            dir: cache_dir.join("results"),
+        }
+    }
+}
+
+impl CacheBackend for LocalBackend {
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.dir.join(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        // Write then rename so concurrent readers never see a partial entry;
+        // every call gets its own temp file, so writers of one key never share it
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let n = WRITES.fetch_add(1, Ordering::Relaxed);
+        let tmp = self
+            .dir
+            .join(format!("{key}.tmp{}.{n}", std::process::id()));
+        std::fs::write(&tmp, value)?;
+        std::fs::rename(tmp, self.dir.join(key))
+    }
+}
+
+/// Largest response body [`HttpBackend`] reads by default
+pub const MAX_REMOTE_RESPONSE: u64 = 64 * 1024 * 1024;
+
+/// Remote backend speaking plain HTTP: `GET` and `PUT` on `<base>/<key>`, with
+/// 404 as a miss. Fits S3-style gateways and generic build caches such as
+/// bazel-remote or nginx with WebDAV. TLS is not supported; put an `https`
+/// store behind a local proxy. An auth token is only ever sent to a loopback
+/// address, such as that proxy.
+pub struct HttpBackend {
+    host: String,
+    port: u16,
+    /// `host[:port]` as given in the url, for the `Host` header
+    authority: String,
+    prefix: String,
+    auth_token: Option<String>,
+    timeout: Duration,
+    max_response: u64,
+}
+
+impl HttpBackend {
+    /// Backend for `url` of the form `http://host[:port][/prefix]`; IPv6
+    /// hosts are written in brackets, e.g. `http://[::1]:8080`
+    pub fn new(url: &str) -> std::io::Result<Self> {
+        let invalid =
+            |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg.to_string());
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("remote cache url must start with http://"))?;
+        let (authority, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => bracketed
+                .split_once(']')
+                .ok_or_else(|| invalid("unclosed '[' in remote cache url"))?,
+            None => authority
+                .rsplit_once(':')
+                .map_or((authority, ""), |(host, port)| (host, port)),
+        };
+        let port = match port.strip_prefix(':').unwrap_or(port) {
+            "" => 80,
+            port => port
+                .parse()
+                .map_err(|_| invalid("invalid port in remote cache url"))?,
+        };
+        if host.is_empty() {
+            return Err(invalid("remote cache url has no host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            authority: authority.to_string(),
+            prefix: prefix.trim_end_matches('/').to_string(),
+            auth_token: None,
+            timeout: Duration::from_secs(10),
+            max_response: MAX_REMOTE_RESPONSE,
+        })
+    }
+
+    /// Send `Authorization: Bearer <token>` with every request. Plain HTTP
+    /// would expose the token, so requests to a host that is not loopback
+    /// fail instead.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Limit for connecting and for each read and write
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Fail responses larger than `bytes` instead of reading them whole
+    pub fn with_max_response(mut self, bytes: u64) -> Self {
+        self.max_response = bytes;
+        self
+    }
+
+    /// Connect to the first address `host` resolves to that answers in time
+    fn connect(&self) -> std::io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} resolved to no addresses", self.host),
+            )
+        }))
+    }
+
+    fn path(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            format!("/{key}")
+        } else {
+            format!("/{}/{key}", self.prefix)
+        }
+    }
+
+    /// One HTTP/1.0 exchange, returning the status code and body. HTTP/1.0
+    /// keeps responses unchunked and closes the connection after the body.
+    fn request(&self, method: &str, key: &str, body: &[u8]) -> std::io::Result<(u16, Vec<u8>)> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut head = format!(
+            "{method} {} HTTP/1.0\r\nHost: {}\r\nContent-Length: {}\r\n",
+            self.path(key),
+            self.authority,
+            body.len()
+        );
+        if let Some(token) = &self.auth_token {
+            if !stream.peer_addr()?.ip().is_loopback() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!(
+                        "refusing to send the remote cache token over plain HTTP to {}",
+                        self.host
+                    ),
+                ));
+            }
+            head.push_str(&format!("Authorization: Bearer {token}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        let mut response = Vec::new();
+        (&mut stream)
+            .take(self.max_response.saturating_add(1))
+            .read_to_end(&mut response)?;
+        if response.len() as u64 > self.max_response {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("remote cache response exceeds {} bytes", self.max_response),
+            ));
+        }
+        let split = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "truncated HTTP response")
+            })?;
+        let status = std::str::from_utf8(&response[..split])
+            .ok()
+            .and_then(|head| head.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "malformed HTTP status line",
+                )
+            })?;
+        Ok((status, response.split_off(split + 4)))
+    }
+}
+
+impl CacheBackend for HttpBackend {
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match self.request("GET", key, &[])? {
+            (200, body) => Ok(Some(body)),
+            (404, _) => Ok(None),
+            (status, _) => Err(std::io::Error::other(format!(
+                "remote cache GET returned {status}"
+            ))),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        match self.request("PUT", key, value)? {
+            (200..=299, _) => Ok(()),
+            (status, _) => Err(std::io::Error::other(format!(
+                "remote cache PUT returned {status}"
+            ))),
+        }
+    }
+}
+
+/// Per-file diagnostics cache over a local backend and an optional shared
+/// remote. Lookups try local first and copy remote hits down; stores write to
+/// both. The cache is an optimisation, so remote failures count as misses and
+/// only local errors are returned.
+pub struct ResultCache {
+    local: Box<dyn CacheBackend>,
+    remote: Option<Box<dyn CacheBackend>>,
+}
+
+impl ResultCache {
+    pub fn new(local: Box<dyn CacheBackend>) -> Self {
+        Self {
+            local,
+            remote: None,
+        }
+    }
+
+    /// Cache under `<cache_dir>/results/`
+    pub fn open(cache_dir: &Path) -> Self {
+        Self::new(Box::new(LocalBackend::new(cache_dir)))
+    }
+
+    /// Share results through `remote`, e.g. an [`HttpBackend`] used by all CI runners
+    pub fn with_remote(mut self, remote: Box<dyn CacheBackend>) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    pub fn get(&self, key: &ResultKey) -> std::io::Result<Option<Vec<Diagnostic>>> {
+        let digest = key.digest();
+        if let Some(bytes) = self.local.get(&digest)?
+            && let Ok(diagnostics) = serde_json::from_slice(&bytes)
+        {
+            return Ok(Some(diagnostics));
+        }
+        let Some(remote) = &self.remote else {
+            return Ok(None);
+        };
+        let Ok(Some(bytes)) = remote.get(&digest) else {
+            return Ok(None);
+        };
+        let Ok(diagnostics) = serde_json::from_slice(&bytes) else {
+            return Ok(None);
+        };
+        self.local.put(&digest, &bytes)?;
+        Ok(Some(diagnostics))
+    }
+
+    pub fn put(&self, key: &ResultKey, diagnostics: &[Diagnostic]) -> std::io::Result<()> {
+        let digest = key.digest();
+        let bytes = serde_json::to_vec(diagnostics)?;
+        self.local.put(&digest, &bytes)?;
+        if let Some(remote) = &self.remote {
+            let _ = remote.put(&digest, &bytes);
+        }
+        Ok(())
+    }
+}
+
+/// Delete cached results under `<cache_dir>/results/` last written more than
+/// `max_age` ago by `clock`, and temp files left by interrupted writes.
+/// Returns how many files were removed.
+pub fn expire_results(
+    cache_dir: &Path,
+    max_age: Duration,
+    clock: &dyn Clock,
+) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(cache_dir.join("results")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let now = clock.system_time();
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        // Another sweep or writer may remove or replace entries as we go
+        let modified = match entry.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age > max_age && remove_if_present(&entry.path())? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Delete all but the newest `keep` crash reports under `<cache_dir>/crashes/`,
+/// returning how many were removed
+pub fn rotate_crash_reports(cache_dir: &Path, keep: usize) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(cache_dir.join("crashes")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    // Reports are named `<ruleset>-<unix seconds>[.<n>].json` (see
+    // `CrashReport::write`); ruleset names never contain '.'
+    let mut reports: Vec<((u64, u32), PathBuf)> = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let order = path.file_stem().and_then(|stem| {
+            let stamp = stem.to_str()?.rsplit_once('-')?.1;
+            match stamp.split_once('.') {
+                Some((seconds, n)) => Some((seconds.parse().ok()?, n.parse().ok()?)),
+                None => Some((stamp.parse().ok()?, 0)),
+            }
+        });
+        if let Some(order) = order {
+            reports.push((order, path));
+        }
+    }
+    reports.sort();
+    let excess = reports.len().saturating_sub(keep);
+    let mut removed = 0;
+    for (_, path) in &reports[..excess] {
+        if remove_if_present(path)? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn remove_if_present(path: &Path) -> std::io::Result<bool> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Periodic housekeeping for a long-lived host: expiring cached results,
+/// rotating crash reports and run history, plus tasks the host registers
+/// (e.g. shutting down rulesets idle for too long). Chores left unset are
+/// skipped. Run it inline with [`Maintenance::run_once`] or on a background
+/// thread with [`Maintenance::spawn`].
+pub struct Maintenance {
+    cache_dir: PathBuf,
+    interval: Duration,
+    result_max_age: Option<Duration>,
+    crash_reports_kept: Option<usize>,
+    runs_kept: Option<usize>,
+    tasks: Vec<Box<dyn FnMut() + Send>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Maintenance {
+    /// Maintenance of `cache_dir`, run every `interval` once spawned
+    pub fn new(cache_dir: &Path, interval: Duration) -> Self {
+        Self {
+            cache_dir: cache_dir.to_path_buf(),
+            interval,
+            result_max_age: None,
+            crash_reports_kept: None,
+            runs_kept: None,
+            tasks: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Expire cached results older than `max_age`; see [`expire_results`]
+    pub fn with_result_max_age(mut self, max_age: Duration) -> Self {
+        self.result_max_age = Some(max_age);
+        self
+    }
+
+    /// Keep only the newest `keep` crash reports; see [`rotate_crash_reports`]
+    pub fn with_crash_reports_kept(mut self, keep: usize) -> Self {
+        self.crash_reports_kept = Some(keep);
+        self
+    }
+
+    /// Keep only the newest `keep` run records; see [`RunHistory::prune`]
+    pub fn with_runs_kept(mut self, keep: usize) -> Self {
+        self.runs_kept = Some(keep);
+        self
+    }
+
+    /// Also run `task` on every pass, after the cache chores
+    pub fn with_task(mut self, task: impl FnMut() + Send + 'static) -> Self {
+        self.tasks.push(Box::new(task));
+        self
+    }
+
+    /// Judge result ages by `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Do one pass now. Every chore runs even if an earlier one fails; the
+    /// first error is returned.
+    pub fn run_once(&mut self) -> std::io::Result<()> {
+        let mut outcome = Ok(());
+        if let Some(max_age) = self.result_max_age {
+            outcome = outcome.and(expire_results(&self.cache_dir, max_age, &*self.clock).map(drop));
+        }
+        if let Some(keep) = self.crash_reports_kept {
+            outcome = outcome.and(rotate_crash_reports(&self.cache_dir, keep).map(drop));
+        }
+        if let Some(keep) = self.runs_kept {
+            outcome = outcome.and(RunHistory::prune(&self.cache_dir, keep));
+        }
+        for task in &mut self.tasks {
+            task();
+        }
+        outcome
+    }
+
+    /// Run a pass every `interval` on a background thread until the returned
+    /// handle is shut down or dropped. Errors on that thread are ignored;
+    /// housekeeping is retried on the next pass.
+    pub fn spawn(mut self) -> std::io::Result<MaintenanceHandle> {
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("forseti-maintenance".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(self.interval) {
+                    let _ = self.run_once();
+                }
+            })?;
+        Ok(MaintenanceHandle {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Owner of a background [`Maintenance`] thread. Shutting it down, or dropping
+/// it, wakes the thread and waits for any pass in progress to finish.
+pub struct MaintenanceHandle {
+    stop: Option<std::sync::mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    /// Stop the thread and wait for it to exit
+    pub fn shutdown(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        // Dropping the sender disconnects the channel, ending the wait early
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RunMetrics;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// Serve one connection with `response`, returning the request it read
+    fn serve_once(response: &'static [u8]) -> (String, JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/cache", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let Some(split) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    continue;
+                };
+                let head = String::from_utf8_lossy(&request[..split]).to_string();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .map_or(0, |n| n.parse().unwrap());
+                if n == 0 || request.len() >= split + 4 + length {
+                    break;
+                }
+            }
+            stream.write_all(response).unwrap();
+            request
+        });
+        (url, handle)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("forseti-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn unique_writes_number_taken_names() {
+        let dir = temp_dir("write-unique");
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|i| write_unique(&dir, "stem", format!("{i}").as_bytes()).unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                dir.join("stem.json"),
+                dir.join("stem.1.json"),
+                dir.join("stem.2.json")
+            ]
+        );
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(std::fs::read_to_string(path).unwrap(), i.to_string());
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unique_writes_give_up_once_every_name_is_taken() {
+        let dir = temp_dir("write-unique-full");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("stem.json"), "").unwrap();
+        for n in 1..WRITE_UNIQUE_ATTEMPTS {
+            std::fs::write(dir.join(format!("stem.{n}.json")), "").unwrap();
+        }
+        let err = write_unique(&dir, "stem", b"x").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("after 1000 attempts"), "{err}");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn crash_reports_and_run_records_never_overwrite_each_other() {
+        let dir = temp_dir("unique-reports");
+        let report = CrashReport::new("acme/rules", Some(1), "boom", None);
+        let first = report.write(&dir).unwrap();
+        let second = report.write(&dir).unwrap();
+        let stem = format!("acme_rules-{}", report.timestamp);
+        assert_eq!(first, dir.join("crashes").join(format!("{stem}.json")));
+        assert_eq!(second, dir.join("crashes").join(format!("{stem}.1.json")));
+
+        let record = RunRecord {
+            timestamp: 42,
+            ..RunRecord::default()
+        };
+        assert_eq!(record.save(&dir).unwrap(), dir.join("runs/42.json"));
+        assert_eq!(record.save(&dir).unwrap(), dir.join("runs/42.1.json"));
+        assert_eq!(RunHistory::load(&dir).unwrap().runs.len(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn timings_keep_the_last_and_mean_duration() {
+        let mut history = TimingHistory::default();
+        history.record("a.rs", ms(100));
+        history.record("a.rs", ms(300));
+        history.record("a.rs", ms(50));
+        let a = &history.files["a.rs"];
+        assert_eq!(
+            (a.last_ms, a.mean_ms, a.runs, a.total_ms),
+            (50, 150, 3, 450)
+        );
+
+        // Histories from before `total_ms` only kept the mean
+        history.files.insert(
+            "old.rs".to_string(),
+            FileTiming {
+                last_ms: 10,
+                mean_ms: 20,
+                runs: 2,
+                total_ms: 0,
+            },
+        );
+        history.record("old.rs", ms(50));
+        assert_eq!(history.files["old.rs"].mean_ms, 30);
+    }
+
+    #[test]
+    fn timing_histories_survive_a_save_and_load() {
+        let dir = temp_dir("timings");
+        assert!(TimingHistory::load(&dir).unwrap().files.is_empty());
+        let mut history = TimingHistory::default();
+        history.record("a.rs", ms(120));
+        history.save(&dir).unwrap();
+        let loaded = TimingHistory::load(&dir).unwrap();
+        assert_eq!(loaded.files["a.rs"].last_ms, 120);
+
+        std::fs::write(dir.join(TIMINGS_FILE), "{").unwrap();
+        let err = TimingHistory::load(&dir).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unknown_files_are_scheduled_first_then_slowest_first() {
+        let mut history = TimingHistory::default();
+        history.record("fast.rs", ms(10));
+        history.record("slow.rs", ms(900));
+        let mut uris = vec![
+            "fast.rs".to_string(),
+            "slow.rs".to_string(),
+            "new.rs".to_string(),
+        ];
+        history.schedule_slowest_first(&mut uris);
+        assert_eq!(uris, ["new.rs", "slow.rs", "fast.rs"]);
+    }
+
+    #[test]
+    fn predictions_assume_the_mean_for_unknown_files() {
+        let uris = ["a.rs".to_string(), "b.rs".to_string(), "new.rs".to_string()];
+        assert_eq!(
+            TimingHistory::default().predict_total(&uris),
+            Duration::ZERO
+        );
+        let mut history = TimingHistory::default();
+        history.record("a.rs", ms(100));
+        history.record("b.rs", ms(300));
+        assert_eq!(history.predict_total(&uris), ms(600));
+    }
+
+    /// Records the calls a run makes on its reporter
+    #[derive(Default)]
+    struct Calls(std::sync::Mutex<Vec<String>>);
+
+    impl ProgressReporter for Calls {
+        fn started(&self, total_files: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("started {total_files}"));
+        }
+
+        fn predicted(&self, duration: Duration) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("predicted {duration:?}"));
+        }
+
+        fn file_done(&self, _uri: &str, _diagnostics: usize) {}
+
+        fn finished(&self, _results: &LintResults) {}
+    }
+
+    #[test]
+    fn runs_start_scheduled_with_a_prediction_once_there_is_history() {
+        let mut uris = vec!["a.rs".to_string(), "b.rs".to_string()];
+        let calls = Calls::default();
+        TimingHistory::default().start_run(&mut uris, &calls);
+        assert_eq!(*calls.0.lock().unwrap(), ["started 2"]);
+
+        let mut history = TimingHistory::default();
+        history.record("a.rs", ms(100));
+        history.record("b.rs", ms(200));
+        let calls = Calls::default();
+        history.start_run(&mut uris, &calls);
+        assert_eq!(uris, ["b.rs", "a.rs"]);
+        assert_eq!(*calls.0.lock().unwrap(), ["started 2", "predicted 300ms"]);
+    }
+
+    #[test]
+    fn timing_snapshots_are_reported_with_the_run_metrics() {
+        let mut history = TimingHistory::default();
+        history.record("a.rs", ms(100));
+        history.record("b.rs", ms(300));
+        history.record("c.rs", ms(200));
+        let snapshot = history.snapshot(2);
+        assert_eq!(snapshot.files_tracked, 3);
+        assert_eq!(snapshot.total_ms, 600);
+        assert_eq!(
+            snapshot.slowest,
+            [("b.rs".to_string(), 300), ("c.rs".to_string(), 200)]
+        );
+
+        let mut metrics = RunMetrics::default();
+        assert!(metrics.is_empty());
+        metrics.timings = Some(snapshot);
+        assert!(!metrics.is_empty());
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(json["timings"]["files_tracked"], 3);
+        let mut merged = RunMetrics::default();
+        merged.merge(metrics);
+        assert_eq!(merged.timings.unwrap().total_ms, 600);
+    }
+
+    /// Runs of one rule as `(uri, ms, findings)`
+    type Runs<'a> = &'a [(&'a str, u64, usize)];
+
+    fn profile(rules: &[(&str, Runs)]) -> RuleProfile {
+        rules
+            .iter()
+            .map(|(rule_id, runs)| {
+                let mut timing = RuleTiming::default();
+                for (uri, millis, findings) in runs.iter() {
+                    timing.record(uri, ms(*millis), *findings);
+                }
+                (rule_id.to_string(), timing)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules_that_never_fire_are_suggested_for_disabling() {
+        let suggestions = advise_rules(&profile(&[
+            ("quiet", &[("a.rs", 20, 0), ("b.rs", 20, 0)]),
+            ("busy", &[("a.rs", 50, 10)]),
+            // Never fires either, but only 5% of the time
+            ("rare", &[("a.rs", 5, 0)]),
+        ]));
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert_eq!(suggestions[0].rule_id, "quiet");
+        assert_eq!(suggestions[0].action, SuggestedAction::Disable);
+        assert_eq!(suggestions[0].findings, 0);
+        assert!((suggestions[0].time_share - 40.0 / 95.0).abs() < 1e-9);
+        assert!(suggestions[0].reason.contains("no findings in 2 files"));
+    }
+
+    #[test]
+    fn rules_idle_on_most_file_types_are_narrowed_to_the_others() {
+        let suggestions = advise_rules(&profile(&[
+            ("docs", &[("a.md", 10, 3), ("b.rs", 30, 0)]),
+            // Idle on .rs too, but for under half its time
+            ("mostly", &[("a.md", 30, 3), ("b.rs", 10, 0)]),
+        ]));
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert_eq!(suggestions[0].rule_id, "docs");
+        assert_eq!(
+            suggestions[0].action,
+            SuggestedAction::RestrictPatterns {
+                patterns: vec!["**/*.md".to_string()]
+            }
+        );
+        assert!(suggestions[0].reason.starts_with("75%"));
+    }
+
+    #[test]
+    fn rules_costing_ten_times_the_average_per_finding_are_flagged() {
+        // 95ms for 1 finding against 5ms per finding overall: 19x
+        let suggestions = advise_rules(&profile(&[
+            ("slow", &[("a.rs", 95, 1)]),
+            ("fast", &[("a.rs", 5, 19)]),
+        ]));
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert_eq!(suggestions[0].rule_id, "slow");
+        assert_eq!(suggestions[0].action, SuggestedAction::Disable);
+        assert!(suggestions[0].reason.contains("19x"));
+
+        // 90ms for 1 finding against 10ms per finding overall: 9x
+        let suggestions = advise_rules(&profile(&[
+            ("slow", &[("a.rs", 90, 1)]),
+            ("fast", &[("a.rs", 10, 9)]),
+        ]));
+        assert!(suggestions.is_empty(), "{suggestions:?}");
+        assert!(advise_rules(&RuleProfile::new()).is_empty());
+    }
+
+    /// Results with one finding in each of `uris`
+    fn findings_in(uris: &[&str]) -> LintResults {
+        use crate::core::{Position, Range, ResultSummary, RulesetResult, Severity};
+        let start = Position {
+            line: 0,
+            character: 0,
+        };
+        let diagnostics: Vec<Diagnostic> = uris
+            .iter()
+            .map(|uri| {
+                let mut d = Diagnostic::new(
+                    "noise",
+                    "finding",
+                    Severity::Warn,
+                    Range { start, end: start },
+                );
+                d.uri = Some(uri.to_string());
+                d
+            })
+            .collect();
+        LintResults {
+            total_files: uris.len(),
+            total_diagnostics: diagnostics.len(),
+            execution_time_ms: 0,
+            summary: ResultSummary {
+                errors: 0,
+                warnings: diagnostics.len(),
+                info: 0,
+                hints: 0,
+                rulesets_used: vec!["demo".to_string()],
+            },
+            compatibility: Default::default(),
+            metrics: Default::default(),
+            file_stats: Default::default(),
+            unmatched: Default::default(),
+            results: vec![RulesetResult {
+                ruleset_id: "demo".to_string(),
+                diagnostics,
+                execution_time_ms: 0,
+                files_processed: uris.len(),
+            }],
+        }
+    }
+
+    fn strings(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn directories_of_noisy_generated_files_get_a_name_pattern() {
+        let files = strings(&[
+            "file:///repo/proto/a_pb2.py",
+            "file:///repo/proto/b_pb2.py",
+            "file:///repo/proto/setup.py",
+        ]);
+        let results = findings_in(&[
+            "file:///repo/proto/a_pb2.py",
+            "file:///repo/proto/a_pb2.py",
+            "file:///repo/proto/b_pb2.py",
+        ]);
+        let suggestions = advise_ignores(&files, &results);
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        let proto = &suggestions[0];
+        assert_eq!(proto.pattern, "/repo/proto/*_pb2.py");
+        assert_eq!(proto.directory, "/repo/proto");
+        assert_eq!((proto.files, proto.noisy_files, proto.findings), (3, 2, 3));
+        assert!(proto.reason.starts_with("67%"), "{}", proto.reason);
+    }
+
+    #[test]
+    fn build_directories_are_ignored_whole_with_their_subdirectories() {
+        let files = strings(&[
+            "web/dist/a.js",
+            "web/dist/b.js",
+            "web/dist/c.js",
+            "web/dist/chunks/d.js",
+            "web/dist/chunks/e.js",
+            "web/dist/chunks/f.js",
+        ]);
+        let results = findings_in(&[
+            "web/dist/a.js",
+            "web/dist/b.js",
+            "web/dist/chunks/d.js",
+            "web/dist/chunks/e.js",
+        ]);
+        let suggestions = advise_ignores(&files, &results);
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert_eq!(suggestions[0].pattern, "web/dist/**");
+    }
+
+    #[test]
+    fn quiet_small_or_hand_written_directories_are_left_alone() {
+        let files = strings(&[
+            // Half generated with findings: not more than the threshold
+            "gen/a_pb2.py",
+            "gen/b_pb2.py",
+            "gen/c.py",
+            "gen/d.py",
+            // Below the minimum file count
+            "tiny/a_pb2.py",
+            "tiny/b_pb2.py",
+            // Findings, but in hand-written code
+            "src/a.py",
+            "src/b.py",
+            "src/c.py",
+            // Generated, but without findings
+            "vendor/a.js",
+            "vendor/b.js",
+            "vendor/c.js",
+        ]);
+        let results = findings_in(&[
+            "gen/a_pb2.py",
+            "gen/b_pb2.py",
+            "tiny/a_pb2.py",
+            "tiny/b_pb2.py",
+            "src/a.py",
+            "src/b.py",
+            "src/c.py",
+        ]);
+        let suggestions = advise_ignores(&files, &results);
+        assert!(suggestions.is_empty(), "{suggestions:?}");
+    }
+
+    #[test]
+    fn concurrent_puts_of_one_key_never_share_a_temp_file() {
+        let dir = temp_dir("local-puts");
+        let backend = LocalBackend::new(&dir);
+        std::thread::scope(|scope| {
+            for t in 0..8u8 {
+                let backend = &backend;
+                scope.spawn(move || {
+                    for _ in 0..25 {
+                        backend.put("key", &[t; 4096]).unwrap();
+                    }
+                });
+            }
+        });
+        let stored = backend.get("key").unwrap().unwrap();
+        assert_eq!(stored.len(), 4096);
+        assert!(stored.iter().all(|&b| b == stored[0]));
+        let names: Vec<_> = std::fs::read_dir(dir.join("results"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["key"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expiry_removes_old_entries_and_skips_vanished_ones() {
+        let dir = temp_dir("expire");
+        let backend = LocalBackend::new(&dir);
+        backend.put("fresh", b"1").unwrap();
+        let clock = crate::core::MockClock::at(std::time::SystemTime::now());
+        assert_eq!(
+            expire_results(&dir, Duration::from_secs(60), &clock).unwrap(),
+            0
+        );
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(
+            expire_results(&dir, Duration::from_secs(60), &clock).unwrap(),
+            1
+        );
+        assert_eq!(backend.get("fresh").unwrap(), None);
+
+        // Entries deleted by someone else mid-sweep are not an error
+        for i in 0..500 {
+            backend.put(&format!("k{i}"), b"x").unwrap();
+        }
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..500 {
+                    let _ = std::fs::remove_file(dir.join("results").join(format!("k{i}")));
+                }
+            });
+            expire_results(&dir, Duration::from_secs(60), &clock).unwrap();
+        });
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn backend(url: &str) -> HttpBackend {
+        HttpBackend::new(url)
+            .unwrap()
+            .with_timeout(Duration::from_secs(5))
+    }
+
+    #[test]
+    fn urls_are_split_into_host_port_and_prefix() {
+        let plain = HttpBackend::new("http://cache.example").unwrap();
+        assert_eq!((plain.host.as_str(), plain.port), ("cache.example", 80));
+        assert_eq!(plain.path("abc"), "/abc");
+
+        let full = HttpBackend::new("http://cache.example:9090/team/results/").unwrap();
+        assert_eq!((full.host.as_str(), full.port), ("cache.example", 9090));
+        assert_eq!(full.authority, "cache.example:9090");
+        assert_eq!(full.path("abc"), "/team/results/abc");
+
+        let v6 = HttpBackend::new("http://[::1]:8080/x").unwrap();
+        assert_eq!((v6.host.as_str(), v6.port), ("::1", 8080));
+        assert_eq!(v6.authority, "[::1]:8080");
+    }
+
+    #[test]
+    fn invalid_urls_are_rejected() {
+        for url in [
+            "https://cache.example",
+            "http://:8080",
+            "http://cache.example:port",
+            "http://[::1:8080",
+        ] {
+            let err = HttpBackend::new(url).err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{url}");
+        }
+    }
+
+    #[test]
+    fn get_returns_the_body_of_a_200() {
+        let (url, server) = serve_once(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        assert_eq!(backend(&url).get("k1").unwrap(), Some(b"hello".to_vec()));
+        let request = String::from_utf8(server.join().unwrap()).unwrap();
+        let host = url.trim_start_matches("http://").trim_end_matches("/cache");
+        assert_eq!(
+            request,
+            format!("GET /cache/k1 HTTP/1.0\r\nHost: {host}\r\nContent-Length: 0\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn get_treats_404_as_a_miss_and_other_statuses_as_errors() {
+        let (url, server) = serve_once(b"HTTP/1.0 404 Not Found\r\n\r\n");
+        assert_eq!(backend(&url).get("k").unwrap(), None);
+        server.join().unwrap();
+
+        let (url, server) = serve_once(b"HTTP/1.0 503 Service Unavailable\r\n\r\nbusy");
+        let err = backend(&url).get("k").unwrap_err();
+        assert!(err.to_string().contains("503"), "{err}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn put_sends_the_body_and_accepts_any_2xx() {
+        let (url, server) = serve_once(b"HTTP/1.0 201 Created\r\n\r\n");
+        backend(&url).put("k", b"payload").unwrap();
+        let request = server.join().unwrap();
+        assert!(request.starts_with(b"PUT /cache/k HTTP/1.0\r\n"));
+        assert!(request.ends_with(b"Content-Length: 7\r\n\r\npayload"));
+
+        let (url, server) = serve_once(b"HTTP/1.0 403 Forbidden\r\n\r\n");
+        let err = backend(&url).put("k", b"payload").unwrap_err();
+        assert!(err.to_string().contains("403"), "{err}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn auth_tokens_are_sent_to_loopback_hosts() {
+        let (url, server) = serve_once(b"HTTP/1.0 404 Not Found\r\n\r\n");
+        let backend = backend(&url).with_auth_token("s3cret");
+        assert_eq!(backend.get("k").unwrap(), None);
+        let request = String::from_utf8(server.join().unwrap()).unwrap();
+        assert!(
+            request.contains("\r\nAuthorization: Bearer s3cret\r\n"),
+            "{request}"
+        );
+    }
+
+    #[test]
+    fn truncated_and_malformed_responses_are_invalid_data() {
+        for response in [
+            &b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n"[..],
+            b"",
+            b"garbage\r\n\r\nbody",
+            b"HTTP/1.0 abc OK\r\n\r\n",
+        ] {
+            let (url, server) = serve_once(response);
+            let err = backend(&url).get("k").unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{response:?}");
+            server.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn oversized_responses_are_refused() {
+        let (url, server) = serve_once(b"HTTP/1.0 200 OK\r\n\r\n0123456789");
+        let err = backend(&url).with_max_response(16).get("k").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("exceeds 16 bytes"), "{err}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn unreachable_hosts_fail_to_connect() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let backend = backend(&format!("http://127.0.0.1:{port}"));
+        assert!(backend.get("k").is_err());
+    }
+}
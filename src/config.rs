@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use std::string::String;
 use thiserror::Error;
 
+pub use crate::types::LogLevel;
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("io error: {0}")]
@@ -11,6 +13,10 @@ pub enum ConfigError {
     Parse(#[from] toml::de::Error),
     #[error("validation error: {0}")]
     Validation(String),
+    #[error("edit error: {0}")]
+    Edit(#[from] toml_edit::TomlError),
+    #[error("conversion error at `{path}`: {reason}")]
+    Conversion { path: String, reason: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +26,12 @@ pub struct Config {
     pub linter: LinterCfg,
     #[serde(default)]
     pub ruleset: HashMap<String, RulesetCfg>,
+    /// Logical rule name => equivalent qualified rule ids ("<ruleset>/<rule>")
+    #[serde(default)]
+    pub equivalences: HashMap<String, Vec<String>>,
+    /// Where each explicitly set key came from (not part of the file format)
+    #[serde(skip)]
+    pub provenance: Provenance,
 }
 
 impl Config {
@@ -28,16 +40,36 @@ impl Config {
         Self {
             linter: LinterCfg::default(),
             ruleset: HashMap::new(),
+            equivalences: HashMap::new(),
+            provenance: Provenance::default(),
         }
     }
 
+    /// Hash of the effective config, equal for equal configs however they were
+    /// assembled; provenance is not part of it
+    pub fn hash(&self) -> Result<String, serde_json::Error> {
+        crate::core::canonical_hash(self)
+    }
+
     pub fn load_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
-        let raw = std::fs::read_to_string(path)?;
-        Self::load_from_str(&raw)
+        let raw = std::fs::read_to_string(path.as_ref())?;
+        Self::load_with_source(&raw, &path.as_ref().display().to_string())
     }
 
     pub fn load_from_str(raw: &str) -> Result<Self, ConfigError> {
+        Self::load_with_source(raw, "<string>")
+    }
+
+    fn load_with_source(raw: &str, source: &str) -> Result<Self, ConfigError> {
         let mut cfg: Config = toml::from_str(raw)?;
+        let table: toml::Table = toml::from_str(raw)?;
+        cfg.provenance.record_table(
+            "",
+            &table,
+            &ConfigSource::File {
+                path: source.to_string(),
+            },
+        );
         cfg.apply_defaults();
         cfg.validate()?;
         Ok(cfg)
@@ -52,9 +84,207 @@ impl Config {
         // Keys are unique by virtue of HashMap. Add rules here if needed.
         // Example: ensure at least one enabled engine/ruleset (optional):
         // if !self.engine.values().any(|e| e.enabled) { ... }
+        self.rule_equivalences()?;
+        for (id, ruleset) in &self.ruleset {
+            let launchers = [
+                ruleset.path.is_some(),
+                ruleset.entry.is_some(),
+                ruleset.address.is_some(),
+            ];
+            if launchers.iter().filter(|set| **set).count() > 1 {
+                return Err(ConfigError::Validation(format!(
+                    "ruleset.{}: `path`, `entry` and `address` are mutually exclusive",
+                    id
+                )));
+            }
+            if ruleset.auth_token_env.is_some() && ruleset.address.is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "ruleset.{}: `auth_token_env` only applies with `address`",
+                    id
+                )));
+            }
+        }
         Ok(())
     }
 
+    /// Drop findings below each ruleset's `min_severity`
+    pub fn apply_min_severity(
+        &self,
+        results: &crate::core::LintResults,
+    ) -> crate::core::LintResults {
+        results.filtered_by_ruleset(|ruleset_id, d| {
+            self.ruleset
+                .get(ruleset_id)
+                .is_none_or(|ruleset| ruleset.accepts(d))
+        })
+    }
+
+    /// The `[linter.filter]` filter, matching paths relative to `root`. Hosts
+    /// apply it once results are aggregated, before any output sink.
+    pub fn diagnostic_filter(&self, root: &std::path::Path) -> crate::core::DiagnosticFilter {
+        self.linter.filter.clone().with_root(root.to_string_lossy())
+    }
+
+    /// Ids of the enabled rulesets, sorted
+    pub fn enabled_ruleset_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self
+            .ruleset
+            .iter()
+            .filter(|(_, ruleset)| ruleset.enabled)
+            .map(|(id, _)| id.as_str())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// How to start ruleset `id` and what to initialize it with
+    pub fn launch_spec(&self, id: &str) -> Result<LaunchSpec, ConfigError> {
+        let ruleset = self
+            .ruleset
+            .get(id)
+            .ok_or_else(|| ConfigError::Validation(format!("no ruleset.{} configured", id)))?;
+        let mut initialize = ruleset.initialize_params()?;
+        initialize.log_level = Some(self.linter.log_level);
+        Ok(LaunchSpec {
+            ruleset_id: id.to_string(),
+            launch: ruleset.launch()?,
+            initialize,
+        })
+    }
+
+    /// [`Config::launch_spec`] for every enabled ruleset. All are resolved;
+    /// failures are returned together.
+    pub fn launch_specs(&self) -> Result<Vec<LaunchSpec>, LaunchErrors> {
+        let mut specs = Vec::new();
+        let mut failures = Vec::new();
+        for id in self.enabled_ruleset_ids() {
+            match self.launch_spec(id) {
+                Ok(spec) => specs.push(spec),
+                Err(e) => failures.push((id.to_string(), e)),
+            }
+        }
+        if failures.is_empty() {
+            Ok(specs)
+        } else {
+            Err(LaunchErrors { failures })
+        }
+    }
+
+    /// Parsed `[equivalences]` table for the merger, baseline and suppressions
+    pub fn rule_equivalences(&self) -> Result<crate::core::RuleEquivalences, ConfigError> {
+        crate::core::RuleEquivalences::new(&self.equivalences).map_err(ConfigError::Validation)
+    }
+
+    /// Where the effective value of `key_path` (e.g. "ruleset.foo.enabled") came from.
+    /// Keys never set explicitly report the closest explicitly set parent, else `Default`.
+    pub fn explain(&self, key_path: &str) -> ConfigSource {
+        let mut path = key_path;
+        loop {
+            if let Some(source) = self.provenance.entries.get(path) {
+                return source.clone();
+            }
+            match path.rfind('.') {
+                Some(i) => path = &path[..i],
+                None => return ConfigSource::Default,
+            }
+        }
+    }
+
+    /// The fully merged configuration that applies to the file at `path`: per-rule
+    /// level and options for every enabled ruleset, plus where each rule entry came from.
+    /// A file matched by a `linter.ignore` glob is linted by nothing, so it gets
+    /// no rulesets and `ignored_by` names the glob. Fails when a rule or
+    /// setting value has no JSON form.
+    pub fn resolve_effective_config(
+        &self,
+        path: &std::path::Path,
+    ) -> Result<EffectiveConfig, ConfigError> {
+        let shown = path.to_string_lossy().replace('\\', "/");
+        let relative = shown.strip_prefix("./").unwrap_or(&shown);
+        let ignored_by = self
+            .linter
+            .ignore
+            .iter()
+            .find(|pattern| crate::core::glob_match(pattern, relative))
+            .cloned();
+        let rulesets = self
+            .ruleset
+            .iter()
+            .filter(|(_, cfg)| cfg.enabled && ignored_by.is_none())
+            .map(|(id, cfg)| {
+                let rules = cfg
+                    .config
+                    .iter()
+                    .map(|(rule_id, value)| {
+                        let value = toml_to_json(value)?;
+                        let (level, options) = split_rule_value(&value);
+                        let rule = EffectiveRule {
+                            level,
+                            options,
+                            source: self.explain(&format!("ruleset.{}.config.{}", id, rule_id)),
+                        };
+                        Ok((rule_id.clone(), rule))
+                    })
+                    .collect::<Result<_, ConfigError>>()?;
+                let settings = cfg
+                    .settings
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), toml_to_json(value)?)))
+                    .collect::<Result<_, ConfigError>>()?;
+                let ruleset = EffectiveRuleset {
+                    settings: serde_json::Value::Object(settings),
+                    rules,
+                };
+                Ok((id.clone(), ruleset))
+            })
+            .collect::<Result<_, ConfigError>>()?;
+
+        Ok(EffectiveConfig {
+            path: shown,
+            ignored_by,
+            linter: self.linter.clone(),
+            rulesets,
+        })
+    }
+
+    /// Rule ids switched on in each enabled ruleset, for [`crate::core::CoverageReport`]:
+    /// the configured rules, or the ruleset's defaults from `catalog` when its
+    /// config table is empty. Rules set to "off" are left out. Fails when a
+    /// rule value has no JSON form.
+    pub fn enabled_rules(
+        &self,
+        catalog: &crate::core::RuleCatalog,
+    ) -> Result<HashMap<String, Vec<String>>, ConfigError> {
+        let is_on = |value: &serde_json::Value| split_rule_value(value).0.as_deref() != Some("off");
+        self.ruleset
+            .iter()
+            .filter(|(_, cfg)| cfg.enabled)
+            .map(|(id, cfg)| {
+                let rules = if cfg.config.is_empty() {
+                    catalog
+                        .ruleset(id)
+                        .map(|rs| {
+                            rs.default_config
+                                .iter()
+                                .filter(|(_, value)| is_on(value))
+                                .map(|(rule_id, _)| rule_id.clone())
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                } else {
+                    let mut rules = Vec::new();
+                    for (rule_id, value) in &cfg.config {
+                        if is_on(&toml_to_json(value)?) {
+                            rules.push(rule_id.clone());
+                        }
+                    }
+                    rules
+                };
+                Ok((id.clone(), rules))
+            })
+            .collect()
+    }
+
     /// Merge overrides from OS environment (std::env::var).
     pub fn merge_env_overrides_from_os(&mut self) {
         self.merge_env_overrides(|k| std::env::var(k).ok());
@@ -67,27 +297,86 @@ impl Config {
             && let Ok(parsed) = parse_log_level(&v)
         {
             self.linter.log_level = parsed;
+            self.provenance.record(
+                "linter.log_level",
+                ConfigSource::env("FORSETI_LINTER_LOG_LEVEL"),
+            );
         }
         if let Some(v) = get("FORSETI_LINTER_OUTPUT_FORMAT")
             && let Ok(parsed) = parse_output_format(&v)
         {
             self.linter.output_format = parsed;
+            self.provenance.record(
+                "linter.output_format",
+                ConfigSource::env("FORSETI_LINTER_OUTPUT_FORMAT"),
+            );
         }
         if let Some(v) = get("FORSETI_LINTER_PARALLELISM")
             && let Ok(n) = v.parse::<u16>()
         {
             self.linter.parallelism = n;
+            self.provenance.record(
+                "linter.parallelism",
+                ConfigSource::env("FORSETI_LINTER_PARALLELISM"),
+            );
         }
         if let Some(v) = get("FORSETI_LINTER_FAIL_ON_ERROR")
             && let Ok(b) = parse_bool(&v)
         {
             self.linter.fail_on_error = b;
+            self.provenance.record(
+                "linter.fail_on_error",
+                ConfigSource::env("FORSETI_LINTER_FAIL_ON_ERROR"),
+            );
+        }
+        if let Some(v) = get("FORSETI_LINTER_STRICT_PROTOCOL")
+            && let Ok(b) = parse_bool(&v)
+        {
+            self.linter.strict_protocol = b;
+            self.provenance.record(
+                "linter.strict_protocol",
+                ConfigSource::env("FORSETI_LINTER_STRICT_PROTOCOL"),
+            );
+        }
+        if let Some(v) = get("FORSETI_LINTER_LOCALE") {
+            self.linter.locale = Some(v.trim().to_string()).filter(|l| !l.is_empty());
+            self.provenance
+                .record("linter.locale", ConfigSource::env("FORSETI_LINTER_LOCALE"));
+        }
+        if let Some(v) = get("FORSETI_LINTER_ONLY_CATEGORIES") {
+            self.linter.only_categories = parse_csv_ids(&v);
+            self.provenance.record(
+                "linter.only_categories",
+                ConfigSource::env("FORSETI_LINTER_ONLY_CATEGORIES"),
+            );
+        }
+        if let Some(v) = get("FORSETI_LINTER_SKIP_CATEGORIES") {
+            self.linter.skip_categories = parse_csv_ids(&v);
+            self.provenance.record(
+                "linter.skip_categories",
+                ConfigSource::env("FORSETI_LINTER_SKIP_CATEGORIES"),
+            );
+        }
+        if let Some(v) = get("FORSETI_LINTER_MAX_BYTES_IN_FLIGHT")
+            && let Ok(n) = v.parse::<u64>()
+        {
+            self.linter.max_bytes_in_flight = n;
+            self.provenance.record(
+                "linter.max_bytes_in_flight",
+                ConfigSource::env("FORSETI_LINTER_MAX_BYTES_IN_FLIGHT"),
+            );
         }
 
 
         // ---- RULESETS ----
         if let Some(ids) = get("FORSETI_RULESET_IDS") {
             for id in parse_csv_ids(&ids) {
+                if !self.ruleset.contains_key(&id) {
+                    self.provenance.record(
+                        &format!("ruleset.{}", id),
+                        ConfigSource::env("FORSETI_RULESET_IDS"),
+                    );
+                }
                 self.ruleset.entry(id).or_default();
             }
         }
@@ -100,6 +389,10 @@ impl Config {
                 && let Some(cfg) = self.ruleset.get_mut(&id)
             {
                 cfg.enabled = b;
+                self.provenance.record(
+                    &format!("ruleset.{}.enabled", id),
+                    ConfigSource::env(&k_enabled),
+                );
             }
 
             let k_cfg = format!("FORSETI_RULESET_{}_CONFIG_JSON", upper(&id));
@@ -109,6 +402,104 @@ impl Config {
                 && let Some(rs) = self.ruleset.get_mut(&id)
             {
                 merge_json_object_into_toml_table(obj, &mut rs.config);
+                for key in obj.keys() {
+                    self.provenance.record(
+                        &format!("ruleset.{}.config.{}", id, key),
+                        ConfigSource::env(&k_cfg),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Resolved configuration for a single file, serializable for debugging and editors
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveConfig {
+    pub path: String,
+    /// The `linter.ignore` glob excluding `path`, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignored_by: Option<String>,
+    pub linter: LinterCfg,
+    /// Enabled rulesets only; none when `path` is ignored
+    pub rulesets: std::collections::BTreeMap<String, EffectiveRuleset>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveRuleset {
+    pub settings: serde_json::Value,
+    pub rules: std::collections::BTreeMap<String, EffectiveRule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveRule {
+    /// "off" | "warn" | "error" | ...; None => the ruleset's default severity
+    pub level: Option<String>,
+    pub options: serde_json::Value,
+    pub source: ConfigSource,
+}
+
+/// Split a rule config value (`"warn"`, `["error", {..}]` or `{..}`) into level and options
+fn split_rule_value(v: &serde_json::Value) -> (Option<String>, serde_json::Value) {
+    use serde_json::Value;
+    match v {
+        Value::String(level) => (Some(level.clone()), Value::Null),
+        Value::Array(items) => (
+            items
+                .first()
+                .and_then(|l| l.as_str())
+                .map(|l| l.to_string()),
+            items.get(1).cloned().unwrap_or(Value::Null),
+        ),
+        other => (None, other.clone()),
+    }
+}
+
+/// Configuration layer a setting was taken from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "layer")]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// Config file (path, or "<string>" when loaded from memory)
+    File { path: String },
+    /// Environment variable override
+    Env { var: String },
+}
+
+impl ConfigSource {
+    fn env(var: &str) -> Self {
+        ConfigSource::Env {
+            var: var.to_string(),
+        }
+    }
+}
+
+/// Per-key provenance recorded while config layers are merged
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    /// Dotted key path (e.g. "linter.log_level") => layer that last set it
+    pub entries: HashMap<String, ConfigSource>,
+}
+
+impl Provenance {
+    pub fn record(&mut self, key_path: &str, source: ConfigSource) {
+        self.entries.insert(key_path.to_string(), source);
+    }
+
+    /// Record every leaf key of a TOML table under `prefix`
+    fn record_table(&mut self, prefix: &str, table: &toml::Table, source: &ConfigSource) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            match value {
+                toml::Value::Table(inner) if !inner.is_empty() => {
+                    self.record_table(&path, inner, source)
+                }
+                _ => self.record(&path, source.clone()),
             }
         }
     }
@@ -148,6 +539,8 @@ fn parse_output_format(s: &str) -> Result<OutputFormat, ()> {
         "ndjson" => Ok(OutputFormat::Ndjson),
         "text" => Ok(OutputFormat::Text),
         "sarif" => Ok(OutputFormat::Sarif),
+        "checkstyle" => Ok(OutputFormat::Checkstyle),
+        "gitlab" => Ok(OutputFormat::Gitlab),
         _ => Err(()),
     }
 }
@@ -163,48 +556,121 @@ fn merge_json_object_into_toml_table(
     toml_tbl: &mut toml::value::Table,
 ) {
     for (k, v) in json_obj {
-        if let Some(tv) = json_to_toml_value(v) {
+        if let Ok(tv) = json_to_toml(v) {
             toml_tbl.insert(k.clone(), tv);
         }
     }
 }
 
-fn json_to_toml_value(v: &serde_json::Value) -> Option<toml::Value> {
-    use serde_json::Value::*;
-    Some(match v {
-        Null => toml::Value::String(std::string::String::new()), // or return None to skip nulls
-        Bool(b) => toml::Value::Boolean(*b),
-        Number(n) => {
-            if let Some(i) = n.as_i64() {
-                toml::Value::Integer(i)
-            } else if let Some(f) = n.as_f64() {
-                toml::Value::Float(f)
-            } else {
-                return None;
-            }
-        }
-        String(s) => toml::Value::String(s.clone()),
-        Array(a) => {
-            let mut out = Vec::with_capacity(a.len());
-            for item in a {
-                if let Some(tv) = json_to_toml_value(item) {
-                    out.push(tv)
-                }
+/// Key of the single-entry object a TOML datetime becomes in JSON; the same
+/// form `toml` itself serializes datetimes to, so serde round-trips it too
+pub const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Convert a TOML value to JSON. Datetimes become `{"$__toml_private_datetime":
+/// "<TOML text>"}`, see [`TOML_DATETIME_KEY`], so they stay distinct from
+/// strings; NaN and infinite floats have no JSON form and are an error
+pub fn toml_to_json(value: &toml::Value) -> Result<serde_json::Value, ConfigError> {
+    toml_to_json_at(value, "$")
+}
+
+fn toml_to_json_at(value: &toml::Value, path: &str) -> Result<serde_json::Value, ConfigError> {
+    use serde_json::Value;
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s.clone()),
+        toml::Value::Integer(i) => Value::from(*i),
+        toml::Value::Float(f) => serde_json::Number::from_f64(*f)
+            .map(Value::Number)
+            .ok_or_else(|| conversion(path, format!("{} has no JSON representation", f)))?,
+        toml::Value::Boolean(b) => Value::Bool(*b),
+        toml::Value::Datetime(d) => serde_json::json!({ TOML_DATETIME_KEY: d.to_string() }),
+        toml::Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| toml_to_json_at(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(toml_table_to_json_at(table, path)?),
+    })
+}
+
+fn toml_table_to_json_at(
+    table: &toml::value::Table,
+    path: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, ConfigError> {
+    table
+        .iter()
+        .map(|(key, value)| {
+            Ok((
+                key.clone(),
+                toml_to_json_at(value, &format!("{}.{}", path, key))?,
+            ))
+        })
+        .collect()
+}
+
+/// Convert a JSON value to TOML, the inverse of [`toml_to_json`]. Only the
+/// tagged datetime objects it produces become datetimes, strings stay strings;
+/// `null` and integers outside the `i64` range TOML allows are an error rather
+/// than being dropped
+pub fn json_to_toml(value: &serde_json::Value) -> Result<toml::Value, ConfigError> {
+    json_to_toml_at(value, "$")
+}
+
+fn json_to_toml_at(value: &serde_json::Value, path: &str) -> Result<toml::Value, ConfigError> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => return Err(conversion(path, "TOML has no null".to_string())),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) => toml::Value::Integer(i),
+            (None, Some(u), _) => {
+                return Err(conversion(
+                    path,
+                    format!("{} is out of range for a TOML integer", u),
+                ));
             }
-            toml::Value::Array(out)
-        }
-        Object(o) => {
-            let mut tbl = toml::map::Map::new();
-            for (kk, vv) in o {
-                if let Some(tv) = json_to_toml_value(vv) {
-                    tbl.insert(kk.clone(), tv);
-                }
+            (None, None, Some(f)) => toml::Value::Float(f),
+            (None, None, None) => {
+                return Err(conversion(path, format!("{} is not representable", n)));
             }
-            toml::Value::Table(tbl)
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Object(object) if object.len() == 1 && object.contains_key(TOML_DATETIME_KEY) => {
+            let text = object[TOML_DATETIME_KEY].as_str().unwrap_or_default();
+            toml::Value::Datetime(
+                text.parse()
+                    .map_err(|_| conversion(path, format!("{:?} is not a TOML datetime", text)))?,
+            )
         }
+        Value::Array(items) => toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| json_to_toml_at(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => toml::Value::Table(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    Ok((
+                        key.clone(),
+                        json_to_toml_at(value, &format!("{}.{}", path, key))?,
+                    ))
+                })
+                .collect::<Result<_, ConfigError>>()?,
+        ),
     })
 }
 
+fn conversion(path: &str, reason: String) -> ConfigError {
+    ConfigError::Conversion {
+        path: path.to_string(),
+        reason,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct LinterCfg {
@@ -217,10 +683,116 @@ pub struct LinterCfg {
     pub parallelism: u16,
     #[serde(default = "default_fail_on_error")]
     pub fail_on_error: bool,
+    /// Cap on file content resident at once during analysis, in bytes; 0 => unbounded
+    #[serde(default)]
+    pub max_bytes_in_flight: u64,
+    /// Only run rules in these categories (empty => all)
+    #[serde(default)]
+    pub only_categories: Vec<String>,
+    /// Never run rules in these categories
+    #[serde(default)]
+    pub skip_categories: Vec<String>,
+    /// Locale for rendering diagnostic messages (e.g. "de"); English when unset
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Truncate floods of findings; set false to keep every diagnostic
+    #[serde(default = "default_cap_diagnostics")]
+    pub cap_diagnostics: bool,
+    /// Max diagnostics kept per file when capping; 0 => unlimited
+    #[serde(default = "default_max_diagnostics_per_file")]
+    pub max_diagnostics_per_file: usize,
+    /// Max diagnostics kept per rule within a file when capping; 0 => unlimited
+    #[serde(default)]
+    pub max_diagnostics_per_rule: usize,
+    /// How duplicate findings from different rulesets are merged
+    #[serde(default)]
+    pub merge_duplicates: crate::core::MergeHeuristic,
+    /// Additional output sinks, each with its own filters (`[[linter.outputs]]`)
+    #[serde(default)]
+    pub outputs: Vec<OutputSinkCfg>,
+    /// Glob patterns of paths that are never linted
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Fail on any protocol irregularity from a ruleset instead of tolerating
+    /// it; for SDK and ruleset development
+    #[serde(default)]
+    pub strict_protocol: bool,
+    /// Findings dropped before any output (`[linter.filter]`)
+    #[serde(default)]
+    pub filter: crate::core::DiagnosticFilter,
+}
+
+/// One output destination with its own severity threshold and rule filters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct OutputSinkCfg {
+    pub format: OutputFormat,
+    /// File to write to; stdout when omitted
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Drop findings below this severity
+    #[serde(default)]
+    pub min_severity: Option<crate::core::Severity>,
+    /// When non-empty, only these rule ids are written
+    #[serde(default)]
+    pub include_rules: Vec<String>,
+    /// Rule ids never written to this sink
+    #[serde(default)]
+    pub exclude_rules: Vec<String>,
+}
+
+impl OutputSinkCfg {
+    /// Whether a diagnostic passes this sink's filters
+    pub fn accepts(&self, d: &crate::core::Diagnostic) -> bool {
+        if let Some(min) = self.min_severity
+            && d.severity < min
+        {
+            return false;
+        }
+        if self.exclude_rules.contains(&d.rule_id) {
+            return false;
+        }
+        self.include_rules.is_empty() || self.include_rules.contains(&d.rule_id)
+    }
+
+    /// The results as this sink should see them, applied before its formatter runs
+    pub fn apply(&self, results: &crate::core::LintResults) -> crate::core::LintResults {
+        results.filtered(|d| self.accepts(d))
+    }
+}
+
+impl LinterCfg {
+    /// Caps to apply during aggregation, or None when capping is disabled
+    pub fn diagnostic_caps(&self) -> Option<crate::core::DiagnosticCaps> {
+        self.cap_diagnostics.then_some(crate::core::DiagnosticCaps {
+            per_file: self.max_diagnostics_per_file,
+            per_rule: self.max_diagnostics_per_rule,
+        })
+    }
+
+    pub fn category_filter(&self) -> crate::core::CategoryFilter {
+        crate::core::CategoryFilter {
+            only: self.only_categories.clone(),
+            skip: self.skip_categories.clone(),
+        }
+    }
+
+    /// `file_uris` split into batches to preprocess and analyze one after the
+    /// other, so at most `max_bytes_in_flight` bytes of content are loaded at
+    /// once (see [`crate::ruleset::run_ruleset_bounded`])
+    pub fn file_batches(&self, file_uris: &[String]) -> Vec<Vec<String>> {
+        crate::ruleset::batch_files_by_size(file_uris, self.max_bytes_in_flight)
+    }
 }
 fn default_fail_on_error() -> bool {
     true
 }
+fn default_cap_diagnostics() -> bool {
+    true
+}
+fn default_max_diagnostics_per_file() -> usize {
+    1000
+}
 impl Default for LinterCfg {
     fn default() -> Self {
         Self {
@@ -228,6 +800,18 @@ impl Default for LinterCfg {
             output_format: OutputFormat::Json,
             parallelism: 0,
             fail_on_error: true,
+            max_bytes_in_flight: 0,
+            only_categories: Vec::new(),
+            skip_categories: Vec::new(),
+            locale: None,
+            cap_diagnostics: true,
+            max_diagnostics_per_file: default_max_diagnostics_per_file(),
+            max_diagnostics_per_rule: 0,
+            merge_duplicates: crate::core::MergeHeuristic::Off,
+            outputs: Vec::new(),
+            ignore: Vec::new(),
+            strict_protocol: false,
+            filter: crate::core::DiagnosticFilter::default(),
         }
     }
 }
@@ -245,23 +829,345 @@ pub struct RulesetCfg {
     /// Opaque, free-form table; defaults to {}
     #[serde(default)]
     pub config: toml::value::Table,
+    /// Ruleset-level settings shared by all rules (validated against ConfigSetting)
+    #[serde(default)]
+    pub settings: toml::value::Table,
     /// Optional git repository URL to clone and build from source
     #[serde(default)]
     pub git: Option<String>,
     /// Optional local path to binary executable
     #[serde(default)]
     pub path: Option<String>,
+    /// Extra arguments passed to the ruleset binary
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables set for the ruleset process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Whether the process starts from the linter's environment or an empty one
+    #[serde(default)]
+    pub env_policy: EnvPolicy,
+    /// Working directory for the ruleset process; defaults to the linter's
+    #[serde(default)]
+    pub cwd: Option<std::path::PathBuf>,
+    /// Findings below this severity are dropped at aggregation
+    #[serde(default)]
+    pub min_severity: Option<crate::core::Severity>,
+    /// JavaScript entry file of a Node-based ruleset, run with `node` instead of `path`
+    #[serde(default)]
+    pub entry: Option<std::path::PathBuf>,
+    /// Node executable for `entry`; see [`resolve_node`] when omitted
+    #[serde(default)]
+    pub node: Option<std::path::PathBuf>,
+    /// `host:port` of a ruleset already served over TCP, instead of spawning one
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Environment variable holding the token sent as `authToken` to `address`
+    #[serde(default)]
+    pub auth_token_env: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+impl RulesetCfg {
+    /// Hash of this ruleset's settings, for cache keys of its results
+    pub fn hash(&self) -> Result<String, serde_json::Error> {
+        crate::core::canonical_hash(self)
+    }
+
+    /// Token for a remote ruleset, read from `auth_token_env`
+    pub fn auth_token(&self) -> Result<Option<String>, ConfigError> {
+        let Some(var) = &self.auth_token_env else {
+            return Ok(None);
+        };
+        std::env::var(var)
+            .map(Some)
+            .map_err(|_| ConfigError::Validation(format!("auth token variable {} is not set", var)))
+    }
+
+    /// Whether a finding from this ruleset passes its `min_severity` gate
+    pub fn accepts(&self, d: &crate::core::Diagnostic) -> bool {
+        self.min_severity.is_none_or(|min| d.severity >= min)
+    }
+
+    /// How this ruleset is reached: `address` if set, else `entry` under
+    /// Node, else the binary at `path`
+    pub fn launch(&self) -> Result<Launch, ConfigError> {
+        if let Some(address) = &self.address {
+            return Ok(Launch::Connect {
+                address: address.clone(),
+            });
+        }
+        if self.entry.is_some() {
+            return self.node_command().map(Launch::Spawn);
+        }
+        match (&self.path, &self.git) {
+            (Some(path), _) => Ok(Launch::Spawn(self.command(path))),
+            (None, Some(git)) => Err(ConfigError::Validation(format!(
+                "ruleset from {} has no `path`; build it first",
+                git
+            ))),
+            (None, None) => Err(ConfigError::Validation(
+                "ruleset has none of `address`, `entry` or `path`".to_string(),
+            )),
+        }
+    }
+
+    /// The `[ruleset.<id>.config]` table as the JSON rule config engines
+    /// initialize with, rule id => level or `[level, options]`
+    pub fn to_rule_options_json(&self) -> Result<HashMap<String, serde_json::Value>, ConfigError> {
+        toml_table_to_json_at(&self.config, "config").map(|rules| rules.into_iter().collect())
+    }
+
+    /// `initialize` payload for this ruleset: its rule config, settings and
+    /// auth token, offering every protocol version this SDK speaks
+    pub fn initialize_params(&self) -> Result<crate::protocol::InitializeParams, ConfigError> {
+        Ok(crate::protocol::InitializeParams {
+            ruleset_config: Some(self.to_rule_options_json()?),
+            ruleset_settings: Some(toml_table_to_json_at(&self.settings, "settings")?),
+            protocol_versions: Some(crate::protocol::VersionRange::supported()),
+            auth_token: self.auth_token()?,
+            ..Default::default()
+        })
+    }
+
+    /// Connect to `address`, giving up after `timeout` per resolved address
+    pub fn connect(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<crate::transport::TcpTransport, ConfigError> {
+        let address = self.address.as_deref().ok_or_else(|| {
+            ConfigError::Validation("ruleset has no `address` to connect to".to_string())
+        })?;
+        Ok(crate::transport::TcpTransport::connect_timeout(
+            address, timeout,
+        )?)
+    }
+
+    /// Command that launches `program` with this ruleset's args, env and cwd
+    pub fn command(&self, program: impl AsRef<std::ffi::OsStr>) -> std::process::Command {
+        self.configure(std::process::Command::new(program))
+    }
+
+    /// Command that runs `entry` under Node, followed by this ruleset's args
+    pub fn node_command(&self) -> Result<std::process::Command, ConfigError> {
+        let entry = self.entry.as_ref().ok_or_else(|| {
+            ConfigError::Validation("ruleset has no `entry` to run with node".to_string())
+        })?;
+        let node = resolve_node(self.node.as_deref()).ok_or_else(|| {
+            ConfigError::Validation(
+                "node executable not found; set `node`, FORSETI_NODE or PATH".to_string(),
+            )
+        })?;
+        let mut command = std::process::Command::new(node);
+        command.arg(entry);
+        Ok(self.configure(command))
+    }
+
+    fn configure(&self, mut command: std::process::Command) -> std::process::Command {
+        command.args(&self.args);
+        if self.env_policy == EnvPolicy::Clean {
+            command.env_clear();
+        }
+        command.envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+}
+
+/// How the host reaches one ruleset
+#[derive(Debug)]
+pub enum Launch {
+    /// Connect to a ruleset already served over TCP
+    Connect { address: String },
+    /// Spawn a process speaking NDJSON on its stdin and stdout
+    Spawn(std::process::Command),
+}
+
+/// An enabled ruleset resolved from config, ready to start
+#[derive(Debug)]
+pub struct LaunchSpec {
+    pub ruleset_id: String,
+    pub launch: Launch,
+    pub initialize: crate::protocol::InitializeParams,
+}
+
+/// Every enabled ruleset that could not be resolved, with the reason
+#[derive(Debug, Error)]
+#[error("{}", failure_lines(.failures).join("; "))]
+pub struct LaunchErrors {
+    pub failures: Vec<(String, ConfigError)>,
+}
+
+fn failure_lines(failures: &[(String, ConfigError)]) -> Vec<String> {
+    failures
+        .iter()
+        .map(|(id, e)| format!("ruleset.{}: {}", id, e))
+        .collect()
+}
+
+/// Locate the Node executable: `explicit` if given, then `FORSETI_NODE`, then
+/// the first `node` on `PATH`
+pub fn resolve_node(explicit: Option<&std::path::Path>) -> Option<std::path::PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
+    if let Some(path) = std::env::var_os("FORSETI_NODE") {
+        return Some(path.into());
+    }
+    let name = if cfg!(windows) { "node.exe" } else { "node" };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Config file name `find_config` looks for
+pub const CONFIG_FILE_NAME: &str = "forseti.toml";
+
+/// The `forseti.toml` in `start` or its closest ancestor directory
+pub fn find_config(start: &std::path::Path) -> Option<std::path::PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Quick fix that sets `rule_id` of `ruleset_id` to `level` ("off", or a lower
+/// severity) in the config file at `path`, as a workspace edit replacing the file
+pub fn rule_level_edit(
+    path: &std::path::Path,
+    ruleset_id: &str,
+    rule_id: &str,
+    level: &str,
+) -> Result<crate::core::WorkspaceEdit, ConfigError> {
+    let raw = std::fs::read_to_string(path)?;
+    let edited = set_rule_level(&raw, ruleset_id, rule_id, level)?;
+    let fix = crate::core::Fix {
+        range: crate::core::Range {
+            start: crate::core::Position {
+                line: 0,
+                character: 0,
+            },
+            end: crate::core::LineIndex::new(&raw).to_pos(raw.len()),
+        },
+        text: edited,
+    };
+    let uri = crate::core::file_uri(&std::fs::canonicalize(path)?);
+    Ok(crate::core::WorkspaceEdit {
+        changes: HashMap::from([(uri, vec![fix])]),
+    })
+}
+
+/// `raw` with `[ruleset.<ruleset_id>.config]` setting `rule_id` to `level`,
+/// "off" or a [`crate::core::Severity`] name; anything else is a validation
+/// error. Rule options are kept, so `["error", {..}]` becomes `[level, {..}]`,
+/// and comments and layout elsewhere in the file are untouched.
+pub fn set_rule_level(
+    raw: &str,
+    ruleset_id: &str,
+    rule_id: &str,
+    level: &str,
+) -> Result<String, ConfigError> {
+    use toml_edit::{Array, Item, Value};
+
+    let level = match level.parse::<crate::core::Severity>() {
+        Ok(severity) => severity.as_str(),
+        Err(_) if level.eq_ignore_ascii_case("off") => "off",
+        Err(_) => {
+            return Err(ConfigError::Validation(format!(
+                "unknown rule level \"{level}\", expected \"off\", \"error\", \"warn\", \"info\" or \"hint\""
+            )));
+        }
+    };
+    let mut doc: toml_edit::DocumentMut = raw.parse()?;
+    let rulesets = child_table(doc.as_item_mut(), "ruleset", "ruleset")?;
+    let ruleset = child_table(rulesets, ruleset_id, &format!("ruleset.{ruleset_id}"))?;
+    let config = child_table(ruleset, "config", &format!("ruleset.{ruleset_id}.config"))?;
+    let config = config
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table(&format!("ruleset.{ruleset_id}.config")))?;
+
+    let options = match config.get(rule_id) {
+        Some(Item::Value(Value::Array(items))) => items.get(1).cloned(),
+        Some(Item::Value(options @ Value::InlineTable(_))) => Some(options.clone()),
+        Some(Item::Table(options)) => Some(Value::InlineTable(options.clone().into_inline_table())),
+        _ => None,
+    };
+    let mut value = match options {
+        Some(options) => {
+            let mut items = Array::new();
+            items.push(level);
+            items.push_formatted(options.decorated(" ", ""));
+            Value::Array(items)
+        }
+        None => Value::from(level),
+    };
+    match config.get_mut(rule_id).and_then(Item::as_value_mut) {
+        Some(existing) => {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        }
+        None => {
+            config.insert(rule_id, Item::Value(value));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// `raw` with `patterns` appended to `linter.ignore`, skipping ones already
+/// listed; comments and layout elsewhere in the file are untouched
+pub fn add_ignore_patterns(raw: &str, patterns: &[String]) -> Result<String, ConfigError> {
+    let mut doc: toml_edit::DocumentMut = raw.parse()?;
+    let linter = child_table(doc.as_item_mut(), "linter", "linter")?;
+    let linter = linter
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table("linter"))?;
+    let ignore = linter
+        .entry("ignore")
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| ConfigError::Validation("`linter.ignore` is not an array".to_string()))?;
+    for pattern in patterns {
+        if !ignore.iter().any(|p| p.as_str() == Some(pattern)) {
+            ignore.push(pattern.as_str());
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Table `key` under `parent`, created (without a header of its own) if missing
+fn child_table<'a>(
+    parent: &'a mut toml_edit::Item,
+    key: &str,
+    path: &str,
+) -> Result<&'a mut toml_edit::Item, ConfigError> {
+    let table = parent
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table(path))?;
+    let child = table.entry(key).or_insert_with(|| {
+        let mut implicit = toml_edit::Table::new();
+        implicit.set_implicit(true);
+        toml_edit::Item::Table(implicit)
+    });
+    if !child.is_table_like() {
+        return Err(not_a_table(path));
+    }
+    Ok(child)
+}
+
+fn not_a_table(path: &str) -> ConfigError {
+    ConfigError::Validation(format!("`{path}` is not a table"))
+}
+
+/// Environment a spawned ruleset process starts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
-pub enum LogLevel {
-    Trace,
-    Debug,
+pub enum EnvPolicy {
+    /// Inherit the linter's environment, then apply `env`
     #[default]
-    Info,
-    Warn,
-    Error,
+    Inherit,
+    /// Start from an empty environment containing only `env`
+    Clean,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
@@ -272,4 +1178,88 @@ pub enum OutputFormat {
     Ndjson,
     Text,
     Sarif,
+    Checkstyle,
+    /// GitLab Code Quality JSON
+    Gitlab,
+}
+
+impl OutputFormat {
+    /// Name the format is configured and registered under (see
+    /// [`crate::format::FormatterRegistry`])
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Text => "text",
+            OutputFormat::Sarif => "sarif",
+            OutputFormat::Checkstyle => "checkstyle",
+            OutputFormat::Gitlab => "gitlab",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(text: &str) -> toml::Value {
+        toml::Value::Table(toml::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn datetimes_and_datetime_like_strings_round_trip() {
+        let value = table(
+            r#"
+            date = 1979-05-27
+            offset = 1979-05-27T07:32:00Z
+            local = 1979-05-27T07:32:00
+            time = 07:32:00
+            text = "1979-05-27"
+            "#,
+        );
+        let json = toml_to_json(&value).unwrap();
+        assert_eq!(json["date"], json!({ TOML_DATETIME_KEY: "1979-05-27" }));
+        assert_eq!(json["text"], "1979-05-27");
+        assert_eq!(json_to_toml(&json).unwrap(), value);
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json,
+            "same form as toml's own serde output"
+        );
+    }
+
+    #[test]
+    fn malformed_datetime_tag_is_an_error() {
+        let json = json!({ "at": { TOML_DATETIME_KEY: "yesterday" } });
+        let err = json_to_toml(&json).unwrap_err();
+        assert!(err.to_string().contains("$.at"), "{err}");
+        // Extra keys make it an ordinary table
+        let json = json!({ TOML_DATETIME_KEY: "1979-05-27", "other": 1 });
+        assert!(json_to_toml(&json).unwrap().is_table());
+    }
+
+    #[test]
+    fn integers_at_the_edges_of_the_toml_range() {
+        let json = json!({ "min": i64::MIN, "max": i64::MAX });
+        let value = json_to_toml(&json).unwrap();
+        assert_eq!(value["min"].as_integer(), Some(i64::MIN));
+        assert_eq!(value["max"].as_integer(), Some(i64::MAX));
+        assert_eq!(toml_to_json(&value).unwrap(), json);
+    }
+
+    #[test]
+    fn integer_above_i64_max_is_an_error() {
+        let too_big = json!({ "n": [1, i64::MAX as u64 + 1] });
+        let err = json_to_toml(&too_big).unwrap_err();
+        assert!(matches!(err, ConfigError::Conversion { ref path, .. } if path == "$.n[1]"));
+        assert!(err.to_string().contains("out of range"), "{err}");
+    }
+
+    #[test]
+    fn null_and_non_finite_floats_are_errors() {
+        assert!(json_to_toml(&json!({ "a": null })).is_err());
+        assert!(toml_to_json(&table("nan = nan")).is_err());
+        assert!(toml_to_json(&table("inf = inf")).is_err());
+    }
 }
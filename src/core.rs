@@ -1,302 +1,2396 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-pub use crate::config::{
-    Config, ConfigError, LinterCfg, LogLevel, OutputFormat, RulesetCfg,
-};
+pub use crate::types::*;
 
+#[cfg(feature = "linter-host")]
+pub use crate::config::{Config, ConfigError, LinterCfg, OutputFormat, RulesetCfg};
 
-pub const PROTOCOL_VERSION: u8 = 1;
 
+/// Unique request id: a ULID (48-bit millisecond timestamp + 80 random bits) in
+/// Crockford base32, so ids sort by creation time and don't collide across handles.
+/// Ids made within one millisecond increment the random part of the previous
+/// one, so they sort in creation order too.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn new() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        /// Timestamp and random part of the last id made
+        static LAST: Mutex<(u64, u128)> = Mutex::new((0, 0));
+        const RANDOM_MASK: u128 = (1u128 << 80) - 1;
+
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            & ((1u64 << 48) - 1);
+        // RandomState is seeded from OS randomness; hashing a process-wide counter
+        // with fresh keys gives distinct random bits without an extra dependency.
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let high = hasher.finish();
+        hasher.write_u64(millis);
+        let low = hasher.finish();
+        let fresh = ((high as u128) << 64 | low as u128) & RANDOM_MASK;
+
+        let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+        // Within the last id's millisecond, or after the clock stepped back,
+        // count up from the last id instead
+        *last = match *last {
+            (at, random) if millis <= at && random < RANDOM_MASK => (at, random + 1),
+            (at, _) if millis <= at => (at + 1, fresh),
+            _ => (millis, fresh),
+        };
+        let (millis, random) = *last;
+        drop(last);
+        let mut value = ((millis as u128) << 80) | random;
+
+        const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        let mut out = [0u8; 26];
+        for slot in out.iter_mut().rev() {
+            *slot = ALPHABET[(value & 31) as usize];
+            value >>= 5;
+        }
+        Self(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<RequestId> for String {
+    fn from(id: RequestId) -> Self {
+        id.0
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+/// Source of time for deadlines, intervals and timestamps. Components take an
+/// `Arc<dyn Clock>` (see their `with_clock` builders) so timeout logic can be
+/// driven by a [`MockClock`].
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// Monotonic time, for measuring durations
+    fn now(&self) -> Instant;
+    /// Wall-clock time, for timestamps that outlive the process
+    fn system_time(&self) -> SystemTime;
+    /// Block the current thread for `duration`
+    fn sleep(&self, duration: Duration);
+
+    /// Milliseconds since the Unix epoch, 0 for clocks set before it
+    fn unix_millis(&self) -> u64 {
+        self.system_time()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// The operating system's clocks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Clock that stands still until advanced; `sleep` advances it instead of
+/// blocking. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    state: Arc<Mutex<(Instant, SystemTime)>>,
+}
+
+impl MockClock {
+    /// Clock reading the current time, frozen
+    pub fn new() -> Self {
+        Self::at(SystemTime::now())
+    }
+
+    /// Clock frozen at wall-clock time `system_time`
+    pub fn at(system_time: SystemTime) -> Self {
+        Self {
+            state: Arc::new(Mutex::new((Instant::now(), system_time))),
+        }
+    }
+
+    /// Move both clocks forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.0 += duration;
+        state.1 += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).1
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Shared flag set when a request is cancelled or its deadline passes.
+/// Long-running work should poll `is_cancelled` and stop early; clones observe
+/// the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    deadline: Option<std::time::Instant>,
+    /// Clock the deadline is checked against; the system clock when unset
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token that also counts as cancelled once `deadline` has passed
+    pub fn with_deadline(mut self, deadline: std::time::Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Check the deadline against `clock` rather than the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(std::sync::atomic::Ordering::Relaxed) || self.is_expired()
+    }
+
+    /// Whether the deadline, if any, has passed
+    pub fn is_expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| {
+            let now = match &self.clock {
+                Some(clock) => clock.now(),
+                None => Instant::now(),
+            };
+            now >= deadline
+        })
+    }
+}
+
+/// Serialized message size above which a negotiated connection compresses the payload
+pub const COMPRESSION_THRESHOLD: usize = 64 * 1024;
+
+/// Largest size a gzip payload may decompress to
+pub const MAX_DECODED_PAYLOAD: usize = 64 * 1024 * 1024;
+
+/// Minimal NDJSON writer.
+pub struct Ndjson<W: Write> {
+    writer: W,
+    #[cfg(feature = "compression")]
+    compress_above: Option<usize>,
+}
+impl<W: Write> Ndjson<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            #[cfg(feature = "compression")]
+            compress_above: None,
+        }
+    }
+    /// Gzip the payload of messages longer than `threshold` bytes; only enable
+    /// once the peer has advertised [`features::GZIP`]
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, threshold: usize) -> Self {
+        self.compress_above = Some(threshold);
+        self
+    }
+    pub fn send<S: Serialize>(&mut self, obj: &S) -> io::Result<()> {
+        self.send_line(&serde_json::to_string(obj)?)
+    }
+    /// Write an already serialized message as one line
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compress_above
+            && line.len() > threshold
+        {
+            return self.send_line_raw(&compress_line(line)?);
+        }
+        self.send_line_raw(line)
+    }
+    fn send_line_raw(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Read one NDJSON line from stdin as raw JSON.
+pub fn read_line_value() -> io::Result<Value> {
+    read_line_value_from(&mut io::stdin().lock())
+}
+
+/// A line of peer output that is not JSON, e.g. a stray `println!` or the
+/// usage text of a binary that does not speak the protocol. Readers return it
+/// inside an `InvalidData` error.
+#[derive(Debug, thiserror::Error)]
+#[error("{source}")]
+pub struct StrayLine {
+    pub line: String,
+    #[source]
+    pub source: serde_json::Error,
+}
+
+/// Read one NDJSON line from `reader` as raw JSON.
+pub fn read_line_value_from<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<Value> {
+    let mut buf = String::new();
+    let n = reader.read_line(&mut buf)?;
+    if n == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "input closed"));
+    }
+    let trimmed = buf.trim();
+    let mut value: Value = serde_json::from_str(trimmed).map_err(|source| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            StrayLine {
+                line: trimmed.to_string(),
+                source,
+            },
+        )
+    })?;
+    decode_payload(&mut value)?;
+    Ok(value)
+}
+
+/// Parse one raw NDJSON message, as read from a peer, into a checked
+/// envelope: gzip payloads are decoded and [`Envelope::validate`] applied.
+/// Returns an error rather than panicking on any input, so it doubles as a
+/// fuzzing entry point.
+pub fn parse_envelope_bytes(data: &[u8]) -> io::Result<Envelope<Value>> {
+    let mut value: Value = serde_json::from_slice(data.trim_ascii())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    decode_payload(&mut value)?;
+    let envelope: Envelope<Value> =
+        serde_json::from_value(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    envelope
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(envelope)
+}
+
+/// Replace the payload of `line` with its gzip+base64 form and mark it with
+/// `"encoding": "gzip"`; in a batch line, every message's payload. Messages
+/// without a payload are left unchanged.
+#[cfg(feature = "compression")]
+pub fn compress_line(line: &str) -> io::Result<String> {
+    let mut value: Value = serde_json::from_str(line)?;
+    match &mut value {
+        Value::Array(batch) => batch.iter_mut().try_for_each(compress_payload)?,
+        message => compress_payload(message)?,
+    }
+    Ok(serde_json::to_string(&value)?)
+}
+
+#[cfg(feature = "compression")]
+fn compress_payload(message: &mut Value) -> io::Result<()> {
+    use base64::Engine;
+    use flate2::{Compression, write::GzEncoder};
+
+    let Some(field) = payload_field(message) else {
+        return Ok(());
+    };
+    let payload = &message[field];
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, payload)?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(encoder.finish()?);
+    message[field] = Value::String(encoded);
+    message["encoding"] = Value::String(features::GZIP.to_string());
+    Ok(())
+}
+
+/// Member holding the payload of `message`: `payload` in an envelope,
+/// `params` or `result` in a JSON-RPC message
+#[cfg(feature = "compression")]
+fn payload_field(message: &Value) -> Option<&'static str> {
+    ["payload", "params", "result"]
+        .into_iter()
+        .find(|field| message.get(field).is_some())
+}
+
+/// Undo [`compress_line`] in place, for every message of a batch; a no-op
+/// for messages without `encoding`. A payload that decompresses to more than
+/// [`MAX_DECODED_PAYLOAD`] bytes is rejected.
+pub fn decode_payload(value: &mut Value) -> io::Result<()> {
+    match value {
+        Value::Array(batch) => batch.iter_mut().try_for_each(decode_message),
+        message => decode_message(message),
+    }
+}
+
+fn decode_message(value: &mut Value) -> io::Result<()> {
+    let Some(encoding) = value.as_object_mut().and_then(|obj| obj.remove("encoding")) else {
+        return Ok(());
+    };
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if encoding.as_str() != Some(features::GZIP) {
+        return Err(invalid(format!("unsupported payload encoding: {encoding}")));
+    }
+    #[cfg(feature = "compression")]
+    {
+        use base64::Engine;
+        use std::io::Read;
+
+        let field = payload_field(value).unwrap_or("payload");
+        let encoded = value
+            .get(field)
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("gzip payload must be a base64 string".into()))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| invalid(e.to_string()))?;
+        let mut json = Vec::new();
+        flate2::read::GzDecoder::new(bytes.as_slice())
+            .take(MAX_DECODED_PAYLOAD as u64 + 1)
+            .read_to_end(&mut json)?;
+        if json.len() > MAX_DECODED_PAYLOAD {
+            return Err(invalid(format!(
+                "gzip payload expands beyond {MAX_DECODED_PAYLOAD} bytes"
+            )));
+        }
+        value[field] = serde_json::from_slice(&json)?;
+        Ok(())
+    }
+    #[cfg(not(feature = "compression"))]
+    Err(invalid(
+        "gzip payload received but the `compression` feature is disabled".into(),
+    ))
+}
+
+/// Encode file bytes for `analyzeFile`: valid UTF-8 is sent as-is, anything
+/// else as base64
+pub fn encode_content(bytes: &[u8]) -> (String, ContentEncoding) {
+    use base64::Engine;
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), ContentEncoding::Utf8),
+        Err(_) => (
+            base64::engine::general_purpose::STANDARD.encode(bytes),
+            ContentEncoding::Base64,
+        ),
+    }
+}
+
+/// Raw bytes of an `analyzeFile` `content` sent with `encoding`
+pub fn decode_content(content: &str, encoding: ContentEncoding) -> Result<Vec<u8>, String> {
+    use base64::Engine;
+
+    match encoding {
+        ContentEncoding::Utf8 => Ok(content.as_bytes().to_vec()),
+        ContentEncoding::Base64 => base64::engine::general_purpose::STANDARD
+            .decode(content)
+            .map_err(|e| format!("invalid base64 content: {e}")),
+    }
+}
+
+/// Whether a `diagnostics` event payload was produced for different content than
+/// `current_hash`. Events without an echoed hash are never considered stale.
+pub fn is_stale_diagnostics(event_payload: &Value, current_hash: &str) -> bool {
+    event_payload
+        .get("contentHash")
+        .and_then(|v| v.as_str())
+        .is_some_and(|hash| hash != current_hash)
+}
+
+/// Utility for line/offset mapping for plain-text rules. Characters are byte
+/// offsets within their line, not UTF-16 code units.
+pub struct LineIndex {
+    text: String,
+    starts: Vec<usize>,
+}
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut s = vec![0usize];
+        for (i, ch) in text.char_indices() {
+            if ch == '\n' {
+                s.push(i + 1);
+            }
+        }
+        Self {
+            text: text.to_string(),
+            starts: s,
+        }
+    }
+    pub fn to_pos(&self, mut off: usize) -> Position {
+        if off > self.text.len() {
+            off = self.text.len();
+        }
+        // binary search
+        let (mut lo, mut hi) = (0usize, self.starts.len().saturating_sub(1));
+        while lo <= hi {
+            let mid = (lo + hi) / 2;
+            let start = self.starts[mid];
+            let next = if mid + 1 < self.starts.len() {
+                self.starts[mid + 1]
+            } else {
+                self.text.len() + 1
+            };
+            if off < start {
+                if mid == 0 {
+                    break;
+                }
+                hi = mid - 1;
+            } else if off >= next {
+                lo = mid + 1;
+            } else {
+                return Position {
+                    line: mid as u32,
+                    character: (off - start) as u32,
+                };
+            }
+        }
+        Position {
+            line: 0,
+            character: off as u32,
+        }
+    }
+    /// Byte offset of `pos`, or None when it lies outside the text or inside a
+    /// multi-byte character
+    pub fn to_offset(&self, pos: Position) -> Option<usize> {
+        let start = *self.starts.get(pos.line as usize)?;
+        if pos.character > self.line_len(pos.line)? {
+            return None;
+        }
+        let offset = start + pos.character as usize;
+        self.text.is_char_boundary(offset).then_some(offset)
+    }
+
+    pub fn to_range(&self, s: usize, e: usize) -> Range {
+        Range {
+            start: self.to_pos(s),
+            end: self.to_pos(e),
+        }
+    }
+
+    /// Number of lines in the text (a trailing newline starts an empty last line)
+    pub fn line_count(&self) -> u32 {
+        self.starts.len() as u32
+    }
+
+    /// Text of `line`, excluding its line terminator
+    pub fn line_text(&self, line: u32) -> Option<&str> {
+        let start = *self.starts.get(line as usize)?;
+        let len = self.line_len(line)? as usize;
+        Some(&self.text[start..start + len])
+    }
+
+    /// Length of `line` in bytes, excluding its line terminator
+    pub fn line_len(&self, line: u32) -> Option<u32> {
+        let start = *self.starts.get(line as usize)?;
+        let end = match self.starts.get(line as usize + 1) {
+            Some(next) => next - 1,
+            None => self.text.len(),
+        };
+        let content = self.text[start..end]
+            .strip_suffix('\r')
+            .unwrap_or(&self.text[start..end]);
+        Some(content.len() as u32)
+    }
+
+    /// Clamp a position into the document: past-the-end lines map to the end of the
+    /// text, past-the-end characters to the end of their line and characters inside
+    /// a multi-byte character back to its start.
+    pub fn clamp_position(&self, pos: Position) -> Position {
+        let last = self.line_count() - 1;
+        if pos.line > last {
+            return Position {
+                line: last,
+                character: self.line_len(last).unwrap_or(0),
+            };
+        }
+        let start = self.starts[pos.line as usize];
+        let mut character = pos.character.min(self.line_len(pos.line).unwrap_or(0));
+        while !self.text.is_char_boundary(start + character as usize) {
+            character -= 1;
+        }
+        Position {
+            line: pos.line,
+            character,
+        }
+    }
+
+    /// Normalize a range reported against this text: swap reversed ends and clamp both
+    /// into the document. Returns None when the range starts beyond the last line,
+    /// since there is no way to tell what it was meant to point at.
+    pub fn normalize_range(&self, range: Range) -> Option<Range> {
+        let (start, end) = if range.end < range.start {
+            (range.end, range.start)
+        } else {
+            (range.start, range.end)
+        };
+        if start.line >= self.line_count() {
+            return None;
+        }
+        Some(Range {
+            start: self.clamp_position(start),
+            end: self.clamp_position(end),
+        })
+    }
+}
+
+/// Normalize incoming diagnostics against the document they were reported for.
+/// Repairable ranges are fixed in place; irreparable diagnostics are dropped and
+/// described in the returned warnings.
+pub fn validate_diagnostic_ranges(
+    text: &str,
+    diagnostics: Vec<Diagnostic>,
+) -> (Vec<Diagnostic>, Vec<String>) {
+    let index = LineIndex::new(text);
+    let mut kept = Vec::with_capacity(diagnostics.len());
+    let mut warnings = Vec::new();
+    for mut d in diagnostics {
+        match index.normalize_range(d.range) {
+            Some(range) => {
+                d.range = range;
+                kept.push(d);
+            }
+            None => warnings.push(format!(
+                "dropped {} diagnostic at line {}: document has {} lines",
+                d.rule_id,
+                d.range.start.line + 1,
+                index.line_count()
+            )),
+        }
+    }
+    (kept, warnings)
+}
+
+/// Consolidated capabilities of every known ruleset, for rule catalogs and docs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleCatalog {
+    /// Sorted by ruleset id
+    pub rulesets: Vec<RulesetCapabilities>,
+}
+
+impl RuleCatalog {
+    pub fn new(capabilities: impl IntoIterator<Item = RulesetCapabilities>) -> Self {
+        let mut rulesets: Vec<RulesetCapabilities> = capabilities.into_iter().collect();
+        rulesets.sort_by(|a, b| a.ruleset_id.cmp(&b.ruleset_id));
+        Self { rulesets }
+    }
+
+    pub fn ruleset(&self, ruleset_id: &str) -> Option<&RulesetCapabilities> {
+        self.rulesets.iter().find(|r| r.ruleset_id == ruleset_id)
+    }
+
+    /// Changes to every ruleset in both catalogs, e.g. the cached catalog and
+    /// one rebuilt after an update; unchanged rulesets are skipped
+    pub fn diff(&self, newer: &RuleCatalog) -> Vec<CapabilitiesDiff> {
+        self.rulesets
+            .iter()
+            .filter_map(|old| {
+                Some(CapabilitiesDiff::between(
+                    old,
+                    newer.ruleset(&old.ruleset_id)?,
+                ))
+            })
+            .filter(|diff| !diff.is_empty() || diff.old_version != diff.new_version)
+            .collect()
+    }
+
+    /// Every rule paired with the ruleset offering it
+    pub fn rules(&self) -> impl Iterator<Item = (&RulesetCapabilities, &RuleInfo)> {
+        self.rulesets
+            .iter()
+            .flat_map(|rs| rs.rules.iter().map(move |rule| (rs, rule)))
+    }
+
+    /// Machine-readable catalog: one flat entry per rule
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.rules()
+                .map(|(rs, rule)| {
+                    serde_json::json!({
+                        "ruleset": rs.ruleset_id,
+                        "id": rule.id,
+                        "description": rule.description,
+                        "categories": rule.categories,
+                        "fixable": rule.fixable,
+                        "defaultLevel": default_level(rs, &rule.id),
+                        "docsUrl": rule.docs_url,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Human-readable markdown table for docs sites and wikis
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from(
+            "| Rule | Category | Fixable | Default | Description | Docs |\n\
+             |------|----------|---------|---------|-------------|------|\n",
+        );
+        for (rs, rule) in self.rules() {
+            let docs = rule
+                .docs_url
+                .as_ref()
+                .map(|url| format!("[docs]({})", url))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "| `{}/{}` | {} | {} | {} | {} | {} |\n",
+                rs.ruleset_id,
+                rule.id,
+                markdown_cell(&rule.categories.join(", ")),
+                if rule.fixable { "yes" } else { "no" },
+                default_level(rs, &rule.id).unwrap_or_default(),
+                markdown_cell(&rule.description),
+                docs,
+            ));
+        }
+        out
+    }
+}
+
+/// A value keyed by `key` that differs between two capability versions;
+/// `None` when absent on that side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueChange {
+    pub key: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// A ruleset setting that was added, removed or redefined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub name: String,
+    /// What changed, e.g. "type string -> integer"; empty when added or removed
+    pub changes: Vec<String>,
+    pub old: Option<ConfigSetting>,
+    pub new: Option<ConfigSetting>,
+}
+
+/// What changed between two versions of a ruleset's capabilities, so an
+/// upgrade can be reviewed before it alters lint results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapabilitiesDiff {
+    pub ruleset_id: String,
+    pub old_version: String,
+    pub new_version: String,
+    pub rules_added: Vec<String>,
+    pub rules_removed: Vec<String>,
+    /// Default rule config changes for rules present in both versions
+    pub defaults_changed: Vec<ValueChange>,
+    pub settings_changed: Vec<SettingChange>,
+    pub file_patterns_added: Vec<String>,
+    pub file_patterns_removed: Vec<String>,
+    pub features_added: Vec<String>,
+    pub features_removed: Vec<String>,
+}
+
+impl CapabilitiesDiff {
+    pub fn between(old: &RulesetCapabilities, new: &RulesetCapabilities) -> Self {
+        let old_rules: BTreeSet<&str> = old.rules.iter().map(|r| r.id.as_str()).collect();
+        let new_rules: BTreeSet<&str> = new.rules.iter().map(|r| r.id.as_str()).collect();
+
+        let defaults: BTreeSet<&String> = old
+            .default_config
+            .keys()
+            .chain(new.default_config.keys())
+            .collect();
+        let defaults_changed = defaults
+            .into_iter()
+            .filter(|key| old_rules.contains(key.as_str()) && new_rules.contains(key.as_str()))
+            .filter(|key| old.default_config.get(*key) != new.default_config.get(*key))
+            .map(|key| ValueChange {
+                key: key.clone(),
+                old: old.default_config.get(key).cloned(),
+                new: new.default_config.get(key).cloned(),
+            })
+            .collect();
+
+        let settings: BTreeSet<&str> = old
+            .config_settings
+            .iter()
+            .chain(&new.config_settings)
+            .map(|s| s.name.as_str())
+            .collect();
+        let settings_changed = settings
+            .into_iter()
+            .filter_map(|name| {
+                let before = old.config_settings.iter().find(|s| s.name == name);
+                let after = new.config_settings.iter().find(|s| s.name == name);
+                let changes = match (before, after) {
+                    (Some(before), Some(after)) => setting_changes(before, after),
+                    _ => Vec::new(),
+                };
+                if before.is_some() && after.is_some() && changes.is_empty() {
+                    return None;
+                }
+                Some(SettingChange {
+                    name: name.to_string(),
+                    changes,
+                    old: before.cloned(),
+                    new: after.cloned(),
+                })
+            })
+            .collect();
+
+        let (file_patterns_added, file_patterns_removed) =
+            added_removed(&old.file_patterns, &new.file_patterns);
+        let (features_added, features_removed) = added_removed(&old.features, &new.features);
+        Self {
+            ruleset_id: new.ruleset_id.clone(),
+            old_version: old.version.clone(),
+            new_version: new.version.clone(),
+            rules_added: new_rules
+                .difference(&old_rules)
+                .map(|id| id.to_string())
+                .collect(),
+            rules_removed: old_rules
+                .difference(&new_rules)
+                .map(|id| id.to_string())
+                .collect(),
+            defaults_changed,
+            settings_changed,
+            file_patterns_added,
+            file_patterns_removed,
+            features_added,
+            features_removed,
+        }
+    }
+
+    /// True when only the version differs
+    pub fn is_empty(&self) -> bool {
+        self.rules_added.is_empty()
+            && self.rules_removed.is_empty()
+            && self.defaults_changed.is_empty()
+            && self.settings_changed.is_empty()
+            && self.file_patterns_added.is_empty()
+            && self.file_patterns_removed.is_empty()
+            && self.features_added.is_empty()
+            && self.features_removed.is_empty()
+    }
+
+    /// Human-readable change report, one line per change
+    pub fn lines(&self) -> Vec<String> {
+        let id = &self.ruleset_id;
+        let mut lines = vec![format!(
+            "{id}: {} -> {}",
+            self.old_version, self.new_version
+        )];
+        let shown = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(none)".to_string(),
+        };
+        lines.extend(self.rules_added.iter().map(|r| format!("  + rule {r}")));
+        lines.extend(self.rules_removed.iter().map(|r| format!("  - rule {r}")));
+        lines.extend(self.defaults_changed.iter().map(|c| {
+            format!(
+                "  ~ rule {} default: {} -> {}",
+                c.key,
+                shown(&c.old),
+                shown(&c.new)
+            )
+        }));
+        lines.extend(
+            self.settings_changed
+                .iter()
+                .map(|s| match (&s.old, &s.new) {
+                    (None, _) => format!("  + setting {}", s.name),
+                    (_, None) => format!("  - setting {}", s.name),
+                    _ => format!("  ~ setting {}: {}", s.name, s.changes.join(", ")),
+                }),
+        );
+        lines.extend(
+            self.file_patterns_added
+                .iter()
+                .map(|p| format!("  + file pattern {p}")),
+        );
+        lines.extend(
+            self.file_patterns_removed
+                .iter()
+                .map(|p| format!("  - file pattern {p}")),
+        );
+        lines.extend(
+            self.features_added
+                .iter()
+                .map(|f| format!("  + feature {f}")),
+        );
+        lines.extend(
+            self.features_removed
+                .iter()
+                .map(|f| format!("  - feature {f}")),
+        );
+        lines
+    }
+}
+
+fn setting_changes(old: &ConfigSetting, new: &ConfigSetting) -> Vec<String> {
+    fn json(value: &impl Serialize) -> String {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+    let mut changes = Vec::new();
+    if old.setting_type != new.setting_type {
+        changes.push(format!(
+            "type {} -> {}",
+            json(&old.setting_type),
+            json(&new.setting_type)
+        ));
+    }
+    if old.default != new.default {
+        changes.push(format!("default {} -> {}", old.default, new.default));
+    }
+    if old.required != new.required {
+        changes.push(if new.required {
+            "now required".to_string()
+        } else {
+            "no longer required".to_string()
+        });
+    }
+    if old.allowed_values != new.allowed_values {
+        changes.push(format!(
+            "allowed values {} -> {}",
+            json(&old.allowed_values),
+            json(&new.allowed_values)
+        ));
+    }
+    if old.min != new.min {
+        changes.push(format!("min {} -> {}", json(&old.min), json(&new.min)));
+    }
+    if old.max != new.max {
+        changes.push(format!("max {} -> {}", json(&old.max), json(&new.max)));
+    }
+    changes
+}
+
+/// Entries only in `new`, then entries only in `old`
+fn added_removed(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = new.iter().filter(|v| !old.contains(v)).cloned().collect();
+    let removed = old.iter().filter(|v| !new.contains(v)).cloned().collect();
+    (added, removed)
+}
+
+/// Default severity of a rule from the ruleset's default config
+/// (`"warn"` or `["warn", {..}]`)
+fn default_level(ruleset: &RulesetCapabilities, rule_id: &str) -> Option<String> {
+    match ruleset.default_config.get(rule_id)? {
+        Value::String(level) => Some(level.clone()),
+        Value::Array(items) => items.first()?.as_str().map(|l| l.to_string()),
+        _ => None,
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Unsatisfiable ruleset dependency declarations
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DependencyError {
+    #[error("ruleset '{ruleset}' depends on '{missing}', which is not available")]
+    Missing { ruleset: String, missing: String },
+    #[error("ruleset dependency cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+/// Order rulesets so every ruleset runs after the rulesets it depends on.
+/// Independent rulesets keep a stable, id-sorted order.
+pub fn ruleset_execution_order(
+    capabilities: &[RulesetCapabilities],
+) -> Result<Vec<String>, DependencyError> {
+    let by_id: HashMap<&str, &RulesetCapabilities> = capabilities
+        .iter()
+        .map(|c| (c.ruleset_id.as_str(), c))
+        .collect();
+    let mut ids: Vec<&str> = by_id.keys().copied().collect();
+    ids.sort();
+
+    fn visit<'a>(
+        id: &'a str,
+        by_id: &HashMap<&'a str, &'a RulesetCapabilities>,
+        done: &mut Vec<String>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<(), DependencyError> {
+        if done.iter().any(|d| d == id) {
+            return Ok(());
+        }
+        if let Some(pos) = stack.iter().position(|s| *s == id) {
+            let mut cycle: Vec<String> = stack[pos..].iter().map(|s| s.to_string()).collect();
+            cycle.push(id.to_string());
+            return Err(DependencyError::Cycle(cycle));
+        }
+        stack.push(id);
+        let mut deps: Vec<&str> = by_id[id].dependencies.iter().map(|d| d.as_str()).collect();
+        deps.sort();
+        for dep in deps {
+            let Some((dep_id, _)) = by_id.get_key_value(dep) else {
+                return Err(DependencyError::Missing {
+                    ruleset: id.to_string(),
+                    missing: dep.to_string(),
+                });
+            };
+            visit(dep_id, by_id, done, stack)?;
+        }
+        stack.pop();
+        done.push(id.to_string());
+        Ok(())
+    }
+
+    let mut done = Vec::with_capacity(ids.len());
+    for id in ids {
+        visit(id, &by_id, &mut done, &mut Vec::new())?;
+    }
+    Ok(done)
+}
+
+/// Category-based rule selection (`--only-category` / `--skip-category`)
+#[derive(Debug, Clone, Default)]
+pub struct CategoryFilter {
+    /// When non-empty, only rules in at least one of these categories run
+    pub only: Vec<String>,
+    /// Rules in any of these categories never run
+    pub skip: Vec<String>,
+}
+
+impl CategoryFilter {
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.skip.is_empty()
+    }
+
+    /// Whether a rule with the given categories passes the filter
+    pub fn allows(&self, categories: &[String]) -> bool {
+        if categories.iter().any(|c| self.skip.contains(c)) {
+            return false;
+        }
+        self.only.is_empty() || categories.iter().any(|c| self.only.contains(c))
+    }
+
+    /// Whether any rule of the ruleset passes the filter, so the linter can skip
+    /// rulesets with nothing to run without starting them
+    pub fn matches_ruleset(&self, capabilities: &RulesetCapabilities) -> bool {
+        self.is_empty()
+            || capabilities
+                .rules
+                .iter()
+                .any(|rule| self.allows(&rule.categories))
+    }
+
+    /// Drop options for rules the filter excludes; rules without options are not run.
+    pub fn apply_to_options(&self, rules: &[RuleInfo], options: &mut HashMap<String, Value>) {
+        if self.is_empty() {
+            return;
+        }
+        for rule in rules {
+            if !self.allows(&rule.categories) {
+                options.remove(&rule.id);
+            }
+        }
+    }
+}
+
+/// File preprocessing context from ruleset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PreprocessingContext {
+    pub ruleset_id: String,
+    pub files: Vec<FileContext>,
+    pub global_context: HashMap<String, Value>, // Cross-file context
+}
+
+/// Context for a single file after preprocessing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileContext {
+    pub uri: String,
+    pub content: String,
+    pub language: Option<String>,
+    pub context: HashMap<String, Value>, // AST, symbols, etc.
+    /// Taint source/sink summaries shared between security rulesets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flow_summaries: Vec<FlowSummary>,
+    /// Embedded sub-documents, e.g. fenced code blocks in markdown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<Region>,
+}
+
+/// Language-tagged byte range of a composite file, analyzed as its own document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Region {
+    pub language: String,
+    /// Byte offset where the region starts in the file
+    pub start: usize,
+    /// Byte offset just past the region's end
+    pub end: usize,
+}
+
+impl Region {
+    pub fn new(language: impl Into<String>, start: usize, end: usize) -> Self {
+        Self {
+            language: language.into(),
+            start,
+            end,
+        }
+    }
+
+    /// The region's text within `file_text`; empty if the offsets are out of bounds
+    pub fn text<'a>(&self, file_text: &'a str) -> &'a str {
+        file_text.get(self.start..self.end).unwrap_or("")
+    }
+
+    /// Translate a region-relative position to file coordinates; `index` is built
+    /// over the whole file
+    pub fn to_file_position(&self, index: &LineIndex, pos: Position) -> Position {
+        let origin = index.to_pos(self.start);
+        if pos.line == 0 {
+            Position {
+                line: origin.line,
+                character: origin.character + pos.character,
+            }
+        } else {
+            Position {
+                line: origin.line + pos.line,
+                character: pos.character,
+            }
+        }
+    }
+
+    pub fn to_file_range(&self, index: &LineIndex, range: Range) -> Range {
+        Range {
+            start: self.to_file_position(index, range.start),
+            end: self.to_file_position(index, range.end),
+        }
+    }
+
+    /// Move a diagnostic reported against the region's text, including its fixes,
+    /// into file coordinates
+    pub fn map_diagnostic(&self, index: &LineIndex, mut diagnostic: Diagnostic) -> Diagnostic {
+        diagnostic.range = self.to_file_range(index, diagnostic.range);
+        for suggestion in diagnostic.suggest.iter_mut().flatten() {
+            if let Some(fix) = &mut suggestion.fix {
+                fix.range = self.to_file_range(index, fix.range);
+            }
+        }
+        diagnostic
+    }
+}
+
+/// Key under which cross-file flow summaries live in `PreprocessingContext::global_context`
+pub const FLOW_SUMMARIES_KEY: &str = "flowSummaries";
+
+impl PreprocessingContext {
+    /// Add a cross-file flow summary to the global context
+    pub fn add_global_flow_summary(&mut self, summary: FlowSummary) {
+        let mut summaries = self.global_flow_summaries();
+        summaries.push(summary);
+        self.global_context.insert(
+            FLOW_SUMMARIES_KEY.to_string(),
+            serde_json::to_value(summaries).unwrap_or(Value::Null),
+        );
+    }
+
+    /// Cross-file flow summaries from the global context (empty if absent or malformed)
+    pub fn global_flow_summaries(&self) -> Vec<FlowSummary> {
+        self.global_context
+            .get(FLOW_SUMMARIES_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Data-flow-free taint summary for one symbol (function, handler, etc.).
+/// A source reaches the sinks `flows` connects it to by label or, when the
+/// summary lists no flows, the sinks of its own kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FlowSummary {
+    /// Symbol the summary describes, e.g. "handlers::login"
+    pub symbol: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<FlowEndpoint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sinks: Vec<FlowEndpoint>,
+    /// Explicit source-to-sink paths
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flows: Vec<FlowEdge>,
+    /// Sink kinds whose input is sanitized on the way, e.g. "sql" for
+    /// parameterized queries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sanitizers: Vec<String>,
+}
+
+/// A taint source or sink inside a flow summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FlowEndpoint {
+    /// Taint kind, e.g. "user-input", "sql", "shell"
+    pub kind: String,
+    /// Human-readable label, e.g. the parameter or call name
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+}
+
+/// Data flowing from the source labeled `source` to the sink labeled `sink`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FlowEdge {
+    pub source: String,
+    pub sink: String,
+}
+
+impl FlowSummary {
+    pub fn new(symbol: impl Into<String>) -> Self {
+        Self {
+            symbol: symbol.into(),
+            sources: vec![],
+            sinks: vec![],
+            flows: vec![],
+            sanitizers: vec![],
+        }
+    }
+    pub fn with_source(
+        mut self,
+        kind: impl Into<String>,
+        label: impl Into<String>,
+        range: Option<Range>,
+    ) -> Self {
+        self.sources.push(FlowEndpoint {
+            kind: kind.into(),
+            label: label.into(),
+            range,
+        });
+        self
+    }
+    pub fn with_sink(
+        mut self,
+        kind: impl Into<String>,
+        label: impl Into<String>,
+        range: Option<Range>,
+    ) -> Self {
+        self.sinks.push(FlowEndpoint {
+            kind: kind.into(),
+            label: label.into(),
+            range,
+        });
+        self
+    }
+    /// Connect the source labeled `source` to the sink labeled `sink`
+    pub fn with_flow(mut self, source: impl Into<String>, sink: impl Into<String>) -> Self {
+        self.flows.push(FlowEdge {
+            source: source.into(),
+            sink: sink.into(),
+        });
+        self
+    }
+    pub fn with_sanitizer(mut self, kind: impl Into<String>) -> Self {
+        self.sanitizers.push(kind.into());
+        self
+    }
+
+    /// Whether a source of `kind` reaches a sink (see [`FlowSummary`]) whose
+    /// kind is not sanitized
+    pub fn is_tainted(&self, kind: &str) -> bool {
+        self.sources
+            .iter()
+            .filter(|source| source.kind == kind)
+            .any(|source| {
+                self.sinks.iter().any(|sink| {
+                    let reached = if self.flows.is_empty() {
+                        sink.kind == source.kind
+                    } else {
+                        self.flows
+                            .iter()
+                            .any(|flow| flow.source == source.label && flow.sink == sink.label)
+                    };
+                    reached && !self.sanitizers.contains(&sink.kind)
+                })
+            })
+    }
+}
+
+/// Time spent in one rule and what it found, accumulated over files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RuleTiming {
+    pub total_us: u64,
+    pub files: u64,
+    pub findings: u64,
+    /// The same totals split by file extension, for pattern suggestions
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_extension: BTreeMap<String, ExtensionTiming>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExtensionTiming {
+    pub total_us: u64,
+    pub findings: u64,
+}
+
+/// Per-rule timings keyed by rule id
+pub type RuleProfile = HashMap<String, RuleTiming>;
+
+impl RuleTiming {
+    /// Record one run of the rule on `uri`
+    pub fn record(&mut self, uri: &str, elapsed: Duration, findings: usize) {
+        let us = elapsed.as_micros() as u64;
+        self.total_us += us;
+        self.files += 1;
+        self.findings += findings as u64;
+        let by_ext = self
+            .by_extension
+            .entry(file_extension(uri).to_string())
+            .or_default();
+        by_ext.total_us += us;
+        by_ext.findings += findings as u64;
+    }
+
+    pub fn merge(&mut self, other: &RuleTiming) {
+        self.total_us += other.total_us;
+        self.files += other.files;
+        self.findings += other.findings;
+        for (ext, timing) in &other.by_extension {
+            let by_ext = self.by_extension.entry(ext.clone()).or_default();
+            by_ext.total_us += timing.total_us;
+            by_ext.findings += timing.findings;
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl LogEvent {
+    /// Re-emit as a `tracing` event; the original target and fields are
+    /// attached as `log.target` and `fields` since tracing targets are static
+    pub fn emit(&self) {
+        let target = self.target.as_deref().unwrap_or("");
+        let fields = Value::Object(self.fields.clone());
+        match self.level {
+            LogLevel::Trace => tracing::trace!(log.target = target, %fields, "{}", self.message),
+            LogLevel::Debug => tracing::debug!(log.target = target, %fields, "{}", self.message),
+            LogLevel::Info => tracing::info!(log.target = target, %fields, "{}", self.message),
+            LogLevel::Warn => tracing::warn!(log.target = target, %fields, "{}", self.message),
+            LogLevel::Error => tracing::error!(log.target = target, %fields, "{}", self.message),
+        }
+    }
+}
+
+/// Ruleset execution result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RulesetResult {
+    pub ruleset_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub execution_time_ms: u64,
+    pub files_processed: usize,
+}
+
+/// Aggregated linting results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintResults {
+    pub results: Vec<RulesetResult>,
+    pub total_files: usize,
+    pub total_diagnostics: usize,
+    pub execution_time_ms: u64,
+    pub summary: ResultSummary,
+    /// Requested behaviors that rulesets could not provide, and the fallbacks used
+    #[serde(default, skip_serializing_if = "CompatibilityReport::is_empty")]
+    pub compatibility: CompatibilityReport,
+    /// Latest `metrics` event from each ruleset that sent one
+    #[serde(default, skip_serializing_if = "RunMetrics::is_empty")]
+    pub metrics: RunMetrics,
+    /// Discovered files per extension, linted or skipped
+    #[serde(default, skip_serializing_if = "FileStats::is_empty")]
+    pub file_stats: FileStats,
+    /// Discovered files no ruleset linted, and why
+    #[serde(default, skip_serializing_if = "UnmatchedFiles::is_empty")]
+    pub unmatched: UnmatchedFiles,
+}
+
+/// A requested feature a ruleset lacks, and what was done instead
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Kind {
-    Req,
-    Res,
-    Event,
+pub struct Degradation {
+    pub feature: String,
+    pub fallback: String,
+}
+
+/// Per-ruleset record of feature downgrades, assembled at startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    pub rulesets: HashMap<String, Vec<Degradation>>,
+}
+
+impl CompatibilityReport {
+    pub fn is_empty(&self) -> bool {
+        self.rulesets.values().all(|d| d.is_empty())
+    }
+
+    /// Record which of the `requested` (feature, fallback) pairs the ruleset lacks
+    pub fn check(&mut self, capabilities: &RulesetCapabilities, requested: &[(&str, &str)]) {
+        let missing: Vec<Degradation> = requested
+            .iter()
+            .filter(|(feature, _)| !capabilities.supports(feature))
+            .map(|(feature, fallback)| Degradation {
+                feature: feature.to_string(),
+                fallback: fallback.to_string(),
+            })
+            .collect();
+        if !missing.is_empty() {
+            self.rulesets
+                .entry(capabilities.ruleset_id.clone())
+                .or_default()
+                .extend(missing);
+        }
+    }
+
+    /// One human-readable line per downgrade, sorted, for verbose output
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .rulesets
+            .iter()
+            .flat_map(|(id, degradations)| {
+                degradations.iter().map(move |d| {
+                    format!("{}: '{}' unavailable, using {}", id, d.feature, d.fallback)
+                })
+            })
+            .collect();
+        lines.sort();
+        lines
+    }
+}
+
+/// Which enabled rules never fired in a run and which files no ruleset looked
+/// at, for trimming configs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Ruleset id => enabled rules without a single finding, sorted
+    pub silent_rules: BTreeMap<String, Vec<String>>,
+    /// Ruleset id => findings per enabled rule that fired
+    pub rule_findings: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Files no enabled ruleset's `file_patterns` match, sorted
+    pub uncovered_files: Vec<String>,
+}
+
+impl CoverageReport {
+    /// Build the report for one run. `enabled` maps each ruleset that ran to
+    /// the rule ids switched on for it; `files` are the discovered paths,
+    /// relative to the workspace root. A ruleset without file patterns
+    /// counts as covering every file.
+    pub fn new(
+        catalog: &RuleCatalog,
+        enabled: &HashMap<String, Vec<String>>,
+        results: &LintResults,
+        files: &[String],
+    ) -> Self {
+        let mut report = CoverageReport::default();
+        for (ruleset_id, rule_ids) in enabled {
+            let diagnostics = results
+                .results
+                .iter()
+                .filter(|r| &r.ruleset_id == ruleset_id)
+                .flat_map(|r| &r.diagnostics);
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for d in diagnostics.filter(|d| rule_ids.contains(&d.rule_id)) {
+                *counts.entry(d.rule_id.clone()).or_default() += 1;
+            }
+            let mut silent: Vec<String> = rule_ids
+                .iter()
+                .filter(|id| !counts.contains_key(*id))
+                .cloned()
+                .collect();
+            silent.sort();
+            silent.dedup();
+            if !silent.is_empty() {
+                report.silent_rules.insert(ruleset_id.clone(), silent);
+            }
+            if !counts.is_empty() {
+                report.rule_findings.insert(ruleset_id.clone(), counts);
+            }
+        }
+
+        let patterns = enabled_patterns(catalog, enabled);
+        report.uncovered_files = files
+            .iter()
+            .filter(|file| !is_covered(&patterns, file))
+            .cloned()
+            .collect();
+        report.uncovered_files.sort();
+        report
+    }
+
+    /// One human-readable line per silent rule, then per uncovered file
+    pub fn lines(&self) -> Vec<String> {
+        let rules = self.silent_rules.iter().flat_map(|(ruleset_id, rules)| {
+            rules
+                .iter()
+                .map(move |rule_id| format!("{ruleset_id}/{rule_id}: no findings"))
+        });
+        let files = self
+            .uncovered_files
+            .iter()
+            .map(|file| format!("{file}: not covered by any ruleset"));
+        rules.chain(files).collect()
+    }
+}
+
+/// File extension => language, for the languages rulesets commonly target
+const LANGUAGES: &[(&str, &str)] = &[
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("css", "css"),
+    ("scss", "css"),
+    ("go", "go"),
+    ("html", "html"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("json", "json"),
+    ("kt", "kotlin"),
+    ("md", "markdown"),
+    ("php", "php"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "shell"),
+    ("swift", "swift"),
+    ("toml", "toml"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("vue", "vue"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+];
+
+/// Language of `path`, judged by its extension
+pub fn language_for(path: &std::path::Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, language)| *language)
+}
+
+/// `file_patterns` of each ruleset in `catalog` that is enabled
+pub(crate) fn enabled_patterns<'a>(
+    catalog: &'a RuleCatalog,
+    enabled: &HashMap<String, Vec<String>>,
+) -> Vec<&'a [String]> {
+    catalog
+        .rulesets
+        .iter()
+        .filter(|rs| enabled.contains_key(&rs.ruleset_id))
+        .map(|rs| rs.file_patterns.as_slice())
+        .collect()
+}
+
+/// Whether some ruleset handles `file`; one without patterns handles all
+pub(crate) fn is_covered(patterns: &[&[String]], file: &str) -> bool {
+    patterns
+        .iter()
+        .any(|patterns| patterns.is_empty() || patterns.iter().any(|p| glob_match(p, file)))
+}
+
+/// Extension of the file `uri` names, without the dot; empty when it has none
+fn file_extension(uri: &str) -> &str {
+    uri.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map_or("", |(_, ext)| ext)
+}
+
+/// Discovered files of one extension
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Files some enabled ruleset handles
+    pub linted: usize,
+    /// Files no enabled ruleset will lint, see [`UnmatchedReason`]
+    pub skipped: usize,
+}
+
+/// How many discovered files of each extension were linted and how many
+/// skipped, so users see what a run covered
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStats {
+    /// Lowercased extension ("" for none) => counts
+    pub by_extension: BTreeMap<String, ExtensionStats>,
+}
+
+impl FileStats {
+    /// Count `files`, the discovered paths relative to the workspace root, by
+    /// whether a ruleset in `enabled` (see [`CoverageReport::new`]) handles them
+    pub fn new(
+        catalog: &RuleCatalog,
+        enabled: &HashMap<String, Vec<String>>,
+        files: &[String],
+    ) -> Self {
+        let patterns = enabled_patterns(catalog, enabled);
+        let mut stats = FileStats::default();
+        for file in files {
+            stats.record(file, is_covered(&patterns, file));
+        }
+        stats
+    }
+
+    /// Count one discovered file as linted or skipped
+    pub fn record(&mut self, file: &str, linted: bool) {
+        let ext = file_extension(file).to_ascii_lowercase();
+        let entry = self
+            .by_extension
+            .entry(ext)
+            .or_insert_with(|| ExtensionStats {
+                language: language_for(std::path::Path::new(file)).map(str::to_string),
+                ..Default::default()
+            });
+        if linted {
+            entry.linted += 1;
+        } else {
+            entry.skipped += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_extension.is_empty()
+    }
+
+    pub fn linted(&self) -> usize {
+        self.by_extension.values().map(|s| s.linted).sum()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.by_extension.values().map(|s| s.skipped).sum()
+    }
+
+    /// Add the counts of another shard of the same run
+    pub fn merge(&mut self, other: FileStats) {
+        for (ext, theirs) in other.by_extension {
+            let ours = self.by_extension.entry(ext).or_default();
+            ours.language = ours.language.take().or(theirs.language);
+            ours.linted += theirs.linted;
+            ours.skipped += theirs.skipped;
+        }
+    }
+
+    /// One line per extension, most files first, e.g.
+    /// "1200 .ts files (typescript): 1200 linted" or
+    /// "300 .css files (css): 300 skipped"; [`UnmatchedFiles`] says why
+    pub fn lines(&self) -> Vec<String> {
+        let mut extensions: Vec<(&String, &ExtensionStats)> = self.by_extension.iter().collect();
+        extensions.sort_by(|a, b| {
+            (b.1.linted + b.1.skipped)
+                .cmp(&(a.1.linted + a.1.skipped))
+                .then(a.0.cmp(b.0))
+        });
+        extensions
+            .into_iter()
+            .map(|(ext, stats)| {
+                let name = if ext.is_empty() {
+                    "extensionless".to_string()
+                } else {
+                    format!(".{}", ext)
+                };
+                let language = stats
+                    .language
+                    .as_ref()
+                    .map(|l| format!(" ({})", l))
+                    .unwrap_or_default();
+                let counts = match (stats.linted, stats.skipped) {
+                    (linted, 0) => format!("{} linted", linted),
+                    (0, skipped) => format!("{} skipped", skipped),
+                    (linted, skipped) => format!("{} linted, {} skipped", linted, skipped),
+                };
+                format!(
+                    "{} {} files{}: {}",
+                    stats.linted + stats.skipped,
+                    name,
+                    language,
+                    counts
+                )
+            })
+            .collect()
+    }
+}
+
+/// How many unmatched files [`UnmatchedFiles`] lists by name
+pub const UNMATCHED_SAMPLE_SIZE: usize = 20;
+
+/// Why no ruleset linted a discovered file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum UnmatchedReason {
+    /// No enabled ruleset's `file_patterns` match it
+    NoRuleset,
+    /// Larger than the `max_file_size` of every ruleset matching it
+    TooLarge { size: u64, max_file_size: u64 },
+}
+
+impl std::fmt::Display for UnmatchedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnmatchedReason::NoRuleset => write!(f, "no enabled ruleset matches it"),
+            UnmatchedReason::TooLarge {
+                size,
+                max_file_size,
+            } => write!(
+                f,
+                "{} bytes, over the {} byte limit of every ruleset matching it",
+                size, max_file_size
+            ),
+        }
+    }
+}
+
+/// Why no ruleset in `enabled` would lint `file`; None when one would. `size`
+/// is the file's length in bytes, when known, checked against each matching
+/// ruleset's `max_file_size`.
+pub fn unmatched_reason(
+    catalog: &RuleCatalog,
+    enabled: &HashMap<String, Vec<String>>,
+    file: &str,
+    size: Option<u64>,
+) -> Option<UnmatchedReason> {
+    let matching: Vec<&RulesetCapabilities> = catalog
+        .rulesets
+        .iter()
+        .filter(|rs| enabled.contains_key(&rs.ruleset_id))
+        .filter(|rs| is_covered(&[rs.file_patterns.as_slice()], file))
+        .collect();
+    if matching.is_empty() {
+        return Some(UnmatchedReason::NoRuleset);
+    }
+    let size = size?;
+    let limits: Option<Vec<u64>> = matching.iter().map(|rs| rs.max_file_size).collect();
+    let max_file_size = limits?.into_iter().max()?;
+    (size > max_file_size).then_some(UnmatchedReason::TooLarge {
+        size,
+        max_file_size,
+    })
+}
+
+/// A discovered file no ruleset linted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmatchedFile {
+    pub path: String,
+    #[serde(flatten)]
+    pub reason: UnmatchedReason,
+}
+
+/// Files that were found but not linted: how many, and the first
+/// [`UNMATCHED_SAMPLE_SIZE`] of them with the reason for each
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmatchedFiles {
+    pub count: usize,
+    pub sample: Vec<UnmatchedFile>,
+}
+
+impl UnmatchedFiles {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn record(&mut self, path: impl Into<String>, reason: UnmatchedReason) {
+        self.count += 1;
+        if self.sample.len() < UNMATCHED_SAMPLE_SIZE {
+            self.sample.push(UnmatchedFile {
+                path: path.into(),
+                reason,
+            });
+        }
+    }
+
+    /// Add the files of another shard of the same run
+    pub fn merge(&mut self, other: UnmatchedFiles) {
+        self.count += other.count;
+        let room = UNMATCHED_SAMPLE_SIZE.saturating_sub(self.sample.len());
+        self.sample.extend(other.sample.into_iter().take(room));
+    }
+
+    /// A count line, then one line per sampled file, for verbose output
+    pub fn lines(&self) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut lines = vec![format!(
+            "{} file{} not linted by any ruleset",
+            self.count,
+            if self.count == 1 { "" } else { "s" }
+        )];
+        lines.extend(
+            self.sample
+                .iter()
+                .map(|file| format!("  {}: {}", file.path, file.reason)),
+        );
+        if self.count > self.sample.len() {
+            lines.push(format!("  ... and {} more", self.count - self.sample.len()));
+        }
+        lines
+    }
+}
+
+/// Match `path` against a glob `pattern`: `*` and `?` stay within a path
+/// segment, `**` spans any number of segments and `{a,b}` lists alternatives.
+/// Patterns without a `/` are matched against the file name only.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    expand_braces(pattern).iter().any(|pattern| {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if !pattern.contains('/') {
+            return segments
+                .last()
+                .is_some_and(|name| match_segment(pattern, name));
+        }
+        let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+        match_segments(&pattern, &segments)
+    })
+}
+
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|at| open + at) else {
+        return vec![pattern.to_string()];
+    };
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{head}{alternative}{tail}")))
+        .collect()
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| match_segment(segment, name) && match_segments(rest, tail)),
+    }
+}
+
+/// `*` and `?` wildcard match of a single segment
+fn match_segment(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Collects the `metrics` events rulesets send during a run. Servers report
+/// cumulative totals, so only the latest event per ruleset is kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunMetrics {
+    pub rulesets: BTreeMap<String, crate::protocol::MetricsEvent>,
+    /// The host's timing history once the run is recorded in it
+    #[cfg(feature = "linter-host")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<crate::cache::TimingSnapshot>,
+}
+
+impl RunMetrics {
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "linter-host")]
+        if self.timings.is_some() {
+            return false;
+        }
+        self.rulesets.is_empty()
+    }
+
+    pub fn record(&mut self, ruleset_id: &str, event: crate::protocol::MetricsEvent) {
+        self.rulesets.insert(ruleset_id.to_string(), event);
+    }
+
+    /// Record `envelope` if it is a `metrics` event, returning whether it was
+    pub fn record_envelope(&mut self, ruleset_id: &str, envelope: &Envelope<Value>) -> bool {
+        if envelope.kind != Kind::Event || envelope.typ != "metrics" {
+            return false;
+        }
+        let Some(event) = envelope
+            .payload
+            .clone()
+            .and_then(|payload| serde_json::from_value(payload).ok())
+        else {
+            return false;
+        };
+        self.record(ruleset_id, event);
+        true
+    }
+
+    /// Counters summed over all rulesets
+    pub fn counters(&self) -> BTreeMap<String, u64> {
+        let mut counters = BTreeMap::new();
+        for (name, value) in self.rulesets.values().flat_map(|event| &event.counters) {
+            *counters.entry(name.clone()).or_default() += value;
+        }
+        counters
+    }
+
+    /// The `top` rules by total time, as (ruleset id, rule id, timing)
+    pub fn slowest_rules(&self, top: usize) -> Vec<(&str, &str, &RuleTiming)> {
+        let mut rules: Vec<(&str, &str, &RuleTiming)> =
+            self.rulesets
+                .iter()
+                .flat_map(|(ruleset_id, event)| {
+                    event.rule_timings.iter().map(move |(rule_id, timing)| {
+                        (ruleset_id.as_str(), rule_id.as_str(), timing)
+                    })
+                })
+                .collect();
+        rules.sort_by(|a, b| b.2.total_us.cmp(&a.2.total_us).then(a.1.cmp(b.1)));
+        rules.truncate(top);
+        rules
+    }
+
+    /// Fold in metrics from another shard: counters and timings are summed,
+    /// gauges keep the larger reading and the first timing snapshot is kept
+    pub fn merge(&mut self, other: RunMetrics) {
+        #[cfg(feature = "linter-host")]
+        if self.timings.is_none() {
+            self.timings = other.timings;
+        }
+        for (ruleset_id, event) in other.rulesets {
+            let merged = self.rulesets.entry(ruleset_id).or_default();
+            for (name, value) in event.counters {
+                *merged.counters.entry(name).or_default() += value;
+            }
+            for (name, value) in event.gauges {
+                let gauge = merged.gauges.entry(name).or_insert(value);
+                *gauge = gauge.max(value);
+            }
+            for (rule_id, timing) in &event.rule_timings {
+                merged
+                    .rule_timings
+                    .entry(rule_id.clone())
+                    .or_default()
+                    .merge(timing);
+            }
+        }
+    }
+}
+
+impl LintResults {
+    /// Copy of these results keeping only diagnostics accepted by `keep`,
+    /// with totals and the severity summary recomputed
+    pub fn filtered(&self, keep: impl Fn(&Diagnostic) -> bool) -> LintResults {
+        self.filtered_by_ruleset(|_, d| keep(d))
+    }
+
+    /// Every attachment referenced by a finding, with the finding's file
+    pub fn attachments(&self) -> Vec<(Option<&str>, &Attachment)> {
+        self.results
+            .iter()
+            .flat_map(|r| &r.diagnostics)
+            .flat_map(|d| d.attachments.iter().map(move |a| (d.uri.as_deref(), a)))
+            .collect()
+    }
+
+    /// Like `filtered`, but `keep` also receives the id of the reporting ruleset
+    pub fn filtered_by_ruleset(&self, keep: impl Fn(&str, &Diagnostic) -> bool) -> LintResults {
+        let results: Vec<RulesetResult> = self
+            .results
+            .iter()
+            .map(|r| RulesetResult {
+                ruleset_id: r.ruleset_id.clone(),
+                diagnostics: r
+                    .diagnostics
+                    .iter()
+                    .filter(|d| keep(&r.ruleset_id, d))
+                    .cloned()
+                    .collect(),
+                execution_time_ms: r.execution_time_ms,
+                files_processed: r.files_processed,
+            })
+            .collect();
+        LintResults {
+            total_files: self.total_files,
+            total_diagnostics: results.iter().map(|r| r.diagnostics.len()).sum(),
+            execution_time_ms: self.execution_time_ms,
+            summary: ResultSummary::of(&results, self.summary.rulesets_used.clone()),
+            compatibility: self.compatibility.clone(),
+            metrics: self.metrics.clone(),
+            file_stats: self.file_stats.clone(),
+            unmatched: self.unmatched.clone(),
+            results,
+        }
+    }
+
+    /// Deduplicate and sort each ruleset's diagnostics (see
+    /// [`dedup_diagnostics`] and [`sort_diagnostics`]) and the rulesets by id,
+    /// recomputing the totals. Hosts call this once all results are in, so
+    /// output is the same whatever order engines answered in.
+    pub fn normalize(&mut self) {
+        for result in &mut self.results {
+            result.diagnostics = dedup_diagnostics(std::mem::take(&mut result.diagnostics));
+            sort_diagnostics(&mut result.diagnostics);
+        }
+        self.results.sort_by(|a, b| a.ruleset_id.cmp(&b.ruleset_id));
+        self.total_diagnostics = self.results.iter().map(|r| r.diagnostics.len()).sum();
+        self.summary = ResultSummary::of(&self.results, self.summary.rulesets_used.clone());
+    }
+
+    /// Combine the results of shards that each linted a disjoint set of files,
+    /// normalized as by [`LintResults::normalize`]; the run takes as long as its
+    /// slowest shard. SARIF logs the shards already wrote are combined with
+    /// [`crate::format::merge_sarif_logs`].
+    pub fn merge_shards(shards: impl IntoIterator<Item = LintResults>) -> LintResults {
+        let mut results: Vec<RulesetResult> = Vec::new();
+        let mut total_files = 0;
+        let mut execution_time_ms = 0;
+        let mut rulesets_used: Vec<String> = Vec::new();
+        let mut compatibility = CompatibilityReport::default();
+        let mut metrics = RunMetrics::default();
+        let mut file_stats = FileStats::default();
+        let mut unmatched = UnmatchedFiles::default();
+        for shard in shards {
+            metrics.merge(shard.metrics);
+            file_stats.merge(shard.file_stats);
+            unmatched.merge(shard.unmatched);
+            total_files += shard.total_files;
+            execution_time_ms = execution_time_ms.max(shard.execution_time_ms);
+            rulesets_used.extend(shard.summary.rulesets_used);
+            for (id, degradations) in shard.compatibility.rulesets {
+                let merged = compatibility.rulesets.entry(id).or_default();
+                for d in degradations {
+                    if !merged.iter().any(|m| m.feature == d.feature) {
+                        merged.push(d);
+                    }
+                }
+            }
+            for result in shard.results {
+                match results
+                    .iter_mut()
+                    .find(|r| r.ruleset_id == result.ruleset_id)
+                {
+                    Some(merged) => {
+                        merged.diagnostics.extend(result.diagnostics);
+                        merged.execution_time_ms += result.execution_time_ms;
+                        merged.files_processed += result.files_processed;
+                    }
+                    None => results.push(result),
+                }
+            }
+        }
+        rulesets_used.sort();
+        rulesets_used.dedup();
+        let mut merged = LintResults {
+            total_files,
+            // Both recomputed by normalize
+            total_diagnostics: 0,
+            execution_time_ms,
+            summary: ResultSummary::of(&[], rulesets_used),
+            compatibility,
+            metrics,
+            file_stats,
+            unmatched,
+            results,
+        };
+        merged.normalize();
+        merged
+    }
+}
+
+/// How findings from different rulesets are recognized as the same issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeHeuristic {
+    /// Keep every finding
+    #[default]
+    Off,
+    /// Same file, same range and same rule id
+    SameRule,
+    /// Same file and range, and either the same rule id or a similar message
+    SimilarMessage,
+}
+
+/// Groups of rules from different rulesets that detect the same logical issue,
+/// e.g. `eslint/no-unused-vars` and `oxc/no-unused-vars`
+#[derive(Debug, Clone, Default)]
+pub struct RuleEquivalences {
+    /// Qualified rule id ("<ruleset>/<rule>") => logical rule name
+    canonical: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Envelope<T = Value> {
-    pub v: u8,
-    pub kind: Kind,
-    #[serde(rename = "type")]
-    pub typ: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub payload: Option<T>,
-}
-
-impl<T> Envelope<T> {
-    pub fn event(typ: &str, payload: T) -> Self {
-        Self {
-            v: PROTOCOL_VERSION,
-            kind: Kind::Event,
-            typ: typ.to_string(),
-            id: None,
-            payload: Some(payload),
+impl RuleEquivalences {
+    /// Build from logical name => qualified member rule ids, rejecting members that
+    /// aren't qualified or that belong to more than one group
+    pub fn new(groups: &HashMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut canonical = HashMap::new();
+        for (name, members) in groups {
+            for member in members {
+                if !member.contains('/') {
+                    return Err(format!(
+                        "equivalence '{}': rule '{}' must be qualified as <ruleset>/<rule>",
+                        name, member
+                    ));
+                }
+                if let Some(previous) = canonical.insert(member.clone(), name.clone()) {
+                    return Err(format!(
+                        "rule '{}' is listed in both '{}' and '{}' equivalences",
+                        member, previous, name
+                    ));
+                }
+            }
         }
+        Ok(Self { canonical })
     }
-    pub fn res(typ: &str, id: impl Into<String>, payload: T) -> Self {
-        Self {
-            v: PROTOCOL_VERSION,
-            kind: Kind::Res,
-            typ: typ.to_string(),
-            id: Some(id.into()),
-            payload: Some(payload),
-        }
+
+    /// Logical rule name for a qualified rule id (the id itself when unmapped)
+    pub fn canonical<'a>(&'a self, qualified_rule_id: &'a str) -> &'a str {
+        self.canonical
+            .get(qualified_rule_id)
+            .map(|s| s.as_str())
+            .unwrap_or(qualified_rule_id)
     }
-    pub fn req(typ: &str, id: impl Into<String>, payload: T) -> Self {
-        Self {
-            v: PROTOCOL_VERSION,
-            kind: Kind::Req,
-            typ: typ.to_string(),
-            id: Some(id.into()),
-            payload: Some(payload),
-        }
+
+    pub fn are_equivalent(&self, a: &str, b: &str) -> bool {
+        self.canonical(a) == self.canonical(b)
     }
 }
 
-/// Minimal NDJSON writer.
-pub struct Ndjson<W: Write> {
-    writer: W,
-}
-impl<W: Write> Ndjson<W> {
-    pub fn new(writer: W) -> Self {
-        Self { writer }
-    }
-    pub fn send<S: Serialize>(&mut self, obj: &S) -> io::Result<()> {
-        let line = serde_json::to_string(obj)?;
-        self.writer.write_all(line.as_bytes())?;
-        self.writer.write_all(b"\n")?;
-        self.writer.flush()
+/// Merge duplicate findings reported by different sources (ruleset ids).
+/// Rules declared equivalent count as the same rule. Each kept diagnostic lists
+/// every source that reported it in `sources`.
+pub fn merge_duplicate_diagnostics(
+    reported: Vec<(String, Diagnostic)>,
+    heuristic: MergeHeuristic,
+    equivalences: &RuleEquivalences,
+) -> Vec<Diagnostic> {
+    let mut merged: Vec<(String, Diagnostic)> = Vec::new();
+    for (source, mut d) in reported {
+        let qualified = format!("{}/{}", source, d.rule_id);
+        let existing = match heuristic {
+            MergeHeuristic::Off => None,
+            _ => merged.iter_mut().find(|(first, m)| {
+                m.uri == d.uri
+                    && m.range == d.range
+                    && (equivalences.are_equivalent(first, &qualified)
+                        || m.rule_id == d.rule_id
+                        || (heuristic == MergeHeuristic::SimilarMessage
+                            && similar_messages(&m.message, &d.message)))
+            }),
+        };
+        match existing {
+            Some((_, m)) => {
+                if !m.sources.contains(&source) {
+                    m.sources.push(source);
+                }
+            }
+            None => {
+                d.sources = vec![source];
+                merged.push((qualified, d));
+            }
+        }
     }
+    merged.into_iter().map(|(_, d)| d).collect()
 }
 
-/// Read one NDJSON line from stdin as raw JSON.
-pub fn read_line_value() -> io::Result<Value> {
-    let stdin = io::stdin();
-    let mut lock = stdin.lock();
-    let mut buf = String::new();
-    buf.clear();
-    let n = lock.read_line(&mut buf)?;
-    if n == 0 {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin closed"));
+/// Drop repeated findings: same file, rule, range and message. The first
+/// occurrence is kept and gains the `sources` of its repeats.
+pub fn dedup_diagnostics(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut seen: HashMap<(Option<String>, String, Range, String), usize> = HashMap::new();
+    let mut kept: Vec<Diagnostic> = Vec::new();
+    for d in diagnostics {
+        let key = (d.uri.clone(), d.rule_id.clone(), d.range, d.message.clone());
+        match seen.get(&key) {
+            Some(&i) => {
+                for source in d.sources {
+                    if !kept[i].sources.contains(&source) {
+                        kept[i].sources.push(source);
+                    }
+                }
+            }
+            None => {
+                seen.insert(key, kept.len());
+                kept.push(d);
+            }
+        }
     }
-    let trimmed = buf.trim();
-    let value: Value =
-        serde_json::from_str(trimmed).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-    Ok(value)
+    kept
 }
 
-/// Common position types and diagnostics.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Position {
-    pub line: u32,
-    pub character: u32,
-}
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Range {
-    pub start: Position,
-    pub end: Position,
+/// Sort into report order: path, line, column, then rule id; end position and
+/// message break remaining ties so the order never depends on arrival
+pub fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| {
+        a.uri
+            .cmp(&b.uri)
+            .then(a.range.start.cmp(&b.range.start))
+            .then_with(|| a.rule_id.cmp(&b.rule_id))
+            .then(a.range.end.cmp(&b.range.end))
+            .then_with(|| a.message.cmp(&b.message))
+    });
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Fix {
-    pub range: Range,
-    pub text: String,
+/// Which diagnostics reach the output: every criterion set must pass. Built
+/// with the `with_*` methods or read from `[linter.filter]` in the config.
+/// Path globs follow [`glob_match`] and see the diagnostic's file decoded from
+/// its `file://` URI and, once [`DiagnosticFilter::with_root`] is set, relative
+/// to the workspace root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct DiagnosticFilter {
+    /// Drop findings below this severity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<Severity>,
+    /// When non-empty, only these rule ids pass
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_rules: Vec<String>,
+    /// Rule ids that never pass
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_rules: Vec<String>,
+    /// When non-empty, only findings in files matching one of these pass
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_paths: Vec<String>,
+    /// Findings in files matching one of these never pass
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_paths: Vec<String>,
+    /// Prefix stripped from paths before matching
+    #[serde(skip)]
+    root: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SuggestFix {
-    pub title: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fix: Option<Fix>,
-}
+impl DiagnosticFilter {
+    /// Filter letting everything through
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Diagnostic {
-    pub rule_id: String,
-    pub message: String,
-    pub severity: String, // "error" | "warn" | "info"
-    pub range: Range,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub code: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub suggest: Option<Vec<SuggestFix>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub docs_url: Option<String>,
-}
-
-/// Utility for line/offset mapping for plain-text rules.
-pub struct LineIndex {
-    text: String,
-    starts: Vec<usize>,
-}
-impl LineIndex {
-    pub fn new(text: &str) -> Self {
-        let mut s = vec![0usize];
-        for (i, ch) in text.char_indices() {
-            if ch == '\n' {
-                s.push(i + 1);
-            }
-        }
-        Self {
-            text: text.to_string(),
-            starts: s,
-        }
+    pub fn with_min_severity(mut self, severity: Severity) -> Self {
+        self.min_severity = Some(severity);
+        self
     }
-    pub fn to_pos(&self, mut off: usize) -> Position {
-        if off > self.text.len() {
-            off = self.text.len();
-        }
-        // binary search
-        let (mut lo, mut hi) = (0usize, self.starts.len().saturating_sub(1));
-        while lo <= hi {
-            let mid = (lo + hi) / 2;
-            let start = self.starts[mid];
-            let next = if mid + 1 < self.starts.len() {
-                self.starts[mid + 1]
-            } else {
-                self.text.len() + 1
-            };
-            if off < start {
-                if mid == 0 {
-                    break;
-                }
-                hi = mid - 1;
-            } else if off >= next {
-                lo = mid + 1;
-            } else {
-                return Position {
-                    line: mid as u32,
-                    character: (off - start) as u32,
-                };
-            }
+
+    pub fn with_allowed_rules<I, S>(mut self, rule_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_rules
+            .extend(rule_ids.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_denied_rules<I, S>(mut self, rule_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deny_rules.extend(rule_ids.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_included_paths<I, S>(mut self, globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_paths.extend(globs.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_excluded_paths<I, S>(mut self, globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_paths.extend(globs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Match path globs relative to `root`, e.g. the workspace directory
+    pub fn with_root(mut self, root: impl Into<String>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Whether every finding passes
+    pub fn is_empty(&self) -> bool {
+        self.min_severity.is_none()
+            && self.allow_rules.is_empty()
+            && self.deny_rules.is_empty()
+            && self.include_paths.is_empty()
+            && self.exclude_paths.is_empty()
+    }
+
+    pub fn accepts(&self, d: &Diagnostic) -> bool {
+        if self.min_severity.is_some_and(|min| d.severity < min) {
+            return false;
         }
-        Position {
-            line: 0,
-            character: off as u32,
+        if self.deny_rules.contains(&d.rule_id)
+            || (!self.allow_rules.is_empty() && !self.allow_rules.contains(&d.rule_id))
+        {
+            return false;
         }
-    }
-    pub fn to_range(&self, s: usize, e: usize) -> Range {
-        Range {
-            start: self.to_pos(s),
-            end: self.to_pos(e),
+        if self.include_paths.is_empty() && self.exclude_paths.is_empty() {
+            return true;
         }
+        let Some(path) = d.uri.as_deref().map(|uri| self.relative_path(uri)) else {
+            // Findings without a file only face the include list
+            return self.include_paths.is_empty();
+        };
+        let matches = |globs: &[String]| globs.iter().any(|glob| glob_match(glob, &path));
+        (self.include_paths.is_empty() || matches(&self.include_paths))
+            && !matches(&self.exclude_paths)
     }
-}
 
-/// Information about a single rule
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RuleInfo {
-    pub id: String,
-    pub description: String,
-}
+    /// Copy of `results` keeping only accepted findings; see [`LintResults::filtered`]
+    pub fn apply(&self, results: &LintResults) -> LintResults {
+        results.filtered(|d| self.accepts(d))
+    }
 
-/// Information about a ruleset and its rules
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RulesetInfo {
-    pub id: String,
-    pub rules: Vec<RuleInfo>,
+    fn relative_path(&self, uri: &str) -> String {
+        let path = uri_to_path(uri);
+        let relative = self
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(&path);
+        relative.to_string_lossy().replace('\\', "/")
+    }
 }
 
-/// Configuration setting definition for rulesets
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConfigSetting {
-    /// Setting name/key
-    pub name: String,
-    /// Human-readable description
-    pub description: String,
-    /// Data type of the setting
-    #[serde(rename = "type")]
-    pub setting_type: ConfigType,
-    /// Default value
-    pub default: Value,
-    /// Whether this setting is required
-    #[serde(default)]
-    pub required: bool,
-    /// Allowed values (for enum types)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub allowed_values: Option<Vec<Value>>,
-    /// Minimum value (for numeric types)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub min: Option<f64>,
-    /// Maximum value (for numeric types)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub max: Option<f64>,
-}
-
-/// Data types for configuration settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum ConfigType {
-    String,
-    Number,
-    Integer,
-    Boolean,
-    Array,
-    Object,
-    /// One of a set of predefined values
-    Enum,
-}
-
-/// Ruleset capabilities and metadata (replaces EngineCapabilities)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RulesetCapabilities {
-    pub ruleset_id: String,
-    pub version: String,
-    pub file_patterns: Vec<String>,
-    pub max_file_size: Option<u64>,
-    /// Comment prefixes used for annotations (e.g., ["//", "#", "/*"])
-    pub annotation_prefixes: Vec<String>,
-    /// Rules available in this ruleset
-    pub rules: Vec<RuleInfo>,
-    /// Default configuration for rules
-    pub default_config: HashMap<String, Value>,
-    /// Configuration settings that can be customized
-    #[serde(default)]
-    pub config_settings: Vec<ConfigSetting>,
+/// Word-set Jaccard similarity of at least 0.6, ignoring case and punctuation
+fn similar_messages(a: &str, b: &str) -> bool {
+    let words = |s: &str| -> std::collections::HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    union > 0 && a.intersection(&b).count() * 10 >= union * 6
 }
 
+/// Rule id of the synthetic diagnostic summarizing findings dropped by caps
+pub const OVERFLOW_RULE_ID: &str = "forseti/overflow";
 
-/// File preprocessing context from ruleset
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PreprocessingContext {
-    pub ruleset_id: String,
-    pub files: Vec<FileContext>,
-    pub global_context: HashMap<String, Value>, // Cross-file context
+/// Limits on how many diagnostics are kept; 0 => unlimited
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiagnosticCaps {
+    pub per_file: usize,
+    pub per_rule: usize,
 }
 
-/// Context for a single file after preprocessing
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FileContext {
-    pub uri: String,
-    pub content: String,
-    pub language: Option<String>,
-    pub context: HashMap<String, Value>, // AST, symbols, etc.
-}
+/// Truncate diagnostics to the per-file and per-rule (within a file) caps.
+/// Each file with dropped findings gets one synthetic `OVERFLOW_RULE_ID` diagnostic
+/// saying how many were suppressed; relative order is otherwise preserved.
+pub fn cap_diagnostics(diagnostics: Vec<Diagnostic>, caps: DiagnosticCaps) -> Vec<Diagnostic> {
+    let limit = |n: usize| if n == 0 { usize::MAX } else { n };
+    let mut per_file: HashMap<Option<String>, usize> = HashMap::new();
+    let mut per_rule: HashMap<(Option<String>, String), usize> = HashMap::new();
+    let mut suppressed: Vec<(Option<String>, usize)> = Vec::new();
+    let mut kept = Vec::new();
 
-/// Ruleset execution result
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RulesetResult {
-    pub ruleset_id: String,
-    pub diagnostics: Vec<Diagnostic>,
-    pub execution_time_ms: u64,
-    pub files_processed: usize,
-}
+    for d in diagnostics {
+        let file_count = per_file.entry(d.uri.clone()).or_default();
+        let rule_count = per_rule
+            .entry((d.uri.clone(), d.rule_id.clone()))
+            .or_default();
+        if *file_count < limit(caps.per_file) && *rule_count < limit(caps.per_rule) {
+            *file_count += 1;
+            *rule_count += 1;
+            kept.push(d);
+        } else {
+            match suppressed.iter_mut().find(|(uri, _)| *uri == d.uri) {
+                Some((_, n)) => *n += 1,
+                None => suppressed.push((d.uri.clone(), 1)),
+            }
+        }
+    }
 
-/// Aggregated linting results
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LintResults {
-    pub results: Vec<RulesetResult>,
-    pub total_files: usize,
-    pub total_diagnostics: usize,
-    pub execution_time_ms: u64,
-    pub summary: ResultSummary,
+    let origin = Position {
+        line: 0,
+        character: 0,
+    };
+    for (uri, n) in suppressed {
+        let mut overflow = Diagnostic::new(
+            OVERFLOW_RULE_ID,
+            format!("{} additional findings suppressed", n),
+            Severity::Info,
+            Range {
+                start: origin,
+                end: origin,
+            },
+        );
+        overflow.uri = uri;
+        kept.push(overflow);
+    }
+    kept
 }
 
 /// Summary of linting results
@@ -305,9 +2399,26 @@ pub struct ResultSummary {
     pub errors: usize,
     pub warnings: usize,
     pub info: usize,
+    #[serde(default)]
+    pub hints: usize,
     pub rulesets_used: Vec<String>,
 }
 
+impl ResultSummary {
+    /// Severity counts over `results`
+    fn of(results: &[RulesetResult], rulesets_used: Vec<String>) -> Self {
+        let all = || results.iter().flat_map(|r| r.diagnostics.iter());
+        let count = |severity: Severity| all().filter(|d| d.severity == severity).count();
+        Self {
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warn),
+            info: count(Severity::Info),
+            hints: count(Severity::Hint),
+            rulesets_used,
+        }
+    }
+}
+
 /// Annotation scope for ignore directives
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AnnotationScope {
@@ -328,11 +2439,49 @@ pub struct Annotation {
 /// Utility for parsing annotations from text
 pub struct AnnotationParser {
     prefixes: Vec<String>,
+    /// Ruleset whose bare rule ids are checked, and the equivalences that let
+    /// a directive naming any rule of a group suppress the others
+    equivalences: Option<(String, RuleEquivalences)>,
 }
 
 impl AnnotationParser {
     pub fn new(prefixes: Vec<String>) -> Self {
-        Self { prefixes }
+        Self {
+            prefixes,
+            equivalences: None,
+        }
+    }
+
+    /// Treat rules declared equivalent as one when matching directives:
+    /// `forseti-ignore other/rule` or the group's logical name then also
+    /// suppresses the matching rule of `ruleset_id`
+    pub fn with_equivalences(
+        mut self,
+        ruleset_id: impl Into<String>,
+        equivalences: RuleEquivalences,
+    ) -> Self {
+        self.equivalences = Some((ruleset_id.into(), equivalences));
+        self
+    }
+
+    /// Whether a directive naming `named` covers `rule_id`
+    fn names_rule(&self, named: &str, rule_id: &str) -> bool {
+        if named == rule_id {
+            return true;
+        }
+        let Some((ruleset_id, equivalences)) = &self.equivalences else {
+            return false;
+        };
+        let qualify = |id: &str| {
+            if id.contains('/') {
+                id.to_string()
+            } else {
+                format!("{}/{}", ruleset_id, id)
+            }
+        };
+        let rule = qualify(rule_id);
+        let canonical = equivalences.canonical(&rule);
+        named == canonical || equivalences.canonical(&qualify(named)) == canonical
     }
 
     /// Parse all annotations from text content
@@ -412,19 +2561,24 @@ impl AnnotationParser {
                 AnnotationScope::File => {
                     // File-level ignores apply to all lines
                     if annotation.rule_ids.is_empty()
-                        || annotation.rule_ids.contains(&rule_id.to_string())
+                        || annotation
+                            .rule_ids
+                            .iter()
+                            .any(|named| self.names_rule(named, rule_id))
                     {
                         return true;
                     }
                 }
                 AnnotationScope::NextLine => {
                     // Next-line ignores apply only to the line immediately following the annotation
-                    if line == annotation.line + 1 {
-                        if annotation.rule_ids.is_empty()
-                            || annotation.rule_ids.contains(&rule_id.to_string())
-                        {
-                            return true;
-                        }
+                    if line == annotation.line + 1
+                        && (annotation.rule_ids.is_empty()
+                            || annotation
+                                .rule_ids
+                                .iter()
+                                .any(|named| self.names_rule(named, rule_id)))
+                    {
+                        return true;
                     }
                 }
             }
@@ -433,9 +2587,88 @@ impl AnnotationParser {
     }
 }
 
+/// Closing delimiter for block comment prefixes, so the directive stays a comment
+fn comment_suffix(prefix: &str) -> &'static str {
+    match prefix {
+        "/*" => " */",
+        "<!--" => " -->",
+        _ => "",
+    }
+}
+
+/// Quick fix that silences `diagnostic` with a `forseti-ignore-next-line`
+/// comment above its first line, indented like that line. `prefix` is one of
+/// the ruleset's `annotation_prefixes`. A next-line directive already on the
+/// line above gains the rule instead of a second comment being stacked; None
+/// when that directive already covers the rule.
+pub fn suppression_fix(diagnostic: &Diagnostic, text: &str, prefix: &str) -> Option<SuggestFix> {
+    let rule_id = diagnostic.rule_id.as_str();
+    let line = diagnostic.range.start.line;
+    let parser = AnnotationParser::new(vec![prefix.to_string()]);
+    let suffix = comment_suffix(prefix);
+    let lines: Vec<&str> = text.lines().collect();
+    let previous = line
+        .checked_sub(1)
+        .and_then(|above| Some((above, *lines.get(above as usize)?)))
+        .and_then(|(above, content)| {
+            let annotation = parser.parse_line_annotation(content, above)?;
+            // The parser keeps a block comment's closing delimiter on the last id
+            let rule_ids: Vec<String> = annotation
+                .rule_ids
+                .iter()
+                .map(|id| id.trim_end_matches(suffix.trim()).trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            (annotation.scope == AnnotationScope::NextLine).then_some((above, content, rule_ids))
+        });
+
+    let fix = match previous {
+        Some((_, _, rule_ids))
+            if rule_ids.is_empty() || rule_ids.iter().any(|id| id == rule_id) =>
+        {
+            return None;
+        }
+        Some((above, content, mut rule_ids)) => {
+            let indent = &content[..content.len() - content.trim_start().len()];
+            rule_ids.push(rule_id.to_string());
+            Fix {
+                range: Range {
+                    start: Position {
+                        line: above,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: above,
+                        character: LineIndex::new(text).line_len(above).unwrap_or(0),
+                    },
+                },
+                text: format!(
+                    "{indent}{prefix} forseti-ignore-next-line {}{suffix}",
+                    rule_ids.join(", ")
+                ),
+            }
+        }
+        None => {
+            let content = lines.get(line as usize).copied().unwrap_or("");
+            let indent = &content[..content.len() - content.trim_start().len()];
+            let start = Position { line, character: 0 };
+            Fix {
+                range: Range { start, end: start },
+                text: format!("{indent}{prefix} forseti-ignore-next-line {rule_id}{suffix}\n"),
+            }
+        }
+    };
+    Some(SuggestFix {
+        title: format!("Suppress {rule_id} for this line"),
+        fix: Some(fix),
+    })
+}
+
+#[cfg(feature = "linter-host")]
 #[derive(Clone)]
 pub struct SharedConfig(pub std::sync::Arc<Config>);
 
+#[cfg(feature = "linter-host")]
 impl SharedConfig {
     /// Stable borrow tied to &self (no temporaries).
     pub fn get(&self) -> &Config {
@@ -447,3 +2680,472 @@ impl SharedConfig {
         self.0.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn positions_past_the_line_or_text_are_clamped() {
+        let index = LineIndex::new("one\ntwo three\n");
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.clamp_position(pos(0, 40)), pos(0, 3));
+        assert_eq!(index.clamp_position(pos(1, 4)), pos(1, 4));
+        assert_eq!(index.clamp_position(pos(2, 5)), pos(2, 0));
+        assert_eq!(index.clamp_position(pos(9, 9)), pos(2, 0));
+        assert_eq!(index.to_offset(pos(0, 4)), None);
+        assert_eq!(index.to_pos(100), pos(2, 0));
+
+        let index = LineIndex::new("no newline");
+        assert_eq!(index.clamp_position(pos(3, 0)), pos(0, 10));
+    }
+
+    #[test]
+    fn ranges_are_swapped_clamped_or_dropped() {
+        let index = LineIndex::new("one\ntwo\n");
+        let range = |start, end| Range { start, end };
+        assert_eq!(
+            index.normalize_range(range(pos(1, 2), pos(0, 1))),
+            Some(range(pos(0, 1), pos(1, 2)))
+        );
+        assert_eq!(
+            index.normalize_range(range(pos(1, 1), pos(7, 0))),
+            Some(range(pos(1, 1), pos(2, 0)))
+        );
+        assert_eq!(index.normalize_range(range(pos(3, 0), pos(3, 1))), None);
+
+        let (kept, warnings) = validate_diagnostic_ranges(
+            "one\n",
+            vec![
+                at("file:///a", "r", 0, "kept"),
+                at("file:///a", "r", 5, "gone"),
+            ],
+        );
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].message, "kept");
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn crlf_terminators_are_not_part_of_the_line() {
+        let index = LineIndex::new("ab\r\ncd\r\n");
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_len(0), Some(2));
+        assert_eq!(index.line_text(0), Some("ab"));
+        assert_eq!(index.line_text(1), Some("cd"));
+        assert_eq!(index.to_pos(4), pos(1, 0));
+        assert_eq!(index.to_offset(pos(1, 1)), Some(5));
+        // The \r is not a column the position can land on
+        assert_eq!(index.clamp_position(pos(0, 3)), pos(0, 2));
+        assert_eq!(index.to_offset(pos(0, 3)), None);
+    }
+
+    #[test]
+    fn columns_count_bytes_and_snap_to_character_starts() {
+        // é is two bytes and one UTF-16 unit; 😀 is four bytes and two UTF-16 units
+        let index = LineIndex::new("é😀x\n");
+        assert_eq!(index.line_len(0), Some(7));
+        let x = "é😀".len();
+        assert_eq!(index.to_pos(x), pos(0, 6));
+        assert_eq!(index.to_offset(pos(0, 6)), Some(x));
+        // UTF-16 would put x at column 3, which is inside 😀 here
+        assert_eq!(index.to_offset(pos(0, 3)), None);
+        assert_eq!(index.clamp_position(pos(0, 1)), pos(0, 0));
+        assert_eq!(index.clamp_position(pos(0, 5)), pos(0, 2));
+        assert_eq!(index.clamp_position(pos(0, 99)), pos(0, 7));
+    }
+
+    fn at(uri: &str, rule_id: &str, line: u32, message: &str) -> Diagnostic {
+        let start = Position { line, character: 0 };
+        let mut d = Diagnostic::new(
+            rule_id,
+            message,
+            Severity::Warn,
+            Range { start, end: start },
+        );
+        d.uri = Some(uri.to_string());
+        d
+    }
+
+    /// `text` with the suppression fix for a `rule_id` finding on `line` applied
+    fn suppressed(text: &str, rule_id: &str, line: u32, prefix: &str) -> Option<String> {
+        let suggestion = suppression_fix(&at("file:///a", rule_id, line, "m"), text, prefix)?;
+        assert_eq!(
+            suggestion.title,
+            format!("Suppress {rule_id} for this line")
+        );
+        let fix = suggestion.fix.unwrap();
+        let index = LineIndex::new(text);
+        let start = index.to_offset(fix.range.start).unwrap();
+        let end = index.to_offset(fix.range.end).unwrap();
+        Some(format!("{}{}{}", &text[..start], fix.text, &text[end..]))
+    }
+
+    #[test]
+    fn suppressions_are_inserted_above_the_line() {
+        assert_eq!(
+            suppressed("let a = 1;\nlet b = 2;\n", "no-let", 1, "//").unwrap(),
+            "let a = 1;\n// forseti-ignore-next-line no-let\nlet b = 2;\n"
+        );
+        assert_eq!(
+            suppressed("<p>\n", "alt-text", 0, "<!--").unwrap(),
+            "<!-- forseti-ignore-next-line alt-text -->\n<p>\n"
+        );
+    }
+
+    #[test]
+    fn suppressions_keep_the_indentation_of_the_line() {
+        assert_eq!(
+            suppressed("fn f() {\n\t  x();\n}\n", "no-call", 1, "//").unwrap(),
+            "fn f() {\n\t  // forseti-ignore-next-line no-call\n\t  x();\n}\n"
+        );
+        assert_eq!(
+            suppressed("def f():\n    x()\n", "no-call", 1, "#").unwrap(),
+            "def f():\n    # forseti-ignore-next-line no-call\n    x()\n"
+        );
+    }
+
+    #[test]
+    fn existing_suppressions_gain_the_rule_instead_of_stacking() {
+        assert_eq!(
+            suppressed(
+                "  // forseti-ignore-next-line no-call\n  x();\n",
+                "no-let",
+                1,
+                "//"
+            )
+            .unwrap(),
+            "  // forseti-ignore-next-line no-call, no-let\n  x();\n"
+        );
+        assert_eq!(
+            suppressed("/* forseti-ignore-next-line a */\nx\n", "b", 1, "/*").unwrap(),
+            "/* forseti-ignore-next-line a, b */\nx\n"
+        );
+        // Already covered, by id or by a directive naming no rules
+        assert_eq!(
+            suppressed("// forseti-ignore-next-line a, b\nx\n", "b", 1, "//"),
+            None
+        );
+        assert_eq!(
+            suppressed("// forseti-ignore-next-line\nx\n", "b", 1, "//"),
+            None
+        );
+        // A comment that is not a next-line directive is left alone
+        assert_eq!(
+            suppressed("// note\nx\n", "b", 1, "//").unwrap(),
+            "// note\n// forseti-ignore-next-line b\nx\n"
+        );
+    }
+
+    #[test]
+    fn sources_taint_the_sinks_they_flow_into() {
+        let summary = FlowSummary::new("handlers::login")
+            .with_source("user-input", "req.body", None)
+            .with_sink("sql", "db.query", None)
+            .with_flow("req.body", "db.query");
+        assert!(summary.is_tainted("user-input"));
+        assert!(!summary.is_tainted("sql"));
+
+        // Without flows, sources reach sinks of their own kind
+        let same_kind = FlowSummary::new("run")
+            .with_source("shell", "argv", None)
+            .with_sink("shell", "exec", None);
+        assert!(same_kind.is_tainted("shell"));
+    }
+
+    #[test]
+    fn sanitized_sinks_are_not_tainted() {
+        let summary = FlowSummary::new("handlers::login")
+            .with_source("user-input", "req.body", None)
+            .with_sink("sql", "db.query", None)
+            .with_sink("shell", "exec", None)
+            .with_flow("req.body", "db.query")
+            .with_flow("req.body", "exec")
+            .with_sanitizer("sql");
+        assert!(summary.is_tainted("user-input"));
+        assert!(
+            !summary
+                .clone()
+                .with_sanitizer("shell")
+                .is_tainted("user-input")
+        );
+    }
+
+    #[test]
+    fn unrelated_sinks_are_not_tainted() {
+        let unconnected = FlowSummary::new("handlers::login")
+            .with_source("user-input", "req.body", None)
+            .with_source("config", "env", None)
+            .with_sink("sql", "db.query", None)
+            .with_flow("env", "db.query");
+        assert!(!unconnected.is_tainted("user-input"));
+        assert!(unconnected.is_tainted("config"));
+
+        let other_kind = FlowSummary::new("render")
+            .with_source("user-input", "req.body", None)
+            .with_sink("html", "template", None);
+        assert!(!other_kind.is_tainted("user-input"));
+    }
+
+    /// Milliseconds encoded in the first ten characters of a request id
+    fn id_millis(id: &RequestId) -> u64 {
+        const ALPHABET: &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        id.as_str()[..10]
+            .chars()
+            .fold(0, |n, c| n * 32 + ALPHABET.find(c).unwrap() as u64)
+    }
+
+    #[test]
+    fn request_ids_are_26_crockford_characters_led_by_the_time() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let id = RequestId::new();
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        assert_eq!(id.as_str().len(), 26);
+        assert!(
+            id.as_str()
+                .chars()
+                .all(|c| "0123456789ABCDEFGHJKMNPQRSTVWXYZ".contains(c)),
+            "{id}"
+        );
+        // 128 bits in 130: the first character carries three
+        assert!(id.as_str() <= "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        let millis = id_millis(&id);
+        assert!(before.as_millis() as u64 <= millis, "{id}");
+        // Later ids of the same millisecond may already have moved past it
+        assert!(millis <= after.as_millis() as u64 + 1, "{id}");
+    }
+
+    #[test]
+    fn request_ids_increase_within_one_millisecond() {
+        let ids: Vec<RequestId> = (0..2000).map(|_| RequestId::new()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        // Far more ids than milliseconds passed, so some shared one
+        let distinct: std::collections::HashSet<u64> = ids.iter().map(id_millis).collect();
+        assert!(distinct.len() < ids.len());
+    }
+
+    #[test]
+    fn request_ids_are_unique_across_threads() {
+        let ids: Vec<RequestId> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..1000).map(|_| RequestId::new()).collect::<Vec<_>>()))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        let unique: std::collections::HashSet<&RequestId> = ids.iter().collect();
+        assert_eq!(unique.len(), 8000);
+    }
+
+    #[test]
+    fn caps_limit_findings_per_rule_and_per_file() {
+        let diagnostics = vec![
+            at("a.rs", "r1", 0, "m"),
+            at("a.rs", "r1", 1, "m"),
+            at("a.rs", "r1", 2, "m"),
+            at("a.rs", "r2", 3, "m"),
+            at("a.rs", "r2", 4, "m"),
+            at("b.rs", "r1", 0, "m"),
+        ];
+        let caps = DiagnosticCaps {
+            per_file: 3,
+            per_rule: 2,
+        };
+        let kept = cap_diagnostics(diagnostics, caps);
+        let summary: Vec<(&str, &str, u32)> = kept
+            .iter()
+            .map(|d| {
+                (
+                    d.uri.as_deref().unwrap(),
+                    d.rule_id.as_str(),
+                    d.range.start.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("a.rs", "r1", 0),
+                ("a.rs", "r1", 1),
+                ("a.rs", "r2", 3),
+                ("b.rs", "r1", 0),
+                ("a.rs", OVERFLOW_RULE_ID, 0),
+            ]
+        );
+        let overflow = kept.last().unwrap();
+        assert_eq!(overflow.message, "2 additional findings suppressed");
+        assert_eq!(overflow.severity, Severity::Info);
+    }
+
+    #[test]
+    fn zero_caps_keep_everything() {
+        let diagnostics: Vec<Diagnostic> = (0..5).map(|line| at("a.rs", "r", line, "m")).collect();
+        assert_eq!(
+            cap_diagnostics(diagnostics, DiagnosticCaps::default()).len(),
+            5
+        );
+    }
+
+    #[test]
+    fn duplicates_merge_by_heuristic() {
+        let reported = || {
+            vec![
+                (
+                    "eslint".to_string(),
+                    at("a.ts", "no-unused-vars", 1, "x is unused"),
+                ),
+                (
+                    "oxc".to_string(),
+                    at("a.ts", "no-unused-vars", 1, "x is unused"),
+                ),
+                (
+                    "biome".to_string(),
+                    at("a.ts", "unused", 1, "Variable x is unused"),
+                ),
+                (
+                    "oxc".to_string(),
+                    at("a.ts", "no-unused-vars", 2, "y is unused"),
+                ),
+            ]
+        };
+        let none = RuleEquivalences::default();
+
+        let off = merge_duplicate_diagnostics(reported(), MergeHeuristic::Off, &none);
+        assert_eq!(off.len(), 4);
+        assert_eq!(off[1].sources, ["oxc"]);
+
+        let same_rule = merge_duplicate_diagnostics(reported(), MergeHeuristic::SameRule, &none);
+        assert_eq!(same_rule.len(), 3);
+        assert_eq!(same_rule[0].sources, ["eslint", "oxc"]);
+        assert_eq!(same_rule[1].sources, ["biome"]);
+
+        let similar =
+            merge_duplicate_diagnostics(reported(), MergeHeuristic::SimilarMessage, &none);
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].sources, ["eslint", "oxc", "biome"]);
+        assert_eq!(similar[1].range.start.line, 2);
+    }
+
+    #[test]
+    fn equivalent_rules_merge_despite_different_messages() {
+        let groups = HashMap::from([(
+            "unused".to_string(),
+            vec![
+                "eslint/no-unused-vars".to_string(),
+                "biome/noUnused".to_string(),
+            ],
+        )]);
+        let equivalences = RuleEquivalences::new(&groups).unwrap();
+        let merged = merge_duplicate_diagnostics(
+            vec![
+                (
+                    "eslint".to_string(),
+                    at("a.ts", "no-unused-vars", 1, "x is unused"),
+                ),
+                (
+                    "biome".to_string(),
+                    at("a.ts", "noUnused", 1, "Remove this"),
+                ),
+                (
+                    "biome".to_string(),
+                    at("b.ts", "noUnused", 1, "Remove this"),
+                ),
+            ],
+            MergeHeuristic::SameRule,
+            &equivalences,
+        );
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].sources, ["eslint", "biome"]);
+        assert_eq!(merged[1].uri.as_deref(), Some("b.ts"));
+    }
+
+    #[test]
+    fn equivalences_must_be_qualified_and_disjoint() {
+        let unqualified = HashMap::from([("g".to_string(), vec!["no-var".to_string()])]);
+        assert!(RuleEquivalences::new(&unqualified).is_err());
+        let overlapping = HashMap::from([
+            ("g1".to_string(), vec!["a/x".to_string()]),
+            ("g2".to_string(), vec!["a/x".to_string()]),
+        ]);
+        assert!(RuleEquivalences::new(&overlapping).is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_lines_decode_to_the_original() {
+        let line = serde_json::json!([
+            {"v": 1, "kind": "res", "type": "analyzeFile", "id": "1", "payload": {"ok": true}},
+            {"v": 1, "kind": "event", "type": "terminated"},
+        ])
+        .to_string();
+        let compressed = compress_line(&line).unwrap();
+        let mut value: Value = serde_json::from_str(&compressed).unwrap();
+        assert_eq!(value[0]["encoding"], features::GZIP);
+        assert!(value[0]["payload"].is_string());
+        assert!(value[1].get("encoding").is_none());
+        decode_payload(&mut value).unwrap();
+        assert_eq!(value, serde_json::from_str::<Value>(&line).unwrap());
+    }
+
+    #[test]
+    fn unknown_encodings_are_rejected() {
+        let mut value = serde_json::json!({"payload": "x", "encoding": "br"});
+        let err = decode_payload(&mut value).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn globs_match_segments_and_file_names() {
+        let cases = [
+            ("*.rs", "src/lib.rs", true),
+            ("*.rs", "src/lib.rsx", false),
+            ("src/*.rs", "src/lib.rs", true),
+            ("src/*.rs", "src/ruleset/cli.rs", false),
+            ("src/**/*.rs", "src/lib.rs", true),
+            ("src/**/*.rs", "src/ruleset/cli.rs", true),
+            ("**/tests/**", "a/b/tests/c/d.rs", true),
+            ("**/tests/**", "a/b/test/c.rs", false),
+            ("lib.?s", "lib.rs", true),
+            ("lib.?s", "lib.s", false),
+            ("*.{ts,tsx}", "app/view.tsx", true),
+            ("*.{ts,tsx}", "app/view.js", false),
+            ("src/{a,b}/*.rs", "src/b/x.rs", true),
+            ("/src/*.rs", "src/lib.rs", true),
+            ("*", "", false),
+            ("a*b*c", "axxbyyc", true),
+            ("a*b*c", "axxbyy", false),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(glob_match(pattern, path), expected, "{pattern} vs {path}");
+        }
+    }
+
+    #[test]
+    fn unclosed_braces_are_literal() {
+        assert!(glob_match("{a,b", "{a,b"));
+        assert!(!glob_match("{a,b", "a"));
+    }
+
+    #[test]
+    fn path_filters_see_decoded_paths_relative_to_the_root() {
+        let uri = file_uri(std::path::Path::new("/work/my repo/src/a b.rs"));
+        assert!(uri.contains("%20"), "{uri}");
+        let d = Diagnostic {
+            uri: Some(uri),
+            ..at("", "r", 0, "m")
+        };
+        let filter = DiagnosticFilter::new().with_root("/work/my repo");
+        assert!(filter.clone().with_included_paths(["src/*.rs"]).accepts(&d));
+        assert!(filter.clone().with_included_paths(["a b.rs"]).accepts(&d));
+        assert!(!filter.clone().with_excluded_paths(["src/**"]).accepts(&d));
+        assert!(!filter.with_included_paths(["lib/**"]).accepts(&d));
+        let unrooted = DiagnosticFilter::new().with_included_paths(["/work/my repo/**"]);
+        assert!(unrooted.accepts(&d));
+    }
+}
@@ -0,0 +1,277 @@
+//! Turn the paths a user passes, a mix of files and directories, into the
+//! files to lint.
+//!
+//! Directories are walked recursively, skipping hidden directories and
+//! build or vendor output ([`crate::cache::GENERATED_DIRS`]). Symlinks are
+//! followed, each directory visited once; subdirectories that cannot be read
+//! are skipped. `linter.ignore` globs apply to
+//! everything found, matched against paths relative to the workspace root;
+//! files named explicitly are kept even when hidden. [`discover`] then
+//! routes each file to the enabled rulesets, keeping count of the ones none
+//! of them will lint.
+
+use crate::cache::GENERATED_DIRS;
+use crate::config::Config;
+use crate::core::{FileStats, RuleCatalog, UnmatchedFiles, glob_match, unmatched_reason};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::ReadDir;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Files under `paths` not matched by an `ignore` glob, as `/`-separated paths
+/// in the form given (relative paths stay relative, a leading `./` dropped),
+/// sorted and without duplicates. Globs match paths relative to `root`, the
+/// workspace directory, however the paths were given.
+pub fn expand_paths<P: AsRef<Path>>(
+    root: &Path,
+    paths: &[P],
+    ignore: &[String],
+) -> io::Result<Vec<String>> {
+    let cwd = std::env::current_dir()?;
+    let root = absolute(&cwd, root);
+    let ignored = |path: &Path| {
+        let absolute = absolute(&cwd, path);
+        let relative = match absolute.strip_prefix(&root) {
+            Ok(relative) => normalize(relative),
+            Err(_) => normalize(path),
+        };
+        ignore.iter().any(|pattern| glob_match(pattern, &relative))
+    };
+    let mut files = BTreeSet::new();
+    let mut visited = HashSet::new();
+    for path in paths {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        if !metadata.is_dir() {
+            if !ignored(path) {
+                files.insert(normalize(path));
+            }
+            continue;
+        }
+        walk_files(path, &mut visited, ignored, |file| {
+            if !ignored(file) {
+                files.insert(normalize(file));
+            }
+        })
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Call `on_file` for every file under `dir`, depth first. Hidden
+/// directories, build or vendor output ([`GENERATED_DIRS`]) and directories
+/// `skip_dir` rejects are not descended into. Symlinks are followed, each
+/// directory visited once across calls sharing `visited`. Subdirectories and
+/// entries that cannot be read are skipped; only `dir` itself must be readable.
+pub(crate) fn walk_files(
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    skip_dir: impl Fn(&Path) -> bool,
+    mut on_file: impl FnMut(&Path),
+) -> io::Result<()> {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match read_unvisited(&current, visited) {
+            Ok(Some(entries)) => entries,
+            Ok(None) => continue,
+            Err(_) if current != dir => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let is_dir = if file_type.is_symlink() {
+                match std::fs::metadata(&path) {
+                    Ok(target) => target.is_dir(),
+                    // Dangling link
+                    Err(_) => continue,
+                }
+            } else {
+                file_type.is_dir()
+            };
+            if !is_dir {
+                on_file(&path);
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.')
+                && !GENERATED_DIRS.contains(&name.as_ref())
+                && !skip_dir(&path)
+            {
+                pending.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Entries of `dir`, or None when it was already walked, e.g. reached again
+/// through a symlink
+fn read_unvisited(dir: &Path, visited: &mut HashSet<PathBuf>) -> io::Result<Option<ReadDir>> {
+    if !visited.insert(std::fs::canonicalize(dir)?) {
+        return Ok(None);
+    }
+    std::fs::read_dir(dir).map(Some)
+}
+
+/// `path` made absolute against `cwd`, with `.` and `..` resolved lexically
+fn absolute(cwd: &Path, path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Outcome of [`discover`]
+#[derive(Debug, Clone, Default)]
+pub struct Discovered {
+    /// Files some enabled ruleset will lint
+    pub files: Vec<String>,
+    pub stats: FileStats,
+    /// Files found but matched by no ruleset, or too large for all that match
+    pub unmatched: UnmatchedFiles,
+}
+
+/// Files to lint under `paths` with `cfg`'s ignore globs, matched relative to
+/// the workspace `root`, routed to the rulesets enabled in `cfg`. Files no
+/// ruleset would lint are counted as skipped and recorded with the reason,
+/// for `LintResults::file_stats` and `LintResults::unmatched`.
+pub fn discover<P: AsRef<Path>>(
+    cfg: &Config,
+    catalog: &RuleCatalog,
+    root: &Path,
+    paths: &[P],
+) -> io::Result<Discovered> {
+    let enabled: HashMap<String, Vec<String>> = cfg
+        .enabled_rules(catalog)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut discovered = Discovered::default();
+    for file in expand_paths(root, paths, &cfg.linter.ignore)? {
+        let size = std::fs::metadata(&file).ok().map(|m| m.len());
+        match unmatched_reason(catalog, &enabled, &file, size) {
+            Some(reason) => {
+                discovered.stats.record(&file, false);
+                discovered.unmatched.record(file, reason);
+            }
+            None => {
+                discovered.stats.record(&file, true);
+                discovered.files.push(file);
+            }
+        }
+    }
+    Ok(discovered)
+}
+
+fn normalize(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    match path.strip_prefix("./") {
+        Some(rest) if !rest.is_empty() => rest.to_string(),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("forseti-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(dir: &Path, relative: &str) {
+        let path = dir.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    fn relative_to(dir: &Path, files: Vec<String>) -> Vec<String> {
+        let prefix = format!("{}/", normalize(dir));
+        files
+            .into_iter()
+            .map(|file| file.strip_prefix(&prefix).unwrap_or(&file).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn skips_hidden_generated_and_ignored_directories() {
+        let dir = scratch_dir("expand");
+        for file in [
+            "src/a.rs",
+            "src/gen/b.rs",
+            ".git/config",
+            "node_modules/x/index.js",
+            "docs/c.md",
+        ] {
+            touch(&dir, file);
+        }
+        let ignore = vec!["src/gen/**".to_string()];
+        let files = expand_paths(&dir, &[&dir], &ignore).unwrap();
+        assert_eq!(relative_to(&dir, files), ["docs/c.md", "src/a.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn explicit_files_are_kept_even_when_hidden() {
+        let dir = scratch_dir("explicit");
+        touch(&dir, ".hidden.rs");
+        let files = expand_paths(&dir, &[dir.join(".hidden.rs")], &[]).unwrap();
+        assert_eq!(relative_to(&dir, files), [".hidden.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_paths_are_errors() {
+        let dir = scratch_dir("missing");
+        let err = expand_paths(&dir, &[dir.join("nope")], &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles_are_walked_once() {
+        let dir = scratch_dir("cycle");
+        touch(&dir, "src/a.rs");
+        std::os::unix::fs::symlink(&dir, dir.join("src/loop")).unwrap();
+        let files = expand_paths(&dir, &[&dir], &[]).unwrap();
+        assert_eq!(relative_to(&dir, files), ["src/a.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_subdirectories_are_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("unreadable");
+        touch(&dir, "src/a.rs");
+        touch(&dir, "locked/b.rs");
+        let locked = dir.join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let files = expand_paths(&dir, &[&dir], &[]);
+        let readable = std::fs::read_dir(&locked).is_ok();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let files = relative_to(&dir, files.unwrap());
+        // Permissions do not apply to root, which still sees the file
+        if readable {
+            assert_eq!(files, ["locked/b.rs", "src/a.rs"]);
+        } else {
+            assert_eq!(files, ["src/a.rs"]);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,677 @@
+//! Apply the fixes attached to one file's diagnostics.
+//!
+//! Each diagnostic contributes the first of its suggestions that carries a
+//! fix. Edits are applied in a single pass, earliest first; an edit that
+//! overlaps one already taken is skipped and reported, so a `--fix` loop can
+//! re-lint and pick it up on the next pass.
+//!
+//! [`diff_fixes`] and [`FixOutcome::diff`] render the same edits as a unified
+//! diff, for a dry-run preview before anything is written.
+
+use crate::core::{Diagnostic, Fix, LineIndex, Range};
+use serde::{Deserialize, Serialize};
+
+/// A fix that made it into the output text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedFix {
+    pub rule_id: String,
+    pub title: String,
+    pub range: Range,
+    /// Replacement text
+    pub text: String,
+}
+
+/// Why a fix was left out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Its range overlaps the applied fix of rule `with_rule`
+    Conflict { with_rule: String },
+    /// Same range and text as the applied fix of rule `with_rule`
+    Duplicate { with_rule: String },
+    /// Its range lies outside the text or splits a character
+    InvalidRange,
+}
+
+/// A fix that was not applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFix {
+    pub rule_id: String,
+    pub title: String,
+    pub range: Range,
+    #[serde(flatten)]
+    pub reason: SkipReason,
+}
+
+/// Result of [`apply_fixes`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixOutcome {
+    pub text: String,
+    pub applied: Vec<AppliedFix>,
+    pub skipped: Vec<SkippedFix>,
+}
+
+impl FixOutcome {
+    /// Whether applying fixes changed the text
+    pub fn changed(&self) -> bool {
+        !self.applied.is_empty()
+    }
+
+    /// The applied fixes as a diff of `original`, the text passed to [`apply_fixes`]
+    pub fn diff(&self, path: &str, original: &str) -> FileDiff {
+        let fixes: Vec<Fix> = self
+            .applied
+            .iter()
+            .map(|applied| Fix {
+                range: applied.range,
+                text: applied.text.clone(),
+            })
+            .collect();
+        diff_fixes(path, original, &fixes)
+    }
+}
+
+/// One candidate edit, resolved to byte offsets
+struct Edit<'a> {
+    rule_id: &'a str,
+    title: &'a str,
+    fix: &'a Fix,
+    start: usize,
+    end: usize,
+}
+
+/// Apply the fixes of `diagnostics` to `text`, which they must all belong to.
+/// Overlapping edits are resolved in favour of the one starting first (ties go
+/// to the earlier diagnostic); two insertions at the same point also conflict,
+/// since their order would be a guess.
+pub fn apply_fixes(text: &str, diagnostics: &[Diagnostic]) -> FixOutcome {
+    let index = LineIndex::new(text);
+    let mut skipped = Vec::new();
+    let mut edits: Vec<Edit> = Vec::new();
+    for diagnostic in diagnostics {
+        let Some((title, fix)) = diagnostic
+            .suggest
+            .iter()
+            .flatten()
+            .find_map(|suggestion| Some((suggestion.title.as_str(), suggestion.fix.as_ref()?)))
+        else {
+            continue;
+        };
+        match offsets(&index, fix) {
+            Some((start, end)) => edits.push(Edit {
+                rule_id: &diagnostic.rule_id,
+                title,
+                fix,
+                start,
+                end,
+            }),
+            None => skipped.push(SkippedFix {
+                rule_id: diagnostic.rule_id.clone(),
+                title: title.to_string(),
+                range: fix.range,
+                reason: SkipReason::InvalidRange,
+            }),
+        }
+    }
+    let (accepted, rejected) = select_edits(edits);
+    skipped.extend(rejected.into_iter().map(|(edit, reason)| SkippedFix {
+        rule_id: edit.rule_id.to_string(),
+        title: edit.title.to_string(),
+        range: edit.fix.range,
+        reason,
+    }));
+
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for edit in &accepted {
+        out.push_str(&text[cursor..edit.start]);
+        out.push_str(&edit.fix.text);
+        cursor = edit.end;
+    }
+    out.push_str(&text[cursor..]);
+    FixOutcome {
+        text: out,
+        applied: accepted
+            .iter()
+            .map(|edit| AppliedFix {
+                rule_id: edit.rule_id.to_string(),
+                title: edit.title.to_string(),
+                range: edit.fix.range,
+                text: edit.fix.text.clone(),
+            })
+            .collect(),
+        skipped,
+    }
+}
+
+/// Byte offsets of `fix`'s range, if it is valid in the indexed text
+fn offsets(index: &LineIndex, fix: &Fix) -> Option<(usize, usize)> {
+    let start = index.to_offset(fix.range.start)?;
+    let end = index.to_offset(fix.range.end)?;
+    (start <= end).then_some((start, end))
+}
+
+/// Order `edits` earliest first and keep those that fit together, returning
+/// the rest with the reason each was left out. Shared by [`apply_fixes`] and
+/// [`diff_fixes`], so a preview shows exactly the edits a fix run makes.
+fn select_edits(mut edits: Vec<Edit>) -> (Vec<Edit>, Vec<(Edit, SkipReason)>) {
+    // Stable, so equal ranges keep diagnostic order
+    edits.sort_by_key(|edit| (edit.start, edit.end));
+    let mut accepted: Vec<Edit> = Vec::new();
+    let mut rejected = Vec::new();
+    for edit in edits {
+        let reason = accepted.last().and_then(|last| {
+            if last.start == edit.start && last.end == edit.end && last.fix.text == edit.fix.text {
+                Some(SkipReason::Duplicate {
+                    with_rule: last.rule_id.to_string(),
+                })
+            } else if clashes((last.start, last.end), (edit.start, edit.end)) {
+                Some(SkipReason::Conflict {
+                    with_rule: last.rule_id.to_string(),
+                })
+            } else {
+                None
+            }
+        });
+        match reason {
+            Some(reason) => rejected.push((edit, reason)),
+            None => accepted.push(edit),
+        }
+    }
+    (accepted, rejected)
+}
+
+/// Whether `next`, starting no earlier than `last`, overlaps it. Two
+/// insertions at the same point clash too.
+fn clashes(last: (usize, usize), next: (usize, usize)) -> bool {
+    next.0 < last.1 || (next.0 == last.1 && last.0 == last.1 && next.0 == next.1)
+}
+
+/// Lines of context kept around each change
+const CONTEXT_LINES: usize = 3;
+
+/// One line of a [`Hunk`], including its line ending
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "line", rename_all = "snake_case")]
+pub enum DiffLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+impl DiffLine {
+    fn render(&self, out: &mut String) {
+        let (prefix, line) = match self {
+            DiffLine::Context(line) => (' ', line),
+            DiffLine::Removed(line) => ('-', line),
+            DiffLine::Added(line) => ('+', line),
+        };
+        out.push(prefix);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// A run of changed lines with surrounding context. Line numbers are 1-based;
+/// a side with no lines gives the line it follows, as `diff -u` does.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Changes fixes would make to one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// The diff in unified format, with `a/` and `b/` prefixed paths; empty
+    /// when nothing changes
+    pub fn to_unified(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let mut out = format!("--- a/{0}\n+++ b/{0}\n", self.path);
+        for hunk in &self.hunks {
+            out.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+            ));
+            for line in &hunk.lines {
+                line.render(&mut out);
+            }
+        }
+        out
+    }
+}
+
+/// Byte range of the original replaced by a text
+type Splice<'a> = (usize, usize, &'a str);
+
+/// Lines `old_start..old_start + old.len()` of the original become `new`
+struct Change<'a> {
+    old_start: usize,
+    old: Vec<&'a str>,
+    new: Vec<String>,
+}
+
+/// Diff of `original` against the result of applying `fixes` to it. Fixes are
+/// taken in the order [`apply_fixes`] would take them; ones with an invalid
+/// range or overlapping an earlier fix are left out.
+pub fn diff_fixes(path: &str, original: &str, fixes: &[Fix]) -> FileDiff {
+    let index = LineIndex::new(original);
+    let edits: Vec<Edit> = fixes
+        .iter()
+        .filter_map(|fix| {
+            let (start, end) = offsets(&index, fix)?;
+            Some(Edit {
+                rule_id: "",
+                title: "",
+                fix,
+                start,
+                end,
+            })
+        })
+        .collect();
+    let accepted = select_edits(edits)
+        .0
+        .into_iter()
+        .map(|edit| (edit.start, edit.end, edit.fix.text.as_str()));
+
+    // Byte offset of each line start; a trailing newline opens no further line
+    let mut starts = vec![0];
+    starts.extend(original.match_indices('\n').map(|(i, _)| i + 1));
+    if starts.last() == Some(&original.len()) {
+        starts.pop();
+    }
+    let line_count = if original.is_empty() { 0 } else { starts.len() };
+    let line_start = |line: usize| starts.get(line).copied().unwrap_or(original.len());
+    let line_of = |offset: usize| starts.partition_point(|&start| start <= offset).max(1) - 1;
+
+    // Group edits into blocks of whole lines, merging blocks that touch
+    let mut blocks: Vec<(usize, usize, Vec<Splice>)> = Vec::new();
+    for edit @ (start, end, _) in accepted {
+        let first = if start == original.len() && (original.is_empty() || original.ends_with('\n'))
+        {
+            line_count
+        } else {
+            line_of(start)
+        };
+        let last = if end > start && starts.binary_search(&end).is_ok() {
+            line_of(end)
+        } else if end == original.len() {
+            line_count
+        } else {
+            line_of(end) + 1
+        };
+        let last = last.min(line_count).max(first);
+        match blocks.last_mut() {
+            Some(block) if first <= block.1 => {
+                block.1 = block.1.max(last);
+                block.2.push(edit);
+            }
+            _ => blocks.push((first, last, vec![edit])),
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (first, last, block_edits) in blocks {
+        let (from, to) = (line_start(first), line_start(last));
+        let mut new = String::new();
+        let mut cursor = from;
+        for (start, end, text) in block_edits {
+            new.push_str(&original[cursor..start]);
+            new.push_str(text);
+            cursor = end;
+        }
+        new.push_str(&original[cursor..to]);
+        let mut old: Vec<&str> = original[from..to].split_inclusive('\n').collect();
+        let mut new: Vec<String> = new.split_inclusive('\n').map(String::from).collect();
+        let prefix = old
+            .iter()
+            .zip(&new)
+            .take_while(|(a, b)| **a == b.as_str())
+            .count();
+        old.drain(..prefix);
+        new.drain(..prefix);
+        let suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take_while(|(a, b)| **a == b.as_str())
+            .count();
+        old.truncate(old.len() - suffix);
+        new.truncate(new.len() - suffix);
+        if !old.is_empty() || !new.is_empty() {
+            changes.push(Change {
+                old_start: first + prefix,
+                old,
+                new,
+            });
+        }
+    }
+
+    let lines: Vec<&str> = original.split_inclusive('\n').collect();
+    FileDiff {
+        path: path.to_string(),
+        hunks: hunks(&lines, changes),
+    }
+}
+
+/// Wrap changes in context lines, joining those whose context would touch
+fn hunks(lines: &[&str], changes: Vec<Change>) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    // Old line just past the last hunk's changes, and new minus old line count so far
+    let mut old_end = 0;
+    let mut shift: isize = 0;
+    for change in changes {
+        let continues = !hunks.is_empty() && change.old_start <= old_end + 2 * CONTEXT_LINES;
+        let hunk = if continues {
+            let hunk = hunks.last_mut().expect("checked non-empty");
+            let gap = &lines[old_end..change.old_start];
+            hunk.lines
+                .extend(gap.iter().map(|line| DiffLine::Context(line.to_string())));
+            hunk.old_lines += gap.len();
+            hunk.new_lines += gap.len();
+            hunk
+        } else {
+            if let Some(hunk) = hunks.last_mut() {
+                trail_context(hunk, lines, old_end);
+            }
+            let lead = change.old_start.saturating_sub(CONTEXT_LINES);
+            hunks.push(Hunk {
+                old_start: lead,
+                old_lines: change.old_start - lead,
+                new_start: lead.saturating_add_signed(shift),
+                new_lines: change.old_start - lead,
+                lines: lines[lead..change.old_start]
+                    .iter()
+                    .map(|line| DiffLine::Context(line.to_string()))
+                    .collect(),
+            });
+            hunks.last_mut().expect("just pushed")
+        };
+        hunk.old_lines += change.old.len();
+        hunk.new_lines += change.new.len();
+        hunk.lines.extend(
+            change
+                .old
+                .iter()
+                .map(|line| DiffLine::Removed(line.to_string())),
+        );
+        hunk.lines
+            .extend(change.new.iter().cloned().map(DiffLine::Added));
+        old_end = change.old_start + change.old.len();
+        shift += change.new.len() as isize - change.old.len() as isize;
+    }
+    if let Some(hunk) = hunks.last_mut() {
+        trail_context(hunk, lines, old_end);
+    }
+    // 0-based first line => 1-based, or the preceding line for an empty side
+    for hunk in &mut hunks {
+        if hunk.old_lines > 0 {
+            hunk.old_start += 1;
+        }
+        if hunk.new_lines > 0 {
+            hunk.new_start += 1;
+        }
+    }
+    hunks
+}
+
+fn trail_context(hunk: &mut Hunk, lines: &[&str], from: usize) {
+    let trail = &lines[from..(from + CONTEXT_LINES).min(lines.len())];
+    hunk.lines
+        .extend(trail.iter().map(|line| DiffLine::Context(line.to_string())));
+    hunk.old_lines += trail.len();
+    hunk.new_lines += trail.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Position, Severity, SuggestFix};
+
+    fn fix(start: (u32, u32), end: (u32, u32), text: &str) -> Fix {
+        let position = |(line, character)| Position { line, character };
+        Fix {
+            range: Range {
+                start: position(start),
+                end: position(end),
+            },
+            text: text.to_string(),
+        }
+    }
+
+    fn diagnostic(rule_id: &str, fix: Fix) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(rule_id, "fixable", Severity::Warn, fix.range);
+        diagnostic.suggest = Some(vec![SuggestFix {
+            title: format!("Fix {rule_id}"),
+            fix: Some(fix),
+        }]);
+        diagnostic
+    }
+
+    fn unified(original: &str, fixes: &[Fix]) -> String {
+        diff_fixes("f.txt", original, fixes).to_unified()
+    }
+
+    const HEADER: &str = "--- a/f.txt\n+++ b/f.txt\n";
+
+    #[test]
+    fn applies_edits_in_one_pass() {
+        let text = "let  x = 1;;\nfoo\n";
+        let outcome = apply_fixes(
+            text,
+            &[
+                diagnostic("no-double-semi", fix((0, 11), (0, 12), "")),
+                diagnostic("no-double-space", fix((0, 3), (0, 5), " ")),
+                diagnostic("rename", fix((1, 0), (1, 3), "bar")),
+            ],
+        );
+        assert_eq!(outcome.text, "let x = 1;\nbar\n");
+        let rules: Vec<&str> = outcome.applied.iter().map(|a| a.rule_id.as_str()).collect();
+        assert_eq!(rules, ["no-double-space", "no-double-semi", "rename"]);
+        assert!(outcome.skipped.is_empty() && outcome.changed());
+    }
+
+    #[test]
+    fn overlapping_edits_conflict_and_identical_ones_are_duplicates() {
+        let text = "abcdef\n";
+        let outcome = apply_fixes(
+            text,
+            &[
+                diagnostic("first", fix((0, 1), (0, 4), "X")),
+                diagnostic("overlap", fix((0, 3), (0, 5), "Y")),
+                diagnostic("same", fix((0, 1), (0, 4), "X")),
+            ],
+        );
+        assert_eq!(outcome.text, "aXef\n");
+        let reasons: Vec<(&str, &SkipReason)> = outcome
+            .skipped
+            .iter()
+            .map(|s| (s.rule_id.as_str(), &s.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (
+                    "same",
+                    &SkipReason::Duplicate {
+                        with_rule: "first".into()
+                    }
+                ),
+                (
+                    "overlap",
+                    &SkipReason::Conflict {
+                        with_rule: "first".into()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn insertions_at_the_same_point_conflict_but_adjacent_edits_do_not() {
+        let outcome = apply_fixes(
+            "ab",
+            &[
+                diagnostic("one", fix((0, 1), (0, 1), "1")),
+                diagnostic("two", fix((0, 1), (0, 1), "2")),
+                diagnostic("swap", fix((0, 1), (0, 2), "B")),
+            ],
+        );
+        assert_eq!(outcome.text, "a1B");
+        assert_eq!(outcome.skipped.len(), 1);
+        assert_eq!(outcome.skipped[0].rule_id, "two");
+    }
+
+    #[test]
+    fn ranges_outside_the_text_are_skipped() {
+        let outcome = apply_fixes("abc\n", &[diagnostic("far", fix((5, 0), (5, 1), "x"))]);
+        assert_eq!(outcome.text, "abc\n");
+        assert!(!outcome.changed());
+        assert_eq!(outcome.skipped[0].reason, SkipReason::InvalidRange);
+    }
+
+    #[test]
+    fn diff_of_the_applied_fixes_matches_diff_fixes() {
+        let text = "a\nb\nc\n";
+        let fixes = [fix((1, 0), (1, 1), "B")];
+        let diagnostics = [diagnostic("upper", fixes[0].clone())];
+        let outcome = apply_fixes(text, &diagnostics);
+        assert_eq!(
+            outcome.diff("f.txt", text).to_unified(),
+            unified(text, &fixes)
+        );
+    }
+
+    #[test]
+    fn insert_at_end_of_file() {
+        assert_eq!(
+            unified("a\nb\n", &[fix((2, 0), (2, 0), "c\n")]),
+            format!("{HEADER}@@ -1,2 +1,3 @@\n a\n b\n+c\n")
+        );
+    }
+
+    #[test]
+    fn insert_at_start_of_file() {
+        assert_eq!(
+            unified("a\nb\n", &[fix((0, 0), (0, 0), "z\n")]),
+            format!("{HEADER}@@ -1,2 +1,3 @@\n+z\n a\n b\n")
+        );
+    }
+
+    #[test]
+    fn insert_into_an_empty_file() {
+        assert_eq!(
+            unified("", &[fix((0, 0), (0, 0), "new\n")]),
+            format!("{HEADER}@@ -0,0 +1,1 @@\n+new\n")
+        );
+    }
+
+    #[test]
+    fn delete_a_line() {
+        assert_eq!(
+            unified("a\nb\nc\n", &[fix((1, 0), (2, 0), "")]),
+            format!("{HEADER}@@ -1,3 +1,2 @@\n a\n-b\n c\n")
+        );
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        assert_eq!(
+            unified("a\nb", &[fix((1, 0), (1, 1), "B")]),
+            format!(
+                "{HEADER}@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+B\n\\ No newline at end of file\n"
+            )
+        );
+        assert_eq!(
+            unified("a\nb", &[fix((1, 1), (1, 1), "\nc\n")]),
+            format!("{HEADER}@@ -1,2 +1,3 @@\n a\n-b\n\\ No newline at end of file\n+b\n+c\n")
+        );
+    }
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|n| format!("{n}\n")).collect()
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let text = numbered(20);
+        let diff = diff_fixes(
+            "f.txt",
+            &text,
+            &[fix((1, 0), (1, 1), "two"), fix((7, 0), (7, 1), "eight")],
+        );
+        assert_eq!(diff.hunks.len(), 1);
+        let hunk = &diff.hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_lines,
+                hunk.new_start,
+                hunk.new_lines
+            ),
+            (1, 11, 1, 11)
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_their_own_hunks() {
+        let text = numbered(20);
+        let expected = format!(
+            "{HEADER}@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n@@ -12,7 +12,7 @@\n 12\n 13\n 14\n-15\n+fifteen\n 16\n 17\n 18\n"
+        );
+        assert_eq!(
+            unified(
+                &text,
+                &[fix((14, 0), (14, 2), "fifteen"), fix((1, 0), (1, 1), "two")]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn hunks_track_line_shifts() {
+        let text = numbered(20);
+        let diff = diff_fixes(
+            "f.txt",
+            &text,
+            &[fix((0, 0), (0, 0), "0\n"), fix((14, 0), (15, 0), "")],
+        );
+        let starts: Vec<(usize, usize)> = diff
+            .hunks
+            .iter()
+            .map(|hunk| (hunk.old_start, hunk.new_start))
+            .collect();
+        assert_eq!(starts, [(1, 1), (12, 13)]);
+    }
+
+    #[test]
+    fn conflicting_and_duplicate_fixes_are_left_out_of_the_diff() {
+        let fixes = [
+            fix((0, 0), (0, 1), "A"),
+            fix((0, 0), (0, 1), "A"),
+            fix((0, 0), (0, 2), "XY"),
+        ];
+        assert_eq!(
+            unified("ab\n", &fixes),
+            format!("{HEADER}@@ -1,1 +1,1 @@\n-ab\n+Ab\n")
+        );
+        assert!(diff_fixes("f.txt", "ab\n", &[]).is_empty());
+    }
+}
@@ -0,0 +1,1042 @@
+//! Output formatters for [`LintResults`], looked up by name.
+//!
+//! Every output format is a [`Formatter`] in a [`FormatterRegistry`], so the
+//! built-in formats and ones a downstream crate registers are chosen the same
+//! way: by the name in `output_format` or an output sink's `format`.
+//! [`FormatterRegistry::default`] holds `json`, `ndjson`, `text`, `sarif`,
+//! `checkstyle` and `gitlab`; registering under one of those names replaces it.
+
+use crate::core::{
+    Diagnostic, LineIndex, LintResults, Range, SecurityMeta, Severity, content_hash, file_uri,
+    uri_to_path,
+};
+use crate::term::{Stream, Style, Terminal, truncate};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Renders a run's results to a writer
+pub trait Formatter: Send + Sync {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()>;
+}
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("unknown output format '{0}'")]
+    Unknown(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// [`LintResults`] as one pretty-printed JSON document
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *w, results)?;
+        writeln!(w)
+    }
+}
+
+/// One JSON line per diagnostic, carrying its `ruleset_id`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NdjsonFormatter;
+
+impl Formatter for NdjsonFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        for result in &results.results {
+            for diagnostic in &result.diagnostics {
+                let mut line = serde_json::to_value(diagnostic)?;
+                if let Value::Object(map) = &mut line {
+                    map.insert(
+                        "ruleset_id".into(),
+                        Value::String(result.ruleset_id.clone()),
+                    );
+                }
+                serde_json::to_writer(&mut *w, &line)?;
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Human-readable report grouped by file, sorted by path then position, with
+/// a code frame under each finding and a closing summary line. Colors and
+/// width follow the terminal.
+#[derive(Debug, Clone)]
+pub struct TextFormatter {
+    terminal: Terminal,
+}
+
+impl TextFormatter {
+    pub fn new(terminal: Terminal) -> Self {
+        Self { terminal }
+    }
+
+    /// The report, with code frames when `source` returns the text of a file
+    /// (given the diagnostic's `uri`). Empty when there are no findings.
+    pub fn render(&self, results: &LintResults, source: impl Fn(&str) -> Option<String>) -> String {
+        let mut files: BTreeMap<&str, Vec<(&str, &Diagnostic)>> = BTreeMap::new();
+        for result in &results.results {
+            for d in &result.diagnostics {
+                files
+                    .entry(d.uri.as_deref().unwrap_or_default())
+                    .or_default()
+                    .push((&result.ruleset_id, d));
+            }
+        }
+        if files.is_empty() {
+            return String::new();
+        }
+        let mut out = String::new();
+        for (uri, mut diagnostics) in files {
+            diagnostics.sort_by_key(|(_, d)| (d.range.start.line, d.range.start.character));
+            let path = uri_to_path(uri).display().to_string();
+            out.push_str(&self.terminal.paint(&path, &[Style::Underline]));
+            out.push('\n');
+            let index = source(uri).map(|text| LineIndex::new(&text));
+            for (ruleset_id, d) in diagnostics {
+                let (label, color) = match d.severity {
+                    Severity::Error => ("error", Style::Red),
+                    Severity::Warn => ("warning", Style::Yellow),
+                    Severity::Info => ("info", Style::Blue),
+                    Severity::Hint => ("hint", Style::Cyan),
+                };
+                let position =
+                    format!("{}:{}", d.range.start.line + 1, d.range.start.character + 1);
+                out.push_str(&format!(
+                    "  {} {}  {}  {}\n",
+                    self.terminal
+                        .paint(&format!("{:<8}", position), &[Style::Dim]),
+                    self.terminal.paint(&format!("{:<7}", label), &[color]),
+                    d.message.replace('\n', " "),
+                    self.terminal
+                        .paint(&format!("{}/{}", ruleset_id, d.rule_id), &[Style::Dim]),
+                ));
+                if let Some(index) = &index {
+                    out.push_str(&code_frame(index, d.range, &self.terminal, color));
+                }
+            }
+            out.push('\n');
+        }
+        let s = &results.summary;
+        let total = s.errors + s.warnings + s.info + s.hints;
+        let summary = format!(
+            "{} problem{} ({} error{}, {} warning{}, {} info, {} hint{})",
+            total,
+            if total == 1 { "" } else { "s" },
+            s.errors,
+            if s.errors == 1 { "" } else { "s" },
+            s.warnings,
+            if s.warnings == 1 { "" } else { "s" },
+            s.info,
+            s.hints,
+            if s.hints == 1 { "" } else { "s" },
+        );
+        let color = if s.errors > 0 {
+            Style::Red
+        } else {
+            Style::Yellow
+        };
+        out.push_str(&self.terminal.paint(&summary, &[color, Style::Bold]));
+        out.push('\n');
+        out
+    }
+}
+
+impl Formatter for TextFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        let text = self.render(results, |uri| {
+            std::fs::read_to_string(uri_to_path(uri)).ok()
+        });
+        w.write_all(text.as_bytes())
+    }
+}
+
+/// Lines of context shown around a finding in a code frame
+const FRAME_CONTEXT: u32 = 1;
+
+/// Excerpt of the lines around `range`, its first line marked with `>` and
+/// underlined with carets from the start character to the end of the range
+/// (or of the line, when the range spans lines), cut to `terminal`'s width
+/// along with the carets
+fn code_frame(index: &LineIndex, range: Range, terminal: &Terminal, color: Style) -> String {
+    let line = range.start.line;
+    let Some(text) = index.line_text(line) else {
+        return String::new();
+    };
+    let first = line.saturating_sub(FRAME_CONTEXT);
+    let last = (line + FRAME_CONTEXT).min(index.line_count().saturating_sub(1));
+    let gutter = (last + 1).to_string().len();
+    let fit = |text: &str| truncate(text, terminal.width().saturating_sub(gutter + 8));
+    let mut out = String::new();
+    for n in first..=last {
+        let Some(content) = index.line_text(n) else {
+            continue;
+        };
+        let marker = if n == line { ">" } else { " " };
+        let number = format!("{:>width$} |", n + 1, width = gutter);
+        out.push_str(&format!(
+            "  {} {} {}\n",
+            terminal.paint(marker, &[color, Style::Bold]),
+            terminal.paint(&number, &[Style::Dim]),
+            fit(content).trim_end()
+        ));
+        if n == line {
+            let mut start = floor_char_boundary(text, range.start.character as usize);
+            let mut end = if range.end.line == line {
+                floor_char_boundary(text, range.end.character as usize).max(start)
+            } else {
+                text.len()
+            };
+            // Keep the carets within a cut line, ending at its "…" at most
+            let shown = fit(text).chars().count();
+            if shown < text.chars().count() {
+                let byte = |chars: usize| {
+                    text.char_indices()
+                        .nth(chars)
+                        .map_or(text.len(), |(i, _)| i)
+                };
+                start = start.min(byte(shown.saturating_sub(1)));
+                end = end.min(byte(shown)).max(start);
+            }
+            // Keep tabs so the carets line up with the code above
+            let pad: String = text[..start]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let carets = "^".repeat(text[start..end].chars().count().max(1));
+            out.push_str(&format!(
+                "    {} {}{}\n",
+                terminal.paint(&format!("{:>width$} |", "", width = gutter), &[Style::Dim]),
+                pad,
+                terminal.paint(&carets, &[color, Style::Bold])
+            ));
+        }
+    }
+    out
+}
+
+/// `offset` clamped into `text` and moved back onto a character boundary
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Taxonomy names under `runs[0].taxonomies`, for [`SecurityMeta`] ids
+const CWE_TAXONOMY: &str = "CWE";
+const OWASP_TAXONOMY: &str = "OWASP";
+
+/// SARIF 2.1.0 log with one run, rule ids qualified by ruleset. CWE and OWASP
+/// ids from [`SecurityMeta`] become taxonomies referenced by each result;
+/// tags, the security metadata and its CVSS score (as `security-severity`)
+/// go into the rule's `properties`. A finding's attachments are listed in
+/// `result.attachments`, relative to the `ARTIFACTS` base directory.
+#[derive(Debug, Clone, Default)]
+pub struct SarifFormatter {
+    artifacts_dir: Option<PathBuf>,
+}
+
+impl SarifFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the `ARTIFACTS` base of attachment uris to `dir`, the run's
+    /// artifacts directory
+    pub fn with_artifacts_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.artifacts_dir = Some(dir.into());
+        self
+    }
+}
+
+/// `uriBaseId` of attachment locations
+const ARTIFACTS_BASE: &str = "ARTIFACTS";
+
+/// Rule metadata gathered from the findings of one rule
+#[derive(Default)]
+struct SarifRule {
+    docs_url: Option<String>,
+    tags: BTreeSet<String>,
+    security: Option<SecurityMeta>,
+}
+
+impl SarifRule {
+    fn add(&mut self, d: &Diagnostic) {
+        if self.docs_url.is_none() {
+            self.docs_url = d.docs_url.clone();
+        }
+        self.tags.extend(d.tags.iter().cloned());
+        if self.security.is_none() {
+            self.security = d.security.clone();
+        }
+    }
+
+    fn to_sarif(&self, id: &str) -> Value {
+        let mut rule = json!({ "id": id });
+        if let Some(url) = &self.docs_url {
+            rule["helpUri"] = json!(url);
+        }
+        let mut properties = serde_json::Map::new();
+        if !self.tags.is_empty() {
+            properties.insert("tags".into(), json!(self.tags));
+        }
+        if let Some(security) = &self.security {
+            properties.insert("security".into(), json!(security));
+            if let Some(cvss) = security.cvss {
+                properties.insert("security-severity".into(), json!(format!("{:.1}", cvss)));
+            }
+        }
+        if !properties.is_empty() {
+            rule["properties"] = Value::Object(properties);
+        }
+        rule
+    }
+}
+
+/// A `toolComponent` holding the ids in `taxa`; None when there are none
+fn sarif_taxonomy(name: &str, taxa: &BTreeSet<String>) -> Option<Value> {
+    (!taxa.is_empty()).then(|| {
+        json!({
+            "name": name,
+            "taxa": taxa.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+        })
+    })
+}
+
+impl Formatter for SarifFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        let mut rules: BTreeMap<String, SarifRule> = BTreeMap::new();
+        let mut cwe = BTreeSet::new();
+        let mut owasp = BTreeSet::new();
+        let mut findings = Vec::new();
+        for result in &results.results {
+            for d in &result.diagnostics {
+                let rule_id = format!("{}/{}", result.ruleset_id, d.rule_id);
+                rules.entry(rule_id.clone()).or_default().add(d);
+                let mut finding = json!({
+                    "ruleId": rule_id,
+                    "level": match d.severity {
+                        Severity::Error => "error",
+                        Severity::Warn => "warning",
+                        Severity::Info | Severity::Hint => "note",
+                    },
+                    "message": { "text": d.message },
+                    "partialFingerprints": { "forseti/v1": d.fingerprint() },
+                });
+                if let Some(data) = &d.data {
+                    finding["properties"] = json!(data);
+                }
+                if let Some(security) = &d.security {
+                    cwe.extend(security.cwe.iter().cloned());
+                    owasp.extend(security.owasp.iter().cloned());
+                    let taxa: Vec<Value> = security
+                        .cwe
+                        .iter()
+                        .map(|id| (CWE_TAXONOMY, id))
+                        .chain(security.owasp.iter().map(|id| (OWASP_TAXONOMY, id)))
+                        .map(|(taxonomy, id)| json!({ "id": id, "toolComponent": { "name": taxonomy } }))
+                        .collect();
+                    if !taxa.is_empty() {
+                        finding["taxa"] = Value::Array(taxa);
+                    }
+                }
+                if !d.attachments.is_empty() {
+                    let attachments: Vec<Value> = d
+                        .attachments
+                        .iter()
+                        .map(|attachment| {
+                            json!({
+                                "artifactLocation": {
+                                    "uri": attachment.path,
+                                    "uriBaseId": ARTIFACTS_BASE,
+                                },
+                                "description": {
+                                    "text": attachment
+                                        .description
+                                        .as_deref()
+                                        .unwrap_or(&attachment.mime_type),
+                                },
+                            })
+                        })
+                        .collect();
+                    finding["attachments"] = Value::Array(attachments);
+                }
+                if let Some(uri) = &d.uri {
+                    finding["locations"] = json!([{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": {
+                                "startLine": d.range.start.line + 1,
+                                "startColumn": d.range.start.character + 1,
+                                "endLine": d.range.end.line + 1,
+                                "endColumn": d.range.end.character + 1,
+                            },
+                        },
+                    }]);
+                }
+                findings.push(finding);
+            }
+        }
+        let mut run = json!({
+            "tool": {
+                "driver": {
+                    "name": "forseti",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules
+                        .iter()
+                        .map(|(id, rule)| rule.to_sarif(id))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "results": findings,
+        });
+        let taxonomies: Vec<Value> = [
+            sarif_taxonomy(CWE_TAXONOMY, &cwe),
+            sarif_taxonomy(OWASP_TAXONOMY, &owasp),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !taxonomies.is_empty() {
+            run["taxonomies"] = Value::Array(taxonomies);
+        }
+        if let Some(dir) = &self.artifacts_dir {
+            let mut uri = file_uri(dir);
+            if !uri.ends_with('/') {
+                uri.push('/');
+            }
+            run["originalUriBaseIds"] = json!({ ARTIFACTS_BASE: { "uri": uri } });
+        }
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [run],
+        });
+        serde_json::to_writer_pretty(&mut *w, &log)?;
+        writeln!(w)
+    }
+}
+
+/// Combine the SARIF logs [`SarifFormatter`] wrote for the shards of one run
+/// (see [`LintResults::merge_shards`]) into a log with a single run: results
+/// are concatenated, rules and taxa are unioned by id, keeping the first
+/// definition seen, and everything else comes from the first run.
+pub fn merge_sarif_logs(logs: impl IntoIterator<Item = Value>) -> Value {
+    let mut header: Option<Value> = None;
+    let mut first_run: Option<Value> = None;
+    let mut findings = Vec::new();
+    let mut rules: BTreeMap<String, Value> = BTreeMap::new();
+    let mut taxonomies: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+    for mut log in logs {
+        let runs = match log.get_mut("runs").map(Value::take) {
+            Some(Value::Array(runs)) => runs,
+            _ => vec![],
+        };
+        for mut run in runs {
+            if let Some(Value::Array(results)) = run.get_mut("results").map(Value::take) {
+                findings.extend(results);
+            }
+            if let Some(Value::Array(found)) =
+                run.pointer_mut("/tool/driver/rules").map(Value::take)
+            {
+                for rule in found {
+                    if let Some(id) = rule["id"].as_str() {
+                        rules.entry(id.to_string()).or_insert(rule);
+                    }
+                }
+            }
+            if let Some(Value::Array(found)) = run.get_mut("taxonomies").map(Value::take) {
+                for mut taxonomy in found {
+                    let name = taxonomy["name"].as_str().unwrap_or_default().to_string();
+                    let taxa = taxonomies.entry(name).or_default();
+                    if let Some(Value::Array(found)) = taxonomy.get_mut("taxa").map(Value::take) {
+                        for taxon in found {
+                            if let Some(id) = taxon["id"].as_str() {
+                                taxa.entry(id.to_string()).or_insert(taxon);
+                            }
+                        }
+                    }
+                }
+            }
+            first_run.get_or_insert(run);
+        }
+        header.get_or_insert(log);
+    }
+    let mut run = first_run.unwrap_or_else(|| {
+        json!({
+            "tool": {
+                "driver": { "name": "forseti", "version": env!("CARGO_PKG_VERSION") },
+            },
+        })
+    });
+    run["tool"]["driver"]["rules"] = Value::Array(rules.into_values().collect());
+    run["results"] = Value::Array(findings);
+    if let Value::Object(map) = &mut run {
+        map.remove("taxonomies");
+    }
+    if !taxonomies.is_empty() {
+        run["taxonomies"] = taxonomies
+            .into_iter()
+            .map(|(name, taxa)| json!({ "name": name, "taxa": taxa.into_values().collect::<Vec<_>>() }))
+            .collect();
+    }
+    let mut log = header.unwrap_or_else(|| {
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+        })
+    });
+    log["runs"] = json!([run]);
+    log
+}
+
+/// Checkstyle XML report, one `<file>` per linted file sorted by its decoded
+/// path, for CI plugins and editors that read checkstyle. Lines and columns are
+/// 1-based, `source` is the qualified rule id and hints report as `info`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckstyleFormatter;
+
+impl Formatter for CheckstyleFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        let mut files: BTreeMap<String, Vec<(&str, &Diagnostic)>> = BTreeMap::new();
+        for result in &results.results {
+            for diagnostic in &result.diagnostics {
+                let uri = diagnostic.uri.as_deref().unwrap_or_default();
+                files
+                    .entry(uri_to_path(uri).display().to_string())
+                    .or_default()
+                    .push((&result.ruleset_id, diagnostic));
+            }
+        }
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n",
+        );
+        for (path, diagnostics) in files {
+            out.push_str(&format!("  <file name=\"{}\">\n", xml_escape(&path)));
+            for (ruleset_id, d) in diagnostics {
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warn => "warning",
+                    Severity::Info | Severity::Hint => "info",
+                };
+                out.push_str(&format!(
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"{}/{}\"/>\n",
+                d.range.start.line + 1,
+                d.range.start.character + 1,
+                severity,
+                xml_escape(&d.message),
+                xml_escape(ruleset_id),
+                xml_escape(&d.rule_id),
+            ));
+            }
+            out.push_str("  </file>\n");
+        }
+        out.push_str("</checkstyle>\n");
+        w.write_all(out.as_bytes())
+    }
+}
+
+/// Escape text for an XML attribute value; control characters XML 1.0 cannot
+/// carry are dropped
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// GitLab Code Quality report (`artifacts:reports:codequality`), one issue
+/// per finding. Paths are decoded from their `file://` URIs and, when `root`
+/// is set, made relative to it so they point into the repository.
+/// Fingerprints are [`Diagnostic::fingerprint`], suffixed with an occurrence count when one
+/// file repeats the same finding, since GitLab collapses equal ones.
+#[derive(Debug, Clone, Default)]
+pub struct GitlabFormatter {
+    root: Option<PathBuf>,
+}
+
+impl GitlabFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report paths relative to `root`
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// The report as a JSON array of issues
+    pub fn report(&self, results: &LintResults) -> Value {
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut issues = Vec::new();
+        for result in &results.results {
+            for d in &result.diagnostics {
+                let path = uri_to_path(d.uri.as_deref().unwrap_or_default());
+                let path = self
+                    .root
+                    .as_deref()
+                    .and_then(|root| path.strip_prefix(root).ok())
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let fingerprint = d.fingerprint();
+                let occurrence = seen.entry(fingerprint.clone()).or_default();
+                let fingerprint = match *occurrence {
+                    0 => fingerprint,
+                    n => content_hash(&format!("{}\0{}", fingerprint, n)),
+                };
+                *occurrence += 1;
+                issues.push(json!({
+                    "description": d.message,
+                    "check_name": format!("{}/{}", result.ruleset_id, d.rule_id),
+                    "fingerprint": fingerprint,
+                    "severity": match d.severity {
+                        Severity::Error => "major",
+                        Severity::Warn => "minor",
+                        Severity::Info | Severity::Hint => "info",
+                    },
+                    "location": {
+                        "path": path,
+                        "lines": {
+                            "begin": d.range.start.line + 1,
+                            "end": d.range.end.line + 1,
+                        },
+                    },
+                }));
+            }
+        }
+        Value::Array(issues)
+    }
+}
+
+impl Formatter for GitlabFormatter {
+    fn format(&self, results: &LintResults, w: &mut dyn Write) -> io::Result<()> {
+        let report = self.report(results);
+        serde_json::to_writer_pretty(&mut *w, &report)?;
+        writeln!(w)
+    }
+}
+
+/// Formatters by name
+#[derive(Clone)]
+pub struct FormatterRegistry {
+    formatters: BTreeMap<String, Arc<dyn Formatter>>,
+}
+
+impl FormatterRegistry {
+    /// A registry without any formatters
+    pub fn empty() -> Self {
+        Self {
+            formatters: BTreeMap::new(),
+        }
+    }
+
+    /// The built-in formatters, `text` colored as `terminal` allows
+    pub fn builtin(terminal: Terminal) -> Self {
+        Self::empty()
+            .with("json", JsonFormatter)
+            .with("ndjson", NdjsonFormatter)
+            .with("text", TextFormatter::new(terminal))
+            .with("sarif", SarifFormatter::new())
+            .with("checkstyle", CheckstyleFormatter)
+            .with("gitlab", GitlabFormatter::new())
+    }
+
+    /// Add `formatter` under `name` (case-insensitive), replacing any already
+    /// registered there
+    pub fn register(&mut self, name: &str, formatter: impl Formatter + 'static) {
+        self.formatters
+            .insert(name.trim().to_ascii_lowercase(), Arc::new(formatter));
+    }
+
+    pub fn with(mut self, name: &str, formatter: impl Formatter + 'static) -> Self {
+        self.register(name, formatter);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Formatter> {
+        self.formatters
+            .get(&name.trim().to_ascii_lowercase())
+            .map(|f| f.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Registered names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.formatters.keys().map(String::as_str)
+    }
+
+    /// Render `results` with the formatter registered as `name`
+    pub fn format(
+        &self,
+        name: &str,
+        results: &LintResults,
+        w: &mut dyn Write,
+    ) -> Result<(), FormatError> {
+        let formatter = self
+            .get(name)
+            .ok_or_else(|| FormatError::Unknown(name.to_string()))?;
+        Ok(formatter.format(results, w)?)
+    }
+
+    /// The formatter for a configured [`OutputFormat`](crate::config::OutputFormat)
+    #[cfg(feature = "linter-host")]
+    pub fn for_output(&self, format: crate::config::OutputFormat) -> Option<&dyn Formatter> {
+        self.get(format.name())
+    }
+}
+
+/// The built-in formatters, `text` following stdout's terminal
+impl Default for FormatterRegistry {
+    fn default() -> Self {
+        Self::builtin(Terminal::detect(Stream::Stdout))
+    }
+}
+
+impl std::fmt::Debug for FormatterRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Attachment, Position, ResultSummary, RulesetResult};
+    use std::path::Path;
+
+    fn at(uri: &str, rule_id: &str, line: u32, message: &str) -> Diagnostic {
+        let start = Position { line, character: 0 };
+        let mut d = Diagnostic::new(
+            rule_id,
+            message,
+            Severity::Warn,
+            Range { start, end: start },
+        );
+        d.uri = Some(uri.to_string());
+        d
+    }
+
+    fn results(diagnostics: Vec<Diagnostic>) -> LintResults {
+        LintResults {
+            total_files: 1,
+            total_diagnostics: diagnostics.len(),
+            execution_time_ms: 0,
+            summary: ResultSummary {
+                errors: 0,
+                warnings: diagnostics.len(),
+                info: 0,
+                hints: 0,
+                rulesets_used: vec!["demo".to_string()],
+            },
+            compatibility: Default::default(),
+            metrics: Default::default(),
+            file_stats: Default::default(),
+            unmatched: Default::default(),
+            results: vec![RulesetResult {
+                ruleset_id: "demo".to_string(),
+                diagnostics,
+                execution_time_ms: 0,
+                files_processed: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn gitlab_paths_are_decoded_and_relative_to_the_root() {
+        let uri = file_uri(Path::new("/work/my repo/src/a b.rs"));
+        let report = GitlabFormatter::new()
+            .with_root("/work/my repo")
+            .report(&results(vec![at(&uri, "r1", 0, "m")]));
+        assert_eq!(report[0]["location"]["path"], "src/a b.rs");
+        assert_eq!(report[0]["check_name"], "demo/r1");
+
+        let outside = file_uri(Path::new("/elsewhere/c.rs"));
+        let report = GitlabFormatter::new()
+            .with_root("/work/my repo")
+            .report(&results(vec![at(&outside, "r1", 0, "m")]));
+        assert_eq!(report[0]["location"]["path"], "/elsewhere/c.rs");
+    }
+
+    #[test]
+    fn gitlab_fingerprints_count_repeated_findings() {
+        let report = GitlabFormatter::new().report(&results(vec![
+            at("file:///a.rs", "r1", 0, "m"),
+            at("file:///a.rs", "r1", 5, "m"),
+            at("file:///a.rs", "r1", 9, "m"),
+            at("file:///a.rs", "r2", 0, "m"),
+        ]));
+        let fingerprints: Vec<&str> = report
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|issue| issue["fingerprint"].as_str().unwrap())
+            .collect();
+        let first = at("file:///a.rs", "r1", 0, "m").fingerprint();
+        assert_eq!(fingerprints[0], first);
+        assert_ne!(fingerprints[1], first);
+        assert_ne!(fingerprints[2], fingerprints[1]);
+        assert_eq!(
+            fingerprints[3],
+            at("file:///a.rs", "r2", 0, "m").fingerprint()
+        );
+        // Stable across runs
+        assert_eq!(
+            GitlabFormatter::new().report(&results(vec![
+                at("file:///a.rs", "r1", 0, "m"),
+                at("file:///a.rs", "r1", 5, "m"),
+            ]))[1]["fingerprint"],
+            fingerprints[1]
+        );
+    }
+
+    #[test]
+    fn checkstyle_names_files_by_decoded_path_and_escapes_attributes() {
+        let uri = file_uri(Path::new("/work/my repo/a&b.rs"));
+        let mut out = Vec::new();
+        CheckstyleFormatter
+            .format(
+                &results(vec![at(&uri, "r1", 2, "use \"x\" < y\n")]),
+                &mut out,
+            )
+            .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(
+            xml.contains("<file name=\"/work/my repo/a&amp;b.rs\">"),
+            "{xml}"
+        );
+        assert!(xml.contains(
+            "<error line=\"3\" column=\"1\" severity=\"warning\" \
+             message=\"use &quot;x&quot; &lt; y&#10;\" source=\"demo/r1\"/>"
+        ));
+    }
+
+    #[test]
+    fn xml_escape_drops_control_characters() {
+        assert_eq!(xml_escape("a\u{0}b\u{1b}c\td'"), "abc&#9;d&apos;");
+        assert_eq!(xml_escape("plain"), "plain");
+    }
+
+    fn frame(text: &str, start: (u32, u32), end: (u32, u32), terminal: &Terminal) -> String {
+        let range = Range {
+            start: Position {
+                line: start.0,
+                character: start.1,
+            },
+            end: Position {
+                line: end.0,
+                character: end.1,
+            },
+        };
+        code_frame(&LineIndex::new(text), range, terminal, Style::Red)
+    }
+
+    #[test]
+    fn text_headers_show_decoded_paths() {
+        let uri = file_uri(Path::new("/work/my repo/a b.rs"));
+        let text = TextFormatter::new(Terminal::plain())
+            .render(&results(vec![at(&uri, "r1", 0, "m")]), |_| None);
+        assert_eq!(text.lines().next(), Some("/work/my repo/a b.rs"));
+    }
+
+    #[test]
+    fn code_frames_keep_tabs_under_the_carets() {
+        let text = frame("a\n\tlet x = 1;\nb\n", (1, 5), (1, 6), &Terminal::plain());
+        assert_eq!(
+            text,
+            "    1 | a\n  > 2 | \tlet x = 1;\n      | \t    ^\n    3 | b\n"
+        );
+    }
+
+    #[test]
+    fn code_frames_underline_multi_line_ranges_to_the_end_of_the_line() {
+        let text = frame("let a =\n  b;\n", (0, 4), (1, 3), &Terminal::plain());
+        assert_eq!(text, "  > 1 | let a =\n      |     ^^^\n    2 |   b;\n");
+    }
+
+    #[test]
+    fn code_frames_clamp_carets_to_truncated_lines() {
+        let terminal = Terminal::plain().with_width(20);
+        let text = frame("0123456789abcdefghij", (0, 15), (0, 18), &terminal);
+        assert_eq!(text, "  > 1 | 0123456789…\n      |           ^\n");
+    }
+
+    #[test]
+    fn code_frames_snap_offsets_onto_character_boundaries() {
+        // Byte 2 falls inside "é"
+        let text = frame("héllo wörld", (0, 2), (0, 4), &Terminal::plain());
+        assert_eq!(text, "  > 1 | héllo wörld\n      |  ^^\n");
+        assert_eq!(floor_char_boundary("é", 1), 0);
+        assert_eq!(floor_char_boundary("ab", 10), 2);
+    }
+
+    fn sarif(formatter: &SarifFormatter, results: &LintResults) -> Value {
+        let mut out = Vec::new();
+        formatter.format(results, &mut out).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn sarif_security_ids_become_taxonomies() {
+        let mut injection = at("file:///a.rs", "sql", 0, "m");
+        injection.security = Some(SecurityMeta {
+            cwe: vec!["CWE-89".to_string()],
+            owasp: vec!["A03:2021".to_string()],
+            cvss: Some(9.81),
+        });
+        let mut xss = at("file:///b.rs", "xss", 0, "m");
+        xss.security = Some(SecurityMeta {
+            cwe: vec!["CWE-79".to_string(), "CWE-89".to_string()],
+            ..Default::default()
+        });
+        let log = sarif(
+            &SarifFormatter::new(),
+            &results(vec![injection, xss, at("file:///c.rs", "style", 0, "m")]),
+        );
+        let run = &log["runs"][0];
+        assert_eq!(
+            run["taxonomies"],
+            json!([
+                { "name": "CWE", "taxa": [{ "id": "CWE-79" }, { "id": "CWE-89" }] },
+                { "name": "OWASP", "taxa": [{ "id": "A03:2021" }] },
+            ])
+        );
+        assert_eq!(
+            run["results"][0]["taxa"],
+            json!([
+                { "id": "CWE-89", "toolComponent": { "name": "CWE" } },
+                { "id": "A03:2021", "toolComponent": { "name": "OWASP" } },
+            ])
+        );
+        assert!(run["results"][2].get("taxa").is_none());
+        let rule = &run["tool"]["driver"]["rules"][0];
+        assert_eq!(rule["id"], "demo/sql");
+        assert_eq!(rule["properties"]["security-severity"], "9.8");
+    }
+
+    #[test]
+    fn sarif_runs_without_security_ids_have_no_taxonomies() {
+        let log = sarif(
+            &SarifFormatter::new(),
+            &results(vec![at("file:///a.rs", "r1", 0, "m")]),
+        );
+        assert!(log["runs"][0].get("taxonomies").is_none());
+    }
+
+    #[test]
+    fn sarif_lists_attachments_under_the_artifacts_base() {
+        let mut d = at("file:///a.rs", "r1", 0, "m");
+        d.attachments = vec![
+            Attachment::new("r1/graph.svg", "image/svg+xml").with_description("Call graph"),
+            Attachment::new("r1/trace.json", "application/json"),
+        ];
+        let log = sarif(
+            &SarifFormatter::new().with_artifacts_dir("/runs/run 1"),
+            &results(vec![d]),
+        );
+        let run = &log["runs"][0];
+        assert_eq!(
+            run["originalUriBaseIds"],
+            json!({ "ARTIFACTS": { "uri": "file:///runs/run%201/" } })
+        );
+        assert_eq!(
+            run["results"][0]["attachments"],
+            json!([
+                {
+                    "artifactLocation": { "uri": "r1/graph.svg", "uriBaseId": "ARTIFACTS" },
+                    "description": { "text": "Call graph" },
+                },
+                {
+                    "artifactLocation": { "uri": "r1/trace.json", "uriBaseId": "ARTIFACTS" },
+                    "description": { "text": "application/json" },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn sarif_findings_without_attachments_omit_them() {
+        let log = sarif(
+            &SarifFormatter::new(),
+            &results(vec![at("file:///a.rs", "r1", 0, "m")]),
+        );
+        assert!(log["runs"][0]["results"][0].get("attachments").is_none());
+        assert!(log["runs"][0].get("originalUriBaseIds").is_none());
+    }
+
+    #[test]
+    fn merged_sarif_logs_union_rules_and_taxa_and_concatenate_results() {
+        let mut a = at("file:///a.rs", "sql", 0, "m");
+        a.security = Some(SecurityMeta {
+            cwe: vec!["CWE-89".to_string()],
+            ..Default::default()
+        });
+        let mut b = at("file:///b.rs", "sql", 0, "m");
+        b.security = Some(SecurityMeta {
+            cwe: vec!["CWE-89".to_string(), "CWE-79".to_string()],
+            ..Default::default()
+        });
+        let first = sarif(&SarifFormatter::new(), &results(vec![a]));
+        let second = sarif(
+            &SarifFormatter::new(),
+            &results(vec![b, at("file:///b.rs", "style", 0, "m")]),
+        );
+        let merged = merge_sarif_logs([first.clone(), second]);
+        assert_eq!(merged["version"], "2.1.0");
+        assert_eq!(merged["runs"].as_array().unwrap().len(), 1);
+        let run = &merged["runs"][0];
+        // The first definition of a rule wins
+        let rules = &run["tool"]["driver"]["rules"];
+        assert_eq!(rules[0], first["runs"][0]["tool"]["driver"]["rules"][0]);
+        assert_eq!(rules[1]["id"], "demo/style");
+        assert_eq!(rules.as_array().unwrap().len(), 2);
+        let uris: Vec<&str> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                r["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                    .as_str()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(uris, ["file:///a.rs", "file:///b.rs", "file:///b.rs"]);
+        assert_eq!(
+            run["taxonomies"],
+            json!([{ "name": "CWE", "taxa": [{ "id": "CWE-79" }, { "id": "CWE-89" }] }])
+        );
+    }
+
+    #[test]
+    fn merging_no_sarif_logs_gives_an_empty_run() {
+        let merged = merge_sarif_logs([]);
+        assert_eq!(merged["runs"][0]["results"], json!([]));
+        assert_eq!(merged["runs"][0]["tool"]["driver"]["rules"], json!([]));
+        assert!(merged["runs"][0].get("taxonomies").is_none());
+    }
+}
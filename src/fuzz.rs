@@ -0,0 +1,218 @@
+//! Entry points for fuzzing the parsers that consume untrusted input: NDJSON
+//! messages from a ruleset process, annotation comments in linted files and
+//! config files.
+//!
+//! Each target takes raw bytes and must never panic. The `fuzz/` directory
+//! wires them into cargo-fuzz; [`write_corpus`] seeds its corpus with
+//! well-formed samples of every message type.
+
+use crate::core::{AnnotationParser, Kind, parse_envelope_bytes};
+use crate::protocol::{Request, check_event, check_reply};
+use std::io;
+use std::path::Path;
+
+/// Comment prefixes the annotation target parses with
+const ANNOTATION_PREFIXES: &[&str] = &["//", "#", "--", ";", "/*", "<!--"];
+
+/// Parse one NDJSON message and decode its payload as its type dictates
+pub fn envelope(data: &[u8]) {
+    let Ok(envelope) = parse_envelope_bytes(data) else {
+        return;
+    };
+    let _ = match envelope.kind {
+        Kind::Req => Request::from_envelope(&envelope).map(drop),
+        Kind::Res => check_reply(&envelope),
+        Kind::Event => check_event(&envelope),
+    };
+}
+
+/// Parse `forseti-ignore` directives and query them
+pub fn annotations(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let parser = AnnotationParser::new(ANNOTATION_PREFIXES.iter().map(|p| p.to_string()).collect());
+    let annotations = parser.parse_annotations(text);
+    for annotation in &annotations {
+        let rule_id = annotation.rule_ids.first().map_or("", String::as_str);
+        parser.should_ignore_rule(&annotations, rule_id, annotation.line.saturating_add(1));
+    }
+}
+
+/// Load a `forseti.toml`
+#[cfg(feature = "linter-host")]
+pub fn config(data: &[u8]) {
+    if let Ok(raw) = std::str::from_utf8(data) {
+        let _ = crate::config::Config::load_from_str(raw);
+    }
+}
+
+/// Well-formed NDJSON messages, one per message type
+const ENVELOPE_SEEDS: &[(&str, &str)] = &[
+    (
+        "initialize",
+        r#"{"v":1,"kind":"req","type":"initialize","id":"1","payload":{"rulesetConfig":{"no-trailing-whitespace":"warn","max-line-length":["info",{"limit":100}]},"protocolVersions":{"min":1,"max":1}}}"#,
+    ),
+    (
+        "initialize-reply",
+        r#"{"v":1,"kind":"res","type":"initialize","id":"1","payload":{"ok":true}}"#,
+    ),
+    (
+        "get-capabilities",
+        r#"{"v":1,"kind":"req","type":"getCapabilities","id":"2"}"#,
+    ),
+    (
+        "get-rule-doc",
+        r#"{"v":1,"kind":"req","type":"getRuleDoc","id":"3","payload":{"ruleId":"max-line-length"}}"#,
+    ),
+    (
+        "preprocess-files",
+        r#"{"v":1,"kind":"req","type":"preprocessFiles","id":"4","payload":{"fileUris":["file:///src/a.rs"]}}"#,
+    ),
+    (
+        "analyze-file",
+        r#"{"v":1,"kind":"req","type":"analyzeFile","id":"5","sessionId":"s1","timeoutMs":5000,"traceId":"t1","payload":{"uri":"mem://sample.txt","content":"hello   \nworld\n","contentHash":"abc"}}"#,
+    ),
+    (
+        "analyze-file-base64",
+        r#"{"v":1,"kind":"req","type":"analyzeFile","id":"6","payload":{"uri":"mem://b.bin","content":"aGVsbG8K","encoding":"base64"}}"#,
+    ),
+    (
+        "cancel",
+        r#"{"v":1,"kind":"req","type":"cancel","id":"7","payload":{"id":"5"}}"#,
+    ),
+    (
+        "set-rules",
+        r#"{"v":1,"kind":"req","type":"setRules","id":"8","payload":{"rules":{"max-line-length":false}}}"#,
+    ),
+    (
+        "shutdown",
+        r#"{"v":1,"kind":"req","type":"shutdown","id":"9","payload":{}}"#,
+    ),
+    ("exit", r#"{"v":1,"kind":"req","type":"exit","id":"10"}"#),
+    (
+        "diagnostics",
+        r#"{"v":1,"kind":"event","type":"diagnostics","parentId":"5","payload":{"uri":"mem://sample.txt","contentHash":"abc","diagnostics":[{"rule_id":"no-trailing-whitespace","message":"Trailing whitespace","severity":"warn","range":{"start":{"line":0,"character":5},"end":{"line":0,"character":8}},"suggest":[{"title":"Remove it","fix":{"range":{"start":{"line":0,"character":5},"end":{"line":0,"character":8}},"text":""}}]}]}}"#,
+    ),
+    (
+        "progress",
+        r#"{"v":1,"kind":"event","type":"progress","payload":{"id":"4","title":"Preprocessing","done":3,"total":10,"message":"src/a.rs"}}"#,
+    ),
+    (
+        "log",
+        r#"{"v":1,"kind":"event","type":"log","payload":{"level":"warn","message":"slow rule","target":"forseti::server","fields":{"ms":120}}}"#,
+    ),
+    (
+        "metrics",
+        r#"{"v":1,"kind":"event","type":"metrics","payload":{"counters":{"requests":4},"gauges":{"inFlight":1.0}}}"#,
+    ),
+    (
+        "terminated",
+        r#"{"v":1,"kind":"event","type":"terminated","payload":{"reason":"parseFailures","count":3}}"#,
+    ),
+    (
+        "fatal",
+        r#"{"v":1,"kind":"event","type":"fatal","payload":{"message":"index out of bounds","location":"src/rules.rs:12:5","thread":"main"}}"#,
+    ),
+    (
+        "error-reply",
+        r#"{"v":1,"kind":"res","type":"analyzeFile","id":"5","payload":{"ok":false,"error":"not_initialized","message":"initialize first"}}"#,
+    ),
+];
+
+/// Files carrying each directive form
+const ANNOTATION_SEEDS: &[(&str, &str)] = &[
+    (
+        "next-line",
+        "fn main() {\n    // forseti-ignore-next-line max-line-length, no-todo\n    let x = 1;\n}\n",
+    ),
+    (
+        "file",
+        "# forseti-ignore-file\nprint('hi')\n-- forseti-ignore no-select-star\n",
+    ),
+    (
+        "block",
+        "/* forseti-ignore-next-line no-important */\na { color: red !important; }\n<!-- forseti-ignore-file -->\n",
+    ),
+];
+
+/// Configs touching every section
+#[cfg(feature = "linter-host")]
+const CONFIG_SEEDS: &[(&str, &str)] = &[
+    ("empty", ""),
+    (
+        "full",
+        r#"[linter]
+log_level = "debug"
+ignore = ["target/**", "**/*.min.js"]
+strict_protocol = true
+
+[ruleset.base]
+enabled = true
+min_severity = "warn"
+
+[ruleset.base.config]
+no-trailing-whitespace = "warn"
+max-line-length = ["info", { limit = 100 }]
+
+[equivalences]
+line-length = ["base/max-line-length", "style/line-length"]
+"#,
+    ),
+];
+
+/// Seed inputs per fuzz target: target name, then file name and content
+pub fn corpus() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+    vec![
+        ("envelope", ENVELOPE_SEEDS.to_vec()),
+        ("annotations", ANNOTATION_SEEDS.to_vec()),
+        #[cfg(feature = "linter-host")]
+        ("config", CONFIG_SEEDS.to_vec()),
+    ]
+}
+
+/// Write [`corpus`] as `<dir>/<target>/<name>`, the layout cargo-fuzz reads,
+/// returning how many files were written
+pub fn write_corpus(dir: &Path) -> io::Result<usize> {
+    let mut written = 0;
+    for (target, seeds) in corpus() {
+        let target_dir = dir.join(target);
+        std::fs::create_dir_all(&target_dir)?;
+        for (name, content) in seeds {
+            std::fs::write(target_dir.join(name), content)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_seeds_are_accepted() {
+        for (name, seed) in ENVELOPE_SEEDS {
+            let envelope = parse_envelope_bytes(seed.as_bytes()).unwrap();
+            let accepted = match envelope.kind {
+                Kind::Req => Request::from_envelope(&envelope).map(drop),
+                Kind::Res => check_reply(&envelope),
+                Kind::Event => check_event(&envelope),
+            };
+            assert!(accepted.is_ok(), "{name}: {accepted:?}");
+        }
+    }
+
+    #[test]
+    fn preprocess_seed_names_its_files() {
+        let (_, seed) = ENVELOPE_SEEDS
+            .iter()
+            .find(|(name, _)| *name == "preprocess-files")
+            .unwrap();
+        let envelope = parse_envelope_bytes(seed.as_bytes()).unwrap();
+        let Ok(Request::PreprocessFiles(params)) = Request::from_envelope(&envelope) else {
+            panic!("not a preprocessFiles request");
+        };
+        assert_eq!(params.file_uris, ["file:///src/a.rs"]);
+    }
+}
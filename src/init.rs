@@ -0,0 +1,196 @@
+//! Logic behind `forseti init`: survey a workspace, match its languages
+//! against the known rulesets and propose a starting config.
+//!
+//! The CLI owns the prompts; [`recommend_config`] gives it a [`Recommendation`]
+//! to show, adjust and write out with [`Recommendation::to_toml`].
+
+use crate::config::{Config, RulesetCfg, json_to_toml};
+pub use crate::core::language_for;
+use crate::core::{RuleCatalog, RulesetCapabilities, glob_match};
+use crate::discovery::walk_files;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+/// Files of one language found under the workspace root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub extensions: BTreeSet<String>,
+}
+
+/// Count files per language under `root`, most common first, walking it as
+/// [`crate::discovery::expand_paths`] does: hidden directories and build or
+/// vendor output are not descended into, and subdirectories that cannot be
+/// read are skipped.
+pub fn detect_languages(root: &Path) -> std::io::Result<Vec<LanguageStats>> {
+    let mut found: BTreeMap<&str, LanguageStats> = BTreeMap::new();
+    walk_files(
+        root,
+        &mut HashSet::new(),
+        |_| false,
+        |path| {
+            let Some(language) = language_for(path) else {
+                return;
+            };
+            let stats = found.entry(language).or_insert_with(|| LanguageStats {
+                language: language.to_string(),
+                files: 0,
+                extensions: BTreeSet::new(),
+            });
+            stats.files += 1;
+            if let Some(ext) = path.extension() {
+                stats
+                    .extensions
+                    .insert(ext.to_string_lossy().to_ascii_lowercase());
+            }
+        },
+    )?;
+    let mut languages: Vec<LanguageStats> = found.into_values().collect();
+    languages.sort_by(|a, b| b.files.cmp(&a.files).then(a.language.cmp(&b.language)));
+    Ok(languages)
+}
+
+/// How aggressively the recommended config turns rules on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// Each ruleset's own defaults
+    #[default]
+    Recommended,
+    /// Every rule on, as an error
+    Strict,
+    /// Only the rules a ruleset reports as errors by default
+    Minimal,
+}
+
+/// Proposed config for a workspace and how it was arrived at
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    pub languages: Vec<LanguageStats>,
+    /// Ruleset id => languages it was chosen for
+    pub rulesets: BTreeMap<String, Vec<String>>,
+    /// Languages present that no known ruleset handles
+    pub uncovered: Vec<String>,
+    pub config: Config,
+}
+
+impl Recommendation {
+    /// The config as `forseti.toml` content. Only the chosen rulesets are
+    /// written; every other setting keeps its default.
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        let rulesets: toml::Table = self
+            .config
+            .ruleset
+            .iter()
+            .map(|(id, ruleset)| {
+                let mut table = toml::Table::new();
+                table.insert("enabled".to_string(), ruleset.enabled.into());
+                table.insert(
+                    "config".to_string(),
+                    toml::Value::Table(ruleset.config.clone()),
+                );
+                (id.clone(), toml::Value::Table(table))
+            })
+            .collect();
+        let mut doc = toml::Table::new();
+        doc.insert("ruleset".to_string(), toml::Value::Table(rulesets));
+        toml::to_string(&doc)
+    }
+}
+
+/// Detect the languages under `root` and enable every ruleset in `catalog`
+/// whose file patterns cover one of them, with its default rule config
+pub fn recommend_config(root: &Path, catalog: &RuleCatalog) -> std::io::Result<Recommendation> {
+    recommend_config_with_preset(root, catalog, Preset::Recommended)
+}
+
+/// Like [`recommend_config`], with rule levels chosen by `preset`
+pub fn recommend_config_with_preset(
+    root: &Path,
+    catalog: &RuleCatalog,
+    preset: Preset,
+) -> std::io::Result<Recommendation> {
+    let languages = detect_languages(root)?;
+    let mut rulesets: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut uncovered = Vec::new();
+    for stats in &languages {
+        let matching: Vec<&RulesetCapabilities> = catalog
+            .rulesets
+            .iter()
+            .filter(|rs| covers(rs, &stats.extensions))
+            .collect();
+        if matching.is_empty() {
+            uncovered.push(stats.language.clone());
+        }
+        for rs in matching {
+            rulesets
+                .entry(rs.ruleset_id.clone())
+                .or_default()
+                .push(stats.language.clone());
+        }
+    }
+
+    let mut config = Config::from_default();
+    for ruleset_id in rulesets.keys() {
+        if let Some(capabilities) = catalog.ruleset(ruleset_id) {
+            let ruleset = RulesetCfg {
+                enabled: true,
+                config: preset_config(capabilities, preset),
+                ..Default::default()
+            };
+            config.ruleset.insert(ruleset_id.clone(), ruleset);
+        }
+    }
+    Ok(Recommendation {
+        languages,
+        rulesets,
+        uncovered,
+        config,
+    })
+}
+
+/// Whether one of the ruleset's file patterns matches a file with one of `extensions`
+fn covers(capabilities: &RulesetCapabilities, extensions: &BTreeSet<String>) -> bool {
+    capabilities.file_patterns.iter().any(|pattern| {
+        let name_pattern = pattern.rsplit('/').next().unwrap_or(pattern);
+        extensions
+            .iter()
+            .any(|ext| glob_match(name_pattern, &format!("file.{ext}")))
+    })
+}
+
+/// Rule config for `capabilities` under `preset`, keeping rule options
+fn preset_config(capabilities: &RulesetCapabilities, preset: Preset) -> toml::value::Table {
+    let mut rule_ids: BTreeSet<&str> = capabilities.rules.iter().map(|r| r.id.as_str()).collect();
+    rule_ids.extend(capabilities.default_config.keys().map(String::as_str));
+    rule_ids
+        .into_iter()
+        .filter_map(|rule_id| {
+            let default = capabilities.default_config.get(rule_id);
+            let level = default.and_then(|value| match value {
+                Value::String(level) => Some(level.as_str()),
+                Value::Array(items) => items.first()?.as_str(),
+                _ => None,
+            });
+            let options = match default {
+                Some(Value::Array(items)) => items.get(1).cloned(),
+                Some(options @ Value::Object(_)) => Some(options.clone()),
+                _ => None,
+            };
+            let level = match preset {
+                Preset::Recommended => level?,
+                Preset::Strict => "error",
+                Preset::Minimal if level == Some("error") => "error",
+                Preset::Minimal => "off",
+            };
+            let value = match options {
+                Some(options) => Value::Array(vec![Value::from(level), options]),
+                None => Value::from(level),
+            };
+            Some((rule_id.to_string(), json_to_toml(&value).ok()?))
+        })
+        .collect()
+}
@@ -0,0 +1,347 @@
+//! JSON-RPC 2.0 compatibility mode.
+//!
+//! [`JsonRpcTransport`] wraps another transport and translates at the edge, so
+//! servers and hosts keep working with envelopes while the wire carries
+//! JSON-RPC: requests become `{"jsonrpc": "2.0", "id", "method", "params"}`,
+//! responses `result` or `error`, and events notifications. `error` events,
+//! sent when there is no request id to answer (e.g. a parse error), become
+//! error responses with a `null` id. Batches are translated message by
+//! message. Numeric ids are preserved. `sessionId`, `timeoutMs`, `traceId`,
+//! `parentId` and the `encoding` of a gzipped payload are passed through as
+//! extra members; the gzipped value then stands in `params` or `result`.
+
+use crate::core::{Envelope, Kind, PROTOCOL_VERSION};
+use crate::protocol::error_codes;
+use crate::transport::{MessageReader, MessageWriter, Transport};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Standard JSON-RPC error codes
+pub mod codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// Application errors; the forseti code is in `error.data.error`
+    pub const SERVER_ERROR: i64 = -32000;
+}
+
+/// Ids of requests seen in either direction, so replies can be restored
+#[derive(Default)]
+struct Pending {
+    /// Envelope id => original JSON-RPC id
+    ids: HashMap<String, Value>,
+    /// Envelope id => method, since JSON-RPC responses do not repeat it
+    methods: HashMap<String, String>,
+}
+
+type SharedPending = Arc<Mutex<Pending>>;
+
+/// Speak JSON-RPC 2.0 over `inner`
+pub struct JsonRpcTransport<T> {
+    inner: T,
+}
+
+impl<T: Transport> JsonRpcTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T: Transport> Transport for JsonRpcTransport<T> {
+    fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>) {
+        let (reader, writer) = self.inner.into_parts();
+        let pending = SharedPending::default();
+        (
+            Box::new(JsonRpcReader {
+                inner: reader,
+                pending: pending.clone(),
+            }),
+            Box::new(JsonRpcWriter {
+                inner: writer,
+                pending,
+            }),
+        )
+    }
+}
+
+struct JsonRpcReader {
+    inner: Box<dyn MessageReader>,
+    pending: SharedPending,
+}
+
+impl MessageReader for JsonRpcReader {
+    fn read_message(&mut self) -> io::Result<Value> {
+        let message = self.inner.read_message()?;
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(match message {
+            Value::Array(batch) => Value::Array(
+                batch
+                    .into_iter()
+                    .map(|message| from_jsonrpc(message, &mut pending))
+                    .collect(),
+            ),
+            message => from_jsonrpc(message, &mut pending),
+        })
+    }
+}
+
+struct JsonRpcWriter {
+    inner: Box<dyn MessageWriter>,
+    pending: SharedPending,
+}
+
+impl MessageWriter for JsonRpcWriter {
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        let translated = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            match serde_json::from_str(message)? {
+                Value::Array(batch) => Value::Array(
+                    batch
+                        .into_iter()
+                        .map(|envelope| translate_outbound(envelope, &mut pending))
+                        .collect::<serde_json::Result<_>>()?,
+                ),
+                envelope => translate_outbound(envelope, &mut pending)?,
+            }
+        };
+        self.inner
+            .write_message(&serde_json::to_string(&translated)?)
+    }
+}
+
+/// [`to_jsonrpc`] for a serialized envelope, keeping the `encoding` marker a
+/// compressed payload carries
+fn translate_outbound(envelope: Value, pending: &mut Pending) -> serde_json::Result<Value> {
+    let encoding = envelope.get("encoding").cloned();
+    let mut message = to_jsonrpc(serde_json::from_value(envelope)?, pending);
+    if let Some(encoding) = encoding {
+        message["encoding"] = encoding;
+    }
+    Ok(message)
+}
+
+/// Envelope id for a JSON-RPC id; strings are kept, anything else is rendered
+fn envelope_id(id: &Value) -> String {
+    match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Translate an inbound JSON-RPC message into envelope form. Messages that do
+/// not look like JSON-RPC are passed through for the envelope parser to reject.
+fn from_jsonrpc(message: Value, pending: &mut Pending) -> Value {
+    let Value::Object(mut fields) = message else {
+        return message;
+    };
+    if fields.get("jsonrpc").and_then(Value::as_str) != Some(JSONRPC_VERSION) {
+        return Value::Object(fields);
+    }
+    let id = fields.remove("id").filter(|id| !id.is_null());
+    let mut envelope = Map::new();
+    envelope.insert("v".into(), json!(PROTOCOL_VERSION));
+    if let Some(Value::String(method)) = fields.remove("method") {
+        let params = fields.remove("params").unwrap_or_else(|| json!({}));
+        match id {
+            Some(id) => {
+                let key = envelope_id(&id);
+                pending.ids.insert(key.clone(), id);
+                pending.methods.insert(key.clone(), method.clone());
+                envelope.insert("kind".into(), json!("req"));
+                envelope.insert("id".into(), json!(key));
+            }
+            None => {
+                envelope.insert("kind".into(), json!("event"));
+            }
+        }
+        envelope.insert("type".into(), json!(method));
+        envelope.insert("payload".into(), params);
+    } else if id.is_none() && fields.contains_key("error") {
+        // An error answering no request, e.g. a parse error
+        let error = fields.remove("error").unwrap_or_default();
+        envelope.insert("kind".into(), json!("event"));
+        envelope.insert("type".into(), json!("error"));
+        envelope.insert("payload".into(), error_payload(error));
+    } else {
+        let key = id.as_ref().map(envelope_id).unwrap_or_default();
+        pending.ids.remove(&key);
+        let method = pending
+            .methods
+            .remove(&key)
+            .unwrap_or_else(|| "response".into());
+        let payload = match (fields.remove("result"), fields.remove("error")) {
+            (Some(result), _) => result,
+            (None, Some(error)) => error_payload(error),
+            (None, None) => Value::Null,
+        };
+        envelope.insert("kind".into(), json!("res"));
+        envelope.insert("type".into(), json!(method));
+        envelope.insert("id".into(), json!(key));
+        envelope.insert("payload".into(), payload);
+    }
+    for member in ["sessionId", "timeoutMs", "traceId", "parentId", "encoding"] {
+        if let Some(value) = fields.remove(member) {
+            envelope.insert(member.into(), value);
+        }
+    }
+    Value::Object(envelope)
+}
+
+/// `ErrorPayload` form of a JSON-RPC error object
+fn error_payload(error: Value) -> Value {
+    if let Some(data) = error.get("data")
+        && data.get("error").is_some()
+    {
+        return data.clone();
+    }
+    let code = match error.get("code").and_then(Value::as_i64) {
+        Some(codes::PARSE_ERROR) => error_codes::PARSE_ERROR,
+        Some(codes::METHOD_NOT_FOUND) => error_codes::UNKNOWN_TYPE,
+        Some(codes::INVALID_PARAMS) => error_codes::INVALID_PAYLOAD,
+        _ => error_codes::INTERNAL,
+    };
+    json!({
+        "ok": false,
+        "error": code,
+        "message": error.get("message").cloned().unwrap_or(Value::Null),
+    })
+}
+
+/// Translate an outbound envelope into a JSON-RPC message
+fn to_jsonrpc(envelope: Envelope<Value>, pending: &mut Pending) -> Value {
+    let mut message = Map::new();
+    message.insert("jsonrpc".into(), json!(JSONRPC_VERSION));
+    let payload = envelope.payload.unwrap_or(Value::Null);
+    match envelope.kind {
+        Kind::Req => {
+            let key = envelope.id.unwrap_or_default();
+            pending.methods.insert(key.clone(), envelope.typ.clone());
+            message.insert("id".into(), json!(key));
+            message.insert("method".into(), json!(envelope.typ));
+            message.insert("params".into(), payload);
+        }
+        Kind::Res => {
+            let key = envelope.id.unwrap_or_default();
+            pending.methods.remove(&key);
+            let id = pending.ids.remove(&key).unwrap_or_else(|| {
+                if key.is_empty() {
+                    Value::Null
+                } else {
+                    json!(key)
+                }
+            });
+            message.insert("id".into(), id);
+            if payload.get("ok") == Some(&Value::Bool(false)) && payload.get("error").is_some() {
+                message.insert("error".into(), jsonrpc_error(payload));
+            } else {
+                message.insert("result".into(), payload);
+            }
+        }
+        Kind::Event if envelope.typ == "error" => {
+            message.insert("id".into(), Value::Null);
+            message.insert("error".into(), jsonrpc_error(payload));
+        }
+        Kind::Event => {
+            message.insert("method".into(), json!(envelope.typ));
+            message.insert("params".into(), payload);
+        }
+    }
+    if let Some(session_id) = envelope.session_id {
+        message.insert("sessionId".into(), json!(session_id));
+    }
+    if let Some(timeout_ms) = envelope.timeout_ms {
+        message.insert("timeoutMs".into(), json!(timeout_ms));
+    }
+    if let Some(trace_id) = envelope.trace_id {
+        message.insert("traceId".into(), json!(trace_id));
+    }
+    if let Some(parent_id) = envelope.parent_id {
+        message.insert("parentId".into(), json!(parent_id));
+    }
+    Value::Object(message)
+}
+
+/// JSON-RPC error object for an `ErrorPayload`, keeping it whole in `data`
+fn jsonrpc_error(payload: Value) -> Value {
+    let code = match payload.get("error").and_then(Value::as_str) {
+        Some(error_codes::PARSE_ERROR) => codes::PARSE_ERROR,
+        Some(error_codes::UNKNOWN_TYPE) => codes::METHOD_NOT_FOUND,
+        Some(error_codes::INVALID_PAYLOAD) => codes::INVALID_PARAMS,
+        Some(error_codes::INTERNAL) => codes::INTERNAL_ERROR,
+        _ => codes::SERVER_ERROR,
+    };
+    let message = payload
+        .get("message")
+        .and_then(Value::as_str)
+        .filter(|m| !m.is_empty())
+        .or_else(|| payload.get("error").and_then(Value::as_str))
+        .unwrap_or("error")
+        .to_string();
+    json!({"code": code, "message": message, "data": payload})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_replies_keep_their_ids() {
+        let mut pending = Pending::default();
+        let request = json!({"jsonrpc": "2.0", "id": 7, "method": "getCapabilities", "params": {}});
+        let envelope = from_jsonrpc(request, &mut pending);
+        assert_eq!(envelope["kind"], "req");
+        assert_eq!(envelope["id"], "7");
+
+        let reply = Envelope::res("getCapabilities", "7", json!({"ok": true}));
+        let message = to_jsonrpc(reply, &mut pending);
+        assert_eq!(message["id"], 7, "numeric id restored");
+        assert_eq!(message["result"], json!({"ok": true}));
+        assert!(pending.ids.is_empty() && pending.methods.is_empty());
+    }
+
+    #[test]
+    fn error_payloads_become_error_objects_and_back() {
+        let mut pending = Pending::default();
+        let payload = json!({"ok": false, "error": error_codes::UNKNOWN_TYPE, "message": "nope"});
+        let message = to_jsonrpc(
+            Envelope::res("frobnicate", "1", payload.clone()),
+            &mut pending,
+        );
+        assert_eq!(message["error"]["code"], codes::METHOD_NOT_FOUND);
+        assert_eq!(message["error"]["message"], "nope");
+
+        let envelope = from_jsonrpc(message, &mut pending);
+        assert_eq!(envelope["kind"], "res");
+        assert_eq!(envelope["payload"], payload);
+    }
+
+    #[test]
+    fn non_jsonrpc_messages_pass_through() {
+        let mut pending = Pending::default();
+        let message = json!({"v": 1, "kind": "req", "type": "shutdown", "id": "1"});
+        assert_eq!(from_jsonrpc(message.clone(), &mut pending), message);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn gzip_replies_keep_their_encoding() {
+        let mut pending = Pending::default();
+        let big = "x".repeat(1024);
+        let reply = Envelope::res("getDefaultConfig", "3", json!({"big": big}));
+        let line = crate::core::compress_line(&serde_json::to_string(&reply).unwrap()).unwrap();
+        let mut message =
+            translate_outbound(serde_json::from_str(&line).unwrap(), &mut pending).unwrap();
+        assert_eq!(message["encoding"], "gzip");
+        assert!(message["result"].is_string());
+
+        crate::core::decode_payload(&mut message).unwrap();
+        assert_eq!(message["result"]["big"], json!(big));
+        assert!(message.get("encoding").is_none());
+    }
+}
@@ -1,4 +1,31 @@
-// Public surface: three modules.
+// Public surface: nineteen modules; `cache`, `config`, `discovery` and `init` need `linter-host`,
+// `async_io` needs `tokio`, `python` needs `python` and `fuzz` needs `fuzzing`.
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "linter-host")]
+pub mod cache;
+#[cfg(feature = "linter-host")]
 pub mod config;
 pub mod core;
+#[cfg(feature = "linter-host")]
+pub mod discovery;
+pub mod fixer;
+pub mod format;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+#[cfg(feature = "linter-host")]
+pub mod init;
+pub mod jsonrpc;
+pub mod prelude;
+pub mod progress;
+pub mod protocol;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod ruleset;
+pub mod term;
+pub mod trace;
+pub mod transport;
+pub mod types;
+
+// Stable top-level paths; see `prelude`
+pub use prelude::*;
@@ -0,0 +1,16 @@
+//! Stable API surface for ruleset authors: `use forseti_sdk::prelude::*;`
+//!
+//! Items here keep their names across internal reorganizations; prefer these
+//! paths over module-qualified ones.
+
+#[cfg(feature = "linter-host")]
+pub use crate::config::Config;
+pub use crate::core::{
+    Attachment, CancellationToken, ConfigSetting, ConfigType, Diagnostic, Envelope, FileContext,
+    Fix, Kind, LineIndex, Position, PreprocessingContext, Range, Region, RuleExample, RuleInfo,
+    RulesetCapabilities, SuggestFix,
+};
+pub use crate::protocol::{ErrorPayload, Request, Termination};
+pub use crate::ruleset::{Rule, RuleContext, Ruleset, RulesetOptions, SharedPass};
+#[cfg(feature = "ruleset-server")]
+pub use crate::ruleset::{RulesetServer, run_server_main};
@@ -0,0 +1,397 @@
+//! Progress reporting for hosts linting many files.
+//!
+//! Whatever drives a run calls a [`ProgressReporter`] as files finish, so
+//! frontends get the same progress output without wiring channels of their
+//! own. [`BarReporter`] redraws a bar in place on interactive terminals,
+//! [`LogReporter`] writes one plain line per file for logs and CI, and
+//! [`SilentReporter`] prints nothing; [`reporter_for`] picks between them.
+
+use crate::core::{LintResults, uri_to_path};
+use crate::protocol::ProgressEvent;
+use crate::term::{Style, Terminal, truncate, visible_width};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Callbacks for the stages of a run. Implementations may be called from
+/// several threads at once.
+pub trait ProgressReporter: Send + Sync {
+    /// A run over `total_files` files begins
+    fn started(&self, total_files: usize);
+
+    /// The run is expected to take `duration`, as predicted from the timing
+    /// history of earlier runs. Hosts that keep one call this after `started`.
+    fn predicted(&self, _duration: Duration) {}
+
+    /// A file has been linted by every ruleset that handles it
+    fn file_done(&self, uri: &str, diagnostics: usize);
+
+    /// A ruleset sent a `progress` event, e.g. while preprocessing
+    fn ruleset_progress(&self, _ruleset_id: &str, _event: &ProgressEvent) {}
+
+    /// The run is over
+    fn finished(&self, results: &LintResults);
+}
+
+/// Reports nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilentReporter;
+
+impl ProgressReporter for SilentReporter {
+    fn started(&self, _total_files: usize) {}
+
+    fn file_done(&self, _uri: &str, _diagnostics: usize) {}
+
+    fn finished(&self, _results: &LintResults) {}
+}
+
+/// Files done out of the total, shared by the writing reporters
+#[derive(Debug, Default)]
+struct Counts {
+    done: usize,
+    total: usize,
+}
+
+/// One line per event, e.g. `[12/40] src/a.rs: 3 findings`
+pub struct LogReporter {
+    out: Mutex<Box<dyn Write + Send>>,
+    counts: Mutex<Counts>,
+    /// Also log files without findings
+    verbose: bool,
+}
+
+impl LogReporter {
+    /// Log to stderr
+    pub fn new() -> Self {
+        Self::to_writer(Box::new(io::stderr()))
+    }
+
+    pub fn to_writer(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Mutex::new(out),
+            counts: Mutex::new(Counts::default()),
+            verbose: false,
+        }
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    fn line(&self, text: &str) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "{}", text);
+        let _ = out.flush();
+    }
+}
+
+impl Default for LogReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter for LogReporter {
+    fn started(&self, total_files: usize) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        *counts = Counts {
+            done: 0,
+            total: total_files,
+        };
+        drop(counts);
+        self.line(&format!("linting {} files", total_files));
+    }
+
+    fn predicted(&self, duration: Duration) {
+        self.line(&format!("expected to take about {}", eta(duration)));
+    }
+
+    fn file_done(&self, uri: &str, diagnostics: usize) {
+        let (done, total) = {
+            let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+            counts.done += 1;
+            (counts.done, counts.total)
+        };
+        if diagnostics > 0 || self.verbose {
+            self.line(&format!(
+                "[{}/{}] {}: {}",
+                done,
+                total,
+                display_path(uri),
+                plural(diagnostics, "finding")
+            ));
+        }
+    }
+
+    fn ruleset_progress(&self, ruleset_id: &str, event: &ProgressEvent) {
+        if self.verbose {
+            self.line(&format!("{}: {}", ruleset_id, event_text(event)));
+        }
+    }
+
+    fn finished(&self, results: &LintResults) {
+        self.line(&summary(results));
+    }
+}
+
+/// Progress bar redrawn in place, e.g. `[=====>    ] 12/40 src/a.rs`, for
+/// interactive terminals. Redraws at most every `interval`.
+pub struct BarReporter {
+    out: Mutex<Box<dyn Write + Send>>,
+    terminal: Terminal,
+    interval: Duration,
+    state: Mutex<BarState>,
+}
+
+#[derive(Debug, Default)]
+struct BarState {
+    counts: Counts,
+    findings: usize,
+    last_drawn: Option<Instant>,
+    drawn: bool,
+    /// When the prediction was made and the predicted duration
+    predicted: Option<(Instant, Duration)>,
+}
+
+impl BarReporter {
+    /// Draw on stderr, as described by `terminal`
+    pub fn new(terminal: Terminal) -> Self {
+        Self::to_writer(Box::new(io::stderr()), terminal)
+    }
+
+    pub fn to_writer(out: Box<dyn Write + Send>, terminal: Terminal) -> Self {
+        Self {
+            out: Mutex::new(out),
+            terminal,
+            interval: Duration::from_millis(50),
+            state: Mutex::new(BarState::default()),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn draw(&self, state: &mut BarState, label: &str, force: bool) {
+        let now = Instant::now();
+        if !force
+            && state
+                .last_drawn
+                .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return;
+        }
+        state.last_drawn = Some(now);
+        state.drawn = true;
+        let Counts { done, total } = state.counts;
+        let mut count = format!("{}/{}", done, total);
+        if let Some((since, duration)) = state.predicted
+            && done < total
+        {
+            let left = duration.saturating_sub(now.duration_since(since));
+            count.push_str(&format!(" ~{} left", eta(left)));
+        }
+        let findings = match state.findings {
+            0 => String::new(),
+            n => format!(
+                " {}",
+                self.terminal.paint(&plural(n, "finding"), &[Style::Yellow])
+            ),
+        };
+        let width = self.terminal.width();
+        let bar_width = (width / 3).clamp(10, 40);
+        let filled = (done * bar_width)
+            .checked_div(total)
+            .unwrap_or(0)
+            .min(bar_width);
+        let mut bar = "=".repeat(filled);
+        if filled < bar_width {
+            bar.push(if done > 0 { '>' } else { ' ' });
+            bar.push_str(&" ".repeat(bar_width - filled - 1));
+        }
+        let head = format!(
+            "[{}] {}{}",
+            self.terminal.paint(&bar, &[Style::Cyan]),
+            count,
+            findings
+        );
+        let room = width.saturating_sub(visible_width(&head) + 2);
+        let line = match room {
+            _ if label.is_empty() => head,
+            0 => head,
+            _ => format!(
+                "{} {}",
+                head,
+                self.terminal.paint(&truncate(label, room), &[Style::Dim])
+            ),
+        };
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = write!(out, "\r\x1b[2K{}", line);
+        let _ = out.flush();
+    }
+
+    fn clear(&self, state: &mut BarState) {
+        if state.drawn {
+            let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = write!(out, "\r\x1b[2K");
+            let _ = out.flush();
+            state.drawn = false;
+        }
+    }
+}
+
+impl ProgressReporter for BarReporter {
+    fn started(&self, total_files: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = BarState::default();
+        state.counts.total = total_files;
+        self.draw(&mut state, "", true);
+    }
+
+    fn predicted(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.predicted = Some((Instant::now(), duration));
+        self.draw(&mut state, "", true);
+    }
+
+    fn file_done(&self, uri: &str, diagnostics: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.counts.done += 1;
+        state.findings += diagnostics;
+        let last = state.counts.done == state.counts.total;
+        self.draw(&mut state, &display_path(uri), last);
+    }
+
+    fn ruleset_progress(&self, ruleset_id: &str, event: &ProgressEvent) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.draw(
+            &mut state,
+            &format!("{}: {}", ruleset_id, event_text(event)),
+            false,
+        );
+    }
+
+    fn finished(&self, results: &LintResults) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.clear(&mut state);
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "{}", summary(results));
+        let _ = out.flush();
+    }
+}
+
+/// Reporter suited to `terminal`: nothing when `quiet`, a bar when it is
+/// interactive, plain lines otherwise
+pub fn reporter_for(terminal: &Terminal, quiet: bool) -> Box<dyn ProgressReporter> {
+    if quiet {
+        Box::new(SilentReporter)
+    } else if terminal.is_interactive() {
+        Box::new(BarReporter::new(terminal.clone()))
+    } else {
+        Box::new(LogReporter::new())
+    }
+}
+
+fn display_path(uri: &str) -> String {
+    uri_to_path(uri).display().to_string()
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// `duration` in whole seconds, rounded up, as `42s` or `3m05s`
+fn eta(duration: Duration) -> String {
+    let secs = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+    match secs {
+        0..60 => format!("{}s", secs),
+        _ => format!("{}m{:02}s", secs / 60, secs % 60),
+    }
+}
+
+fn event_text(event: &ProgressEvent) -> String {
+    let mut text = event.title.clone();
+    match event.total {
+        Some(total) => text.push_str(&format!(" {}/{}", event.done, total)),
+        None => text.push_str(&format!(" {}", event.done)),
+    }
+    if let Some(message) = &event.message {
+        text.push_str(&format!(" {}", message));
+    }
+    text
+}
+
+fn summary(results: &LintResults) -> String {
+    let s = &results.summary;
+    format!(
+        "linted {} in {} ms: {}, {}, {} info, {}",
+        plural(results.total_files, "file"),
+        results.execution_time_ms,
+        plural(s.errors, "error"),
+        plural(s.warnings, "warning"),
+        s.info,
+        plural(s.hints, "hint")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer whose output the test can read back
+    #[derive(Clone, Default)]
+    struct Output(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn predictions_are_logged_and_counted_down_on_the_bar() {
+        let out = Output::default();
+        let log = LogReporter::to_writer(Box::new(out.clone()));
+        log.started(3);
+        log.predicted(Duration::from_millis(61_500));
+        assert_eq!(
+            out.text(),
+            "linting 3 files\nexpected to take about 1m02s\n"
+        );
+
+        let out = Output::default();
+        let bar = BarReporter::to_writer(Box::new(out.clone()), Terminal::plain());
+        bar.started(3);
+        bar.predicted(Duration::from_secs(30));
+        assert!(out.text().ends_with("0/3 ~30s left"), "{:?}", out.text());
+    }
+
+    #[test]
+    fn etas_round_up_to_whole_seconds() {
+        assert_eq!(eta(Duration::ZERO), "0s");
+        assert_eq!(eta(Duration::from_millis(1)), "1s");
+        assert_eq!(eta(Duration::from_secs(59)), "59s");
+        assert_eq!(eta(Duration::from_secs(185)), "3m05s");
+    }
+
+    #[test]
+    fn paths_are_shown_decoded() {
+        assert_eq!(
+            display_path("file:///work/my%20repo/a.rs"),
+            "/work/my repo/a.rs"
+        );
+    }
+}
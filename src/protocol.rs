@@ -0,0 +1,624 @@
+//! Typed view of the NDJSON protocol.
+//!
+//! Each request `type` maps to a [`Request`] variant carrying its params, so
+//! servers and hosts can skip hand-rolled `json!` payloads. `Envelope<Value>`
+//! remains the wire format and the escape hatch for anything not modelled here.
+
+use crate::core::{
+    ContentEncoding, Diagnostic, Envelope, EnvelopeError, LogEvent, LogLevel, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION, Region, RuleProfile,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    /// Per-rule configuration; the ruleset defaults apply when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ruleset_config: Option<HashMap<String, Value>>,
+    /// Ruleset-level settings overlaid on the declared defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ruleset_settings: Option<Map<String, Value>>,
+    /// Run-scoped directory where rules may write attachment files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifacts_dir: Option<String>,
+    /// Versions the host can speak; hosts predating negotiation omit it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_versions: Option<VersionRange>,
+    /// Optional features the host supports, e.g. [`crate::core::features::GZIP`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Shared secret for servers started with `RulesetServer::with_auth_token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    /// Least severe `log` event the server should send; `info` when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LogLevel>,
+    /// Send a `metrics` event at most this often; none are sent when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_interval_ms: Option<u64>,
+}
+
+/// Inclusive range of protocol versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionRange {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl VersionRange {
+    /// Versions supported by this SDK
+    pub fn supported() -> Self {
+        Self {
+            min: MIN_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }
+    }
+}
+
+/// No protocol version is supported by both sides
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[error(
+    "no common protocol version: requested {}..={}, supported {}..={}",
+    requested.min, requested.max, supported.min, supported.max
+)]
+pub struct VersionMismatch {
+    pub requested: VersionRange,
+    pub supported: VersionRange,
+}
+
+/// Pick the highest version in both ranges; a host without a range is
+/// assumed to speak the current version only
+pub fn negotiate_version(requested: Option<VersionRange>) -> Result<u8, VersionMismatch> {
+    let supported = VersionRange::supported();
+    let requested = requested.unwrap_or(VersionRange {
+        min: PROTOCOL_VERSION,
+        max: PROTOCOL_VERSION,
+    });
+    let low = requested.min.max(supported.min);
+    let high = requested.max.min(supported.max);
+    if low > high {
+        return Err(VersionMismatch {
+            requested,
+            supported,
+        });
+    }
+    Ok(high)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeFileParams {
+    #[serde(default)]
+    pub uri: String,
+    #[serde(default)]
+    pub content: String,
+    /// How `content` is encoded; absent means a plain UTF-8 string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ContentEncoding>,
+    /// Echoed back in the diagnostics event to detect stale results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Embedded regions of a composite file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<Region>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PreprocessFilesParams {
+    #[serde(default)]
+    pub file_uris: Vec<String>,
+}
+
+/// Session-scoped rule toggles layered over the `initialize` config, e.g. for
+/// an editor's "disable rule X in this workspace"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SetRulesParams {
+    /// Rule id => enabled; enabling a rule absent from the config uses its default
+    #[serde(default)]
+    pub rules: HashMap<String, bool>,
+    /// Turn the whole ruleset off or back on for the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Drop every earlier toggle before applying these
+    #[serde(default)]
+    pub reset: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GetRuleDocParams {
+    #[serde(default)]
+    pub rule_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CancelParams {
+    /// Id of the in-flight request to abort
+    #[serde(default)]
+    pub id: String,
+}
+
+/// Payload of the `diagnostics` event emitted after `analyzeFile`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsEvent {
+    pub uri: String,
+    pub diagnostics: Vec<Diagnostic>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+/// Payload of the `metrics` event. Values are cumulative since `initialize`,
+/// so a host only needs the latest event from each server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsEvent {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gauges: BTreeMap<String, f64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rule_timings: RuleProfile,
+}
+
+/// Payload of the `fatal` event a server sends from its panic hook, as the
+/// last line before the process exits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct FatalEvent {
+    /// The panic message
+    pub message: String,
+    /// `file:line:column` of the panic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    /// Captured when `RUST_BACKTRACE` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+/// Payload of the `progress` event sent while a long request runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    /// Id of the request being reported on
+    pub id: String,
+    pub title: String,
+    pub done: u64,
+    /// Total units of work, when known up front
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ProgressEvent {
+    pub fn new(id: impl Into<String>, title: impl Into<String>, done: u64) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            done,
+            total: None,
+            message: None,
+        }
+    }
+
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// Machine-readable codes carried in the `error` field of an [`ErrorPayload`]
+pub mod error_codes {
+    /// Line was not valid JSON or not a valid envelope
+    pub const PARSE_ERROR: &str = "parse_error";
+    /// Request `type` is not understood by this server
+    pub const UNKNOWN_TYPE: &str = "unknown_type";
+    /// Request payload does not match the expected params
+    pub const INVALID_PAYLOAD: &str = "invalid_payload";
+    pub const NOT_INITIALIZED: &str = "not_initialized";
+    /// Missing or wrong `authToken` on a server that requires one
+    pub const UNAUTHORIZED: &str = "unauthorized";
+    /// Request arrived between `shutdown` and `exit`
+    pub const SHUTTING_DOWN: &str = "shutting_down";
+    pub const INVALID_SETTINGS: &str = "invalid_settings";
+    pub const UNKNOWN_RULE: &str = "unknown_rule";
+    pub const UNSUPPORTED_PROTOCOL_VERSION: &str = "unsupported_protocol_version";
+    /// Request was aborted by a `cancel` message
+    pub const CANCELLED: &str = "cancelled";
+    /// Request ran past the `timeoutMs` it was sent with
+    pub const DEADLINE_EXCEEDED: &str = "deadline_exceeded";
+    /// Handler failed while processing a well-formed request
+    pub const INTERNAL: &str = "internal";
+}
+
+/// Failure reply: `{"ok": false, "error": <code>, "message": ..., "details": ...}`.
+/// Sent as the response to the failing request, or as an `error` event when
+/// the request carried no id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ErrorPayload {
+    /// Always `false`, mirroring the `ok` flag of successful responses
+    #[serde(default)]
+    pub ok: bool,
+    #[serde(rename = "error")]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ErrorPayload {
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            code: code.to_string(),
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+}
+
+impl From<&ProtocolError> for ErrorPayload {
+    fn from(err: &ProtocolError) -> Self {
+        let code = match err {
+            ProtocolError::UnknownType(_) => error_codes::UNKNOWN_TYPE,
+            ProtocolError::InvalidPayload { .. } => error_codes::INVALID_PAYLOAD,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+/// Why a server's read loop stopped; also sent as the final `terminated` event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum Termination {
+    /// Input stream closed
+    Eof,
+    /// `exit` received after a default-session `shutdown`
+    Shutdown,
+    /// `exit` received without a preceding `shutdown`
+    Exit,
+    /// Too many consecutive unparseable lines
+    ParseFailures { count: usize },
+    /// Read interrupted by a signal
+    Signal,
+    /// Output stream closed; nothing more can be delivered
+    OutputClosed,
+}
+
+impl Termination {
+    /// Process exit status for a server stopped this way (see [`ServerExit`])
+    pub fn exit_code(&self) -> i32 {
+        ServerExit::from(self).code()
+    }
+}
+
+/// Exit status convention for engine and ruleset binaries, so a host can say
+/// why a server process ended from its status alone. Codes follow
+/// `sysexits.h` where one fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerExit {
+    /// `shutdown` then `exit`, or the host closed the input
+    Clean,
+    /// `exit` without a preceding `shutdown`, as in LSP
+    ExitWithoutShutdown,
+    /// Failed reading or writing its streams
+    Io,
+    /// Too many unparseable messages; the host is not speaking the protocol
+    Protocol,
+    /// Configuration given on the command line or in files was invalid
+    Config,
+    /// Stopped by a signal it handled
+    Interrupted,
+    /// A panic, reported by the panic hook or Rust's default handler
+    Panic,
+}
+
+impl ServerExit {
+    pub const ALL: [ServerExit; 7] = [
+        ServerExit::Clean,
+        ServerExit::ExitWithoutShutdown,
+        ServerExit::Io,
+        ServerExit::Protocol,
+        ServerExit::Config,
+        ServerExit::Interrupted,
+        ServerExit::Panic,
+    ];
+
+    pub const fn code(self) -> i32 {
+        match self {
+            ServerExit::Clean => 0,
+            ServerExit::ExitWithoutShutdown => 1,
+            ServerExit::Io => 74,
+            ServerExit::Protocol => 76,
+            ServerExit::Config => 78,
+            ServerExit::Panic => 101,
+            ServerExit::Interrupted => 130,
+        }
+    }
+
+    /// The convention's meaning of `code`, if it has one
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|exit| exit.code() == code)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ServerExit::Clean => "exited cleanly",
+            ServerExit::ExitWithoutShutdown => "exited without a shutdown request",
+            ServerExit::Io => "failed reading or writing its streams",
+            ServerExit::Protocol => "stopped after unparseable protocol messages",
+            ServerExit::Config => "rejected its configuration",
+            ServerExit::Interrupted => "was interrupted by a signal",
+            ServerExit::Panic => "panicked",
+        }
+    }
+}
+
+impl From<&Termination> for ServerExit {
+    fn from(termination: &Termination) -> Self {
+        match termination {
+            Termination::Eof | Termination::Shutdown => ServerExit::Clean,
+            Termination::Exit => ServerExit::ExitWithoutShutdown,
+            Termination::ParseFailures { .. } => ServerExit::Protocol,
+            Termination::Signal => ServerExit::Interrupted,
+            Termination::OutputClosed => ServerExit::Io,
+        }
+    }
+}
+
+impl From<ServerExit> for std::process::ExitCode {
+    fn from(exit: ServerExit) -> Self {
+        std::process::ExitCode::from(exit.code() as u8)
+    }
+}
+
+impl std::fmt::Display for ServerExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (exit code {})", self.description(), self.code())
+    }
+}
+
+/// Every request a ruleset server understands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", content = "payload", rename_all = "camelCase")]
+pub enum Request {
+    Initialize(InitializeParams),
+    Shutdown,
+    /// Stop the process; sent after `shutdown`, as a request or an event
+    Exit,
+    GetDefaultConfig,
+    GetCapabilities,
+    GetRuleDoc(GetRuleDocParams),
+    PreprocessFiles(PreprocessFilesParams),
+    AnalyzeFile(AnalyzeFileParams),
+    Cancel(CancelParams),
+    SetRules(SetRulesParams),
+}
+
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error("unknown message type: {0}")]
+    UnknownType(String),
+    #[error("invalid payload for {typ}: {source}")]
+    InvalidPayload {
+        typ: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl Request {
+    /// Wire name used in the envelope `type` field
+    pub fn method(&self) -> &'static str {
+        match self {
+            Request::Initialize(_) => "initialize",
+            Request::Shutdown => "shutdown",
+            Request::Exit => "exit",
+            Request::GetDefaultConfig => "getDefaultConfig",
+            Request::GetCapabilities => "getCapabilities",
+            Request::GetRuleDoc(_) => "getRuleDoc",
+            Request::PreprocessFiles(_) => "preprocessFiles",
+            Request::AnalyzeFile(_) => "analyzeFile",
+            Request::Cancel(_) => "cancel",
+            Request::SetRules(_) => "setRules",
+        }
+    }
+
+    /// Decode the request carried by an envelope; a missing payload reads as `{}`
+    pub fn from_envelope(envelope: &Envelope<Value>) -> Result<Self, ProtocolError> {
+        let typ = envelope.typ.as_str();
+        let payload = envelope
+            .payload
+            .clone()
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        let invalid = |source| ProtocolError::InvalidPayload {
+            typ: typ.to_string(),
+            source,
+        };
+        Ok(match typ {
+            "initialize" => Request::Initialize(serde_json::from_value(payload).map_err(invalid)?),
+            "shutdown" => Request::Shutdown,
+            "exit" => Request::Exit,
+            "getDefaultConfig" => Request::GetDefaultConfig,
+            "getCapabilities" => Request::GetCapabilities,
+            "getRuleDoc" => Request::GetRuleDoc(serde_json::from_value(payload).map_err(invalid)?),
+            "preprocessFiles" => {
+                Request::PreprocessFiles(serde_json::from_value(payload).map_err(invalid)?)
+            }
+            "analyzeFile" => {
+                Request::AnalyzeFile(serde_json::from_value(payload).map_err(invalid)?)
+            }
+            "cancel" => Request::Cancel(serde_json::from_value(payload).map_err(invalid)?),
+            "setRules" => Request::SetRules(serde_json::from_value(payload).map_err(invalid)?),
+            other => return Err(ProtocolError::UnknownType(other.to_string())),
+        })
+    }
+
+    /// Encode as a request envelope with the given correlation id
+    pub fn into_envelope(self, id: impl Into<String>) -> Envelope<Value> {
+        let typ = self.method();
+        let payload = match self {
+            Request::Initialize(p) => serde_json::to_value(p),
+            Request::GetRuleDoc(p) => serde_json::to_value(p),
+            Request::PreprocessFiles(p) => serde_json::to_value(p),
+            Request::AnalyzeFile(p) => serde_json::to_value(p),
+            Request::Cancel(p) => serde_json::to_value(p),
+            Request::SetRules(p) => serde_json::to_value(p),
+            Request::Shutdown
+            | Request::Exit
+            | Request::GetDefaultConfig
+            | Request::GetCapabilities => Ok(Value::Object(Map::new())),
+        };
+        Envelope::req(typ, id, payload.unwrap_or(Value::Null))
+    }
+}
+
+/// Event types servers send to hosts
+pub const EVENT_TYPES: &[&str] = &[
+    "diagnostics",
+    "progress",
+    "log",
+    "metrics",
+    "error",
+    "terminated",
+    "fatal",
+];
+
+/// Check that a server-sent event has a known type and a payload of the
+/// documented shape
+pub fn check_event(envelope: &Envelope<Value>) -> Result<(), ProtocolError> {
+    let typ = envelope.typ.as_str();
+    let payload = envelope.payload.clone().unwrap_or(Value::Null);
+    let parsed = match typ {
+        "diagnostics" => serde_json::from_value::<DiagnosticsEvent>(payload).map(drop),
+        "progress" => serde_json::from_value::<ProgressEvent>(payload).map(drop),
+        "log" => serde_json::from_value::<LogEvent>(payload).map(drop),
+        "metrics" => serde_json::from_value::<MetricsEvent>(payload).map(drop),
+        "error" => serde_json::from_value::<ErrorPayload>(payload).map(drop),
+        "terminated" => serde_json::from_value::<Termination>(payload).map(drop),
+        "fatal" => serde_json::from_value::<FatalEvent>(payload).map(drop),
+        _ => return Err(ProtocolError::UnknownType(typ.to_string())),
+    };
+    parsed.map_err(|source| ProtocolError::InvalidPayload {
+        typ: typ.to_string(),
+        source,
+    })
+}
+
+/// Check that a failure reply (`"ok": false`) is a well-formed [`ErrorPayload`]
+pub fn check_reply(envelope: &Envelope<Value>) -> Result<(), ProtocolError> {
+    match &envelope.payload {
+        Some(payload) if payload.get("ok") == Some(&Value::Bool(false)) => {
+            serde_json::from_value::<ErrorPayload>(payload.clone())
+                .map(drop)
+                .map_err(|source| ProtocolError::InvalidPayload {
+                    typ: envelope.typ.clone(),
+                    source,
+                })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Irregularity a strict host refuses to tolerate; see
+/// [`crate::transport::Correlator::with_strict`]
+#[derive(Debug, Error)]
+pub enum ProtocolViolation {
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+    #[error(transparent)]
+    Payload(#[from] ProtocolError),
+    #[error("reply to unknown request id {0}")]
+    UnknownReply(String),
+    #[error("reply to {id} has type {actual}, expected {expected}")]
+    ReplyType {
+        id: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{typ} event for request {id} arrived after its reply")]
+    LateEvent { typ: String, id: String },
+    #[error("server sent a request: {0}")]
+    UnexpectedRequest(String),
+}
+
+/// Versioned JSON Schema (draft 2020-12) bundle of the wire protocol, for
+/// validating messages from engines written in other languages. `messages`
+/// maps each top-level message shape to its definition under `$defs`.
+#[cfg(feature = "schema")]
+pub fn export_schema() -> Value {
+    use crate::core::{InitializeResult, PreprocessingContext, RulesetCapabilities};
+
+    let mut generator = schemars::generate::SchemaSettings::draft2020_12().into_generator();
+    let mut messages = Map::new();
+    let mut add = |name: &str, schema: schemars::Schema| {
+        messages.insert(name.to_string(), schema.to_value());
+    };
+    add("envelope", generator.subschema_for::<Envelope<Value>>());
+    add("request", generator.subschema_for::<Request>());
+    add(
+        "initializeResult",
+        generator.subschema_for::<InitializeResult>(),
+    );
+    add(
+        "capabilities",
+        generator.subschema_for::<RulesetCapabilities>(),
+    );
+    add(
+        "preprocessingContext",
+        generator.subschema_for::<PreprocessingContext>(),
+    );
+    add(
+        "diagnosticsEvent",
+        generator.subschema_for::<DiagnosticsEvent>(),
+    );
+    add("progressEvent", generator.subschema_for::<ProgressEvent>());
+    add("metricsEvent", generator.subschema_for::<MetricsEvent>());
+    add("error", generator.subschema_for::<ErrorPayload>());
+    add("terminated", generator.subschema_for::<Termination>());
+    add("fatalEvent", generator.subschema_for::<FatalEvent>());
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "forseti wire protocol",
+        "protocolVersion": PROTOCOL_VERSION,
+        "minProtocolVersion": MIN_PROTOCOL_VERSION,
+        "messages": messages,
+        "$defs": generator.take_definitions(true),
+    })
+}
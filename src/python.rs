@@ -0,0 +1,177 @@
+//! Rules written in Python, hosted by a Rust ruleset (feature `python`).
+//!
+//! A Python rule is any object with `id` and `description` string attributes
+//! and a `check(text, uri, options)` method returning a list of diagnostic
+//! dicts in wire format. `rule_id` and `severity` may be omitted; they default
+//! to the rule's id and `"warn"`.
+//!
+//! ```python
+//! class NoTodo:
+//!     id = "no-todo"
+//!     description = "Disallow TODO comments"
+//!
+//!     def check(self, text, uri, options):
+//!         return [
+//!             {"message": "TODO found", "range": {"start": {"line": i, "character": 0},
+//!                                                 "end": {"line": i, "character": len(l)}}}
+//!             for i, l in enumerate(text.splitlines()) if "TODO" in l
+//!         ]
+//!
+//! RULES = [NoTodo()]
+//! ```
+
+use crate::core::{Diagnostic, Position, Range, Severity};
+use crate::ruleset::{Rule, RuleContext, Ruleset};
+use pyo3::prelude::*;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
+/// A Python object adapted to the [`Rule`] trait
+pub struct PyRule {
+    object: Py<PyAny>,
+    // Rule ids are `&'static str`; interned, since servers reload rules per request
+    id: &'static str,
+    description: &'static str,
+}
+
+impl PyRule {
+    /// Wrap a Python rule object, reading its `id` and `description` once
+    pub fn new(object: Py<PyAny>) -> PyResult<Self> {
+        Python::attach(|py| {
+            let bound = object.bind(py);
+            let id: String = bound.getattr("id")?.extract()?;
+            let description: String = bound.getattr("description")?.extract()?;
+            Ok(Self {
+                id: intern(id),
+                description: intern(description),
+                object,
+            })
+        })
+    }
+
+    fn run(&self, py: Python<'_>, ctx: &RuleContext) -> PyResult<Vec<Diagnostic>> {
+        let json = py.import("json")?;
+        let options = json.call_method1("loads", (ctx.options.to_string(),))?;
+        let found = self
+            .object
+            .bind(py)
+            .call_method1("check", (ctx.text, ctx.uri, options))?;
+        let encoded: String = json.call_method1("dumps", (found,))?.extract()?;
+        let items: Vec<Value> = serde_json::from_str(&encoded)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        items
+            .into_iter()
+            .map(|mut item| {
+                if let Some(fields) = item.as_object_mut() {
+                    fields
+                        .entry("rule_id")
+                        .or_insert_with(|| Value::String(self.id.to_string()));
+                    fields
+                        .entry("severity")
+                        .or_insert_with(|| Value::String("warn".to_string()));
+                }
+                serde_json::from_value(item)
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Rule for PyRule {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn check(&self, ctx: &mut RuleContext) {
+        match Python::attach(|py| self.run(py, ctx)) {
+            Ok(diagnostics) => diagnostics.into_iter().for_each(|d| ctx.report(d)),
+            // Surface the traceback instead of silently reporting nothing
+            Err(err) => {
+                let start = Position {
+                    line: 0,
+                    character: 0,
+                };
+                ctx.report(Diagnostic::new(
+                    self.id,
+                    format!("Python rule failed: {err}"),
+                    Severity::Error,
+                    Range { start, end: start },
+                ));
+            }
+        }
+    }
+}
+
+/// `text` with a `'static` lifetime. Each distinct string is leaked once per
+/// process, however often rules carrying it are loaded.
+fn intern(text: String) -> &'static str {
+    static INTERNED: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut interned = INTERNED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = interned.get(text.as_str()) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(text.into_boxed_str());
+    interned.insert(leaked);
+    leaked
+}
+
+/// Import `module` from `sys.path` and wrap every object in its `RULES` list
+pub fn load_rules(module: &str) -> PyResult<Vec<PyRule>> {
+    let objects: Vec<Py<PyAny>> = Python::attach(|py| {
+        py.import(module)?
+            .getattr("RULES")?
+            .try_iter()?
+            .map(|object| object.map(Bound::unbind))
+            .collect::<PyResult<_>>()
+    })?;
+    objects.into_iter().map(PyRule::new).collect()
+}
+
+/// Ruleset `id` made of the rules in the Python `module`
+pub fn load_ruleset(id: impl Into<String>, module: &str) -> PyResult<Ruleset> {
+    Ok(load_rules(module)?
+        .into_iter()
+        .fold(Ruleset::new(id), |ruleset, rule| {
+            ruleset.with_rule(Box::new(rule))
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_strings_are_shared() {
+        let first = intern("no-todo".to_string());
+        let second = intern(String::from("no-todo"));
+        assert!(std::ptr::eq(first, second));
+        assert_eq!(intern("other".to_string()), "other");
+    }
+
+    #[test]
+    fn reloading_rules_reuses_their_ids() {
+        let object = Python::attach(|py| {
+            let module = PyModule::from_code(
+                py,
+                c"class NoTodo:\n    id = 'py-no-todo'\n    description = 'Disallow TODO'\n",
+                c"rules.py",
+                c"rules",
+            )?;
+            Ok::<_, PyErr>(module.getattr("NoTodo")?.call0()?.unbind())
+        })
+        .unwrap();
+        let first = PyRule::new(Python::attach(|py| object.clone_ref(py))).unwrap();
+        let second = PyRule::new(object).unwrap();
+        assert_eq!(first.id(), "py-no-todo");
+        assert!(std::ptr::eq(first.id(), second.id()));
+        assert!(std::ptr::eq(first.description(), second.description()));
+    }
+}
@@ -1,18 +1,137 @@
-use crate::core::{Annotation, AnnotationParser, Diagnostic, PreprocessingContext, RuleInfo, RulesetInfo, RulesetCapabilities, Envelope};
+use crate::core::{
+    Annotation, AnnotationParser, CancellationToken, ConfigSetting, Diagnostic, LineIndex,
+    PreprocessingContext, Region, RuleExample, RuleInfo, RuleProfile, RulesetCapabilities,
+    RulesetInfo, uri_to_path,
+};
+#[cfg(feature = "linter-host")]
 use crate::core::{RulesetCfg, SharedConfig};
-use serde_json::{Value, json};
-use std::collections::HashMap;
+use crate::protocol::ProgressEvent;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[cfg(feature = "ruleset-server")]
+mod cli;
+#[cfg(feature = "ruleset-server")]
+mod server;
+#[cfg(feature = "ruleset-server")]
+pub use cli::{
+    ArgsError, Framing, Listen, ServeArgs, ServerCommand, USAGE, parse_args, run_server_main,
+};
+#[cfg(feature = "ruleset-server")]
+pub use server::{
+    MAX_PARSE_FAILURES, PANIC_EXIT_CODE, RulesetServer, StartupError, exit_status,
+    install_panic_hook,
+};
 
+/// What a rule sees of the file it checks. Build one with [`RuleContext::new`]
+/// and the `with_*` setters; fields added later default the same way.
 pub struct RuleContext<'a> {
     pub uri: &'a str,
     pub text: &'a str,
     pub options: &'a Value,
+    /// Ruleset-level settings shared by every rule (e.g. `tab_width`)
+    pub settings: &'a Value,
     pub diagnostics: Vec<Diagnostic>,
     pub annotations: &'a [Annotation],
     pub annotation_parser: Option<&'a AnnotationParser>,
+    /// Shared passes registered on the ruleset, computed on first request
+    pub passes: Option<&'a PassCache<'a>>,
+    /// Set when the host cancels the request this context belongs to
+    pub cancellation: Option<&'a CancellationToken>,
+    /// Run-scoped directory for auxiliary artifacts, when the host provided one
+    pub artifacts_dir: Option<&'a std::path::Path>,
+    /// Original bytes when the file was sent base64-encoded; `text` is then a
+    /// lossy UTF-8 decoding of them
+    pub raw_bytes: Option<&'a [u8]>,
+    /// Sends `progress` events for the request being handled, when served
+    pub progress: Option<&'a dyn Fn(ProgressEvent)>,
 }
+/// Settings of a context built without any
+static NO_SETTINGS: Value = Value::Null;
+
 impl<'a> RuleContext<'a> {
+    /// Context for checking `text` of the file at `uri` with the rule's
+    /// `options`: no settings, annotations, passes, cancellation, artifacts
+    /// directory, raw bytes or progress sink
+    pub fn new(uri: &'a str, text: &'a str, options: &'a Value) -> Self {
+        Self {
+            uri,
+            text,
+            options,
+            settings: &NO_SETTINGS,
+            diagnostics: Vec::new(),
+            annotations: &[],
+            annotation_parser: None,
+            passes: None,
+            cancellation: None,
+            artifacts_dir: None,
+            raw_bytes: None,
+            progress: None,
+        }
+    }
+
+    pub fn with_settings(mut self, settings: &'a Value) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Drop diagnostics that `annotations`, parsed by `parser`, suppress
+    pub fn with_annotations(
+        mut self,
+        annotations: &'a [Annotation],
+        parser: Option<&'a AnnotationParser>,
+    ) -> Self {
+        self.annotations = annotations;
+        self.annotation_parser = parser;
+        self
+    }
+
+    pub fn with_passes(mut self, passes: &'a PassCache<'a>) -> Self {
+        self.passes = Some(passes);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: Option<&'a CancellationToken>) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn with_artifacts_dir(mut self, artifacts_dir: Option<&'a std::path::Path>) -> Self {
+        self.artifacts_dir = artifacts_dir;
+        self
+    }
+
+    pub fn with_raw_bytes(mut self, raw_bytes: Option<&'a [u8]>) -> Self {
+        self.raw_bytes = raw_bytes;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Option<&'a dyn Fn(ProgressEvent)>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Report intermediate progress of a long check, e.g. `done` of `total`
+    /// files indexed. The server fills in the request id; outside a server
+    /// this does nothing.
+    pub fn report_progress(&self, title: &str, done: u64, total: Option<u64>) {
+        if let Some(progress) = self.progress {
+            let event = ProgressEvent::new("", title, done);
+            progress(match total {
+                Some(total) => event.with_total(total),
+                None => event,
+            });
+        }
+    }
+
+    /// File content as bytes, exact even for files that are not valid UTF-8
+    pub fn bytes(&self) -> &[u8] {
+        self.raw_bytes.unwrap_or(self.text.as_bytes())
+    }
+
     pub fn report(&mut self, d: Diagnostic) {
         // Check if this diagnostic should be ignored based on annotations
         if let Some(parser) = self.annotation_parser {
@@ -24,6 +143,50 @@ impl<'a> RuleContext<'a> {
         self.diagnostics.push(d);
     }
 
+    /// Consume the context, returning its diagnostics tagged with the file uri
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        let uri = self.uri;
+        let mut diagnostics = self.diagnostics;
+        for d in &mut diagnostics {
+            d.uri.get_or_insert_with(|| uri.to_string());
+        }
+        diagnostics
+    }
+
+    /// Write an artifact under the run's artifacts directory and return an attachment
+    /// referencing it. None when the host gave no directory, `name` would escape it,
+    /// or the write failed.
+    pub fn write_artifact(
+        &self,
+        name: &str,
+        mime_type: &str,
+        contents: &[u8],
+    ) -> Option<crate::core::Attachment> {
+        use std::path::{Component, Path};
+        let dir = self.artifacts_dir?;
+        let relative = Path::new(name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        let path = dir.join(relative);
+        std::fs::create_dir_all(path.parent()?).ok()?;
+        std::fs::write(&path, contents).ok()?;
+        Some(crate::core::Attachment::new(name, mime_type))
+    }
+
+    /// Whether the request was cancelled; long-running rules should stop early
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_some_and(|token| token.is_cancelled())
+    }
+
+    /// Result of the shared pass `id`, computing it for this file if no rule has yet
+    pub fn shared_pass(&self, id: &str) -> Option<&Value> {
+        self.passes.and_then(|passes| passes.get(id))
+    }
+
     /// Check if a specific rule should be ignored for a given line
     pub fn should_ignore_rule(&self, rule_id: &str, line: u32) -> bool {
         if let Some(parser) = self.annotation_parser {
@@ -39,6 +202,26 @@ pub trait Rule: Send + Sync {
     fn description(&self) -> &'static str;
     fn check(&self, ctx: &mut RuleContext);
 
+    /// Categories this rule belongs to (e.g. "security", "style")
+    fn categories(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether this rule attaches automatic fixes to its diagnostics
+    fn fixable(&self) -> bool {
+        false
+    }
+
+    /// Link to the rule's documentation
+    fn docs_url(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Before/after examples shown by editors and reports
+    fn examples(&self) -> Vec<RuleExample> {
+        vec![]
+    }
+
     /// Default configuration for this rule (severity and options)
     fn default_config(&self) -> serde_json::Value {
         serde_json::Value::String("warn".to_string())
@@ -53,6 +236,17 @@ pub trait RulesetOptions: Send + Sync {
     /// Preprocess files and return context for rules
     fn preprocess_files(&self, file_uris: &[String]) -> Result<PreprocessingContext>;
 
+    /// Preprocess files, stopping early once `cancellation` is set.
+    /// The default ignores the token and runs `preprocess_files` to completion.
+    fn preprocess_files_cancellable(
+        &self,
+        file_uris: &[String],
+        cancellation: &CancellationToken,
+    ) -> Result<PreprocessingContext> {
+        let _ = cancellation;
+        self.preprocess_files(file_uris)
+    }
+
     /// Create the ruleset with all its rules
     fn create_ruleset(&self) -> Ruleset;
 
@@ -70,44 +264,95 @@ pub trait RulesetOptions: Send + Sync {
     }
 }
 
+/// Expensive analysis (e.g. building a link graph) shared by several rules
+pub trait SharedPass: Send + Sync {
+    fn id(&self) -> &'static str;
+    fn compute(&self, uri: &str, text: &str) -> Value;
+}
+
+/// Per-file cache of shared pass results; each pass runs at most once, and only
+/// when a rule asks for it
+pub struct PassCache<'a> {
+    passes: &'a [Box<dyn SharedPass>],
+    uri: &'a str,
+    text: &'a str,
+    results: Vec<std::cell::OnceCell<Value>>,
+}
+
+impl<'a> PassCache<'a> {
+    pub fn new(passes: &'a [Box<dyn SharedPass>], uri: &'a str, text: &'a str) -> Self {
+        Self {
+            passes,
+            uri,
+            text,
+            results: passes.iter().map(|_| std::cell::OnceCell::new()).collect(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        let index = self.passes.iter().position(|p| p.id() == id)?;
+        Some(self.results[index].get_or_init(|| self.passes[index].compute(self.uri, self.text)))
+    }
+}
+
 pub struct Ruleset {
     pub id: String,
     pub rules: Vec<Box<dyn Rule>>,
+    pub passes: Vec<Box<dyn SharedPass>>,
 }
 impl Ruleset {
     pub fn new(id: impl Into<String>) -> Self {
         Self {
             id: id.into(),
             rules: vec![],
+            passes: vec![],
         }
     }
     pub fn with_rule(mut self, rule: Box<dyn Rule>) -> Self {
         self.rules.push(rule);
         self
     }
+    pub fn with_pass(mut self, pass: Box<dyn SharedPass>) -> Self {
+        self.passes.push(pass);
+        self
+    }
 
     /// Generate information about this ruleset and its rules
     pub fn info(&self) -> RulesetInfo {
         RulesetInfo {
             id: self.id.clone(),
-            rules: self.rules.iter().map(|rule| RuleInfo {
-                id: rule.id().to_string(),
-                description: rule.description().to_string(),
-            }).collect(),
+            rules: self
+                .rules
+                .iter()
+                .map(|rule| rule_info(rule.as_ref()))
+                .collect(),
         }
     }
 }
 
+/// Describe a rule for capabilities and ruleset info
+pub fn rule_info(rule: &dyn Rule) -> RuleInfo {
+    RuleInfo {
+        id: rule.id().to_string(),
+        description: rule.description().to_string(),
+        categories: rule.categories().iter().map(|c| c.to_string()).collect(),
+        fixable: rule.fixable(),
+        docs_url: rule.docs_url().map(|url| url.to_string()),
+        examples: rule.examples(),
+    }
+}
+
 pub fn run_ruleset(
     uri: &str,
     text: &str,
     rs: &Ruleset,
     options: &std::collections::HashMap<String, Value>,
 ) -> Vec<Diagnostic> {
-    run_ruleset_with_annotations(uri, text, rs, options, &[], None)
+    run_ruleset_with_settings(uri, text, rs, options, &[], None, &Value::Null)
 }
 
 /// Run ruleset with annotation support
+#[deprecated(note = "use run_ruleset_with_settings, which also passes ruleset settings")]
 pub fn run_ruleset_with_annotations(
     uri: &str,
     text: &str,
@@ -115,25 +360,199 @@ pub fn run_ruleset_with_annotations(
     options: &std::collections::HashMap<String, Value>,
     annotations: &[Annotation],
     annotation_parser: Option<&AnnotationParser>,
+) -> Vec<Diagnostic> {
+    run_ruleset_with_settings(
+        uri,
+        text,
+        rs,
+        options,
+        annotations,
+        annotation_parser,
+        &Value::Null,
+    )
+}
+
+/// Run ruleset with annotation support and ruleset-level `settings`
+pub fn run_ruleset_with_settings(
+    uri: &str,
+    text: &str,
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    annotations: &[Annotation],
+    annotation_parser: Option<&AnnotationParser>,
+    settings: &Value,
+) -> Vec<Diagnostic> {
+    let env = RunEnv {
+        annotations,
+        annotation_parser,
+        ..RunEnv::new(settings)
+    };
+    run_rules(uri, text, rs, options, &env)
+}
+
+/// Run a ruleset, skipping the remaining rules once `cancellation` is set
+pub fn run_ruleset_cancellable(
+    uri: &str,
+    text: &str,
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    settings: &Value,
+    cancellation: &CancellationToken,
+) -> Vec<Diagnostic> {
+    let env = RunEnv {
+        cancellation: Some(cancellation),
+        ..RunEnv::new(settings)
+    };
+    run_rules(uri, text, rs, options, &env)
+}
+
+/// Run a ruleset, adding each rule's time and findings on this file to `profile`
+pub fn run_ruleset_profiled(
+    uri: &str,
+    text: &str,
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    settings: &Value,
+    profile: &mut RuleProfile,
+) -> Vec<Diagnostic> {
+    let guard = ProfileGuard {
+        cell: RefCell::new(std::mem::take(profile)),
+        target: profile,
+    };
+    let env = RunEnv {
+        profile: Some(&guard.cell),
+        ..RunEnv::new(settings)
+    };
+    run_rules(uri, text, rs, options, &env)
+}
+
+/// Hands the profile back to the caller on drop, so the timings recorded so
+/// far survive a panicking rule
+struct ProfileGuard<'a> {
+    cell: RefCell<RuleProfile>,
+    target: &'a mut RuleProfile,
+}
+
+impl Drop for ProfileGuard<'_> {
+    fn drop(&mut self) {
+        *self.target = std::mem::take(self.cell.get_mut());
+    }
+}
+
+/// Inputs shared by every rule invocation on a file
+struct RunEnv<'a> {
+    annotations: &'a [Annotation],
+    annotation_parser: Option<&'a AnnotationParser>,
+    settings: &'a Value,
+    cancellation: Option<&'a CancellationToken>,
+    artifacts_dir: Option<&'a std::path::Path>,
+    raw_bytes: Option<&'a [u8]>,
+    profile: Option<&'a RefCell<RuleProfile>>,
+    progress: Option<&'a dyn Fn(ProgressEvent)>,
+}
+
+impl<'a> RunEnv<'a> {
+    fn new(settings: &'a Value) -> Self {
+        Self {
+            annotations: &[],
+            annotation_parser: None,
+            settings,
+            cancellation: None,
+            artifacts_dir: None,
+            raw_bytes: None,
+            profile: None,
+            progress: None,
+        }
+    }
+}
+
+fn run_rules(
+    uri: &str,
+    text: &str,
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    env: &RunEnv,
 ) -> Vec<Diagnostic> {
     let mut all = Vec::new();
+    let passes = PassCache::new(&rs.passes, uri, text);
     for r in &rs.rules {
+        if env.cancellation.is_some_and(|token| token.is_cancelled()) {
+            break;
+        }
         if let Some(opts) = options.get(r.id()) {
-            let mut ctx = RuleContext {
-                uri,
-                text,
-                options: opts,
-                diagnostics: vec![],
-                annotations,
-                annotation_parser,
-            };
+            let mut ctx = RuleContext::new(uri, text, opts)
+                .with_settings(env.settings)
+                .with_annotations(env.annotations, env.annotation_parser)
+                .with_passes(&passes)
+                .with_cancellation(env.cancellation)
+                .with_artifacts_dir(env.artifacts_dir)
+                .with_raw_bytes(env.raw_bytes)
+                .with_progress(env.progress);
+            let started = env.profile.map(|_| std::time::Instant::now());
             r.check(&mut ctx);
-            all.extend(ctx.diagnostics);
+            let found = ctx.into_diagnostics();
+            if let (Some(profile), Some(started)) = (env.profile, started) {
+                profile
+                    .borrow_mut()
+                    .entry(r.id().to_string())
+                    .or_default()
+                    .record(uri, started.elapsed(), found.len());
+            }
+            all.extend(found);
         }
     }
     all
 }
 
+/// Run a ruleset over the embedded regions of a file whose language is in
+/// `languages`, reporting diagnostics in file coordinates
+pub fn run_ruleset_on_regions(
+    uri: &str,
+    text: &str,
+    regions: &[Region],
+    languages: &[String],
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    settings: &Value,
+) -> Vec<Diagnostic> {
+    run_rules_on_regions(
+        uri,
+        text,
+        regions,
+        languages,
+        rs,
+        options,
+        &RunEnv::new(settings),
+    )
+}
+
+fn run_rules_on_regions(
+    uri: &str,
+    text: &str,
+    regions: &[Region],
+    languages: &[String],
+    rs: &Ruleset,
+    options: &std::collections::HashMap<String, Value>,
+    env: &RunEnv,
+) -> Vec<Diagnostic> {
+    let index = LineIndex::new(text);
+    let mut all = Vec::new();
+    for region in regions
+        .iter()
+        .filter(|region| languages.contains(&region.language))
+    {
+        if env.cancellation.is_some_and(|token| token.is_cancelled()) {
+            break;
+        }
+        all.extend(
+            run_rules(uri, region.text(text), rs, options, env)
+                .into_iter()
+                .map(|d| region.map_diagnostic(&index, d)),
+        );
+    }
+    all
+}
+
 /// Run a ruleset with preprocessing context (new flow)
 pub fn run_ruleset_with_context(
     rs: &Ruleset,
@@ -149,36 +568,56 @@ pub fn run_ruleset_with_context_and_annotations(
     preprocessing_context: &PreprocessingContext,
     options: &std::collections::HashMap<String, Value>,
     annotation_parser: Option<&AnnotationParser>,
+) -> Vec<Diagnostic> {
+    run_ruleset_with_provider(
+        rs,
+        preprocessing_context,
+        options,
+        annotation_parser,
+        &FsFileProvider,
+        &Value::Null,
+    )
+}
+
+/// Run a ruleset with preprocessing context, loading file content through `provider`
+pub fn run_ruleset_with_provider(
+    rs: &Ruleset,
+    preprocessing_context: &PreprocessingContext,
+    options: &std::collections::HashMap<String, Value>,
+    annotation_parser: Option<&AnnotationParser>,
+    provider: &dyn FileProvider,
+    settings: &Value,
 ) -> Vec<Diagnostic> {
     let mut all = Vec::new();
 
     for file_context in &preprocessing_context.files {
         // Load file content on-demand only when needed
+        let loaded;
         let content = if file_context.content.is_empty() {
-            load_file_content(&file_context.uri).unwrap_or_default()
+            loaded = provider
+                .load(&file_context.uri)
+                .unwrap_or_else(|_| FileContent::Owned(String::new()));
+            loaded.as_str()
         } else {
-            file_context.content.clone()
+            file_context.content.as_str()
         };
 
         // Parse annotations if parser is provided
         let annotations = if let Some(parser) = annotation_parser {
-            parser.parse_annotations(&content)
+            parser.parse_annotations(content)
         } else {
             Vec::new()
         };
 
+        let passes = PassCache::new(&rs.passes, &file_context.uri, content);
         for rule in &rs.rules {
             if let Some(opts) = options.get(rule.id()) {
-                let mut ctx = RuleContext {
-                    uri: &file_context.uri,
-                    text: &content,
-                    options: opts,
-                    diagnostics: vec![],
-                    annotations: &annotations,
-                    annotation_parser,
-                };
+                let mut ctx = RuleContext::new(&file_context.uri, content, opts)
+                    .with_settings(settings)
+                    .with_annotations(&annotations, annotation_parser)
+                    .with_passes(&passes);
                 rule.check(&mut ctx);
-                all.extend(ctx.diagnostics);
+                all.extend(ctx.into_diagnostics());
             }
         }
     }
@@ -186,220 +625,561 @@ pub fn run_ruleset_with_context_and_annotations(
     all
 }
 
-/// Load file content on-demand
-fn load_file_content(uri: &str) -> Result<String, std::io::Error> {
-    let path = if uri.starts_with("file://") {
-        uri.strip_prefix("file://").unwrap_or(uri)
-    } else {
-        uri
-    };
-    std::fs::read_to_string(path)
+/// Run a ruleset over `batches` of file uris, preprocessing and analyzing each batch
+/// before the next one is loaded so only one batch of file content is resident at
+/// once. Hosts get batches within `linter.max_bytes_in_flight` from
+/// `LinterCfg::file_batches`; a single batch is the unbounded run.
+pub fn run_ruleset_bounded(
+    rs: &Ruleset,
+    opts: &dyn RulesetOptions,
+    batches: impl IntoIterator<Item = Vec<String>>,
+    options: &std::collections::HashMap<String, Value>,
+    annotation_parser: Option<&AnnotationParser>,
+    provider: &dyn FileProvider,
+    settings: &Value,
+) -> Result<Vec<Diagnostic>> {
+    let mut all = Vec::new();
+    for batch in batches {
+        let context = opts.preprocess_files(&batch)?;
+        all.extend(run_ruleset_with_provider(
+            rs,
+            &context,
+            options,
+            annotation_parser,
+            provider,
+            settings,
+        ));
+    }
+    Ok(all)
+}
+
+/// Split files into batches whose combined on-disk size stays within `max_bytes`.
+/// A single file larger than the budget gets a batch of its own; 0 => one batch.
+#[cfg(feature = "linter-host")]
+pub(crate) fn batch_files_by_size(file_uris: &[String], max_bytes: u64) -> Vec<Vec<String>> {
+    if max_bytes == 0 {
+        return vec![file_uris.to_vec()];
+    }
+
+    let mut batches = Vec::new();
+    let mut current = Vec::new();
+    let mut current_bytes = 0u64;
+    for uri in file_uris {
+        let size = std::fs::metadata(uri_to_path(uri))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        if !current.is_empty() && current_bytes + size > max_bytes {
+            batches.push(std::mem::take(&mut current));
+            current_bytes = 0;
+        }
+        current.push(uri.clone());
+        current_bytes += size;
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// How [`Shard`] assigns files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardStrategy {
+    /// By hash of the uri; a file stays on its shard as other files come and go
+    #[default]
+    Hash,
+    /// Largest files first onto the lightest shard, so shards finish together
+    SizeBalanced,
+}
+
+/// One slice of a sharded run, e.g. one job of a CI matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// Zero-based
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Parse the one-based `"2/4"` form CI matrices use
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (index, count) = spec.split_once('/')?;
+        let (index, count): (usize, usize) =
+            (index.trim().parse().ok()?, count.trim().parse().ok()?);
+        (index >= 1 && index <= count).then(|| Self {
+            index: index - 1,
+            count,
+        })
+    }
+
+    /// Files of `file_uris` that belong to this shard, in their original order
+    pub fn select(&self, file_uris: &[String], strategy: ShardStrategy) -> Vec<String> {
+        partition_files(file_uris, self.count, strategy)
+            .into_iter()
+            .nth(self.index)
+            .unwrap_or_default()
+    }
+}
+
+/// Split files into `count` shards. The result depends only on the uris (and
+/// sizes, for `SizeBalanced`), so every machine computes the same partition.
+pub fn partition_files(
+    file_uris: &[String],
+    count: usize,
+    strategy: ShardStrategy,
+) -> Vec<Vec<String>> {
+    let count = count.max(1);
+    let mut assignment = vec![0usize; file_uris.len()];
+    match strategy {
+        ShardStrategy::Hash => {
+            for (slot, uri) in assignment.iter_mut().zip(file_uris) {
+                let hash = u64::from_str_radix(&crate::core::content_hash(uri), 16).unwrap_or(0);
+                *slot = (hash % count as u64) as usize;
+            }
+        }
+        ShardStrategy::SizeBalanced => {
+            let size = |uri: &str| {
+                std::fs::metadata(uri_to_path(uri))
+                    .map(|m| m.len())
+                    .unwrap_or(0)
+            };
+            let mut order: Vec<(u64, usize)> = file_uris
+                .iter()
+                .enumerate()
+                .map(|(i, uri)| (size(uri), i))
+                .collect();
+            // Ties broken by uri so the order is identical everywhere
+            order.sort_by(|a, b| {
+                b.0.cmp(&a.0)
+                    .then_with(|| file_uris[a.1].cmp(&file_uris[b.1]))
+            });
+            let mut load = vec![0u64; count];
+            for (bytes, i) in order {
+                let lightest = (0..count).min_by_key(|&s| (load[s], s)).unwrap_or(0);
+                load[lightest] += bytes;
+                assignment[i] = lightest;
+            }
+        }
+    }
+    let mut shards = vec![Vec::new(); count];
+    for (uri, shard) in file_uris.iter().zip(assignment) {
+        shards[shard].push(uri.clone());
+    }
+    shards
+}
+
+/// Validate the settings a host supplied against their declarations, before
+/// defaults are filled in, returning one message per problem
+pub fn validate_settings(
+    settings: &serde_json::Map<String, Value>,
+    declared: &[ConfigSetting],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for setting in declared {
+        match settings.get(&setting.name) {
+            Some(value) => {
+                if let Err(e) = setting.validate(value) {
+                    errors.push(e);
+                }
+            }
+            None if setting.required => {
+                errors.push(format!("missing required setting '{}'", setting.name));
+            }
+            None => {}
+        }
+    }
+    for name in settings.keys() {
+        if !declared.iter().any(|s| &s.name == name) {
+            errors.push(format!("unknown setting '{}'", name));
+        }
+    }
+    errors
+}
+
+/// Source of file content for on-demand loading during analysis
+pub trait FileProvider: Send + Sync {
+    fn load(&self, uri: &str) -> std::io::Result<FileContent>;
+}
+
+/// File content handed to rules, either owned or memory-mapped
+pub enum FileContent {
+    Owned(String),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedText),
+}
+
+impl FileContent {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FileContent::Owned(s) => s,
+            #[cfg(feature = "mmap")]
+            FileContent::Mapped(m) => m.as_str(),
+        }
+    }
+}
+
+/// Default provider: reads the whole file with `std::fs::read_to_string`
+pub struct FsFileProvider;
+
+impl FileProvider for FsFileProvider {
+    fn load(&self, uri: &str) -> std::io::Result<FileContent> {
+        std::fs::read_to_string(uri_to_path(uri)).map(FileContent::Owned)
+    }
 }
 
-/// Ruleset server that handles NDJSON protocol communication
-pub struct RulesetServer {
-    initialized: bool,
-    config: HashMap<String, Value>,
-    ruleset: Option<Ruleset>,
-    opts: Box<dyn RulesetOptions>,
-    out: crate::core::Ndjson<std::io::BufWriter<std::io::Stdout>>,
+/// Memory-mapped provider: large files are mapped instead of copied into a `String`
+#[cfg(feature = "mmap")]
+pub struct MmapFileProvider {
+    /// Files smaller than this many bytes are read normally
+    pub min_size: u64,
 }
 
-impl RulesetServer {
-    pub fn new(opts: Box<dyn RulesetOptions>) -> Self {
+#[cfg(feature = "mmap")]
+impl Default for MmapFileProvider {
+    fn default() -> Self {
         Self {
-            initialized: false,
-            config: HashMap::new(),
-            ruleset: None,
-            opts,
-            out: crate::core::Ndjson::new(std::io::BufWriter::new(std::io::stdout())),
+            min_size: 64 * 1024,
         }
     }
+}
 
-    pub fn run_stdio(&mut self) -> Result<()> {
-        use crate::core::read_line_value;
+#[cfg(feature = "mmap")]
+impl FileProvider for MmapFileProvider {
+    fn load(&self, uri: &str) -> std::io::Result<FileContent> {
+        let file = std::fs::File::open(uri_to_path(uri))?;
+        if file.metadata()?.len() < self.min_size {
+            return FsFileProvider.load(uri);
+        }
+        // SAFETY: the mapping is read-only; callers must not truncate the file mid-analysis.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        MappedText::new(map).map(FileContent::Mapped)
+    }
+}
 
-        loop {
-            let msg: serde_json::Value = match read_line_value() {
-                Ok(v) => v,
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(anyhow::anyhow!("Failed to read input: {}", e)),
-            };
+/// UTF-8 validated view over a memory-mapped file
+#[cfg(feature = "mmap")]
+pub struct MappedText {
+    map: memmap2::Mmap,
+}
 
-            let envelope: Envelope<serde_json::Value> = serde_json::from_value(msg)?;
-            let msg_type = envelope.typ.as_str();
-            let id = envelope.id.unwrap_or_default();
+#[cfg(feature = "mmap")]
+impl MappedText {
+    fn new(map: memmap2::Mmap) -> std::io::Result<Self> {
+        std::str::from_utf8(&map)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Self { map })
+    }
 
-            match msg_type {
-                "initialize" => {
-                    self.on_initialize(&id, envelope.payload.unwrap_or(json!({})))?
-                }
-                "shutdown" => self.on_shutdown(&id)?,
-                "getDefaultConfig" => self.on_get_default_config(&id)?,
-                "getCapabilities" => self.on_get_capabilities(&id)?,
-                "preprocessFiles" => {
-                    self.on_preprocess_files(&id, envelope.payload.unwrap_or(json!({})))?
-                }
-                "analyzeFile" => {
-                    self.on_analyze_file(&id, envelope.payload.unwrap_or(json!({})))?
-                }
-                _ => {
-                    return Err(anyhow::anyhow!("Unknown message type: {}", msg_type));
+    pub fn as_str(&self) -> &str {
+        // SAFETY: validated as UTF-8 in `MappedText::new`.
+        unsafe { std::str::from_utf8_unchecked(&self.map) }
+    }
+}
+
+#[cfg(feature = "linter-host")]
+pub fn enabled_rulesets(cfg: &SharedConfig) -> impl Iterator<Item = (&String, &RulesetCfg)> {
+    cfg.get().ruleset.iter().filter(|(_, r)| r.enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Position, Range, Severity};
+
+    /// Flags every line containing "TODO"
+    struct NoTodo;
+
+    impl Rule for NoTodo {
+        fn id(&self) -> &'static str {
+            "no-todo"
+        }
+
+        fn description(&self) -> &'static str {
+            "Disallow TODO"
+        }
+
+        fn check(&self, ctx: &mut RuleContext) {
+            for (line, text) in ctx.text.lines().enumerate() {
+                if let Some(character) = text.find("TODO") {
+                    let start = Position {
+                        line: line as u32,
+                        character: character as u32,
+                    };
+                    let range = Range { start, end: start };
+                    ctx.report(Diagnostic::new(
+                        self.id(),
+                        "TODO found",
+                        Severity::Warn,
+                        range,
+                    ));
                 }
             }
         }
+    }
 
-        Ok(())
+    #[test]
+    fn contexts_built_with_new_run_rules() {
+        let options = Value::Null;
+        let mut ctx = RuleContext::new("mem://a.rs", "ok\n// TODO\n", &options);
+        NoTodo.check(&mut ctx);
+        assert!(ctx.settings.is_null() && !ctx.is_cancelled());
+        let diagnostics = ctx.into_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert_eq!(diagnostics[0].uri.as_deref(), Some("mem://a.rs"));
     }
 
-    fn send(&mut self, envelope: &Envelope<serde_json::Value>) {
-        let _ = self.out.send(envelope);
+    #[test]
+    fn annotations_set_on_the_context_suppress_reports() {
+        let text = "// forseti-ignore-next-line no-todo\n// TODO\n// TODO\n";
+        let parser = AnnotationParser::new(vec!["//".to_string()]);
+        let annotations = parser.parse_annotations(text);
+        let options = Value::Null;
+        let mut ctx = RuleContext::new("mem://a.rs", text, &options)
+            .with_annotations(&annotations, Some(&parser));
+        NoTodo.check(&mut ctx);
+        assert_eq!(ctx.diagnostics.len(), 1);
+        assert_eq!(ctx.diagnostics[0].range.start.line, 2);
     }
 
-    fn on_initialize(&mut self, id: &str, payload: serde_json::Value) -> Result<()> {
-        // Extract config from payload
-        if let Some(config) = payload.get("rulesetConfig").and_then(|v| v.as_object()) {
-            self.config = config.iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect();
-        } else {
-            self.config = self.opts.get_default_config();
-        }
+    /// Fresh directory under the system temp dir, named with a space so
+    /// `file://` URIs of its files need percent-encoding
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("forseti {name} {}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-        // Create the ruleset
-        self.ruleset = Some(self.opts.create_ruleset());
-        self.initialized = true;
+    #[test]
+    fn providers_load_percent_encoded_uris() {
+        let dir = scratch_dir("provider");
+        let path = dir.join("x.rs");
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        let uri = crate::core::file_uri(&path);
+        assert!(uri.contains("%20"), "{uri}");
 
-        self.send(&Envelope::res(
-            "initialize",
-            id.to_string(),
-            json!({"ok": true}),
-        ));
-        Ok(())
+        assert_eq!(
+            FsFileProvider.load(&uri).unwrap().as_str(),
+            "fn main() {}\n"
+        );
+        #[cfg(feature = "mmap")]
+        assert_eq!(
+            MmapFileProvider { min_size: 0 }
+                .load(&uri)
+                .unwrap()
+                .as_str(),
+            "fn main() {}\n"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    fn on_get_default_config(&mut self, id: &str) -> Result<()> {
-        let defaults = self.opts.get_default_config();
-        self.send(&Envelope::res(
-            "getDefaultConfig",
-            id.to_string(),
-            serde_json::to_value(defaults)?,
-        ));
-        Ok(())
+    #[cfg(feature = "linter-host")]
+    #[test]
+    fn batches_measure_percent_encoded_uris() {
+        let dir = scratch_dir("batches");
+        let uris: Vec<String> = ["a.rs", "b.rs"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, "0123456789").unwrap();
+                crate::core::file_uri(&path)
+            })
+            .collect();
+        assert_eq!(batch_files_by_size(&uris, 15).len(), 2);
+        assert_eq!(batch_files_by_size(&uris, 20).len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    fn on_get_capabilities(&mut self, id: &str) -> Result<()> {
-        let mut capabilities = self.opts.get_capabilities();
+    /// Preprocesses lazily, leaving content to the file provider, and
+    /// records the batches it was given
+    #[cfg(feature = "linter-host")]
+    #[derive(Default)]
+    struct Lazy(std::sync::Mutex<Vec<Vec<String>>>);
 
-        // Populate rules from the created ruleset
-        let ruleset = self.opts.create_ruleset();
-        capabilities.rules = ruleset.rules.iter().map(|rule| RuleInfo {
-            id: rule.id().to_string(),
-            description: rule.description().to_string(),
-        }).collect();
+    #[cfg(feature = "linter-host")]
+    impl RulesetOptions for Lazy {
+        fn get_capabilities(&self) -> RulesetCapabilities {
+            unreachable!("bounded runs do not ask for capabilities")
+        }
 
-        // Auto-inject rule enable/disable settings
-        for rule in &ruleset.rules {
-            capabilities.config_settings.push(crate::core::ConfigSetting {
-                name: rule.id().to_string(),
-                description: format!("Enable or disable the {} rule", rule.id()),
-                setting_type: crate::core::ConfigType::Enum,
-                default: rule.default_config(),
-                required: false,
-                allowed_values: Some(vec![
-                    serde_json::Value::String("off".to_string()),
-                    serde_json::Value::String("warn".to_string()),
-                    serde_json::Value::String("error".to_string()),
-                ]),
-                min: None,
-                max: None,
-            });
+        fn preprocess_files(&self, file_uris: &[String]) -> Result<PreprocessingContext> {
+            self.0.lock().unwrap().push(file_uris.to_vec());
+            Ok(PreprocessingContext {
+                ruleset_id: "lazy".to_string(),
+                files: file_uris
+                    .iter()
+                    .map(|uri| crate::core::FileContext {
+                        uri: uri.clone(),
+                        content: String::new(),
+                        language: None,
+                        context: HashMap::new(),
+                        flow_summaries: vec![],
+                        regions: vec![],
+                    })
+                    .collect(),
+                global_context: HashMap::new(),
+            })
         }
 
-        self.send(&Envelope::res(
-            "getCapabilities",
-            id.to_string(),
-            serde_json::to_value(capabilities)?,
-        ));
-        Ok(())
-    }
-
-    fn on_preprocess_files(&mut self, id: &str, payload: serde_json::Value) -> Result<()> {
-        let file_uris: Vec<String> = payload
-            .get("fileUris")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .collect()
+        fn create_ruleset(&self) -> Ruleset {
+            Ruleset::new("lazy").with_rule(Box::new(NoTodo))
+        }
+    }
+
+    /// Reports once per file when the `strict` setting is on
+    #[cfg(feature = "linter-host")]
+    struct Strict;
+
+    #[cfg(feature = "linter-host")]
+    impl Rule for Strict {
+        fn id(&self) -> &'static str {
+            "strict"
+        }
+
+        fn description(&self) -> &'static str {
+            "Reports when strict"
+        }
+
+        fn check(&self, ctx: &mut RuleContext) {
+            if ctx.settings["strict"] == true {
+                let start = Position {
+                    line: 0,
+                    character: 0,
+                };
+                ctx.report(Diagnostic::new(
+                    self.id(),
+                    "strict",
+                    Severity::Info,
+                    Range { start, end: start },
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "linter-host")]
+    #[test]
+    fn bounded_runs_stay_within_the_byte_cap_and_match_unbounded_ones() {
+        let dir = scratch_dir("bounded");
+        let uris: Vec<String> = ["a.rs", "b.rs", "c.rs"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, "// TODO\nok\n").unwrap();
+                crate::core::file_uri(&path)
             })
-            .unwrap_or_default();
+            .collect();
+        let linter = crate::config::LinterCfg {
+            max_bytes_in_flight: 25,
+            ..Default::default()
+        };
+        let batches = linter.file_batches(&uris);
+        assert_eq!(batches, [uris[..2].to_vec(), uris[2..].to_vec()]);
 
-        let context = self.opts.preprocess_files(&file_uris)?;
+        let rs = Ruleset::new("lazy")
+            .with_rule(Box::new(NoTodo))
+            .with_rule(Box::new(Strict));
+        let options = HashMap::from([
+            ("no-todo".to_string(), Value::Null),
+            ("strict".to_string(), Value::Null),
+        ]);
+        let settings = serde_json::json!({ "strict": true });
+        let bounded_opts = Lazy::default();
+        let bounded = run_ruleset_bounded(
+            &rs,
+            &bounded_opts,
+            batches,
+            &options,
+            None,
+            &FsFileProvider,
+            &settings,
+        )
+        .unwrap();
+        let unbounded = run_ruleset_bounded(
+            &rs,
+            &Lazy::default(),
+            [uris.clone()],
+            &options,
+            None,
+            &FsFileProvider,
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(bounded_opts.0.lock().unwrap().len(), 2);
+        // One TODO and one strict finding per file
+        assert_eq!(bounded.len(), 6);
+        assert_eq!(
+            serde_json::to_value(&bounded).unwrap(),
+            serde_json::to_value(&unbounded).unwrap()
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
-        self.send(&Envelope::res(
-            "preprocessFiles",
-            id.to_string(),
-            serde_json::to_value(context)?,
-        ));
-        Ok(())
-    }
-
-    fn on_analyze_file(&mut self, id: &str, payload: serde_json::Value) -> Result<()> {
-        if !self.initialized {
-            self.send(&Envelope::res(
-                "analyzeFile",
-                id.to_string(),
-                json!({"ok": false, "error": "not_initialized"}),
-            ));
-            return Ok(());
-        }
-
-        let uri = payload
-            .get("uri")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        let content = payload
-            .get("content")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        if let Some(ruleset) = &self.ruleset {
-            let diagnostics = run_ruleset(&uri, &content, ruleset, &self.config);
-
-            // Emit diagnostics event
-            self.send(&Envelope::event(
-                "diagnostics",
-                json!({
-                    "uri": uri,
-                    "diagnostics": diagnostics
-                }),
-            ));
-        }
-
-        // Send completion response
-        self.send(&Envelope::res(
-            "analyzeFile",
-            id.to_string(),
-            json!({"ok": true}),
-        ));
-        Ok(())
+    #[test]
+    fn shards_parse_from_one_based_specs() {
+        assert_eq!(Shard::parse("1/4"), Some(Shard { index: 0, count: 4 }));
+        assert_eq!(Shard::parse(" 4 / 4 "), Some(Shard { index: 3, count: 4 }));
+        for spec in ["0/4", "5/4", "1/0", "1", "a/b", ""] {
+            assert_eq!(Shard::parse(spec), None, "{spec}");
+        }
     }
 
-    fn on_shutdown(&mut self, id: &str) -> Result<()> {
-        self.send(&Envelope::res(
-            "shutdown",
-            id.to_string(),
-            json!({"ok": true}),
-        ));
-        Ok(())
+    #[test]
+    fn hash_partitions_cover_every_file_once_in_order() {
+        let uris: Vec<String> = (0..40).map(|i| format!("file:///src/{i}.rs")).collect();
+        let shards = partition_files(&uris, 3, ShardStrategy::Hash);
+        assert_eq!(shards.len(), 3);
+        assert!(shards.iter().all(|shard| !shard.is_empty()));
+        for uri in &uris {
+            assert_eq!(shards.iter().flatten().filter(|u| *u == uri).count(), 1);
+        }
+        for shard in &shards {
+            let positions: Vec<usize> = shard
+                .iter()
+                .map(|uri| uris.iter().position(|u| u == uri).unwrap())
+                .collect();
+            assert!(positions.is_sorted(), "{shard:?}");
+        }
+        // Every machine computes the same split, whatever order it listed files in
+        let mut reversed = uris.clone();
+        reversed.reverse();
+        let again = partition_files(&reversed, 3, ShardStrategy::Hash);
+        for (a, b) in shards.iter().zip(&again) {
+            let mut b = b.clone();
+            b.reverse();
+            assert_eq!(*a, b);
+        }
+        let shard = Shard { index: 1, count: 3 };
+        assert_eq!(shard.select(&uris, ShardStrategy::Hash), shards[1]);
     }
-}
 
-pub fn enabled_rulesets(cfg: &SharedConfig) -> impl Iterator<Item = (&String, &RulesetCfg)> {
-    cfg.get().ruleset.iter().filter(|(_, r)| r.enabled)
+    #[test]
+    fn zero_shards_means_one() {
+        let uris = vec!["file:///a.rs".to_string()];
+        assert_eq!(partition_files(&uris, 0, ShardStrategy::Hash), [uris]);
+    }
+
+    #[test]
+    fn size_balanced_shards_even_out_bytes() {
+        let dir = scratch_dir("shards");
+        let uris: Vec<String> = [("a", 20), ("b", 50), ("c", 10), ("d", 40), ("e", 30)]
+            .iter()
+            .map(|(name, size)| {
+                let path = dir.join(name);
+                std::fs::write(&path, vec![b'x'; *size]).unwrap();
+                crate::core::file_uri(&path)
+            })
+            .collect();
+        let names = |shard: &[String]| -> Vec<String> {
+            shard
+                .iter()
+                .map(|uri| uri.rsplit('/').next().unwrap().to_string())
+                .collect()
+        };
+        let shards = partition_files(&uris, 2, ShardStrategy::SizeBalanced);
+        // Largest first onto the lightest shard: 50+20+10 against 40+30
+        assert_eq!(names(&shards[0]), ["a", "b", "c"]);
+        assert_eq!(names(&shards[1]), ["d", "e"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,198 @@
+//! Standard command line for ruleset binaries, so every server's `main` is
+//! one call to [`run_server_main`] and hosts can rely on the same flags.
+
+use super::{RulesetOptions, RulesetServer, exit_status};
+use crate::jsonrpc::JsonRpcTransport;
+use crate::protocol::ServerExit;
+use crate::transport::NdjsonTransport;
+use std::io::Write;
+use std::process::ExitCode;
+use thiserror::Error;
+
+pub const USAGE: &str = "\
+usage: <server> [--stdio | --tcp <addr>] [--framing ndjson|jsonrpc] [--auth-token-env <var>]
+       <server> --capabilities | --version | --help
+
+  --stdio                 serve one host over stdin/stdout (default)
+  --tcp <addr>            listen on <addr>, serving one connection at a time
+  --framing <framing>     ndjson (default) or jsonrpc (JSON-RPC 2.0 messages)
+  --auth-token-env <var>  require the token in <var> as `authToken` in initialize
+  --capabilities          print the capabilities as JSON and exit
+  --version               print the ruleset id and version and exit";
+
+/// Wire format of each message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Forseti envelopes, one per line
+    #[default]
+    Ndjson,
+    /// JSON-RPC 2.0, one message per line (see [`crate::jsonrpc`])
+    JsonRpc,
+}
+
+/// Where a server takes its connection from
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Listen {
+    #[default]
+    Stdio,
+    Tcp(String),
+}
+
+/// Options for serving
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServeArgs {
+    pub listen: Listen,
+    pub framing: Framing,
+    /// Environment variable holding the token connections must present
+    pub auth_token_env: Option<String>,
+}
+
+/// What a server binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerCommand {
+    Serve(ServeArgs),
+    Capabilities,
+    Version,
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ArgsError {
+    #[error("unknown argument '{0}'")]
+    Unknown(String),
+    #[error("{0} needs a value")]
+    MissingValue(&'static str),
+    #[error("unknown framing '{0}', expected ndjson or jsonrpc")]
+    Framing(String),
+    #[error("{0} and {1} cannot be combined")]
+    Conflict(String, String),
+}
+
+/// Parse a server's arguments, without the program name
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<ServerCommand, ArgsError> {
+    let mut serve = ServeArgs::default();
+    let mut listen_flag: Option<String> = None;
+    let mut command: Option<(String, ServerCommand)> = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.into())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &'static str| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(ArgsError::MissingValue(name))
+        };
+        match flag.as_str() {
+            "--stdio" | "--tcp" => {
+                if let Some(previous) = &listen_flag
+                    && *previous != flag
+                {
+                    return Err(ArgsError::Conflict(previous.clone(), flag));
+                }
+                serve.listen = match flag.as_str() {
+                    "--tcp" => Listen::Tcp(value("--tcp")?),
+                    _ => Listen::Stdio,
+                };
+                listen_flag = Some(flag);
+            }
+            "--framing" => {
+                serve.framing = match value("--framing")?.to_ascii_lowercase().as_str() {
+                    "ndjson" => Framing::Ndjson,
+                    "jsonrpc" | "json-rpc" => Framing::JsonRpc,
+                    other => return Err(ArgsError::Framing(other.to_string())),
+                }
+            }
+            "--auth-token-env" => serve.auth_token_env = Some(value("--auth-token-env")?),
+            "--capabilities" | "--version" | "--help" | "-h" => {
+                let next = match flag.as_str() {
+                    "--capabilities" => ServerCommand::Capabilities,
+                    "--version" => ServerCommand::Version,
+                    _ => ServerCommand::Help,
+                };
+                if let Some((previous, _)) = &command
+                    && *previous != flag
+                {
+                    return Err(ArgsError::Conflict(previous.clone(), flag));
+                }
+                command = Some((flag, next));
+            }
+            _ => return Err(ArgsError::Unknown(arg)),
+        }
+    }
+    Ok(command.map_or(ServerCommand::Serve(serve), |(_, command)| command))
+}
+
+impl RulesetServer {
+    /// Act on a server binary's arguments (see [`USAGE`]), returning the exit
+    /// status for `main`. Bad arguments print the usage to stderr and exit
+    /// with [`ServerExit::Config`].
+    pub fn run_cli<I: IntoIterator<Item = String>>(&mut self, args: I) -> ExitCode {
+        let command = match parse_args(args) {
+            Ok(command) => command,
+            Err(e) => {
+                eprintln!("{}\n\n{}", e, USAGE);
+                return ServerExit::Config.into();
+            }
+        };
+        let serve = match command {
+            ServerCommand::Serve(serve) => serve,
+            ServerCommand::Help => return print(USAGE),
+            ServerCommand::Version => {
+                let capabilities = self.advertised_capabilities();
+                return print(&format!(
+                    "{} {}",
+                    capabilities.ruleset_id, capabilities.version
+                ));
+            }
+            ServerCommand::Capabilities => {
+                return match serde_json::to_string_pretty(&self.advertised_capabilities()) {
+                    Ok(json) => print(&json),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ServerExit::Io.into()
+                    }
+                };
+            }
+        };
+        if let Some(var) = &serve.auth_token_env {
+            match std::env::var(var) {
+                Ok(token) if !token.is_empty() => self.set_auth_token(token),
+                _ => {
+                    eprintln!("--auth-token-env: {} is not set", var);
+                    return ServerExit::Config.into();
+                }
+            }
+        }
+        let outcome = match (serve.listen, serve.framing) {
+            (Listen::Stdio, Framing::Ndjson) => self.run_stdio(),
+            (Listen::Stdio, Framing::JsonRpc) => {
+                self.run_with(JsonRpcTransport::new(NdjsonTransport::stdio()))
+            }
+            (Listen::Tcp(addr), Framing::Ndjson) => self.run_tcp(addr),
+            (Listen::Tcp(addr), Framing::JsonRpc) => {
+                self.run_tcp_wrapped(addr, JsonRpcTransport::new)
+            }
+        };
+        exit_status(outcome).into()
+    }
+}
+
+/// Write `text` to stdout; a reader that went away is an I/O failure, not a panic
+fn print(text: &str) -> ExitCode {
+    let mut stdout = std::io::stdout().lock();
+    match writeln!(stdout, "{}", text).and_then(|_| stdout.flush()) {
+        Ok(()) => ServerExit::Clean.into(),
+        Err(_) => ServerExit::Io.into(),
+    }
+}
+
+/// `main` for a ruleset binary: serve `opts` as the command line asks, with
+/// the panic hook installed. `fn main() -> ExitCode { run_server_main(..) }`
+pub fn run_server_main(opts: Box<dyn RulesetOptions>) -> ExitCode {
+    RulesetServer::new(opts)
+        .with_panic_hook()
+        .run_cli(std::env::args().skip(1))
+}
@@ -0,0 +1,1780 @@
+use super::{
+    Ruleset, RulesetOptions, RunEnv, rule_info, run_rules, run_rules_on_regions, validate_settings,
+};
+use crate::core::{
+    CancellationToken, Clock, ContentEncoding, Envelope, InitializeResult, Kind, LogEvent,
+    LogLevel, RuleProfile, RulesetCapabilities, SystemClock, decode_content,
+};
+use crate::protocol::{
+    AnalyzeFileParams, CancelParams, DiagnosticsEvent, ErrorPayload, FatalEvent, GetRuleDocParams,
+    InitializeParams, MetricsEvent, PreprocessFilesParams, ProgressEvent, Request, ServerExit,
+    SetRulesParams, Termination, error_codes, negotiate_version,
+};
+use crate::transport::{MessageReader, MessageWriter, NdjsonTransport, Transport, is_final_read};
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Per-workspace state; requests without a `sessionId` share the default session
+#[derive(Default)]
+struct Session {
+    initialized: bool,
+    config: HashMap<String, Value>,
+    settings: Value,
+    ruleset: Option<Ruleset>,
+    /// Region languages from capabilities, used to route `analyzeFile`
+    region_languages: Vec<String>,
+    artifacts_dir: Option<std::path::PathBuf>,
+    /// `setRules` toggles by rule id, applied over `config`
+    rule_overrides: HashMap<String, bool>,
+    /// Whole ruleset switched off by `setRules`
+    disabled: bool,
+    /// Version agreed in `initialize`, stamped on the session's messages
+    protocol_version: Option<u8>,
+}
+
+impl Session {
+    /// `config` with the session's rule toggles applied
+    fn effective_config(&self) -> std::borrow::Cow<'_, HashMap<String, Value>> {
+        if self.rule_overrides.is_empty() {
+            return std::borrow::Cow::Borrowed(&self.config);
+        }
+        let mut config = self.config.clone();
+        for (rule_id, enabled) in &self.rule_overrides {
+            if !enabled {
+                config.remove(rule_id);
+            } else if !config.contains_key(rule_id)
+                && let Some(rule) = self
+                    .ruleset
+                    .iter()
+                    .flat_map(|ruleset| &ruleset.rules)
+                    .find(|rule| rule.id() == rule_id)
+            {
+                config.insert(rule_id.clone(), rule.default_config());
+            }
+        }
+        std::borrow::Cow::Owned(config)
+    }
+}
+
+/// Connection totals reported in `metrics` events, kept once the host asks
+struct ServerMetrics {
+    interval: Duration,
+    last_sent: Instant,
+    counters: BTreeMap<String, u64>,
+    rules: RuleProfile,
+}
+
+impl ServerMetrics {
+    fn new(interval_ms: u64, now: Instant) -> Self {
+        Self {
+            interval: Duration::from_millis(interval_ms),
+            last_sent: now,
+            counters: BTreeMap::new(),
+            rules: RuleProfile::new(),
+        }
+    }
+
+    fn count(&mut self, name: &str, n: u64) {
+        *self.counters.entry(name.to_string()).or_default() += n;
+    }
+}
+
+/// `target` of the `log` events the server sends
+const LOG_TARGET: &str = "forseti::server";
+
+/// Consecutive malformed lines tolerated before the read loop gives up
+pub const MAX_PARSE_FAILURES: usize = 8;
+
+/// Thread reading the transport of a `run_with` call
+struct ReaderThread {
+    handle: std::thread::JoinHandle<()>,
+    /// Set when the read loop is done; later lines are neither tracked nor sent
+    stop: Arc<AtomicBool>,
+}
+
+/// Outcome of handling one message
+enum Step {
+    Handled,
+    Malformed,
+    Stop(Termination),
+}
+
+#[cfg(feature = "tokio")]
+impl RulesetServer {
+    /// Async counterpart of `run_stdio` on tokio's stdin and stdout
+    pub async fn run_stdio_async(&mut self) -> Result<Termination> {
+        self.run_async(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await
+    }
+
+    /// Serve requests from an async reader/writer pair. Reading and writing run on
+    /// tokio tasks, so no thread is dedicated to I/O; handlers run on the calling
+    /// task between messages and block it while they do. Use a multi-thread
+    /// runtime: on a current-thread one the reader cannot see a `cancel` and
+    /// replies are not flushed until the handler returns.
+    pub async fn run_async<R, W>(&mut self, reader: R, writer: W) -> Result<Termination>
+    where
+        R: tokio::io::AsyncBufRead + Unpin + Send + 'static,
+        W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        use crate::async_io::{ChannelWriter, read_line_value_async, write_lines};
+
+        let (out_tx, out_rx) = tokio::sync::mpsc::unbounded_channel();
+        let writer_task = tokio::spawn(write_lines(writer, out_rx));
+        self.out = Box::new(ChannelWriter::new(out_tx));
+        self.output_closed = false;
+        self.authenticated = false;
+
+        let (tx, mut incoming) = tokio::sync::mpsc::unbounded_channel();
+        let in_flight = self.in_flight.clone();
+        let clock = self.clock.clone();
+        let reader_task = tokio::spawn(async move {
+            let mut reader = reader;
+            loop {
+                let line = read_line_value_async(&mut reader).await;
+                track_line(&in_flight, &line, &clock);
+                let done = is_final_read(&line);
+                if tx.send(line).is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        let mut parse_failures = 0;
+        let termination = loop {
+            let Some(line) = incoming.recv().await else {
+                break Ok(Termination::Eof);
+            };
+            match self.process_line(line, &mut parse_failures) {
+                Ok(None) => {}
+                Ok(Some(termination)) => break Ok(termination),
+                Err(e) => break Err(e),
+            }
+        };
+        // The reader may be waiting on input that never comes, e.g. after `exit`
+        reader_task.abort();
+        // Dropping the channel writer lets the writer task flush and exit
+        let termination = self.finish(termination);
+        let _ = writer_task.await;
+        termination
+    }
+}
+
+/// Cancellation tokens of requests read but not yet answered, keyed by
+/// session (empty for the default session) and request id
+type InFlight = HashMap<(String, String), CancellationToken>;
+
+/// Track every request in a line read from the transport (see `track_request`)
+fn track_line(
+    in_flight: &std::sync::Mutex<InFlight>,
+    line: &std::io::Result<Value>,
+    clock: &Arc<dyn Clock>,
+) {
+    let Ok(msg) = line else {
+        return;
+    };
+    let mut in_flight = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+    match msg {
+        Value::Array(batch) => batch
+            .iter()
+            .for_each(|msg| track_request(&mut in_flight, msg, clock)),
+        msg => track_request(&mut in_flight, msg, clock),
+    }
+}
+
+/// Whether `presented` is the expected auth token. Compares without an early
+/// exit so response timing does not leak the prefix.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Register a cancellation token for a request, or flag the target of a `cancel`
+fn track_request(in_flight: &mut InFlight, msg: &Value, clock: &Arc<dyn Clock>) {
+    let Some(id) = msg.get("id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    let session = msg
+        .get("sessionId")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if msg.get("type").and_then(|v| v.as_str()) == Some("cancel") {
+        // A cancel only reaches requests of its own session
+        let target = msg.pointer("/payload/id").and_then(|v| v.as_str());
+        if let Some(token) = target.and_then(|target| in_flight.get(&(session, target.to_string())))
+        {
+            token.cancel();
+        }
+    } else {
+        let mut token = CancellationToken::new().with_clock(clock.clone());
+        // A timeout too large to represent is no deadline at all
+        if let Some(deadline) = msg
+            .get("timeoutMs")
+            .and_then(|v| v.as_u64())
+            .and_then(|timeout_ms| clock.now().checked_add(Duration::from_millis(timeout_ms)))
+        {
+            token = token.with_deadline(deadline);
+        }
+        in_flight.insert((session, id.to_string()), token);
+    }
+}
+
+/// Exit status of a process ended by the panic hook, as for an uncaught panic
+pub const PANIC_EXIT_CODE: i32 = ServerExit::Panic.code();
+
+/// How long the panic hook waits for a write in progress on another thread
+const PANIC_WRITE_WAIT: Duration = Duration::from_millis(200);
+
+type SharedOutput = Arc<Mutex<Box<dyn MessageWriter>>>;
+
+/// Output of the connection a server built `with_panic_hook` is serving
+static PANIC_OUTPUT: Mutex<Option<SharedOutput>> = Mutex::new(None);
+
+static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+
+/// Connection output the panic hook can reach, one whole line at a time
+struct SharedWriter(SharedOutput);
+
+impl MessageWriter for SharedWriter {
+    fn write_message(&mut self, message: &str) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_message(message)
+    }
+}
+
+/// Install a process-wide panic hook for ruleset binaries. While a server
+/// built [`RulesetServer::with_panic_hook`] is serving, a panic on any thread
+/// writes a final `fatal` event (message, location, thread and, with
+/// `RUST_BACKTRACE` set, the backtrace) after the last complete line, then
+/// exits with [`PANIC_EXIT_CODE`], so the host never reads a truncated line
+/// or waits on a server whose reader thread died. The previous hook runs
+/// first; panics outside a served connection behave as before. Idempotent.
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if report_panic(info) {
+                std::process::exit(PANIC_EXIT_CODE);
+            }
+        }));
+    });
+}
+
+/// A server that could not start with the settings it was given
+#[derive(Debug, thiserror::Error)]
+pub enum StartupError {
+    /// The listen address could not be resolved or bound
+    #[error("cannot listen on the given address")]
+    Listen(#[source] std::io::Error),
+}
+
+/// [`ServerExit`] for a server run's outcome, reporting errors on stderr.
+/// [`StartupError`]s map to [`ServerExit::Config`], other errors to
+/// [`ServerExit::Io`].
+pub fn exit_status(outcome: Result<Termination>) -> ServerExit {
+    match outcome {
+        Ok(termination) => ServerExit::from(&termination),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            if e.is::<StartupError>() {
+                ServerExit::Config
+            } else {
+                ServerExit::Io
+            }
+        }
+    }
+}
+
+/// Send the `fatal` event; false when no connection is being served
+fn report_panic(info: &std::panic::PanicHookInfo<'_>) -> bool {
+    let Some(output) = PANIC_OUTPUT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+    else {
+        return false;
+    };
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic with a non-string payload".to_string());
+    let backtrace = std::backtrace::Backtrace::capture();
+    let event = FatalEvent {
+        message,
+        location: info.location().map(ToString::to_string),
+        thread: std::thread::current().name().map(str::to_string),
+        backtrace: (backtrace.status() == std::backtrace::BacktraceStatus::Captured)
+            .then(|| backtrace.to_string()),
+    };
+    let Ok(line) = serde_json::to_value(event)
+        .and_then(|payload| serde_json::to_string(&Envelope::event("fatal", payload)))
+    else {
+        return true;
+    };
+    // A panic in the middle of a write on this thread still holds the lock;
+    // never block on it
+    let deadline = Instant::now() + PANIC_WRITE_WAIT;
+    loop {
+        match output.try_lock() {
+            Ok(mut writer) => {
+                let _ = writer.write_message(&line);
+                return true;
+            }
+            Err(std::sync::TryLockError::Poisoned(e)) => {
+                let _ = e.into_inner().write_message(&line);
+                return true;
+            }
+            Err(std::sync::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            Err(std::sync::TryLockError::WouldBlock) => return true,
+        }
+    }
+}
+
+/// Ruleset server that handles NDJSON protocol communication
+pub struct RulesetServer {
+    sessions: HashMap<String, Session>,
+    /// Session of the request being handled, echoed on every reply
+    current_session: Option<String>,
+    /// Trace id and request id of the request being handled; replies join the
+    /// trace and events name the request as their parent
+    current_trace: Option<(String, String)>,
+    /// Set once a write fails, ending the read loop
+    output_closed: bool,
+    /// Replies collected while a batch line is handled
+    batch: Option<Vec<Envelope<Value>>>,
+    /// Payload size above which replies are gzipped, once the host opted in
+    #[cfg(feature = "compression")]
+    compress_above: Option<usize>,
+    /// Token every connection must present in `initialize`
+    auth_token: Option<String>,
+    /// Whether the current, or last, connection presented `auth_token`
+    authenticated: bool,
+    /// Set by a default-session `shutdown`; only `exit` is accepted afterwards
+    shutting_down: bool,
+    /// Least severe `log` event sent, chosen by the host at `initialize`
+    log_level: LogLevel,
+    /// Set when the host passed `metricsIntervalMs` to `initialize`
+    metrics: Option<ServerMetrics>,
+    /// Cancellation tokens of requests read but not yet answered. Shared with
+    /// the stdin reader thread so `cancel` takes effect mid-request.
+    in_flight: std::sync::Arc<std::sync::Mutex<InFlight>>,
+    /// Time source for request deadlines and the metrics interval
+    clock: Arc<dyn Clock>,
+    /// Report panics as a `fatal` event, see [`install_panic_hook`]
+    panic_hook: bool,
+    /// Reader of the last transport served, which may still be blocked on it
+    reader_thread: Option<ReaderThread>,
+    opts: Box<dyn RulesetOptions>,
+    out: Box<dyn MessageWriter>,
+}
+
+impl RulesetServer {
+    pub fn new(opts: Box<dyn RulesetOptions>) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            current_session: None,
+            current_trace: None,
+            output_closed: false,
+            batch: None,
+            #[cfg(feature = "compression")]
+            compress_above: None,
+            auth_token: None,
+            authenticated: false,
+            shutting_down: false,
+            log_level: LogLevel::Info,
+            metrics: None,
+            in_flight: Default::default(),
+            clock: Arc::new(SystemClock),
+            panic_hook: false,
+            reader_thread: None,
+            opts,
+            out: Box::new(crate::core::Ndjson::new(std::io::BufWriter::new(
+                std::io::stdout(),
+            ))),
+        }
+    }
+
+    /// Require each connection to send `token` as `authToken` in `initialize`;
+    /// until it does, every request is answered with `unauthorized`. Meant for
+    /// `run_tcp`, where anyone who can reach the port can connect.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.set_auth_token(token);
+        self
+    }
+
+    pub(crate) fn set_auth_token(&mut self, token: impl Into<String>) {
+        self.auth_token = Some(token.into());
+    }
+
+    /// Measure request deadlines (`timeoutMs`) and the metrics interval with
+    /// `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Send a final `fatal` event and exit when the process panics while this
+    /// server runs over `run_stdio`, `run_tcp` or `run_with`; installs the
+    /// hook from [`install_panic_hook`]
+    pub fn with_panic_hook(mut self) -> Self {
+        install_panic_hook();
+        self.panic_hook = true;
+        self
+    }
+
+    /// Serve requests from stdin until the input ends or the server must stop,
+    /// returning the cause. A final `terminated` event is sent when stdout is open.
+    pub fn run_stdio(&mut self) -> Result<Termination> {
+        self.run_with(NdjsonTransport::stdio())
+    }
+
+    /// [`run_stdio`](Self::run_stdio), then the exit status for how it stopped
+    /// ([`ServerExit`]), to return from `main`. Read or write failures are
+    /// logged to stderr.
+    pub fn serve_stdio(&mut self) -> std::process::ExitCode {
+        exit_status(self.run_stdio()).into()
+    }
+
+    /// Listen on `addr` and serve one connection at a time. Session state survives
+    /// reconnects; returns once a connection ends for any reason other than the
+    /// peer disconnecting. With an auth token set, a peer that never presented
+    /// it only ends its own connection, by `exit` or by malformed input.
+    pub fn run_tcp(&mut self, addr: impl std::net::ToSocketAddrs) -> Result<Termination> {
+        self.run_tcp_wrapped(addr, |transport| transport)
+    }
+
+    /// [`run_tcp`](Self::run_tcp), serving each connection through `wrap`
+    /// (e.g. [`crate::jsonrpc::JsonRpcTransport::new`])
+    pub(crate) fn run_tcp_wrapped<T: Transport>(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        wrap: impl Fn(crate::transport::TcpTransport) -> T,
+    ) -> Result<Termination> {
+        let listener = std::net::TcpListener::bind(addr).map_err(StartupError::Listen)?;
+        loop {
+            let (stream, _) = listener.accept()?;
+            match self.run_with(wrap(NdjsonTransport::tcp(stream)?))? {
+                Termination::Eof | Termination::OutputClosed => continue,
+                _ if self.auth_token.is_some() && !self.authenticated => continue,
+                termination => return Ok(termination),
+            }
+        }
+    }
+
+    /// Serve requests over `transport`, as `run_stdio` does over stdin/stdout.
+    /// The previous transport's reader is stopped and joined first, so a
+    /// second call on stdin waits for the first reader's pending line to end.
+    pub fn run_with(&mut self, transport: impl Transport) -> Result<Termination> {
+        let (reader, writer) = transport.into_parts();
+        self.out = writer;
+        self.authenticated = false;
+        if self.panic_hook {
+            let output: SharedOutput = Arc::new(Mutex::new(std::mem::replace(
+                &mut self.out,
+                Box::new(crate::core::Ndjson::new(std::io::sink())),
+            )));
+            *PANIC_OUTPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(output.clone());
+            self.out = Box::new(SharedWriter(output));
+        }
+        self.output_closed = false;
+        let termination = self.read_loop(reader);
+        let termination = self.finish(termination);
+        if self.panic_hook {
+            *PANIC_OUTPUT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        termination
+    }
+
+    /// Send the final `terminated` event and release the writer so the peer sees
+    /// the stream close
+    fn finish(&mut self, termination: Result<Termination>) -> Result<Termination> {
+        if let Ok(cause) = &termination
+            && *cause != Termination::OutputClosed
+        {
+            self.current_session = None;
+            self.current_trace = None;
+            self.send_metrics(true);
+            self.send(&Envelope::event("terminated", serde_json::to_value(cause)?));
+        }
+        self.metrics = None;
+        self.out = Box::new(crate::core::Ndjson::new(std::io::sink()));
+        self.shutting_down = false;
+        self.log_level = LogLevel::Info;
+        #[cfg(feature = "compression")]
+        {
+            self.compress_above = None;
+        }
+        termination
+    }
+    /// Read stdin on a separate thread so `cancel` messages are seen while a
+    /// request is still being handled. The reader of a previous transport is
+    /// joined first, so two readers never share an input.
+    fn spawn_reader(
+        &mut self,
+        mut reader: Box<dyn MessageReader>,
+    ) -> std::sync::mpsc::Receiver<std::io::Result<Value>> {
+        if let Some(previous) = self.reader_thread.take() {
+            previous.stop.store(true, Ordering::SeqCst);
+            let _ = previous.handle.join();
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let in_flight = self.in_flight.clone();
+        let clock = self.clock.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            loop {
+                let line = reader.read_message();
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                track_line(&in_flight, &line, &clock);
+                let done = is_final_read(&line);
+                if tx.send(line).is_err() || done {
+                    break;
+                }
+            }
+        });
+        self.reader_thread = Some(ReaderThread { handle, stop });
+        rx
+    }
+
+    fn read_loop(&mut self, reader: Box<dyn MessageReader>) -> Result<Termination> {
+        let incoming = self.spawn_reader(reader);
+        let mut parse_failures = 0;
+        let termination = loop {
+            let Ok(line) = incoming.recv() else {
+                break Ok(Termination::Eof);
+            };
+            match self.process_line(line, &mut parse_failures) {
+                Ok(None) => {}
+                Ok(Some(termination)) => break Ok(termination),
+                Err(e) => break Err(e),
+            }
+        };
+        if let Some(reader) = &self.reader_thread {
+            reader.stop.store(true, Ordering::SeqCst);
+        }
+        termination
+    }
+
+    /// Answer one line read from the transport, returning the termination cause
+    /// once the loop has to stop
+    fn process_line(
+        &mut self,
+        line: std::io::Result<Value>,
+        parse_failures: &mut usize,
+    ) -> Result<Option<Termination>> {
+        let msg = match line {
+            Ok(v) => v,
+            Err(e) => match e.kind() {
+                // Invalid JSON is reported like any other malformed message
+                std::io::ErrorKind::InvalidData => Value::Null,
+                std::io::ErrorKind::UnexpectedEof => return Ok(Some(Termination::Eof)),
+                std::io::ErrorKind::Interrupted => return Ok(Some(Termination::Signal)),
+                _ => return Err(anyhow::anyhow!("Failed to read input: {}", e)),
+            },
+        };
+
+        // A batch line is answered with one line holding every reply it produced
+        let steps = match msg {
+            Value::Array(batch) => {
+                self.batch = Some(Vec::new());
+                let mut steps = Vec::with_capacity(batch.len());
+                for msg in batch {
+                    let step = self.handle_message(msg);
+                    let stop = matches!(step, Step::Stop(_));
+                    steps.push(step);
+                    if stop {
+                        break;
+                    }
+                }
+                let replies = self.batch.take().unwrap_or_default();
+                self.write(&replies);
+                steps
+            }
+            msg => vec![self.handle_message(msg)],
+        };
+
+        for step in steps {
+            match step {
+                Step::Handled => *parse_failures = 0,
+                Step::Malformed => *parse_failures += 1,
+                Step::Stop(termination) => return Ok(Some(termination)),
+            }
+        }
+        if *parse_failures >= MAX_PARSE_FAILURES {
+            return Ok(Some(Termination::ParseFailures {
+                count: *parse_failures,
+            }));
+        }
+        if self.output_closed {
+            return Ok(Some(Termination::OutputClosed));
+        }
+        Ok(None)
+    }
+
+    /// Decode and answer one message
+    fn handle_message(&mut self, msg: Value) -> Step {
+        // Malformed JSON and malformed envelopes both count as parse failures
+        let Ok(envelope) = serde_json::from_value::<Envelope<serde_json::Value>>(msg) else {
+            self.current_session = None;
+            self.current_trace = None;
+            self.send_error(
+                "",
+                "",
+                ErrorPayload::new(error_codes::PARSE_ERROR, "malformed message"),
+            );
+            return Step::Malformed;
+        };
+        let id = envelope.id.clone().unwrap_or_default();
+        self.current_session = envelope.session_id.clone();
+        self.current_trace = envelope.trace_id.clone().map(|trace| (trace, id.clone()));
+
+        let request = match Request::from_envelope(&envelope) {
+            Ok(request) => request,
+            Err(e) => {
+                self.send_error(&envelope.typ, &id, ErrorPayload::from(&e));
+                self.finish_request(&id);
+                return Step::Handled;
+            }
+        };
+        if matches!(request, Request::Exit) {
+            self.finish_request(&id);
+            return Step::Stop(if self.shutting_down {
+                Termination::Shutdown
+            } else {
+                Termination::Exit
+            });
+        }
+        let typ = request.method();
+        let cancellation = self.token_for(&id);
+        // A failing handler answers its request instead of ending the loop
+        if let Err(e) = self.dispatch(&id, request, &cancellation) {
+            self.send_error(
+                typ,
+                &id,
+                ErrorPayload::new(error_codes::INTERNAL, e.to_string()),
+            );
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.count("requests", 1);
+        }
+        self.send_metrics(false);
+        Step::Handled
+    }
+
+    /// Key of request `id` of the current session in `in_flight`
+    fn request_key(&self, id: &str) -> (String, String) {
+        (
+            self.current_session.clone().unwrap_or_default(),
+            id.to_string(),
+        )
+    }
+
+    /// Token the reader registered for request `id`, or a fresh one
+    fn token_for(&self, id: &str) -> CancellationToken {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&self.request_key(id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn finish_request(&self, id: &str) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.request_key(id));
+    }
+
+    fn dispatch(
+        &mut self,
+        id: &str,
+        request: Request,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let result = self.dispatch_request(id, request, cancellation);
+        self.finish_request(id);
+        result
+    }
+
+    fn dispatch_request(
+        &mut self,
+        id: &str,
+        request: Request,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        if cancellation.is_cancelled() {
+            self.send_cancelled(request.method(), id, cancellation);
+            return Ok(());
+        }
+        if self.shutting_down {
+            self.send_error(
+                request.method(),
+                id,
+                ErrorPayload::new(
+                    error_codes::SHUTTING_DOWN,
+                    "only exit is accepted after shutdown",
+                ),
+            );
+            return Ok(());
+        }
+        if let Some(expected) = &self.auth_token
+            && !self.authenticated
+        {
+            let presented = match &request {
+                Request::Initialize(params) => params.auth_token.as_deref(),
+                _ => None,
+            };
+            if !presented.is_some_and(|token| tokens_match(token, expected)) {
+                self.log(
+                    LogEvent::new(
+                        LogLevel::Warn,
+                        "rejected request without a valid auth token",
+                    )
+                    .with_field("type", request.method()),
+                );
+                self.send_error(
+                    request.method(),
+                    id,
+                    ErrorPayload::new(error_codes::UNAUTHORIZED, "missing or invalid auth token"),
+                );
+                return Ok(());
+            }
+            self.authenticated = true;
+        }
+        match request {
+            Request::Initialize(params) => self.on_initialize(id, params),
+            Request::Shutdown => self.on_shutdown(id),
+            // Handled before dispatch; it ends the read loop
+            Request::Exit => Ok(()),
+            Request::GetDefaultConfig => self.on_get_default_config(id),
+            Request::GetCapabilities => self.on_get_capabilities(id),
+            Request::GetRuleDoc(params) => self.on_get_rule_doc(id, params),
+            Request::PreprocessFiles(params) => self.on_preprocess_files(id, params, cancellation),
+            Request::AnalyzeFile(params) => self.on_analyze_file(id, params, cancellation),
+            Request::Cancel(params) => self.on_cancel(id, params),
+            Request::SetRules(params) => self.on_set_rules(id, params),
+        }
+    }
+
+    /// State of the current session; only `initialize` creates one
+    fn session(&mut self) -> Option<&mut Session> {
+        let key = self.current_session.as_deref().unwrap_or_default();
+        self.sessions.get_mut(key)
+    }
+
+    /// Whether the current session has completed `initialize`
+    fn initialized(&mut self) -> bool {
+        self.session().is_some_and(|session| session.initialized)
+    }
+
+    /// Emit a `progress` event for an in-flight request
+    fn report_progress(&mut self, progress: ProgressEvent) {
+        if let Ok(payload) = serde_json::to_value(progress) {
+            self.send(&Envelope::event("progress", payload));
+        }
+    }
+
+    fn send_cancelled(&mut self, typ: &str, id: &str, cancellation: &CancellationToken) {
+        let error = if cancellation.is_expired() {
+            self.log(
+                LogEvent::new(LogLevel::Warn, "request deadline exceeded")
+                    .with_field("type", typ)
+                    .with_field("id", id),
+            );
+            ErrorPayload::new(error_codes::DEADLINE_EXCEEDED, "request deadline exceeded")
+        } else {
+            ErrorPayload::new(error_codes::CANCELLED, "request cancelled")
+        };
+        self.send_error(typ, id, error);
+    }
+
+    /// Send a `log` event unless it is below the host's chosen level
+    fn log(&mut self, event: LogEvent) {
+        if event.level >= self.log_level
+            && let Ok(payload) = serde_json::to_value(event.with_target(LOG_TARGET))
+        {
+            self.send(&Envelope::event("log", payload));
+        }
+    }
+
+    /// Send a `metrics` event once the interval has passed, or now when `force`d.
+    /// The event belongs to the connection, not the request that triggered it.
+    fn send_metrics(&mut self, force: bool) {
+        let sessions = self.sessions.len() as f64;
+        let in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len() as f64;
+        let Some(metrics) = &mut self.metrics else {
+            return;
+        };
+        let now = self.clock.now();
+        if !force && now.saturating_duration_since(metrics.last_sent) < metrics.interval {
+            return;
+        }
+        metrics.last_sent = now;
+        let event = MetricsEvent {
+            counters: metrics.counters.clone(),
+            gauges: BTreeMap::from([
+                ("sessions".to_string(), sessions),
+                ("inFlight".to_string(), in_flight),
+            ]),
+            rule_timings: metrics.rules.clone(),
+        };
+        let Ok(payload) = serde_json::to_value(event) else {
+            return;
+        };
+        let session = self.current_session.take();
+        let trace = self.current_trace.take();
+        self.send(&Envelope::event("metrics", payload));
+        self.current_session = session;
+        self.current_trace = trace;
+    }
+
+    /// Capabilities with the features this server implements itself
+    fn capabilities(&self) -> RulesetCapabilities {
+        let mut capabilities = self.opts.get_capabilities();
+        for feature in [
+            crate::core::features::CANCELLATION,
+            crate::core::features::BATCH_ANALYZE,
+            crate::core::features::METRICS,
+            #[cfg(feature = "compression")]
+            crate::core::features::GZIP,
+        ] {
+            if !capabilities.supports(feature) {
+                capabilities.features.push(feature.to_string());
+            }
+        }
+        capabilities
+    }
+
+    /// Reply with an error, or emit an `error` event when there is no id to answer
+    fn send_error(&mut self, typ: &str, id: &str, error: ErrorPayload) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.count("errors", 1);
+        }
+        let payload = serde_json::to_value(error).unwrap_or(Value::Null);
+        if id.is_empty() || typ.is_empty() {
+            self.send(&Envelope::event("error", payload));
+        } else {
+            self.send(&Envelope::res(typ, id.to_string(), payload));
+        }
+    }
+
+    /// Write one line to the transport, noting when the peer has gone away
+    fn write(&mut self, message: &impl serde::Serialize) {
+        let written = serde_json::to_string(message)
+            .map_err(std::io::Error::from)
+            .and_then(|message| self.compress(message))
+            .and_then(|message| self.out.write_message(&message));
+        if written.is_err() {
+            self.output_closed = true;
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn compress(&self, message: String) -> std::io::Result<String> {
+        match self.compress_above {
+            Some(threshold) if message.len() > threshold => crate::core::compress_line(&message),
+            _ => Ok(message),
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn compress(&self, message: String) -> std::io::Result<String> {
+        Ok(message)
+    }
+
+    fn send(&mut self, envelope: &Envelope<serde_json::Value>) {
+        let mut envelope = envelope.clone().with_session(self.current_session.clone());
+        if let Some(version) = self.session().and_then(|session| session.protocol_version) {
+            envelope.v = version;
+        }
+        if envelope.trace_id.is_none()
+            && let Some((trace_id, request_id)) = &self.current_trace
+        {
+            let parent_id = (envelope.kind == Kind::Event && !request_id.is_empty())
+                .then(|| request_id.clone());
+            envelope = envelope.with_trace(Some(trace_id.clone()), parent_id);
+        }
+        debug_assert!(
+            envelope.validate().is_ok(),
+            "invalid envelope: {:?}",
+            envelope.validate()
+        );
+        if let Some(batch) = &mut self.batch {
+            batch.push(envelope);
+        } else {
+            self.write(&envelope);
+        }
+    }
+
+    fn on_initialize(&mut self, id: &str, params: InitializeParams) -> Result<()> {
+        let protocol_version = match negotiate_version(params.protocol_versions) {
+            Ok(version) => version,
+            Err(mismatch) => {
+                self.send_error(
+                    "initialize",
+                    id,
+                    ErrorPayload::new(
+                        error_codes::UNSUPPORTED_PROTOCOL_VERSION,
+                        mismatch.to_string(),
+                    )
+                    .with_details(mismatch),
+                );
+                return Ok(());
+            }
+        };
+
+        let config = params
+            .ruleset_config
+            .unwrap_or_else(|| self.opts.get_default_config());
+
+        // Ruleset-level settings: declared defaults overlaid with the provided values
+        let capabilities = self.capabilities();
+        let declared = &capabilities.config_settings;
+        let provided = params.ruleset_settings.unwrap_or_default();
+        let errors = validate_settings(&provided, declared);
+        if !errors.is_empty() {
+            self.send_error(
+                "initialize",
+                id,
+                ErrorPayload::new(error_codes::INVALID_SETTINGS, "invalid ruleset settings")
+                    .with_details(errors),
+            );
+            return Ok(());
+        }
+        let mut settings: serde_json::Map<String, Value> = declared
+            .iter()
+            .map(|setting| (setting.name.clone(), setting.default.clone()))
+            .collect();
+        settings.extend(provided);
+        if let Some(level) = params.log_level {
+            self.log_level = level;
+        }
+        if let Some(interval_ms) = params.metrics_interval_ms {
+            match &mut self.metrics {
+                Some(metrics) => metrics.interval = Duration::from_millis(interval_ms),
+                None => self.metrics = Some(ServerMetrics::new(interval_ms, self.clock.now())),
+            }
+        }
+        let ruleset = self.opts.create_ruleset();
+        let rules = ruleset.rules.len();
+        let key = self.current_session.clone().unwrap_or_default();
+        let session = self.sessions.entry(key).or_default();
+        session.config = config;
+        session.settings = Value::Object(settings);
+        session.ruleset = Some(ruleset);
+        session.region_languages = capabilities.region_languages.clone();
+        session.artifacts_dir = params.artifacts_dir.map(std::path::PathBuf::from);
+        session.rule_overrides.clear();
+        session.disabled = false;
+        session.protocol_version = Some(protocol_version);
+        session.initialized = true;
+        self.log(
+            LogEvent::new(LogLevel::Debug, "session initialized")
+                .with_field("rules", rules)
+                .with_field("protocolVersion", protocol_version),
+        );
+
+        self.send(&Envelope::res(
+            "initialize",
+            id.to_string(),
+            serde_json::to_value(InitializeResult {
+                ok: true,
+                features: capabilities.features,
+                protocol_version: Some(protocol_version),
+            })?,
+        ));
+        #[cfg(feature = "compression")]
+        if params
+            .features
+            .iter()
+            .any(|f| f == crate::core::features::GZIP)
+        {
+            self.compress_above = Some(crate::core::COMPRESSION_THRESHOLD);
+        }
+        Ok(())
+    }
+
+    fn on_get_default_config(&mut self, id: &str) -> Result<()> {
+        let defaults = self.opts.get_default_config();
+        self.send(&Envelope::res(
+            "getDefaultConfig",
+            id.to_string(),
+            serde_json::to_value(defaults)?,
+        ));
+        Ok(())
+    }
+
+    /// Capabilities as `getCapabilities` answers them: the ruleset's own,
+    /// with the server's features, every rule and an on/off setting per rule
+    pub fn advertised_capabilities(&self) -> RulesetCapabilities {
+        let mut capabilities = self.capabilities();
+
+        // Populate rules from the created ruleset
+        let ruleset = self.opts.create_ruleset();
+        capabilities.rules = ruleset
+            .rules
+            .iter()
+            .map(|rule| rule_info(rule.as_ref()))
+            .collect();
+
+        // Auto-inject rule enable/disable settings
+        for rule in &ruleset.rules {
+            capabilities
+                .config_settings
+                .push(crate::core::ConfigSetting {
+                    name: rule.id().to_string(),
+                    description: format!("Enable or disable the {} rule", rule.id()),
+                    setting_type: crate::core::ConfigType::Enum,
+                    default: rule.default_config(),
+                    required: false,
+                    allowed_values: Some(vec![
+                        serde_json::Value::String("off".to_string()),
+                        serde_json::Value::String("warn".to_string()),
+                        serde_json::Value::String("error".to_string()),
+                    ]),
+                    min: None,
+                    max: None,
+                });
+        }
+        capabilities
+    }
+
+    fn on_get_capabilities(&mut self, id: &str) -> Result<()> {
+        let capabilities = self.advertised_capabilities();
+        self.send(&Envelope::res(
+            "getCapabilities",
+            id.to_string(),
+            serde_json::to_value(capabilities)?,
+        ));
+        Ok(())
+    }
+
+    fn on_get_rule_doc(&mut self, id: &str, params: GetRuleDocParams) -> Result<()> {
+        let ruleset = self.opts.create_ruleset();
+        match ruleset
+            .rules
+            .iter()
+            .find(|rule| rule.id() == params.rule_id)
+        {
+            Some(rule) => {
+                let payload = serde_json::to_value(rule_info(rule.as_ref()))?;
+                self.send(&Envelope::res("getRuleDoc", id.to_string(), payload));
+            }
+            None => self.send_error(
+                "getRuleDoc",
+                id,
+                ErrorPayload::new(
+                    error_codes::UNKNOWN_RULE,
+                    format!("unknown rule {}", params.rule_id),
+                ),
+            ),
+        }
+        Ok(())
+    }
+
+    fn on_preprocess_files(
+        &mut self,
+        id: &str,
+        params: PreprocessFilesParams,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let total = params.file_uris.len() as u64;
+        self.report_progress(ProgressEvent::new(id, "Preprocessing files", 0).with_total(total));
+        let context = self
+            .opts
+            .preprocess_files_cancellable(&params.file_uris, cancellation)?;
+        self.report_progress(
+            ProgressEvent::new(id, "Preprocessing files", total).with_total(total),
+        );
+        if cancellation.is_cancelled() {
+            self.send_cancelled("preprocessFiles", id, cancellation);
+            return Ok(());
+        }
+
+        self.send(&Envelope::res(
+            "preprocessFiles",
+            id.to_string(),
+            serde_json::to_value(context)?,
+        ));
+        Ok(())
+    }
+
+    fn on_analyze_file(
+        &mut self,
+        id: &str,
+        params: AnalyzeFileParams,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let raw_bytes = match params.encoding {
+            Some(ContentEncoding::Base64) => {
+                match decode_content(&params.content, ContentEncoding::Base64) {
+                    Ok(bytes) => Some(bytes),
+                    Err(message) => {
+                        self.send_error(
+                            "analyzeFile",
+                            id,
+                            ErrorPayload::new(error_codes::INVALID_PAYLOAD, message),
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+            _ => None,
+        };
+        let content = match &raw_bytes {
+            Some(bytes) => String::from_utf8_lossy(bytes),
+            None => std::borrow::Cow::Borrowed(params.content.as_str()),
+        };
+
+        if !self.initialized() {
+            self.send_error(
+                "analyzeFile",
+                id,
+                ErrorPayload::new(error_codes::NOT_INITIALIZED, "initialize has not completed"),
+            );
+            return Ok(());
+        }
+
+        // Rule timings are only collected when the host asked for metrics
+        let profile = self
+            .metrics
+            .as_mut()
+            .map(|metrics| RefCell::new(std::mem::take(&mut metrics.rules)));
+        let session = self.session().expect("initialized session");
+        let config = session.effective_config().into_owned();
+        // Moved out while the rules run, so they can report progress through the server
+        let ruleset = session.ruleset.take();
+        let settings = session.settings.clone();
+        let region_languages = session.region_languages.clone();
+        let artifacts_dir = session.artifacts_dir.clone();
+        let disabled = session.disabled;
+        let diagnostics = ruleset.as_ref().map(|ruleset| {
+            let server = RefCell::new(&mut *self);
+            let progress = |event: ProgressEvent| {
+                server.borrow_mut().report_progress(ProgressEvent {
+                    id: id.to_string(),
+                    ..event
+                })
+            };
+            let env = RunEnv {
+                cancellation: Some(cancellation),
+                artifacts_dir: artifacts_dir.as_deref(),
+                profile: profile.as_ref(),
+                progress: Some(&progress),
+                ..RunEnv::new(&settings)
+            };
+            // Region-specific rulesets only see the embedded regions in their languages
+            if disabled {
+                // Still emit the event so editors clear stale results
+                Vec::new()
+            } else if params.regions.is_empty() || region_languages.is_empty() {
+                let env = RunEnv {
+                    raw_bytes: raw_bytes.as_deref(),
+                    ..env
+                };
+                run_rules(&params.uri, &content, ruleset, &config, &env)
+            } else {
+                run_rules_on_regions(
+                    &params.uri,
+                    &content,
+                    &params.regions,
+                    &region_languages,
+                    ruleset,
+                    &config,
+                    &env,
+                )
+            }
+        });
+        if let Some(session) = self.session() {
+            session.ruleset = ruleset;
+        }
+        if let Some(diagnostics) = diagnostics {
+            if let Some(metrics) = &mut self.metrics {
+                metrics.rules = profile.map(RefCell::into_inner).unwrap_or_default();
+                metrics.count("filesAnalyzed", 1);
+                metrics.count("diagnostics", diagnostics.len() as u64);
+            }
+            if cancellation.is_cancelled() {
+                self.send_cancelled("analyzeFile", id, cancellation);
+                return Ok(());
+            }
+
+            // Emit diagnostics event; the content hash is echoed back so the
+            // host can drop results for an outdated buffer
+            let event = DiagnosticsEvent {
+                uri: params.uri,
+                diagnostics,
+                content_hash: params.content_hash,
+            };
+            self.send(&Envelope::event(
+                "diagnostics",
+                serde_json::to_value(event)?,
+            ));
+        }
+
+        // Send completion response
+        self.send(&Envelope::res(
+            "analyzeFile",
+            id.to_string(),
+            json!({"ok": true}),
+        ));
+        Ok(())
+    }
+
+    fn on_set_rules(&mut self, id: &str, params: SetRulesParams) -> Result<()> {
+        if !self.initialized() {
+            self.send_error(
+                "setRules",
+                id,
+                ErrorPayload::new(error_codes::NOT_INITIALIZED, "initialize has not completed"),
+            );
+            return Ok(());
+        }
+        let session = self.session().expect("initialized session");
+        let mut unknown: Vec<&String> = params
+            .rules
+            .keys()
+            .filter(|rule_id| {
+                !session
+                    .ruleset
+                    .iter()
+                    .flat_map(|ruleset| &ruleset.rules)
+                    .any(|rule| rule.id() == rule_id.as_str())
+            })
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            let message = format!("unknown rules: {unknown:?}");
+            let details = serde_json::to_value(&unknown)?;
+            self.send_error(
+                "setRules",
+                id,
+                ErrorPayload::new(error_codes::UNKNOWN_RULE, message).with_details(details),
+            );
+            return Ok(());
+        }
+
+        if params.reset {
+            session.rule_overrides.clear();
+            session.disabled = false;
+        }
+        session.rule_overrides.extend(params.rules);
+        if let Some(enabled) = params.enabled {
+            session.disabled = !enabled;
+        }
+        let mut disabled_rules: Vec<&String> = session
+            .rule_overrides
+            .iter()
+            .filter(|(_, enabled)| !**enabled)
+            .map(|(rule_id, _)| rule_id)
+            .collect();
+        disabled_rules.sort();
+        let payload = json!({
+            "ok": true,
+            "enabled": !session.disabled,
+            "disabledRules": disabled_rules,
+        });
+        self.send(&Envelope::res("setRules", id.to_string(), payload));
+        Ok(())
+    }
+
+    fn on_cancel(&mut self, id: &str, _params: CancelParams) -> Result<()> {
+        // The reader thread flags the target as soon as the cancel line arrives;
+        // this only acknowledges it. The cancelled request answers with `cancelled`.
+        self.send(&Envelope::res(
+            "cancel",
+            id.to_string(),
+            json!({"ok": true}),
+        ));
+        Ok(())
+    }
+
+    fn on_shutdown(&mut self, id: &str) -> Result<()> {
+        // Shutting down a named session releases its state; the process keeps serving others
+        match &self.current_session {
+            Some(key) => {
+                self.sessions.remove(key);
+            }
+            None => self.shutting_down = true,
+        }
+        self.send(&Envelope::res(
+            "shutdown",
+            id.to_string(),
+            json!({"ok": true}),
+        ));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Diagnostic, MockClock, Position, PreprocessingContext, Range, Severity};
+    use crate::ruleset::{Rule, RuleContext};
+
+    /// Rule `.0` flagging the first occurrence of `.1`
+    struct Marker(&'static str, &'static str);
+
+    impl Rule for Marker {
+        fn id(&self) -> &'static str {
+            self.0
+        }
+
+        fn description(&self) -> &'static str {
+            "Flag a marker"
+        }
+
+        fn check(&self, ctx: &mut RuleContext) {
+            if let Some(character) = ctx.text.find(self.1) {
+                let start = Position {
+                    line: 0,
+                    character: character as u32,
+                };
+                let range = Range { start, end: start };
+                ctx.report(Diagnostic::new(
+                    self.0,
+                    "marker found",
+                    Severity::Warn,
+                    range,
+                ));
+            }
+        }
+    }
+
+    struct Markers;
+
+    impl RulesetOptions for Markers {
+        fn get_capabilities(&self) -> RulesetCapabilities {
+            RulesetCapabilities {
+                ruleset_id: "markers".to_string(),
+                version: "1.0.0".to_string(),
+                file_patterns: vec!["**/*.txt".to_string()],
+                max_file_size: None,
+                annotation_prefixes: vec![],
+                rules: vec![],
+                default_config: HashMap::new(),
+                config_settings: vec![],
+                dependencies: vec![],
+                region_languages: vec![],
+                features: vec![],
+                message_bundles: Default::default(),
+                requirements: vec![],
+            }
+        }
+
+        fn preprocess_files(&self, _file_uris: &[String]) -> Result<PreprocessingContext> {
+            Ok(PreprocessingContext {
+                ruleset_id: "markers".to_string(),
+                files: vec![],
+                global_context: HashMap::new(),
+            })
+        }
+
+        fn create_ruleset(&self) -> Ruleset {
+            Ruleset::new("markers")
+                .with_rule(Box::new(Marker("no-todo", "TODO")))
+                .with_rule(Box::new(Marker("no-fixme", "FIXME")))
+        }
+    }
+
+    /// In-memory output the test reads back after the server is done
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn server() -> RulesetServer {
+        RulesetServer::new(Box::new(Markers))
+    }
+
+    /// Feed `lines` to `server` as NDJSON, returning how it stopped and every
+    /// line it wrote
+    fn serve(server: &mut RulesetServer, lines: &[Value]) -> (Termination, Vec<Value>) {
+        let input: String = lines.iter().map(|line| format!("{line}\n")).collect();
+        let output = Output::default();
+        let transport = NdjsonTransport::new(std::io::Cursor::new(input), output.clone());
+        let termination = server.run_with(transport).unwrap();
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let messages = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (termination, messages)
+    }
+
+    fn request(typ: &str, id: &str, payload: Value) -> Value {
+        json!({"v": 1, "kind": "req", "type": typ, "id": id, "payload": payload})
+    }
+
+    fn in_session(mut message: Value, session: &str) -> Value {
+        message["sessionId"] = json!(session);
+        message
+    }
+
+    fn analyze(id: &str, uri: &str, content: &str) -> Value {
+        request("analyzeFile", id, json!({"uri": uri, "content": content}))
+    }
+
+    /// The reply to request `id`
+    fn reply<'a>(messages: &'a [Value], id: &str) -> &'a Value {
+        messages
+            .iter()
+            .find(|m| m["kind"] == "res" && m["id"] == id)
+            .unwrap_or_else(|| panic!("no reply to {id} in {messages:?}"))
+    }
+
+    fn error_code(messages: &[Value], id: &str) -> String {
+        reply(messages, id)["payload"]["error"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Sorted rule ids of the `diagnostics` event for `uri`
+    fn rule_ids(messages: &[Value], uri: &str) -> Vec<String> {
+        let event = messages
+            .iter()
+            .find(|m| m["type"] == "diagnostics" && m["payload"]["uri"] == uri)
+            .unwrap_or_else(|| panic!("no diagnostics for {uri} in {messages:?}"));
+        let mut ids: Vec<String> = event["payload"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["rule_id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn sessions_keep_separate_state() {
+        let mut server = server();
+        let only_todo = json!({"rulesetConfig": {"no-todo": "warn"}});
+        let (termination, messages) = serve(
+            &mut server,
+            &[
+                in_session(request("initialize", "1", only_todo), "a"),
+                in_session(analyze("2", "file:///b.txt", "TODO FIXME"), "b"),
+                in_session(analyze("3", "file:///a.txt", "TODO FIXME"), "a"),
+                analyze("4", "file:///default.txt", "TODO"),
+                in_session(request("shutdown", "5", Value::Null), "a"),
+                in_session(analyze("6", "file:///a2.txt", "TODO"), "a"),
+                request("initialize", "7", json!({})),
+                request("exit", "8", Value::Null),
+            ],
+        );
+        assert_eq!(error_code(&messages, "2"), error_codes::NOT_INITIALIZED);
+        assert_eq!(reply(&messages, "2")["sessionId"], "b");
+        assert_eq!(rule_ids(&messages, "file:///a.txt"), ["no-todo"]);
+        assert_eq!(reply(&messages, "3")["sessionId"], "a");
+        assert_eq!(error_code(&messages, "4"), error_codes::NOT_INITIALIZED);
+        assert!(reply(&messages, "4").get("sessionId").is_none());
+        // Shutting a named session down releases it without stopping the server
+        assert_eq!(reply(&messages, "5")["payload"]["ok"], true);
+        assert_eq!(error_code(&messages, "6"), error_codes::NOT_INITIALIZED);
+        assert_eq!(reply(&messages, "7")["payload"]["ok"], true);
+        assert_eq!(termination, Termination::Exit);
+    }
+
+    #[test]
+    fn files_are_analyzed_after_initialize() {
+        let mut server = server();
+        let (termination, messages) = serve(
+            &mut server,
+            &[
+                analyze("1", "file:///early.txt", "TODO"),
+                request("initialize", "2", json!({})),
+                analyze("3", "file:///a.txt", "TODO FIXME"),
+            ],
+        );
+        assert_eq!(termination, Termination::Eof);
+        assert_eq!(error_code(&messages, "1"), error_codes::NOT_INITIALIZED);
+        assert_eq!(reply(&messages, "2")["payload"]["ok"], true);
+        assert_eq!(
+            rule_ids(&messages, "file:///a.txt"),
+            ["no-fixme", "no-todo"]
+        );
+        assert_eq!(reply(&messages, "3")["payload"], json!({"ok": true}));
+        let last = messages.last().unwrap();
+        assert_eq!(last["type"], "terminated");
+        assert_eq!(last["payload"], json!({"reason": "eof"}));
+    }
+
+    #[test]
+    fn repeated_malformed_lines_end_the_loop() {
+        let mut server = server();
+        let mut lines = vec![json!({"not": "an envelope"}); MAX_PARSE_FAILURES - 1];
+        // A valid request in between resets the count
+        lines.push(request("getDefaultConfig", "1", Value::Null));
+        lines.extend(vec![json!(42); MAX_PARSE_FAILURES]);
+        let (termination, messages) = serve(&mut server, &lines);
+        assert_eq!(
+            termination,
+            Termination::ParseFailures {
+                count: MAX_PARSE_FAILURES
+            }
+        );
+        let errors = messages
+            .iter()
+            .filter(|m| m["type"] == "error" && m["payload"]["error"] == error_codes::PARSE_ERROR)
+            .count();
+        assert_eq!(errors, 2 * MAX_PARSE_FAILURES - 1);
+    }
+
+    #[test]
+    fn cancel_aborts_a_pending_request_of_its_own_session() {
+        let mut server = server();
+        let cancel = |id: &str, target: &str| request("cancel", id, json!({"id": target}));
+        // The reader registers every request of a batch before any is handled,
+        // so the cancel is seen first
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                request("initialize", "1", json!({})),
+                json!([analyze("2", "file:///a.txt", "TODO"), cancel("3", "2")]),
+                json!([
+                    analyze("4", "file:///b.txt", "TODO"),
+                    in_session(cancel("5", "4"), "other"),
+                ]),
+            ],
+        );
+        let messages: Vec<Value> = messages
+            .into_iter()
+            .flat_map(|line| match line {
+                Value::Array(batch) => batch,
+                message => vec![message],
+            })
+            .collect();
+        assert_eq!(error_code(&messages, "2"), error_codes::CANCELLED);
+        assert!(
+            !messages
+                .iter()
+                .any(|m| m["type"] == "diagnostics" && m["payload"]["uri"] == "file:///a.txt")
+        );
+        assert_eq!(reply(&messages, "3")["payload"], json!({"ok": true}));
+        assert_eq!(rule_ids(&messages, "file:///b.txt"), ["no-todo"]);
+        assert_eq!(reply(&messages, "5")["payload"], json!({"ok": true}));
+    }
+
+    #[test]
+    fn batches_are_answered_on_one_line() {
+        let mut server = server();
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                json!([
+                    request("initialize", "1", json!({})),
+                    analyze("2", "file:///a.txt", "FIXME"),
+                ]),
+                request("getDefaultConfig", "3", Value::Null),
+            ],
+        );
+        let batch = messages[0].as_array().expect("batch reply");
+        assert_eq!(reply(batch, "1")["payload"]["ok"], true);
+        assert_eq!(rule_ids(batch, "file:///a.txt"), ["no-fixme"]);
+        assert_eq!(reply(batch, "2")["payload"], json!({"ok": true}));
+        assert_eq!(
+            messages[1],
+            *reply(&messages, "3"),
+            "requests after the batch are answered on their own lines"
+        );
+        assert_eq!(
+            reply(&messages, "3")["payload"],
+            json!({"no-todo": "warn", "no-fixme": "warn"})
+        );
+    }
+
+    #[test]
+    fn requests_past_their_timeout_fail_with_deadline_exceeded() {
+        let mut server = server().with_clock(Arc::new(MockClock::new()));
+        let with_timeout = |mut message: Value, timeout_ms: u64| {
+            message["timeoutMs"] = json!(timeout_ms);
+            message
+        };
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                request("initialize", "1", json!({})),
+                with_timeout(analyze("2", "file:///a.txt", "TODO"), 0),
+                with_timeout(analyze("3", "file:///b.txt", "TODO"), 60_000),
+                with_timeout(analyze("4", "file:///c.txt", "TODO"), u64::MAX),
+            ],
+        );
+        assert_eq!(error_code(&messages, "2"), error_codes::DEADLINE_EXCEEDED);
+        assert!(
+            messages
+                .iter()
+                .any(|m| m["type"] == "log"
+                    && m["payload"]["message"] == "request deadline exceeded")
+        );
+        assert_eq!(rule_ids(&messages, "file:///b.txt"), ["no-todo"]);
+        // A timeout too large to represent is no deadline at all
+        assert_eq!(rule_ids(&messages, "file:///c.txt"), ["no-todo"]);
+    }
+
+    #[test]
+    fn auth_tokens_are_required_on_every_connection() {
+        let mut server = server().with_auth_token("s3cret");
+        let initialize =
+            |id: &str, token: &str| request("initialize", id, json!({"authToken": token}));
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                request("getCapabilities", "1", Value::Null),
+                initialize("2", "s3cres"),
+                initialize("3", "s3cret"),
+                request("getDefaultConfig", "4", Value::Null),
+            ],
+        );
+        assert_eq!(error_code(&messages, "1"), error_codes::UNAUTHORIZED);
+        assert_eq!(error_code(&messages, "2"), error_codes::UNAUTHORIZED);
+        assert_eq!(reply(&messages, "3")["payload"]["ok"], true);
+        assert_eq!(reply(&messages, "4")["payload"]["no-todo"], "warn");
+
+        let (_, messages) = serve(
+            &mut server,
+            &[request("getDefaultConfig", "1", Value::Null)],
+        );
+        assert_eq!(error_code(&messages, "1"), error_codes::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn tokens_are_compared_whole() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cre", "s3cret"));
+        assert!(!tokens_match("s3cret!", "s3cret"));
+        assert!(!tokens_match("S3cret", "s3cret"));
+        assert!(!tokens_match("", "s3cret"));
+    }
+
+    #[test]
+    fn shutdown_only_accepts_exit_afterwards() {
+        let mut server = server();
+        let (termination, messages) = serve(
+            &mut server,
+            &[
+                request("initialize", "1", json!({})),
+                request("shutdown", "2", Value::Null),
+                request("getCapabilities", "3", Value::Null),
+                request("exit", "4", Value::Null),
+                request("getCapabilities", "5", Value::Null),
+            ],
+        );
+        assert_eq!(reply(&messages, "2")["payload"], json!({"ok": true}));
+        assert_eq!(error_code(&messages, "3"), error_codes::SHUTTING_DOWN);
+        assert_eq!(termination, Termination::Shutdown);
+        // Nothing after `exit` is read
+        assert!(!messages.iter().any(|m| m["id"] == "5"));
+        let last = messages.last().unwrap();
+        assert_eq!(last["payload"], json!({"reason": "shutdown"}));
+
+        // The next connection starts without the pending shutdown
+        let (termination, messages) = serve(
+            &mut server,
+            &[
+                request("getCapabilities", "1", Value::Null),
+                request("exit", "2", Value::Null),
+            ],
+        );
+        assert_eq!(reply(&messages, "1")["payload"]["ruleset_id"], "markers");
+        assert_eq!(termination, Termination::Exit);
+    }
+
+    #[test]
+    fn set_rules_toggles_rules_and_the_whole_ruleset() {
+        let mut server = server();
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                request("setRules", "0", json!({"rules": {"no-todo": false}})),
+                request("initialize", "1", json!({})),
+                request("setRules", "2", json!({"rules": {"no-todo": false}})),
+                analyze("3", "file:///a.txt", "TODO FIXME"),
+                request("setRules", "4", json!({"rules": {"no-such-rule": true}})),
+                request("setRules", "5", json!({"enabled": false})),
+                analyze("6", "file:///b.txt", "TODO FIXME"),
+                request("setRules", "7", json!({"reset": true})),
+                analyze("8", "file:///c.txt", "TODO FIXME"),
+            ],
+        );
+        assert_eq!(error_code(&messages, "0"), error_codes::NOT_INITIALIZED);
+        assert_eq!(
+            reply(&messages, "2")["payload"],
+            json!({"ok": true, "enabled": true, "disabledRules": ["no-todo"]})
+        );
+        assert_eq!(rule_ids(&messages, "file:///a.txt"), ["no-fixme"]);
+        assert_eq!(error_code(&messages, "4"), error_codes::UNKNOWN_RULE);
+        assert_eq!(
+            reply(&messages, "4")["payload"]["details"],
+            json!(["no-such-rule"])
+        );
+        assert_eq!(reply(&messages, "5")["payload"]["enabled"], false);
+        // A disabled ruleset still clears stale results with an empty event
+        assert!(rule_ids(&messages, "file:///b.txt").is_empty());
+        assert_eq!(
+            reply(&messages, "7")["payload"],
+            json!({"ok": true, "enabled": true, "disabledRules": []})
+        );
+        assert_eq!(
+            rule_ids(&messages, "file:///c.txt"),
+            ["no-fixme", "no-todo"]
+        );
+    }
+
+    #[test]
+    fn set_rules_enables_rules_missing_from_the_config() {
+        let mut server = server();
+        let config = json!({"rulesetConfig": {"no-todo": "warn"}});
+        let (_, messages) = serve(
+            &mut server,
+            &[
+                request("initialize", "1", config),
+                analyze("2", "file:///a.txt", "TODO FIXME"),
+                request("setRules", "3", json!({"rules": {"no-fixme": true}})),
+                analyze("4", "file:///b.txt", "TODO FIXME"),
+            ],
+        );
+        assert_eq!(rule_ids(&messages, "file:///a.txt"), ["no-todo"]);
+        assert_eq!(
+            rule_ids(&messages, "file:///b.txt"),
+            ["no-fixme", "no-todo"]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Terminal capability detection shared by all human-facing output.
+//!
+//! [`Terminal::detect`] decides once whether a stream is a TTY, how many
+//! colors it takes and how wide it is, so formatters and progress reporting
+//! agree instead of each probing on its own. `NO_COLOR` turns color off,
+//! `FORCE_COLOR` turns it on (`0`/`false` off, `1`-`3` picks the depth),
+//! `TERM=dumb` means no color, and CI services known to render ANSI get basic
+//! color even without a TTY. `COLUMNS` sets the width.
+
+use std::io::IsTerminal;
+
+/// Width assumed when the terminal does not say
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// CI services by the variable that identifies them, and whether their log
+/// viewer renders ANSI colors
+const CI_SERVICES: &[(&str, &str, bool)] = &[
+    ("GITHUB_ACTIONS", "github-actions", true),
+    ("GITLAB_CI", "gitlab", true),
+    ("BUILDKITE", "buildkite", true),
+    ("CIRCLECI", "circleci", true),
+    ("TF_BUILD", "azure-pipelines", true),
+    ("TEAMCITY_VERSION", "teamcity", false),
+    ("JENKINS_URL", "jenkins", false),
+    ("CI", "ci", false),
+];
+
+/// Which standard stream output goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn is_terminal(self) -> bool {
+        match self {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// How many colors a terminal can show, from none to 24-bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColorDepth {
+    #[default]
+    None,
+    /// The 16 standard ANSI colors
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+/// Text styles human-facing output uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Bold,
+    Dim,
+    Underline,
+    Red,
+    Yellow,
+    Blue,
+    Cyan,
+    Green,
+    Magenta,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Bold => "1",
+            Style::Dim => "2",
+            Style::Underline => "4",
+            Style::Red => "31",
+            Style::Yellow => "33",
+            Style::Blue => "34",
+            Style::Cyan => "36",
+            Style::Green => "32",
+            Style::Magenta => "35",
+        }
+    }
+}
+
+/// What an output stream supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminal {
+    pub is_tty: bool,
+    pub color: ColorDepth,
+    /// Columns, when known
+    pub width: Option<usize>,
+    /// Name of the CI service output is running under, if any
+    pub ci: Option<&'static str>,
+}
+
+impl Terminal {
+    /// Detect from the process environment and `stream`
+    pub fn detect(stream: Stream) -> Self {
+        Self::from_env(stream.is_terminal(), |k| std::env::var(k).ok())
+    }
+
+    /// Detect from a custom variable getter, `is_tty` telling whether the stream
+    /// is a terminal
+    pub fn from_env<F: Fn(&str) -> Option<String>>(is_tty: bool, get: F) -> Self {
+        let ci_service = CI_SERVICES
+            .iter()
+            .find(|(var, _, _)| get(var).is_some_and(|v| !v.is_empty() && v != "false"));
+        Self {
+            is_tty,
+            color: color_depth(is_tty, ci_service.is_some_and(|s| s.2), &get),
+            width: get("COLUMNS")
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|&w| w > 0),
+            ci: ci_service.map(|s| s.1),
+        }
+    }
+
+    /// Plain output: no TTY, no color, unknown width
+    pub fn plain() -> Self {
+        Self {
+            is_tty: false,
+            color: ColorDepth::None,
+            width: None,
+            ci: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: ColorDepth) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn has_color(&self) -> bool {
+        self.color > ColorDepth::None
+    }
+
+    /// Whether output can redraw in place (progress bars, spinners)
+    pub fn is_interactive(&self) -> bool {
+        self.is_tty && self.ci.is_none()
+    }
+
+    /// Columns available, [`DEFAULT_WIDTH`] when unknown
+    pub fn width(&self) -> usize {
+        self.width.unwrap_or(DEFAULT_WIDTH)
+    }
+
+    /// `text` wrapped in the escape codes for `styles`, unchanged without color
+    pub fn paint(&self, text: &str, styles: &[Style]) -> String {
+        if !self.has_color() || styles.is_empty() || text.is_empty() {
+            return text.to_string();
+        }
+        let codes: Vec<&str> = styles.iter().map(|s| s.code()).collect();
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+    }
+
+    /// `text` cut to fit the terminal width, ending in "…" when shortened
+    pub fn fit(&self, text: &str) -> String {
+        truncate(text, self.width())
+    }
+}
+
+/// `text` cut to at most `width` characters, ending in "…" when shortened
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+/// Number of characters `text` shows, ignoring ANSI escape sequences
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+fn color_depth<F: Fn(&str) -> Option<String>>(
+    is_tty: bool,
+    ci_renders_color: bool,
+    get: &F,
+) -> ColorDepth {
+    if get("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return ColorDepth::None;
+    }
+    if let Some(force) = get("FORCE_COLOR") {
+        match force.trim() {
+            "0" | "false" => return ColorDepth::None,
+            "2" => return ColorDepth::Ansi256,
+            "3" => return ColorDepth::TrueColor,
+            _ => return ColorDepth::Basic.max(term_depth(get)),
+        }
+    }
+    if !is_tty && !ci_renders_color {
+        return ColorDepth::None;
+    }
+    term_depth(get)
+}
+
+/// Depth advertised by `COLORTERM` and `TERM`, at least basic unless `TERM=dumb`
+fn term_depth<F: Fn(&str) -> Option<String>>(get: &F) -> ColorDepth {
+    let term = get("TERM").unwrap_or_default();
+    if term == "dumb" {
+        return ColorDepth::None;
+    }
+    let colorterm = get("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        ColorDepth::TrueColor
+    } else if term.contains("256color") {
+        ColorDepth::Ansi256
+    } else {
+        ColorDepth::Basic
+    }
+}
@@ -0,0 +1,209 @@
+//! Record every message of a connection to a file, and feed a recording back
+//! into a server to debug protocol issues offline.
+//!
+//! A trace is NDJSON, one [`TraceEntry`] per line. Wrap any transport in a
+//! [`TracingTransport`] to record it, then pass a [`ReplayTransport`] to
+//! `RulesetServer::run_with` to feed the recording back in. Auth tokens are
+//! replaced with [`REDACTED`] before a message is written, since traces are
+//! meant to be shared.
+
+use crate::core::Ndjson;
+use crate::transport::{MessageReader, MessageWriter, Transport};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Which way a message travelled, seen from the side that recorded it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// One line of a trace file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub direction: Direction,
+    pub message: Value,
+}
+
+type TraceSink = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Transport wrapper that appends every message read or written to a trace.
+/// Lines that are not valid JSON are not recorded.
+pub struct TracingTransport<T> {
+    inner: T,
+    sink: TraceSink,
+}
+
+impl<T: Transport> TracingTransport<T> {
+    pub fn new(inner: T, trace: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            sink: Arc::new(Mutex::new(Box::new(trace))),
+        }
+    }
+
+    /// Record into a new file at `path`, replacing any previous trace
+    pub fn to_file(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(inner, BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<T: Transport> Transport for TracingTransport<T> {
+    fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>) {
+        let (reader, writer) = self.inner.into_parts();
+        (
+            Box::new(TracingReader {
+                inner: reader,
+                sink: self.sink.clone(),
+            }),
+            Box::new(TracingWriter {
+                inner: writer,
+                sink: self.sink,
+            }),
+        )
+    }
+}
+
+struct TracingReader {
+    inner: Box<dyn MessageReader>,
+    sink: TraceSink,
+}
+
+impl MessageReader for TracingReader {
+    fn read_message(&mut self) -> io::Result<Value> {
+        let message = self.inner.read_message()?;
+        record(&self.sink, Direction::In, message.clone());
+        Ok(message)
+    }
+}
+
+struct TracingWriter {
+    inner: Box<dyn MessageWriter>,
+    sink: TraceSink,
+}
+
+impl MessageWriter for TracingWriter {
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.inner.write_message(message)?;
+        if let Ok(value) = serde_json::from_str(message) {
+            record(&self.sink, Direction::Out, value);
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for secrets in recorded messages
+pub const REDACTED: &str = "[redacted]";
+
+/// Blank `authToken` in an envelope's `payload` or a JSON-RPC message's
+/// `params`, in each member of a batch
+fn redact(message: &mut Value) {
+    match message {
+        Value::Array(batch) => batch.iter_mut().for_each(redact),
+        Value::Object(map) => {
+            for key in ["payload", "params"] {
+                if let Some(token) = map.get_mut(key).and_then(|body| body.get_mut("authToken")) {
+                    *token = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Append one entry; tracing is a debugging aid, so failures never break the
+/// connection being traced
+fn record(sink: &TraceSink, direction: Direction, mut message: Value) {
+    redact(&mut message);
+    let entry = TraceEntry {
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        direction,
+        message,
+    };
+    if let Ok(mut sink) = sink.lock()
+        && serde_json::to_writer(&mut *sink, &entry).is_ok()
+    {
+        let _ = sink.write_all(b"\n").and_then(|_| sink.flush());
+    }
+}
+
+/// Read every entry of a trace file, in recorded order
+pub fn read_trace(path: impl AsRef<Path>) -> io::Result<Vec<TraceEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+    }
+    Ok(entries)
+}
+
+/// Transport that serves the recorded messages of one direction as input and
+/// writes whatever the server answers to `output`. Recorded on the server
+/// side, the server's input is [`Direction::In`]; recorded on the host side,
+/// it is [`Direction::Out`].
+pub struct ReplayTransport<W> {
+    messages: Vec<Value>,
+    output: W,
+}
+
+impl<W: Write + Send + 'static> ReplayTransport<W> {
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = TraceEntry>,
+        direction: Direction,
+        output: W,
+    ) -> Self {
+        Self {
+            messages: entries
+                .into_iter()
+                .filter(|entry| entry.direction == direction)
+                .map(|entry| entry.message)
+                .collect(),
+            output,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>, direction: Direction, output: W) -> io::Result<Self> {
+        Ok(Self::from_entries(read_trace(path)?, direction, output))
+    }
+}
+
+impl<W: Write + Send + 'static> Transport for ReplayTransport<W> {
+    fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>) {
+        (
+            Box::new(ReplayReader {
+                messages: self.messages.into_iter(),
+            }),
+            Box::new(Ndjson::new(self.output)),
+        )
+    }
+}
+
+struct ReplayReader {
+    messages: std::vec::IntoIter<Value>,
+}
+
+impl MessageReader for ReplayReader {
+    fn read_message(&mut self) -> io::Result<Value> {
+        self.messages
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "input closed"))
+    }
+}
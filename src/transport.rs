@@ -0,0 +1,1248 @@
+//! Byte-stream transports that servers read requests from and write replies to,
+//! and the [`Correlator`] hosts use to talk to a server over one.
+
+use crate::core::{
+    Clock, Envelope, Kind, LogEvent, Ndjson, PROTOCOL_VERSION, RequestId, StrayLine, SystemClock,
+    read_line_value_from,
+};
+use crate::protocol::{ProtocolViolation, Request, ServerExit, check_event, check_reply};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::process::{Child, ExitStatus};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Source of inbound messages. Returns `UnexpectedEof` once the peer is gone and
+/// `InvalidData` for a message that is not valid JSON.
+pub trait MessageReader: Send {
+    fn read_message(&mut self) -> io::Result<Value>;
+}
+
+/// Sink for outbound messages, each already serialized as one JSON document
+pub trait MessageWriter: Send {
+    fn write_message(&mut self, message: &str) -> io::Result<()>;
+}
+
+/// Whether a read result ends the input; malformed messages do not
+pub(crate) fn is_final_read(message: &io::Result<Value>) -> bool {
+    matches!(message, Err(e) if e.kind() != io::ErrorKind::InvalidData)
+}
+
+/// Bidirectional channel a server runs over. It is split so a reader thread can
+/// watch for `cancel` while replies are written.
+pub trait Transport {
+    fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>);
+}
+
+/// Reads one JSON message per line
+pub struct NdjsonReader<R> {
+    reader: R,
+}
+
+impl<R: BufRead + Send> NdjsonReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: BufRead + Send> MessageReader for NdjsonReader<R> {
+    fn read_message(&mut self) -> io::Result<Value> {
+        read_line_value_from(&mut self.reader)
+    }
+}
+
+impl<W: Write + Send> MessageWriter for Ndjson<W> {
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.send_line(message)
+    }
+}
+
+/// NDJSON over any reader/writer pair, e.g. pipes, sockets or in-memory buffers
+pub struct NdjsonTransport<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> NdjsonTransport<R, W>
+where
+    R: BufRead + Send + 'static,
+    W: Write + Send + 'static,
+{
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl NdjsonTransport<io::BufReader<io::Stdin>, io::BufWriter<io::Stdout>> {
+    /// The process's stdin and stdout
+    pub fn stdio() -> Self {
+        Self::new(
+            io::BufReader::new(io::stdin()),
+            io::BufWriter::new(io::stdout()),
+        )
+    }
+}
+
+/// NDJSON over a TCP connection
+pub type TcpTransport = NdjsonTransport<io::BufReader<TcpStream>, io::BufWriter<TcpWriter>>;
+
+/// Write half of a TCP connection. Dropping it shuts the connection down in
+/// both directions, so the peer sees the end of the stream and a reader
+/// blocked on the other half returns.
+pub struct TcpWriter(TcpStream);
+
+impl Write for TcpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Drop for TcpWriter {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
+
+impl TcpTransport {
+    /// NDJSON over an established TCP connection
+    pub fn tcp(stream: TcpStream) -> io::Result<Self> {
+        let reader = stream.try_clone()?;
+        Ok(Self::new(
+            io::BufReader::new(reader),
+            io::BufWriter::new(TcpWriter(stream)),
+        ))
+    }
+
+    /// Connect to a server listening on `addr`, e.g. a ruleset on a remote CI host
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::tcp(TcpStream::connect(addr)?)
+    }
+
+    /// Connect to `address` (`host:port`), giving up after `timeout` per
+    /// resolved address
+    pub fn connect_timeout(address: &str, timeout: Duration) -> io::Result<Self> {
+        let mut last_error = None;
+        for addr in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Self::tcp(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{address} resolved to no addresses"),
+            )
+        }))
+    }
+}
+
+impl<R, W> Transport for NdjsonTransport<R, W>
+where
+    R: BufRead + Send + 'static,
+    W: Write + Send + 'static,
+{
+    fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>) {
+        (
+            Box::new(NdjsonReader::new(self.reader)),
+            Box::new(Ndjson::new(self.writer)),
+        )
+    }
+}
+
+/// Host end of a connection: issues requests and matches replies by `id`, so
+/// several requests may be in flight and events may arrive in any order.
+/// Messages are read on a background thread so waits can honour deadlines.
+pub struct Correlator {
+    inbox: Receiver<io::Result<Value>>,
+    writer: Box<dyn MessageWriter>,
+    /// Ids sent but not yet returned by [`Correlator::wait`], with their type
+    pending: HashMap<String, &'static str>,
+    /// When each request sent with a timeout must have been answered
+    deadlines: HashMap<String, Instant>,
+    /// Replies read while waiting for a different id
+    responses: HashMap<String, Envelope<Value>>,
+    /// Events read while waiting for a reply, oldest first
+    events: VecDeque<Envelope<Value>>,
+    /// Trace id and parent request id stamped on every request sent
+    trace: (Option<String>, Option<String>),
+    /// Receives `log` events instead of the event buffer
+    log_handler: Option<Box<dyn FnMut(LogEvent) + Send>>,
+    /// Fail on protocol irregularities instead of tolerating them
+    strict: bool,
+    /// Ids whose reply has been read, kept in strict mode to catch late events
+    answered: HashSet<String>,
+    /// Version stamped on requests; the one agreed in `initialize` once answered
+    protocol_version: u8,
+    /// The last request written, as sent
+    last_request: Option<String>,
+    /// The last line read that was not JSON
+    last_stray_line: Option<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Correlator {
+    pub fn new(transport: impl Transport) -> Self {
+        let (mut reader, writer) = transport.into_parts();
+        let (tx, inbox) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            loop {
+                let message = reader.read_message();
+                let done = is_final_read(&message);
+                if tx.send(message).is_err() || done {
+                    break;
+                }
+            }
+        });
+        Self {
+            inbox,
+            writer,
+            pending: HashMap::new(),
+            deadlines: HashMap::new(),
+            responses: HashMap::new(),
+            events: VecDeque::new(),
+            trace: (None, None),
+            log_handler: None,
+            strict: false,
+            answered: HashSet::new(),
+            protocol_version: PROTOCOL_VERSION,
+            last_request: None,
+            last_stray_line: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Measure request deadlines with `clock`. With a
+    /// [`MockClock`](crate::core::MockClock), a request whose deadline the
+    /// clock has been advanced past times out at the next [`Correlator::wait`];
+    /// one that has not still waits for input in real time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Fail fast on protocol irregularities: lines that are not JSON,
+    /// malformed envelopes or ids,
+    /// unknown event types, payloads not matching their documented shape,
+    /// requests from the server, replies to ids not in flight or of the wrong
+    /// type, and events arriving after the reply to their parent request.
+    /// [`Correlator::wait`] then returns an `InvalidData` error wrapping a
+    /// [`ProtocolViolation`]. Meant for SDK and ruleset development.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Hand every `log` event to `handler` as it is read, rather than
+    /// buffering it for [`Correlator::take_events`]. With the `tracing`
+    /// feature, pass `|event| event.emit()` to forward to `tracing`.
+    pub fn with_log_handler(mut self, handler: impl FnMut(LogEvent) + Send + 'static) -> Self {
+        self.log_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Join subsequent requests to `trace_id`, as children of `parent_id`. A
+    /// linter handling a traced request passes that request's trace and id so
+    /// ruleset calls show up under it.
+    pub fn set_trace(&mut self, trace_id: Option<String>, parent_id: Option<String>) {
+        self.trace = (trace_id, parent_id);
+    }
+
+    /// Send `request` under a fresh id and return that id without waiting
+    pub fn send(&mut self, request: Request) -> io::Result<String> {
+        self.send_envelope(request, None)
+    }
+
+    /// Like [`Correlator::send`], but the server is told to give up after
+    /// `timeout` and [`Correlator::wait`] returns `TimedOut` once it passes
+    pub fn send_with_timeout(&mut self, request: Request, timeout: Duration) -> io::Result<String> {
+        self.send_envelope(request, Some(timeout))
+    }
+
+    fn send_envelope(&mut self, request: Request, timeout: Option<Duration>) -> io::Result<String> {
+        let id = String::from(RequestId::new());
+        let method = request.method();
+        let mut envelope = request
+            .into_envelope(id.clone())
+            .with_trace(self.trace.0.clone(), self.trace.1.clone());
+        envelope.v = self.protocol_version;
+        if let Some(timeout) = timeout {
+            envelope =
+                envelope.with_timeout(u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX));
+            // A timeout too large to represent leaves the wait unbounded
+            if let Some(deadline) = self.clock.now().checked_add(timeout) {
+                self.deadlines.insert(id.clone(), deadline);
+            }
+        }
+        let message = serde_json::to_string(&envelope)?;
+        self.writer.write_message(&message)?;
+        self.last_request = Some(message);
+        self.pending.insert(id.clone(), method);
+        Ok(id)
+    }
+
+    /// Block until the reply to `id` arrives or its deadline passes. Other
+    /// replies and events read in the meantime are buffered rather than
+    /// dropped. After a timeout the id is forgotten and a late reply is
+    /// discarded; an unresponsive server should be restarted.
+    pub fn wait(&mut self, id: &str) -> io::Result<Envelope<Value>> {
+        if !self.pending.contains_key(id) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no request in flight with id {id}"),
+            ));
+        }
+        loop {
+            if let Some(response) = self.responses.remove(id) {
+                self.forget(id);
+                return Ok(response);
+            }
+            let message = match self.deadlines.get(id) {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(self.clock.now());
+                    match self.inbox.recv_timeout(remaining) {
+                        Ok(message) => message,
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.forget(id);
+                            return Err(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                format!("request {id} timed out"),
+                            ));
+                        }
+                        Err(RecvTimeoutError::Disconnected) => Err(input_closed()),
+                    }
+                }
+                None => self.inbox.recv().unwrap_or_else(|_| Err(input_closed())),
+            };
+            self.receive(message)?;
+        }
+    }
+
+    /// Take in what the peer writes until its output closes or `timeout`
+    /// passes, buffering replies and events as [`Correlator::wait`] does; e.g.
+    /// to see the last output of a process that exited
+    pub fn drain(&mut self, timeout: Duration) {
+        let deadline = self.clock.now().checked_add(timeout);
+        loop {
+            let remaining = deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_duration_since(self.clock.now())
+            });
+            match self.inbox.recv_timeout(remaining) {
+                Ok(message) if !is_final_read(&message) => {
+                    let _ = self.receive(message);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Buffer one message read from the peer
+    fn receive(&mut self, message: io::Result<Value>) -> io::Result<()> {
+        if let Err(e) = &message
+            && let Some(stray) = e.get_ref().and_then(|e| e.downcast_ref::<StrayLine>())
+        {
+            self.last_stray_line = Some(stray.line.clone());
+        }
+        // Stray output (e.g. a `println!` in a rule) is skipped unless strict
+        let envelope: Envelope<Value> = match message.and_then(|message| {
+            serde_json::from_value(message)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }) {
+            Ok(envelope) => envelope,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData && !self.strict => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if self.strict {
+            self.check_strict(&envelope)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        match envelope.kind {
+            Kind::Res => {
+                // Replies to unknown ids (e.g. timed out) are dropped
+                if let Some(reply_id) = envelope.id.clone()
+                    && self.pending.contains_key(&reply_id)
+                    && !self.responses.contains_key(&reply_id)
+                {
+                    if self.strict {
+                        self.answered.insert(reply_id.clone());
+                    }
+                    if envelope.typ == "initialize"
+                        && let Some(version) = negotiated_version(&envelope)
+                    {
+                        self.protocol_version = version;
+                    }
+                    self.responses.insert(reply_id, envelope);
+                }
+            }
+            Kind::Event => self.on_event(envelope),
+            Kind::Req => {}
+        }
+        Ok(())
+    }
+
+    fn check_strict(&self, envelope: &Envelope<Value>) -> Result<(), ProtocolViolation> {
+        envelope.validate()?;
+        match envelope.kind {
+            Kind::Req => Err(ProtocolViolation::UnexpectedRequest(envelope.typ.clone())),
+            Kind::Res => {
+                let id = envelope.id.clone().unwrap_or_default();
+                let expected = match self.pending.get(&id) {
+                    Some(expected) if !self.responses.contains_key(&id) => *expected,
+                    _ => return Err(ProtocolViolation::UnknownReply(id)),
+                };
+                if envelope.typ != expected {
+                    return Err(ProtocolViolation::ReplyType {
+                        id,
+                        expected: expected.to_string(),
+                        actual: envelope.typ.clone(),
+                    });
+                }
+                Ok(check_reply(envelope)?)
+            }
+            Kind::Event => {
+                if let Some(parent_id) = &envelope.parent_id
+                    && self.answered.contains(parent_id)
+                {
+                    return Err(ProtocolViolation::LateEvent {
+                        typ: envelope.typ.clone(),
+                        id: parent_id.clone(),
+                    });
+                }
+                Ok(check_event(envelope)?)
+            }
+        }
+    }
+
+    fn on_event(&mut self, envelope: Envelope<Value>) {
+        if envelope.typ == "log"
+            && let Some(handler) = &mut self.log_handler
+            && let Some(event) = envelope
+                .payload
+                .clone()
+                .and_then(|payload| serde_json::from_value::<LogEvent>(payload).ok())
+        {
+            handler(event);
+            return;
+        }
+        self.events.push_back(envelope);
+    }
+
+    fn forget(&mut self, id: &str) {
+        self.pending.remove(id);
+        self.deadlines.remove(id);
+    }
+
+    /// Send `request` and wait for its reply
+    pub fn call(&mut self, request: Request) -> io::Result<Envelope<Value>> {
+        let id = self.send(request)?;
+        self.wait(&id)
+    }
+
+    /// Send `request` and wait at most `timeout` for its reply
+    pub fn call_with_timeout(
+        &mut self,
+        request: Request,
+        timeout: Duration,
+    ) -> io::Result<Envelope<Value>> {
+        let id = self.send_with_timeout(request, timeout)?;
+        self.wait(&id)
+    }
+
+    /// Two-phase stop: wait up to `timeout` for the `shutdown` reply, then send
+    /// `exit`. The server may still be flushing; pair with [`wait_or_kill`].
+    pub fn shutdown(&mut self, timeout: Duration) -> io::Result<()> {
+        self.call_with_timeout(Request::Shutdown, timeout)?;
+        let mut exit = Envelope::event(Request::Exit.method(), Value::Object(Default::default()));
+        exit.v = self.protocol_version;
+        self.writer.write_message(&serde_json::to_string(&exit)?)
+    }
+
+    /// Protocol version in use: the one the server agreed to in its
+    /// `initialize` reply, or [`PROTOCOL_VERSION`] before that
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    /// Events buffered so far, oldest first
+    pub fn take_events(&mut self) -> Vec<Envelope<Value>> {
+        self.events.drain(..).collect()
+    }
+
+    /// Number of requests sent whose replies have not been collected
+    pub fn in_flight(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The last request sent, serialized as it went over the wire, e.g. for
+    /// a [`crate::cache::CrashReport`]
+    pub fn last_request(&self) -> Option<&str> {
+        self.last_request.as_deref()
+    }
+
+    /// The last line the peer wrote that was not a JSON message
+    pub fn last_stray_line(&self) -> Option<&str> {
+        self.last_stray_line.as_deref()
+    }
+}
+
+/// Version an `initialize` reply agreed to, when this SDK supports it
+fn negotiated_version(reply: &Envelope<Value>) -> Option<u8> {
+    let version = reply.payload.as_ref()?.get("protocolVersion")?.as_u64()?;
+    let supported = crate::protocol::VersionRange::supported();
+    u8::try_from(version)
+        .ok()
+        .filter(|version| (supported.min..=supported.max).contains(version))
+}
+
+/// Wait up to `timeout` for `child` to exit, then kill it so no server
+/// process outlives its host
+pub fn wait_or_kill(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    wait_or_kill_with(child, timeout, &SystemClock)
+}
+
+/// [`wait_or_kill`], polling and sleeping on `clock`
+pub fn wait_or_kill_with(
+    child: &mut Child,
+    timeout: Duration,
+    clock: &dyn Clock,
+) -> io::Result<ExitStatus> {
+    let deadline = clock.now().checked_add(timeout);
+    while deadline.is_none_or(|deadline| clock.now() < deadline) {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        clock.sleep(Duration::from_millis(10));
+    }
+    child.kill()?;
+    child.wait()
+}
+
+/// How long a host gives a server that broke off a handshake to exit
+#[cfg(feature = "linter-host")]
+const EXIT_GRACE: Duration = Duration::from_millis(200);
+
+/// `child`'s status if it exits on its own within `grace`
+#[cfg(feature = "linter-host")]
+fn exited_within(child: &mut Child, grace: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + grace;
+    loop {
+        if let Some(status) = child.try_wait().ok()? {
+            return Some(status);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Why a server process ended, read through the [`ServerExit`] convention,
+/// e.g. "panicked (exit code 101)" or "exit code 3"
+pub fn describe_exit(status: &ExitStatus) -> String {
+    match status.code() {
+        Some(code) => match ServerExit::from_code(code) {
+            Some(exit) => exit.to_string(),
+            None => format!("exit code {}", code),
+        },
+        None => {
+            #[cfg(unix)]
+            if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(status) {
+                return format!("killed by signal {}", signal);
+            }
+            "terminated without an exit code".to_string()
+        }
+    }
+}
+
+/// A declared [`crate::core::Requirement`] this machine does not meet
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RequirementError {
+    #[error("`{binary}` not found")]
+    Missing { binary: String },
+    #[error("`{binary}` {found} does not satisfy `{wanted}`")]
+    Version {
+        binary: String,
+        found: String,
+        wanted: String,
+    },
+    #[error("could not read a version from `{binary} {args}`")]
+    UnknownVersion { binary: String, args: String },
+    #[error("`{binary}` requirement has an invalid version range `{range}`")]
+    InvalidRange { binary: String, range: String },
+    #[error("`{binary}` could not be run: {reason}")]
+    Failed { binary: String, reason: String },
+}
+
+/// Check each requirement by running its binary with its version arguments
+/// (stdout, then stderr, is searched for a version), giving each `timeout`.
+/// Returns the ones that are not met, in order.
+pub fn check_requirements(
+    requirements: &[crate::core::Requirement],
+    timeout: Duration,
+) -> Vec<RequirementError> {
+    requirements
+        .iter()
+        .filter_map(|requirement| check_requirement(requirement, timeout).err())
+        .collect()
+}
+
+/// Read `pipe` to its end on a separate thread
+fn drain<R: io::Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut out);
+        }
+        out
+    })
+}
+
+fn check_requirement(
+    requirement: &crate::core::Requirement,
+    timeout: Duration,
+) -> Result<(), RequirementError> {
+    use std::process::{Command, Stdio};
+
+    let binary = requirement.binary.clone();
+    let failed = |e: io::Error| match e.kind() {
+        io::ErrorKind::NotFound => RequirementError::Missing {
+            binary: binary.clone(),
+        },
+        _ => RequirementError::Failed {
+            binary: binary.clone(),
+            reason: e.to_string(),
+        },
+    };
+    let mut child = Command::new(&requirement.binary)
+        .args(&requirement.version_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+    // Drain both pipes while polling, so a chatty tool cannot fill one and
+    // block before exiting
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let deadline = Instant::now().checked_add(timeout);
+    while child.try_wait().map_err(failed)?.is_none() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(RequirementError::Failed {
+                binary,
+                reason: format!("no answer within {:?}", timeout),
+            });
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let Some(range) = &requirement.version else {
+        return Ok(());
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let stdout = String::from_utf8_lossy(&stdout);
+    let stderr = String::from_utf8_lossy(&stderr);
+    let found = crate::core::parse_tool_version(&stdout)
+        .or_else(|| crate::core::parse_tool_version(&stderr))
+        .ok_or_else(|| RequirementError::UnknownVersion {
+            binary: binary.clone(),
+            args: requirement.version_args.join(" "),
+        })?;
+    match crate::core::version_matches(found, range) {
+        Some(true) => Ok(()),
+        Some(false) => Err(RequirementError::Version {
+            binary,
+            found: found.to_string(),
+            wanted: range.clone(),
+        }),
+        None => Err(RequirementError::InvalidRange {
+            binary,
+            range: range.clone(),
+        }),
+    }
+}
+
+fn input_closed() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "input closed")
+}
+
+/// Why an enabled ruleset could not be started
+#[cfg(feature = "linter-host")]
+#[derive(Debug, thiserror::Error)]
+pub enum StartError {
+    #[error(transparent)]
+    Config(#[from] crate::config::ConfigError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The ruleset answered `initialize` with an error
+    #[error("initialize failed: {0}")]
+    Rejected(String),
+    /// The process wrote `output` that is not the protocol and never answered
+    /// `initialize`, as a binary that is not a ruleset does with its usage
+    /// text; `source` ([`StartError::Io`] or [`StartError::Exited`]) is how
+    /// the handshake ended
+    #[error(
+        "not a forseti ruleset: it printed {output:?} instead of answering initialize ({source})"
+    )]
+    NotARuleset {
+        output: String,
+        source: Box<StartError>,
+    },
+    /// Tools the ruleset declares in its capabilities are missing or too old
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Requirements(Vec<RequirementError>),
+    /// The spawned process ended before answering; see [`describe_exit`]
+    #[error("ruleset process {}", describe_exit(.0))]
+    Exited(ExitStatus),
+    /// A spawned process broke off the handshake (`source` is
+    /// [`StartError::Io`], [`StartError::Exited`] or
+    /// [`StartError::NotARuleset`]); `report` is the
+    /// [`crate::cache::CrashReport`] written for it, unless writing failed
+    #[error("{source}{}", crash_details(.stderr_tail, .report.as_deref()))]
+    Crashed {
+        source: Box<StartError>,
+        /// Last lines the process wrote to stderr
+        stderr_tail: Vec<String>,
+        report: Option<std::path::PathBuf>,
+    },
+}
+
+#[cfg(feature = "linter-host")]
+fn crash_details(stderr_tail: &[String], report: Option<&std::path::Path>) -> String {
+    let mut details = String::new();
+    if let Some(line) = stderr_tail
+        .iter()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+    {
+        details.push_str(&format!(": {}", line.trim()));
+    }
+    if let Some(report) = report {
+        details.push_str(&format!(" (crash report: {})", report.display()));
+    }
+    details
+}
+
+/// Last lines a spawned ruleset wrote to stderr, kept while it runs so a
+/// [`crate::cache::CrashReport`] can be written if it dies. Each line is also
+/// passed on to this process's stderr as it is read.
+#[cfg(feature = "linter-host")]
+#[derive(Debug, Clone, Default)]
+pub struct StderrTail {
+    lines: Arc<std::sync::Mutex<VecDeque<String>>>,
+}
+
+#[cfg(feature = "linter-host")]
+impl StderrTail {
+    /// Read `pipe` to its end on a separate thread, keeping the last
+    /// [`crate::cache::CRASH_STDERR_LINES`] lines
+    pub fn capture<R: io::Read + Send + 'static>(pipe: R) -> (Self, std::thread::JoinHandle<()>) {
+        let tail = Self::default();
+        let lines = tail.lines.clone();
+        let handle = std::thread::spawn(move || {
+            for line in io::BufReader::new(pipe).split(b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                let line = String::from_utf8_lossy(&line)
+                    .trim_end_matches('\r')
+                    .to_string();
+                eprintln!("{line}");
+                let mut lines = lines.lock().unwrap_or_else(|e| e.into_inner());
+                if lines.len() == crate::cache::CRASH_STDERR_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        });
+        (tail, handle)
+    }
+
+    /// Lines kept so far, oldest first, joined by newlines
+    pub fn text(&self) -> String {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A ruleset started and initialized by [`start_from_config`]
+#[cfg(feature = "linter-host")]
+pub struct StartedRuleset {
+    pub ruleset_id: String,
+    pub correlator: Correlator,
+    /// The spawned process; None for rulesets reached over TCP
+    pub child: Option<Child>,
+    /// What the spawned process writes to stderr; None for rulesets reached over TCP
+    pub stderr: Option<StderrTail>,
+    pub initialized: crate::core::InitializeResult,
+    pub capabilities: crate::core::RulesetCapabilities,
+}
+
+/// Outcome of [`start_from_config`]: the rulesets that came up and the ones
+/// that did not, in ruleset id order
+#[cfg(feature = "linter-host")]
+#[derive(Default)]
+pub struct StartReport {
+    pub started: Vec<StartedRuleset>,
+    pub failed: Vec<(String, StartError)>,
+}
+
+#[cfg(feature = "linter-host")]
+impl StartReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.failed
+            .iter()
+            .map(|(id, e)| format!("{id}: {e}"))
+            .collect()
+    }
+}
+
+/// Start every enabled ruleset in `cfg`, send it `initialize` and fetch its
+/// capabilities, waiting up to `timeout` for each to connect and answer.
+/// Spawned rulesets whose [`crate::core::Requirement`]s are not met fail with
+/// [`StartError::Requirements`]. Rulesets start in parallel, one thread each,
+/// and one failing does not stop the others; processes that fail to start are
+/// killed. A spawned process that breaks off the handshake fails with
+/// [`StartError::Crashed`], its crash report written under `cache_dir`.
+/// Correlators are strict when `linter.strict_protocol` is set.
+#[cfg(feature = "linter-host")]
+pub fn start_from_config(
+    cfg: &crate::core::SharedConfig,
+    cache_dir: &std::path::Path,
+    timeout: Duration,
+) -> StartReport {
+    let mut report = StartReport::default();
+    let cfg = cfg.get();
+    let strict = cfg.linter.strict_protocol;
+    let specs: Vec<_> = cfg
+        .enabled_ruleset_ids()
+        .into_iter()
+        .map(|id| (id.to_string(), cfg.launch_spec(id)))
+        .collect();
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = specs
+            .into_iter()
+            .map(|(id, spec)| {
+                let handle = scope.spawn(move || {
+                    spec.map_err(StartError::from)
+                        .and_then(|spec| start_ruleset(spec, cache_dir, strict, timeout))
+                });
+                (id, handle)
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|(id, handle)| {
+                let started = handle.join().unwrap_or_else(|_| {
+                    Err(StartError::Io(io::Error::other("ruleset start panicked")))
+                });
+                (id, started)
+            })
+            .collect()
+    });
+    for (ruleset_id, started) in results {
+        match started {
+            Ok(started) => report.started.push(started),
+            Err(e) => report.failed.push((ruleset_id, e)),
+        }
+    }
+    report
+}
+
+#[cfg(feature = "linter-host")]
+fn start_ruleset(
+    spec: crate::config::LaunchSpec,
+    cache_dir: &std::path::Path,
+    strict: bool,
+    timeout: Duration,
+) -> Result<StartedRuleset, StartError> {
+    use crate::config::Launch;
+    use std::process::Stdio;
+
+    let (mut correlator, mut child, stderr) = match spec.launch {
+        Launch::Connect { address } => (
+            Correlator::new(TcpTransport::connect_timeout(&address, timeout)?).with_strict(strict),
+            None,
+            None,
+        ),
+        Launch::Spawn(mut command) => {
+            let mut child = command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            let (Some(stdin), Some(stdout), Some(stderr)) =
+                (child.stdin.take(), child.stdout.take(), child.stderr.take())
+            else {
+                unreachable!("stdin, stdout and stderr are piped");
+            };
+            let transport = NdjsonTransport::new(io::BufReader::new(stdout), stdin);
+            (
+                Correlator::new(transport).with_strict(strict),
+                Some(child),
+                Some(StderrTail::capture(stderr)),
+            )
+        }
+    };
+    let initialize = spec.initialize;
+    let mut answered = false;
+    let initialized = correlator
+        .send_with_timeout(Request::Initialize(initialize), timeout)
+        .and_then(|id| correlator.wait(&id))
+        .map_err(StartError::from)
+        .and_then(|reply| {
+            answered = true;
+            let payload = reply.payload.unwrap_or(Value::Null);
+            if payload.get("ok") == Some(&Value::Bool(false)) {
+                let error: crate::protocol::ErrorPayload = serde_json::from_value(payload)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                return Err(StartError::Rejected(if error.message.is_empty() {
+                    error.code
+                } else {
+                    format!("{}: {}", error.code, error.message)
+                }));
+            }
+            let initialized: crate::core::InitializeResult = serde_json::from_value(payload)
+                .map_err(|e| StartError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+            match initialized.protocol_version {
+                Some(version) if version != correlator.protocol_version() => Err(
+                    StartError::Rejected(format!("unsupported protocol version {version}")),
+                ),
+                _ => Ok(initialized),
+            }
+        })
+        .and_then(|initialized| {
+            let id = correlator.send_with_timeout(Request::GetCapabilities, timeout)?;
+            let payload = correlator.wait(&id)?.payload.unwrap_or(Value::Null);
+            let capabilities: crate::core::RulesetCapabilities = serde_json::from_value(payload)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // A ruleset reached over TCP runs its tools on its own machine
+            if child.is_some() {
+                let unmet = check_requirements(&capabilities.requirements, timeout);
+                if !unmet.is_empty() {
+                    return Err(StartError::Requirements(unmet));
+                }
+            }
+            Ok((initialized, capabilities))
+        });
+    let e = match initialized {
+        Ok((initialized, capabilities)) => {
+            return Ok(StartedRuleset {
+                ruleset_id: spec.ruleset_id,
+                correlator,
+                child,
+                stderr: stderr.map(|(tail, _)| tail),
+                initialized,
+                capabilities,
+            });
+        }
+        Err(e) => e,
+    };
+    let (Some(child), Some((stderr, reader))) = (&mut child, stderr) else {
+        return Err(e);
+    };
+    // A server that died mid-handshake usually left its reason in the exit
+    // status, which says more than the broken pipe
+    let (e, status) = match e {
+        StartError::Io(_) => match exited_within(child, EXIT_GRACE) {
+            Some(status) => (StartError::Exited(status), Some(status)),
+            None => (e, None),
+        },
+        e => (e, None),
+    };
+    let status = status.or_else(|| {
+        let _ = child.kill();
+        child.wait().ok()
+    });
+    if !matches!(e, StartError::Io(_) | StartError::Exited(_)) {
+        return Err(e);
+    }
+    // A binary that is not a ruleset, e.g. one printing its usage, says so on
+    // stdout; read what is left of it
+    correlator.drain(EXIT_GRACE);
+    let e = match correlator.last_stray_line() {
+        Some(output) if !answered => StartError::NotARuleset {
+            output: output.to_string(),
+            source: Box::new(e),
+        },
+        _ => e,
+    };
+    // The pipe closes with the process, unless it left children holding it
+    let deadline = Instant::now() + EXIT_GRACE;
+    while !reader.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let report = crate::cache::CrashReport::new(
+        &spec.ruleset_id,
+        status.and_then(|status| status.code()),
+        &stderr.text(),
+        correlator.last_request(),
+    );
+    Err(StartError::Crashed {
+        source: Box::new(e),
+        report: report.write(cache_dir).ok(),
+        stderr_tail: report.stderr_tail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::mpsc::{Receiver, Sender, channel};
+
+    /// In-memory transport: the test feeds inbound messages and reads what
+    /// the correlator wrote
+    struct Channel {
+        inbound: Receiver<io::Result<Value>>,
+        outbound: Sender<String>,
+    }
+
+    struct ChannelReader(Receiver<io::Result<Value>>);
+    struct ChannelWriter(Sender<String>);
+
+    impl MessageReader for ChannelReader {
+        fn read_message(&mut self) -> io::Result<Value> {
+            self.0.recv().unwrap_or_else(|_| Err(input_closed()))
+        }
+    }
+
+    impl MessageWriter for ChannelWriter {
+        fn write_message(&mut self, message: &str) -> io::Result<()> {
+            self.0.send(message.to_string()).map_err(io::Error::other)
+        }
+    }
+
+    impl Transport for Channel {
+        fn into_parts(self) -> (Box<dyn MessageReader>, Box<dyn MessageWriter>) {
+            (
+                Box::new(ChannelReader(self.inbound)),
+                Box::new(ChannelWriter(self.outbound)),
+            )
+        }
+    }
+
+    fn correlator() -> (Correlator, Sender<io::Result<Value>>, Receiver<String>) {
+        let (inbound_tx, inbound) = channel();
+        let (outbound, outbound_rx) = channel();
+        let correlator = Correlator::new(Channel { inbound, outbound });
+        (correlator, inbound_tx, outbound_rx)
+    }
+
+    fn stray_line() -> io::Result<Value> {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "expected value"))
+    }
+
+    fn reply(typ: &str, id: &str) -> io::Result<Value> {
+        Ok(json!({"v": 1, "kind": "res", "type": typ, "id": id, "payload": {"ok": true}}))
+    }
+
+    #[test]
+    fn requests_carry_fresh_request_ids() {
+        let (mut correlator, _inbound, outbound) = correlator();
+        let first = correlator.send(Request::Shutdown).unwrap();
+        let second = correlator.send(Request::Shutdown).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 26, "ULID form");
+        let written: Value = serde_json::from_str(&outbound.recv().unwrap()).unwrap();
+        assert_eq!(written["id"], json!(first));
+    }
+
+    #[test]
+    fn stray_lines_are_skipped() {
+        let (mut correlator, inbound, _outbound) = correlator();
+        let id = correlator.send(Request::Shutdown).unwrap();
+        inbound.send(stray_line()).unwrap();
+        inbound.send(Ok(json!({"not": "an envelope"}))).unwrap();
+        inbound.send(reply("shutdown", &id)).unwrap();
+        assert_eq!(correlator.wait(&id).unwrap().typ, "shutdown");
+
+        // The connection is still usable afterwards
+        let id = correlator.send(Request::Shutdown).unwrap();
+        inbound.send(reply("shutdown", &id)).unwrap();
+        assert!(correlator.wait(&id).is_ok());
+    }
+
+    #[test]
+    fn strict_mode_fails_on_stray_lines() {
+        let (correlator, inbound, _outbound) = correlator();
+        let mut correlator = correlator.with_strict(true);
+        let id = correlator.send(Request::Shutdown).unwrap();
+        inbound.send(stray_line()).unwrap();
+        let err = correlator.wait(&id).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // The reader kept going, so the reply is still delivered
+        inbound.send(reply("shutdown", &id)).unwrap();
+        assert!(correlator.wait(&id).is_ok());
+    }
+
+    #[test]
+    fn strict_mode_rejects_replies_of_the_wrong_type() {
+        let (correlator, inbound, _outbound) = correlator();
+        let mut correlator = correlator.with_strict(true);
+        let id = correlator.send(Request::Shutdown).unwrap();
+        inbound.send(reply("initialize", &id)).unwrap();
+        let err = correlator.wait(&id).unwrap_err();
+        assert!(err.to_string().contains("expected shutdown"), "{err}");
+    }
+
+    #[test]
+    fn replies_and_events_are_buffered_until_asked_for() {
+        let (mut correlator, inbound, _outbound) = correlator();
+        let first = correlator.send(Request::Shutdown).unwrap();
+        let second = correlator.send(Request::GetDefaultConfig).unwrap();
+        inbound.send(reply("getDefaultConfig", &second)).unwrap();
+        inbound
+            .send(Ok(
+                json!({"v": 1, "kind": "event", "type": "progress", "payload": {}}),
+            ))
+            .unwrap();
+        inbound.send(reply("shutdown", &first)).unwrap();
+        assert!(correlator.wait(&first).is_ok());
+        assert_eq!(correlator.take_events().len(), 1);
+        assert_eq!(correlator.wait(&second).unwrap().typ, "getDefaultConfig");
+        assert_eq!(correlator.in_flight(), 0);
+    }
+
+    #[test]
+    fn unanswered_requests_time_out_and_late_replies_are_dropped() {
+        let (mut correlator, inbound, _outbound) = correlator();
+        let id = correlator
+            .send_with_timeout(Request::Shutdown, Duration::from_millis(10))
+            .unwrap();
+        let err = correlator.wait(&id).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        inbound.send(reply("shutdown", &id)).unwrap();
+        assert_eq!(
+            correlator.wait(&id).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn closed_input_ends_the_wait() {
+        let (mut correlator, inbound, _outbound) = correlator();
+        let id = correlator.send(Request::Shutdown).unwrap();
+        drop(inbound);
+        let err = correlator.wait(&id).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(all(unix, feature = "linter-host"))]
+    fn start_shell(
+        script: &str,
+        cache_dir: &std::path::Path,
+    ) -> Result<StartedRuleset, StartError> {
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", script]);
+        let spec = crate::config::LaunchSpec {
+            ruleset_id: "@acme/broken".into(),
+            launch: crate::config::Launch::Spawn(command),
+            initialize: Default::default(),
+        };
+        start_ruleset(spec, cache_dir, false, Duration::from_millis(500))
+    }
+
+    #[cfg(all(unix, feature = "linter-host"))]
+    #[test]
+    fn failed_handshakes_write_a_crash_report() {
+        let cache_dir = std::env::temp_dir().join(format!("forseti-crash-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let Err(StartError::Crashed {
+            source,
+            stderr_tail,
+            report,
+        }) = start_shell("read line; echo 'config missing' >&2; exit 3", &cache_dir)
+        else {
+            panic!("expected a crash");
+        };
+        assert!(matches!(*source, StartError::Exited(status) if status.code() == Some(3)));
+        assert_eq!(stderr_tail, ["config missing"]);
+        let report = report.expect("crash report written");
+        assert!(report.starts_with(cache_dir.join("crashes")));
+        let written: crate::cache::CrashReport =
+            serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+        assert_eq!(written.exit_code, Some(3));
+        assert!(written.last_request.unwrap().contains("\"initialize\""));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[cfg(all(unix, feature = "linter-host"))]
+    #[test]
+    fn unresponsive_servers_are_killed_and_reported() {
+        let cache_dir = std::env::temp_dir().join(format!("forseti-hang-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let Err(e) = start_shell("echo 'waiting' >&2; exec sleep 30", &cache_dir) else {
+            panic!("expected a failure");
+        };
+        let StartError::Crashed { ref source, .. } = e else {
+            panic!("expected a crash, got {e}");
+        };
+        assert!(matches!(**source, StartError::Io(ref io) if io.kind() == io::ErrorKind::TimedOut));
+        assert!(e.to_string().contains("waiting"), "{e}");
+        assert!(e.to_string().contains("crash report"), "{e}");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[cfg(all(unix, feature = "linter-host"))]
+    #[test]
+    fn binaries_that_are_not_rulesets_are_named_as_such() {
+        let cache_dir =
+            std::env::temp_dir().join(format!("forseti-not-ruleset-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        // Prints its usage and exits
+        let Err(e) = start_shell("echo 'usage: tool [options] FILE'; exit 0", &cache_dir) else {
+            panic!("expected a failure");
+        };
+        let StartError::Crashed { ref source, .. } = e else {
+            panic!("expected a crash, got {e}");
+        };
+        assert!(
+            matches!(**source, StartError::NotARuleset { ref output, .. } if output == "usage: tool [options] FILE")
+        );
+        assert!(
+            e.to_string().starts_with(
+                "not a forseti ruleset: it printed \"usage: tool [options] FILE\" instead of answering initialize"
+            ),
+            "{e}"
+        );
+
+        // Prints its usage and waits for input that makes sense to it
+        let Err(e) = start_shell(
+            "echo 'usage: tool [options] FILE'; exec sleep 30",
+            &cache_dir,
+        ) else {
+            panic!("expected a failure");
+        };
+        let StartError::Crashed { ref source, .. } = e else {
+            panic!("expected a crash, got {e}");
+        };
+        assert!(matches!(
+            **source,
+            StartError::NotARuleset { ref source, .. }
+                if matches!(**source, StartError::Io(ref io) if io.kind() == io::ErrorKind::TimedOut)
+        ));
+        assert!(e.to_string().contains("not a forseti ruleset"), "{e}");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+}
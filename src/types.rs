@@ -0,0 +1,1136 @@
+//! Plain protocol data: envelopes, diagnostics, ranges and capabilities.
+//!
+//! Everything here depends only on `serde`/`serde_json` (plus `thiserror` for
+//! [`EnvelopeError`]) and performs no I/O, so WASM guests and FFI shims can share
+//! these definitions. [`crate::core`] re-exports every item.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Oldest protocol version this SDK still speaks
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Req,
+    Res,
+    Event,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Envelope<T = Value> {
+    pub v: u8,
+    pub kind: Kind,
+    #[serde(rename = "type")]
+    pub typ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<T>,
+    /// Workspace session this message belongs to; absent means the default session
+    #[serde(rename = "sessionId", default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Request deadline in milliseconds from receipt; the server gives up afterwards
+    #[serde(rename = "timeoutMs", default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Distributed trace this message belongs to, shared by every process in a run
+    #[serde(rename = "traceId", default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// Id of the request that caused this message, possibly on another connection
+    #[serde(rename = "parentId", default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+/// Broken envelope invariants
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("unsupported protocol version {0}")]
+    UnsupportedVersion(u8),
+    #[error("envelope type must not be empty")]
+    EmptyType,
+    #[error("{0:?} envelope requires a non-empty id")]
+    MissingId(Kind),
+    #[error("event envelope must not carry an id")]
+    UnexpectedId,
+}
+
+impl<T> Envelope<T> {
+    /// Build an envelope, checking the same invariants as `validate`
+    pub fn try_new(
+        kind: Kind,
+        typ: &str,
+        id: Option<String>,
+        payload: Option<T>,
+    ) -> Result<Self, EnvelopeError> {
+        let envelope = Self {
+            v: PROTOCOL_VERSION,
+            kind,
+            typ: typ.to_string(),
+            id,
+            payload,
+            session_id: None,
+            timeout_ms: None,
+            trace_id: None,
+            parent_id: None,
+        };
+        envelope.validate()?;
+        Ok(envelope)
+    }
+
+    /// Check protocol invariants: known version, non-empty type, ids on req/res only
+    pub fn validate(&self) -> Result<(), EnvelopeError> {
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&self.v) {
+            return Err(EnvelopeError::UnsupportedVersion(self.v));
+        }
+        if self.typ.is_empty() {
+            return Err(EnvelopeError::EmptyType);
+        }
+        match (&self.kind, self.id.as_deref()) {
+            (Kind::Req | Kind::Res, None | Some("")) => {
+                Err(EnvelopeError::MissingId(self.kind.clone()))
+            }
+            (Kind::Event, Some(_)) => Err(EnvelopeError::UnexpectedId),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn event(typ: &str, payload: T) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            kind: Kind::Event,
+            typ: typ.to_string(),
+            id: None,
+            payload: Some(payload),
+            session_id: None,
+            timeout_ms: None,
+            trace_id: None,
+            parent_id: None,
+        }
+    }
+    pub fn res(typ: &str, id: impl Into<String>, payload: T) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            kind: Kind::Res,
+            typ: typ.to_string(),
+            id: Some(id.into()),
+            payload: Some(payload),
+            session_id: None,
+            timeout_ms: None,
+            trace_id: None,
+            parent_id: None,
+        }
+    }
+    pub fn req(typ: &str, id: impl Into<String>, payload: T) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            kind: Kind::Req,
+            typ: typ.to_string(),
+            id: Some(id.into()),
+            payload: Some(payload),
+            session_id: None,
+            timeout_ms: None,
+            trace_id: None,
+            parent_id: None,
+        }
+    }
+
+    /// Tag the envelope with a session id
+    pub fn with_session(mut self, session_id: Option<String>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Tag the envelope with a trace id and the id of the request that caused it
+    pub fn with_trace(mut self, trace_id: Option<String>, parent_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self.parent_id = parent_id;
+        self
+    }
+
+    /// Ask the server to abandon the request after `timeout_ms` milliseconds
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = Some(timeout_ms);
+        self
+    }
+}
+
+/// Names of optional protocol features advertised in capabilities and `initialize`.
+/// Hosts must check for a feature before relying on it and fall back otherwise.
+pub mod features {
+    /// Accepts `cancel` requests for in-flight work
+    pub const CANCELLATION: &str = "cancellation";
+    /// Accepts several requests in one batch line
+    pub const BATCH_ANALYZE: &str = "batchAnalyze";
+    /// Accepts incremental content updates instead of full file content
+    pub const INCREMENTAL_UPDATES: &str = "incrementalUpdates";
+    /// Sends and accepts gzip-compressed payloads (feature `compression`)
+    pub const GZIP: &str = "gzip";
+    /// Sends `metrics` events when `initialize` sets `metricsIntervalMs`
+    pub const METRICS: &str = "metrics";
+}
+
+/// Payload of a successful `initialize` response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InitializeResult {
+    pub ok: bool,
+    /// Optional features enabled for this connection
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Version agreed during the handshake
+    #[serde(
+        rename = "protocolVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub protocol_version: Option<u8>,
+}
+
+/// Severity of a log record, ordered from most to least verbose
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Default,
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// Payload of the `log` event: one structured record from a server, for the
+/// host to forward to its own logger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LogEvent {
+    pub level: LogLevel,
+    pub message: String,
+    /// Component that logged, e.g. `forseti::server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub fields: Map<String, Value>,
+}
+
+impl LogEvent {
+    pub fn new(level: LogLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            target: None,
+            fields: Map::new(),
+        }
+    }
+
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Attach a structured field; values that fail to serialize are dropped
+    pub fn with_field(mut self, key: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.fields.insert(key.to_string(), value);
+        }
+        self
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of file content as 16 hex digits, sent as `contentHash`
+/// in `analyzeFile` and echoed in the matching `diagnostics` event.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// `file://` URI of the absolute `path`, percent-encoding what a URI path
+/// cannot carry (spaces, `#`, `%`, non-ASCII). Windows paths, including
+/// verbatim `\\?\` ones, become `file:///C:/...`.
+pub fn file_uri(path: &std::path::Path) -> String {
+    let text = path.to_string_lossy();
+    let text = text
+        .strip_prefix(r"\\?\")
+        .unwrap_or(&text)
+        .replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !text.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'/'
+            | b':'
+            | b'@'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b';'
+            | b'=' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Filesystem path a `file://` URI names, undoing the percent-encoding of
+/// [`file_uri`]; `file:///C:/...` becomes `C:/...`. Anything not starting
+/// with `file://` is taken as a plain path, unchanged. Malformed escapes are
+/// kept as written.
+pub fn uri_to_path(uri: &str) -> std::path::PathBuf {
+    let Some(rest) = uri.strip_prefix("file://") else {
+        return std::path::PathBuf::from(uri);
+    };
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8_lossy(&decoded);
+    // In `/C:/...` the slash only ends the empty authority
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => {
+            std::path::PathBuf::from(&path[1..])
+        }
+        _ => std::path::PathBuf::from(path.as_ref()),
+    }
+}
+
+/// SHA-256 of `data` as 64 hex digits. For keys shared between machines,
+/// where an FNV collision could hand one file's results to another.
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Serialize `value` identically however its maps were built: object keys
+/// sorted, no whitespace, and integral floats written as integers so `1.0`
+/// and `1` agree. The input for every hash of structured data.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+/// [`content_hash`] of the canonical JSON form of `value`
+pub fn canonical_hash<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    Ok(content_hash(&canonical_json(&serde_json::to_value(value)?)))
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Null | Value::Bool(_) | Value::String(_) => out.push_str(&value.to_string()),
+        Value::Number(number) => match number.as_f64() {
+            // Integral floats print without a fraction (-0.0 as 0); the rest,
+            // and huge magnitudes, in serde_json's shortest round-trip form
+            Some(float) if number.is_f64() && float.fract() == 0.0 && float.abs() < 1e21 => {
+                out.push_str(&format!("{:.0}", float + 0.0));
+            }
+            _ => out.push_str(&number.to_string()),
+        },
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// How `content` is carried in an `analyzeFile` payload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    /// Plain JSON string
+    #[default]
+    Utf8,
+    /// Base64 of the raw bytes, for files that are not valid UTF-8
+    Base64,
+}
+
+/// Common position types and diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Fix {
+    pub range: Range,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SuggestFix {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Fix>,
+}
+
+/// Edits to whole documents keyed by URI, shaped like an LSP `WorkspaceEdit`
+/// for fixes that touch files other than the one linted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WorkspaceEdit {
+    pub changes: HashMap<String, Vec<Fix>>,
+}
+
+/// How serious a finding is, ordered from least to most severe. Parsed
+/// case-insensitively, with `"warning"` accepted for `warn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Hint,
+    Info,
+    #[default]
+    Warn,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Hint => "hint",
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown severity \"{0}\", expected \"error\", \"warn\", \"info\" or \"hint\"")]
+pub struct UnknownSeverity(pub String);
+
+impl std::str::FromStr for Severity {
+    type Err = UnknownSeverity;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hint" => Ok(Severity::Hint),
+            "info" => Ok(Severity::Info),
+            "warn" | "warning" => Ok(Severity::Warn),
+            "error" => Ok(Severity::Error),
+            _ => Err(UnknownSeverity(s.to_string())),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Diagnostic {
+    pub rule_id: String,
+    pub message: String,
+    /// Defaults to `warn` when omitted; unknown names are rejected
+    #[serde(default)]
+    pub severity: Severity,
+    pub range: Range,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggest: Option<Vec<SuggestFix>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs_url: Option<String>,
+    /// Free-form taxonomy tags (e.g. "injection", "crypto")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<SecurityMeta>,
+    /// Stable message id for localized rendering via `RulesetCapabilities::message_bundles`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Structured arguments for the message template placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<serde_json::Map<String, Value>>,
+    /// Uninterpolated message, e.g. "'{name}' is never used"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_template: Option<String>,
+    /// File the diagnostic belongs to; filled in by the runners when rules omit it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// Rulesets that reported this finding, when duplicates were merged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// Auxiliary files (graphs, proofs, long explanations) backing this finding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Machine-readable context for code actions and formatters, e.g. the
+    /// offending identifier or a suggested import
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Map<String, Value>>,
+}
+
+/// File written by a ruleset under the run's artifacts directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Attachment {
+    /// Path relative to the artifacts directory
+    pub path: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Attachment {
+    pub fn new(path: impl Into<String>, mime_type: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mime_type: mime_type.into(),
+            description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+impl Diagnostic {
+    pub fn new(
+        rule_id: impl Into<String>,
+        message: impl Into<String>,
+        severity: Severity,
+        range: Range,
+    ) -> Self {
+        Self {
+            rule_id: rule_id.into(),
+            message: message.into(),
+            severity,
+            range,
+            code: None,
+            suggest: None,
+            docs_url: None,
+            tags: vec![],
+            security: None,
+            message_id: None,
+            args: None,
+            message_template: None,
+            uri: None,
+            sources: vec![],
+            attachments: vec![],
+            data: None,
+        }
+    }
+
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Add `key` to [`data`](Self::data); values that fail to serialize are dropped
+    pub fn with_data(mut self, key: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.data
+                .get_or_insert_with(Default::default)
+                .insert(key.to_string(), value);
+        }
+        self
+    }
+
+    /// Set the message from a template and its args; `message` is always rendered
+    /// so consumers unaware of templates keep working.
+    pub fn with_template(
+        mut self,
+        template: impl Into<String>,
+        args: serde_json::Map<String, Value>,
+    ) -> Self {
+        let template = template.into();
+        self.message = render_template(&template, &args);
+        self.message_template = Some(template);
+        self.args = Some(args);
+        self
+    }
+
+    /// Key grouping identical issues regardless of interpolated values
+    pub fn group_key(&self) -> (&str, &str) {
+        (
+            &self.rule_id,
+            self.message_template.as_deref().unwrap_or(&self.message),
+        )
+    }
+
+    /// Stable identity of a finding across runs: file, rule and message, but not
+    /// position, so unrelated edits that shift lines keep the fingerprint.
+    pub fn fingerprint(&self) -> String {
+        let (rule_id, message) = self.group_key();
+        content_hash(&format!(
+            "{}\0{}\0{}",
+            self.uri.as_deref().unwrap_or(""),
+            rule_id,
+            message
+        ))
+    }
+}
+
+/// Security classification for findings from security-focused rulesets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SecurityMeta {
+    /// CWE identifiers, e.g. "CWE-79"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cwe: Vec<String>,
+    /// OWASP Top 10 categories, e.g. "A03:2021"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owasp: Vec<String>,
+    /// CVSS base score (0.0 - 10.0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cvss: Option<f64>,
+}
+
+/// Information about a single rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RuleInfo {
+    pub id: String,
+    pub description: String,
+    /// Categories such as "security" or "style"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// Whether the rule attaches automatic fixes
+    #[serde(default)]
+    pub fixable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs_url: Option<String>,
+    /// Before/after snippets illustrating the rule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<RuleExample>,
+}
+
+/// A documented example for a rule: code it flags and the corrected version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RuleExample {
+    pub description: String,
+    pub bad: String,
+    pub good: String,
+}
+
+/// Information about a ruleset and its rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RulesetInfo {
+    pub id: String,
+    pub rules: Vec<RuleInfo>,
+}
+
+/// Configuration setting definition for rulesets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ConfigSetting {
+    /// Setting name/key
+    pub name: String,
+    /// Human-readable description
+    pub description: String,
+    /// Data type of the setting
+    #[serde(rename = "type")]
+    pub setting_type: ConfigType,
+    /// Default value
+    pub default: Value,
+    /// Whether this setting is required
+    #[serde(default)]
+    pub required: bool,
+    /// Allowed values (for enum types)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<Value>>,
+    /// Minimum value (for numeric types)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Maximum value (for numeric types)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl ConfigSetting {
+    /// Check a value against this setting's type, allowed values and bounds
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        let type_ok = match self.setting_type {
+            ConfigType::String => value.is_string(),
+            ConfigType::Number => value.is_number(),
+            ConfigType::Integer => value.is_i64() || value.is_u64(),
+            ConfigType::Boolean => value.is_boolean(),
+            ConfigType::Array => value.is_array(),
+            ConfigType::Object => value.is_object(),
+            ConfigType::Enum => true,
+        };
+        if !type_ok {
+            return Err(format!(
+                "setting '{}' expects {:?}, got {}",
+                self.name, self.setting_type, value
+            ));
+        }
+        if let Some(allowed) = &self.allowed_values
+            && !allowed.contains(value)
+        {
+            return Err(format!("setting '{}' does not allow {}", self.name, value));
+        }
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = self.min
+                && n < min
+            {
+                return Err(format!("setting '{}' must be >= {}", self.name, min));
+            }
+            if let Some(max) = self.max
+                && n > max
+            {
+                return Err(format!("setting '{}' must be <= {}", self.name, max));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Data types for configuration settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+    /// One of a set of predefined values
+    Enum,
+}
+
+/// Ruleset capabilities and metadata (replaces EngineCapabilities)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RulesetCapabilities {
+    pub ruleset_id: String,
+    pub version: String,
+    pub file_patterns: Vec<String>,
+    pub max_file_size: Option<u64>,
+    /// Comment prefixes used for annotations (e.g., ["//", "#", "/*"])
+    pub annotation_prefixes: Vec<String>,
+    /// Rules available in this ruleset
+    pub rules: Vec<RuleInfo>,
+    /// Default configuration for rules
+    pub default_config: HashMap<String, Value>,
+    /// Configuration settings that can be customized
+    #[serde(default)]
+    pub config_settings: Vec<ConfigSetting>,
+    /// Ids of rulesets whose preprocessing output this ruleset consumes
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Languages of embedded regions this ruleset analyzes (e.g. "css" in a Vue SFC);
+    /// empty means it only sees whole files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub region_languages: Vec<String>,
+    /// Optional protocol features this ruleset supports (see `features`)
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Localized message templates keyed by locale, then message id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub message_bundles: MessageBundles,
+    /// External tools the ruleset needs on the machine it runs on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requirements: Vec<Requirement>,
+}
+
+/// A binary a ruleset shells out to, e.g. `node` at `>=18, <23`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Requirement {
+    /// Executable name looked up on `PATH`, or a path
+    pub binary: String,
+    /// Accepted versions; any version when absent. See [`version_matches`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Arguments that make the binary print its version
+    #[serde(default = "default_version_args")]
+    pub version_args: Vec<String>,
+}
+
+fn default_version_args() -> Vec<String> {
+    vec!["--version".to_string()]
+}
+
+impl Requirement {
+    pub fn new(binary: impl Into<String>) -> Self {
+        Self {
+            binary: binary.into(),
+            version: None,
+            version_args: default_version_args(),
+        }
+    }
+
+    pub fn with_version(mut self, range: impl Into<String>) -> Self {
+        self.version = Some(range.into());
+        self
+    }
+
+    pub fn with_version_args(mut self, args: Vec<String>) -> Self {
+        self.version_args = args;
+        self
+    }
+}
+
+/// First dotted version number in a tool's `--version` output, e.g. "18.17.0"
+/// from "v18.17.0" or "3.11.4" from "Python 3.11.4"
+pub fn parse_tool_version(output: &str) -> Option<&str> {
+    output.split_whitespace().find_map(|token| {
+        let start = token.find(|c: char| c.is_ascii_digit())?;
+        let rest = &token[start..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let version = rest[..end].trim_end_matches('.');
+        version.contains('.').then_some(version)
+    })
+}
+
+/// Whether `version` satisfies `range`: comparators separated by commas or
+/// spaces, all of which must hold. Each is `>=`, `>`, `<=`, `<` or `=` plus a
+/// version, optionally spaced apart (`>= 18`), `^1.2` (same leftmost non-zero component, as in semver: `^0.2`
+/// is `>=0.2, <0.3`), `~1.2` (same major and minor), `*`, or a bare version
+/// matching every version it is a prefix of ("18" matches "18.2.0"). Missing
+/// components count as 0. None when either does not parse.
+pub fn version_matches(version: &str, range: &str) -> Option<bool> {
+    let version = parse_version_parts(version)?;
+    // Join each operator to its version so only spaces between comparators split
+    let mut joined = String::with_capacity(range.len());
+    for c in range.chars() {
+        if !(c.is_whitespace() && joined.ends_with(['<', '>', '=', '^', '~'])) {
+            joined.push(c);
+        }
+    }
+    let mut matches = true;
+    for comparator in joined.split(',').flat_map(str::split_whitespace) {
+        if comparator == "*" {
+            continue;
+        }
+        let (op, wanted) = [">=", "<=", "==", ">", "<", "=", "^", "~"]
+            .iter()
+            .find_map(|op| Some((*op, comparator.strip_prefix(op)?)))
+            .unwrap_or(("", comparator));
+        let wanted_parts = parse_version_parts(wanted)?;
+        let ordering = compare_versions(&version, &wanted_parts);
+        let prefix = |n: usize| {
+            version
+                .iter()
+                .chain(std::iter::repeat(&0))
+                .take(n)
+                .eq(wanted_parts.iter().take(n))
+        };
+        matches &= match op {
+            ">=" => ordering.is_ge(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            "<" => ordering.is_lt(),
+            "=" | "==" => ordering.is_eq(),
+            "^" => {
+                let fixed = wanted_parts
+                    .iter()
+                    .position(|&part| part != 0)
+                    .map_or(wanted_parts.len(), |i| i + 1);
+                ordering.is_ge() && prefix(fixed)
+            }
+            "~" => ordering.is_ge() && prefix(wanted_parts.len().min(2)),
+            _ => prefix(wanted_parts.len()),
+        };
+    }
+    Some(matches)
+}
+
+fn parse_version_parts(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim().trim_start_matches('v');
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn compare_versions(a: &[u64], b: &[u64]) -> std::cmp::Ordering {
+    let len = a.len().max(b.len());
+    let pad = |v: &[u64]| {
+        v.iter()
+            .copied()
+            .chain(std::iter::repeat(0))
+            .take(len)
+            .collect::<Vec<_>>()
+    };
+    pad(a).cmp(&pad(b))
+}
+
+/// Message templates keyed by locale (e.g. "de", "pt-BR"), then message id
+pub type MessageBundles = HashMap<String, HashMap<String, String>>;
+
+/// Locale used when the requested one has no template for a message
+pub const FALLBACK_LOCALE: &str = "en";
+
+/// Render `{name}` placeholders in `template` from `args`.
+/// String args are inserted verbatim, other values as JSON; unknown placeholders are kept.
+pub fn render_template(template: &str, args: &serde_json::Map<String, Value>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let name = &after[..close];
+                match args.get(name) {
+                    Some(Value::String(s)) => out.push_str(s),
+                    Some(v) => out.push_str(&v.to_string()),
+                    None => {
+                        out.push('{');
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Render a diagnostic's message in `locale`, falling back to the language without
+/// region, then English, then the diagnostic's preformatted `message`.
+pub fn localize_message(diagnostic: &Diagnostic, bundles: &MessageBundles, locale: &str) -> String {
+    let Some(message_id) = &diagnostic.message_id else {
+        return diagnostic.message.clone();
+    };
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    let template = [locale, language, FALLBACK_LOCALE]
+        .iter()
+        .find_map(|l| bundles.get(*l).and_then(|b| b.get(message_id)));
+    match template {
+        Some(t) => render_template(t, diagnostic.args.as_ref().unwrap_or(&Default::default())),
+        None => diagnostic.message.clone(),
+    }
+}
+
+impl RulesetCapabilities {
+    /// Whether the ruleset advertises an optional protocol feature
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// All distinct rule categories offered by this ruleset, sorted
+    pub fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .rules
+            .iter()
+            .flat_map(|rule| rule.categories.iter().cloned())
+            .collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn diagnostic(severity: Option<&str>) -> serde_json::Result<Diagnostic> {
+        let mut value = serde_json::json!({
+            "rule_id": "no-todo",
+            "message": "TODO found",
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 4}},
+        });
+        if let Some(severity) = severity {
+            value["severity"] = severity.into();
+        }
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn diagnostic_severities_are_parsed_strictly() {
+        assert_eq!(diagnostic(Some("ERROR")).unwrap().severity, Severity::Error);
+        assert_eq!(
+            diagnostic(Some("warning")).unwrap().severity,
+            Severity::Warn
+        );
+        assert_eq!(diagnostic(None).unwrap().severity, Severity::Warn);
+        for unknown in ["eror", "fatal", "critical"] {
+            let err = diagnostic(Some(unknown)).unwrap_err();
+            assert!(err.to_string().contains("unknown severity"), "{err}");
+        }
+    }
+
+    #[test]
+    fn sha256_known_answers() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256_hex(&vec![b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn caret_ranges_fix_the_leftmost_non_zero_component() {
+        assert_eq!(version_matches("1.9.0", "^1.2"), Some(true));
+        assert_eq!(version_matches("2.0.0", "^1.2"), Some(false));
+        assert_eq!(version_matches("1.1.0", "^1.2"), Some(false));
+        assert_eq!(version_matches("0.2.5", "^0.2"), Some(true));
+        assert_eq!(version_matches("0.3.0", "^0.2"), Some(false));
+    }
+
+    #[test]
+    fn tilde_ranges_fix_major_and_minor() {
+        assert_eq!(version_matches("1.2.9", "~1.2.3"), Some(true));
+        assert_eq!(version_matches("1.2.2", "~1.2.3"), Some(false));
+        assert_eq!(version_matches("1.3.0", "~1.2.3"), Some(false));
+    }
+
+    #[test]
+    fn bare_versions_match_as_prefixes() {
+        assert_eq!(version_matches("18.2.0", "18"), Some(true));
+        assert_eq!(version_matches("v18.2.0", "18.2"), Some(true));
+        assert_eq!(version_matches("180.0.0", "18"), Some(false));
+        assert_eq!(version_matches("3.1.0", "*"), Some(true));
+    }
+
+    #[test]
+    fn spaced_and_comma_separated_comparators() {
+        assert_eq!(version_matches("18.2.0", ">= 18"), Some(true));
+        assert_eq!(version_matches("17.0.0", ">= 18"), Some(false));
+        assert_eq!(version_matches("18.2.0", ">=18, <20"), Some(true));
+        assert_eq!(version_matches("18.2.0", ">= 18 < 20"), Some(true));
+        assert_eq!(version_matches("20.0.0", ">=18 <20"), Some(false));
+        assert_eq!(version_matches("18.2.0", ">= 18 ,  <  19"), Some(true));
+    }
+
+    #[test]
+    fn malformed_ranges_do_not_parse() {
+        assert_eq!(version_matches("18.2.0", ">="), None);
+        assert_eq!(version_matches("18.2.0", "latest"), None);
+        assert_eq!(version_matches("unknown", ">=1"), None);
+    }
+
+    #[test]
+    fn uri_to_path_undoes_file_uri() {
+        let path = Path::new("/tmp/a b/#1/100%/ünï.rs");
+        let uri = file_uri(path);
+        assert_eq!(uri, "file:///tmp/a%20b/%231/100%25/%C3%BCn%C3%AF.rs");
+        assert_eq!(uri_to_path(&uri), path);
+    }
+
+    #[test]
+    fn uri_to_path_handles_drive_letters_and_hosts() {
+        assert_eq!(
+            uri_to_path("file:///C:/src/a%20b.rs"),
+            PathBuf::from("C:/src/a b.rs")
+        );
+        assert_eq!(
+            uri_to_path("file://localhost/tmp/x"),
+            PathBuf::from("/tmp/x")
+        );
+    }
+
+    #[test]
+    fn uri_to_path_keeps_plain_paths_and_bad_escapes() {
+        assert_eq!(uri_to_path("src/100%20.rs"), PathBuf::from("src/100%20.rs"));
+        assert_eq!(
+            uri_to_path("file:///tmp/%zz%4"),
+            PathBuf::from("/tmp/%zz%4")
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Scripted conformance run of `adapters/node/forseti-ruleset.js`: spawn the
+//! adapter with a small ruleset and drive it as a host would, through a
+//! strict [`Correlator`]. Skipped when `node` is not on PATH.
+
+use forseti_sdk::protocol::{InitializeParams, Request, SetRulesParams};
+use forseti_sdk::transport::{Correlator, NdjsonTransport, wait_or_kill};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const AUTH_TOKEN: &str = "conformance-token";
+
+/// Ruleset served by the adapter: `no-todo` flags "TODO", `no-tabs` flags tabs
+const RULESET: &str = r#"
+const { serve } = require(process.env.FORSETI_ADAPTER);
+const find = (needle, message) => (text) => {
+  const i = text.indexOf(needle);
+  if (i < 0) return [];
+  const range = { start: { line: 0, character: i }, end: { line: 0, character: i + needle.length } };
+  return [{ message, range }];
+};
+serve({
+  id: "conformance",
+  version: "1.0.0",
+  filePatterns: ["**/*.txt"],
+  rules: [
+    { id: "no-todo", description: "Disallow TODO", check: find("TODO", "TODO found") },
+    { id: "no-tabs", description: "Disallow tabs", defaultConfig: "error", check: find("\t", "tab found") },
+  ],
+}, process.stdin, process.stdout, { authToken: process.env.FORSETI_TOKEN });
+"#;
+
+fn spawn_adapter() -> Option<Child> {
+    let adapter = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/adapters/node/forseti-ruleset.js"
+    );
+    match Command::new("node")
+        .args(["-e", RULESET])
+        .env("FORSETI_ADAPTER", adapter)
+        .env("FORSETI_TOKEN", AUTH_TOKEN)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => Some(child),
+        Err(e) => {
+            eprintln!("skipping Node adapter conformance run: {e}");
+            None
+        }
+    }
+}
+
+fn correlator(child: &mut Child) -> Correlator {
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let stdin = child.stdin.take().unwrap();
+    Correlator::new(NdjsonTransport::new(stdout, stdin)).with_strict(true)
+}
+
+fn payload(correlator: &mut Correlator, request: Request) -> Value {
+    correlator
+        .call_with_timeout(request, TIMEOUT)
+        .unwrap()
+        .payload
+        .unwrap_or(Value::Null)
+}
+
+fn analyze(correlator: &mut Correlator, content: &str) -> Vec<Value> {
+    let request = json!({ "uri": "file:///a.txt", "content": content });
+    let reply = payload(
+        correlator,
+        Request::AnalyzeFile(serde_json::from_value(request).unwrap()),
+    );
+    assert_eq!(reply, json!({ "ok": true }));
+    let events = correlator.take_events();
+    let diagnostics = events
+        .iter()
+        .find(|event| event.typ == "diagnostics")
+        .and_then(|event| event.payload.as_ref())
+        .expect("diagnostics event before the analyzeFile reply");
+    diagnostics["diagnostics"].as_array().unwrap().clone()
+}
+
+fn set_rules(correlator: &mut Correlator, params: Value) -> Value {
+    payload(
+        correlator,
+        Request::SetRules(serde_json::from_value::<SetRulesParams>(params).unwrap()),
+    )
+}
+
+#[test]
+fn node_adapter_conformance() {
+    let Some(mut child) = spawn_adapter() else {
+        return;
+    };
+    let mut host = correlator(&mut child);
+
+    // Nothing is served before the token is presented
+    let reply = payload(&mut host, Request::GetCapabilities);
+    assert_eq!(reply["error"], "unauthorized");
+    let wrong = InitializeParams {
+        auth_token: Some("wrong".into()),
+        ..Default::default()
+    };
+    let reply = payload(&mut host, Request::Initialize(wrong));
+    assert_eq!(reply["error"], "unauthorized");
+
+    let initialize = InitializeParams {
+        ruleset_config: Some(HashMap::from([("no-todo".to_string(), json!("warn"))])),
+        auth_token: Some(AUTH_TOKEN.into()),
+        ..Default::default()
+    };
+    let reply = payload(&mut host, Request::Initialize(initialize));
+    assert_eq!(reply["ok"], true);
+    assert_eq!(reply["protocolVersion"], 1);
+    host.take_events();
+
+    let capabilities = payload(&mut host, Request::GetCapabilities);
+    assert_eq!(capabilities["ruleset_id"], "conformance");
+    assert_eq!(capabilities["rules"].as_array().unwrap().len(), 2);
+
+    let diagnostics = analyze(&mut host, "TODO\tx");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["rule_id"], "no-todo");
+    assert_eq!(diagnostics[0]["severity"], "warn");
+
+    // setRules toggles over the initialize config; enabling uses the default
+    let reply = set_rules(
+        &mut host,
+        json!({ "rules": { "no-tabs": true, "no-todo": false } }),
+    );
+    assert_eq!(
+        reply,
+        json!({ "ok": true, "enabled": true, "disabledRules": ["no-todo"] })
+    );
+    let diagnostics = analyze(&mut host, "TODO\tx");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["rule_id"], "no-tabs");
+    assert_eq!(diagnostics[0]["severity"], "error");
+
+    let reply = set_rules(&mut host, json!({ "rules": { "bogus": true } }));
+    assert_eq!(reply["error"], "unknown_rule");
+    assert_eq!(reply["details"], json!(["bogus"]));
+
+    let reply = set_rules(&mut host, json!({ "enabled": false }));
+    assert_eq!(reply["enabled"], false);
+    assert!(analyze(&mut host, "TODO\tx").is_empty());
+    let reply = set_rules(&mut host, json!({ "reset": true }));
+    assert_eq!(reply["disabledRules"], json!([]));
+    assert_eq!(analyze(&mut host, "TODO\tx").len(), 1);
+
+    host.shutdown(TIMEOUT).unwrap();
+    let status = wait_or_kill(&mut child, TIMEOUT).unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+/// Batch lines and the parse-failure limit, which a `Correlator` never
+/// produces, checked on the raw streams
+#[test]
+fn node_adapter_batches_and_parse_failures() {
+    let Some(mut child) = spawn_adapter() else {
+        return;
+    };
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut read = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        serde_json::from_str::<Value>(&line).unwrap()
+    };
+
+    let initialize = json!({
+        "v": 1, "kind": "req", "type": "initialize", "id": "1",
+        "payload": { "authToken": AUTH_TOKEN },
+    });
+    let analyze = json!({
+        "v": 1, "kind": "req", "type": "analyzeFile", "id": "2",
+        "payload": { "uri": "file:///a.txt", "content": "TODO" },
+    });
+    writeln!(stdin, "{}", json!([initialize, analyze, 5])).unwrap();
+    let replies = read();
+    let types: Vec<&str> = replies
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|reply| reply["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, ["initialize", "diagnostics", "analyzeFile", "error"]);
+    assert_eq!(replies[3]["payload"]["error"], "parse_error");
+
+    // The malformed batch member counts; seven more lines reach the limit
+    for _ in 0..7 {
+        writeln!(stdin, "not json").unwrap();
+        assert_eq!(read()["payload"]["error"], "parse_error");
+    }
+    let terminated = read();
+    assert_eq!(terminated["type"], "terminated");
+    assert_eq!(
+        terminated["payload"],
+        json!({ "reason": "parseFailures", "count": 8 })
+    );
+    let status = wait_or_kill(&mut child, TIMEOUT).unwrap();
+    assert_eq!(status.code(), Some(76));
+}